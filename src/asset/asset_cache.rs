@@ -0,0 +1,269 @@
+use std::cell::{Ref, RefMut};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::rc::{Rc, Weak};
+use crate::asset::{ASSET_MANAGER_INSTANCE, AssetPath, AssetPathBuf, AssetLoadService, AssetLoadError, LoadHandle, LoadState, LoadStatus};
+
+/// Loads assets of a specific type for an `AssetCache`.
+pub trait AssetLoader<T: 'static> {
+	/// Loads the asset synchronously on the calling (main) thread.
+	fn load(&self, path: &AssetPath) -> Result<T, AssetLoadError>;
+	
+	/// Queues an asynchronous load that completes the given handle.
+	///
+	/// The default implementation just loads synchronously, loaders that
+	/// can split their work into a decode and upload stage should override this.
+	fn load_async(&self, path: &AssetPath, _service: &mut AssetLoadService, handle: &LoadHandle<T>) {
+		handle.set_state(match self.load(path) {
+			Ok(asset) => LoadState::Ready(asset),
+			Err(err) => LoadState::Failed(err),
+		});
+	}
+	
+	/// Reloads an already loaded asset. If this fails the asset should still be usable.
+	///
+	/// The default implementation loads the asset from scratch and replaces it. Loaders
+	/// of assets that hold on to state worth keeping across reloads should override this.
+	fn reload(&self, path: &AssetPath, asset: &mut T) -> Result<(), AssetLoadError> {
+		*asset = self.load(path)?;
+		Ok(())
+	}
+}
+
+/// Cache of assets of a single type keyed by their asset path.
+///
+/// Every path is only ever loaded once; loading an already cached path
+/// just returns a new handle to the existing asset. The cache itself holds a
+/// strong reference to every entry, so assets stay alive until `unload_unused`
+/// is called and no other `Handle` to them exists anymore (`WeakHandle`s don't count).
+pub struct AssetCache<T: 'static> {
+	loader: Box<dyn AssetLoader<T>>,
+	entries: HashMap<AssetPathBuf, Rc<AssetSlot<T>>>,
+}
+
+impl<T: 'static> AssetCache<T> {
+	/// Returns a handle to the asset if it is cached (regardless of its load state).
+	pub fn get(&self, path: &AssetPath) -> Option<Handle<T>> {
+		self.entries.get(&Self::cache_key(path)).map(|slot| Handle {slot: Rc::clone(slot)})
+	}
+	
+	/// Returns the handle to the cached asset or loads it synchronously.
+	pub fn load(&mut self, path: &AssetPath) -> Handle<T> {
+		let loader = &self.loader;
+		Self::get_or_insert_with(&mut self.entries, path, |slot| {
+			slot.state.set_state(match loader.load(&slot.path.as_path()) {
+				Ok(asset) => LoadState::Ready(asset),
+				Err(err) => {
+					println!("Failed to load asset {:?}: {}", slot.path.as_path(), err);
+					LoadState::Failed(err)
+				}
+			});
+		})
+	}
+	
+	/// Returns the handle to the cached asset or queues an asynchronous load.
+	pub fn load_async(&mut self, path: &AssetPath, service: &mut AssetLoadService) -> Handle<T> {
+		let loader = &self.loader;
+		Self::get_or_insert_with(&mut self.entries, path, |slot| {
+			loader.load_async(&slot.path.as_path(), service, &slot.state);
+		})
+	}
+	
+	/// Synchronously reloads a cached asset in place. All existing handles see the new asset.
+	pub fn reload(&mut self, path: &AssetPath) -> bool {
+		if let Some(slot) = self.entries.get(&Self::cache_key(path)) {
+			Self::reload_slot(self.loader.as_ref(), slot);
+			true
+		}
+		else {false}
+	}
+	
+	/// Synchronously reloads all cached assets that are built from any of the given files,
+	/// directly or through other assets, according to the asset dependency graph.
+	/// Assets that failed to load are retried too, their dependencies may never have been recorded.
+	/// Every affected asset is only reloaded once. Returns the number of reloaded assets.
+	pub fn reload_affected_by(&mut self, changed_files: &[AssetPathBuf]) -> usize {
+		let dependency_graph = unsafe {&ASSET_MANAGER_INSTANCE}.dependency_graph();
+		
+		let mut affected = BTreeSet::new();
+		for changed_file in changed_files {
+			affected.insert(changed_file.as_path().to_absolute());
+			affected.extend(dependency_graph.dependents_of(&changed_file.as_path()));
+		}
+		affected.extend(self.entries.iter().filter(|(_, slot)| slot.state.status() == LoadStatus::Failed).map(|(path, _)| path.clone()));
+		
+		let mut num_reloaded = 0;
+		for path in &affected {
+			if let Some(slot) = self.entries.get(path) {
+				Self::reload_slot(self.loader.as_ref(), slot);
+				num_reloaded += 1;
+			}
+		}
+		num_reloaded
+	}
+	
+	/// Synchronously reloads all cached assets in place.
+	pub fn reload_all(&mut self) {
+		for slot in self.entries.values() {
+			Self::reload_slot(self.loader.as_ref(), slot);
+		}
+	}
+	
+	/// Removes all assets from the cache that aren't referenced by any `Handle` anymore.
+	/// Returns the number of unloaded assets.
+	pub fn unload_unused(&mut self) -> usize {
+		let num_before = self.entries.len();
+		self.entries.retain(|_, slot| Rc::strong_count(slot) > 1);
+		num_before - self.entries.len()
+	}
+	
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+	
+	/// Iterates over handles to all cached assets.
+	pub fn iter<'a>(&'a self) -> impl Iterator<Item = Handle<T>> + 'a {
+		self.entries.values().map(|slot| Handle {slot: Rc::clone(slot)})
+	}
+	
+	fn reload_slot(loader: &dyn AssetLoader<T>, slot: &AssetSlot<T>) {
+		// Reload in place if the asset is loaded
+		if let Some(mut asset) = slot.state.get_mut() {
+			if let Err(err) = loader.reload(&slot.path.as_path(), &mut asset) {
+				println!("Failed to reload asset {:?}: {}", slot.path.as_path(), err);
+			}
+			return;
+		}
+		
+		match loader.load(&slot.path.as_path()) {
+			Ok(asset) => slot.state.set_state(LoadState::Ready(asset)),
+			Err(err) => {
+				println!("Failed to reload asset {:?}: {}", slot.path.as_path(), err);
+				slot.state.set_state(LoadState::Failed(err));
+			}
+		}
+	}
+	
+	fn get_or_insert_with<F: FnOnce(&AssetSlot<T>)>(entries: &mut HashMap<AssetPathBuf, Rc<AssetSlot<T>>>, path: &AssetPath, start_load: F) -> Handle<T> {
+		let key = Self::cache_key(path);
+		
+		if let Some(slot) = entries.get(&key) {
+			return Handle {slot: Rc::clone(slot)};
+		}
+		
+		// Create new slot and start loading
+		let slot = Rc::new(AssetSlot {
+			path: key.clone(),
+			state: LoadHandle::new_pending(),
+		});
+		start_load(&slot);
+		
+		entries.insert(key, Rc::clone(&slot));
+		Handle {slot}
+	}
+	
+	/// Cache entries are keyed by absolute path so `a/b` and `/a/b` share the same entry.
+	fn cache_key(path: &AssetPath) -> AssetPathBuf {
+		path.to_absolute()
+	}
+	
+	pub fn new(loader: Box<dyn AssetLoader<T>>) -> Self {
+		Self {
+			loader,
+			entries: HashMap::new(),
+		}
+	}
+}
+
+struct AssetSlot<T> {
+	path: AssetPathBuf,
+	state: LoadHandle<T>,
+}
+
+/// Strong reference to a cached asset.
+pub struct Handle<T> {
+	slot: Rc<AssetSlot<T>>,
+}
+
+impl<T> Handle<T> {
+	pub fn path<'a>(&'a self) -> AssetPath<'a> {
+		self.slot.path.as_path()
+	}
+	
+	pub fn status(&self) -> LoadStatus {
+		self.slot.state.status()
+	}
+	
+	pub fn is_ready(&self) -> bool {
+		self.slot.state.is_ready()
+	}
+	
+	/// Borrows the asset, or returns `None` if it is not loaded (yet).
+	pub fn get(&self) -> Option<Ref<'_, T>> {
+		self.slot.state.get()
+	}
+	
+	/// Mutably borrows the asset, or returns `None` if it is not loaded (yet).
+	pub fn get_mut(&self) -> Option<RefMut<'_, T>> {
+		self.slot.state.get_mut()
+	}
+	
+	pub fn load_handle(&self) -> &LoadHandle<T> {
+		&self.slot.state
+	}
+	
+	pub fn downgrade(&self) -> WeakHandle<T> {
+		WeakHandle {
+			slot: Rc::downgrade(&self.slot),
+		}
+	}
+	
+	/// Checks if both handles refer to the same cache entry.
+	pub fn ptr_eq(&self, other: &Handle<T>) -> bool {
+		Rc::ptr_eq(&self.slot, &other.slot)
+	}
+}
+
+impl<T> Clone for Handle<T> {
+	fn clone(&self) -> Self {
+		Self {
+			slot: Rc::clone(&self.slot),
+		}
+	}
+}
+
+impl<T> fmt::Debug for Handle<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		write!(f, "Handle({:?}, {:?})", self.path(), self.status())
+	}
+}
+
+/// Weak reference to a cached asset that doesn't keep it from being unloaded.
+pub struct WeakHandle<T> {
+	slot: Weak<AssetSlot<T>>,
+}
+
+impl<T> WeakHandle<T> {
+	/// Returns a strong handle, or `None` if the asset was unloaded.
+	pub fn upgrade(&self) -> Option<Handle<T>> {
+		self.slot.upgrade().map(|slot| Handle {slot})
+	}
+	
+	pub fn new() -> Self {
+		Self {
+			slot: Weak::new(),
+		}
+	}
+}
+
+impl<T> Clone for WeakHandle<T> {
+	fn clone(&self) -> Self {
+		Self {
+			slot: Weak::clone(&self.slot),
+		}
+	}
+}
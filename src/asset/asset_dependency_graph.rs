@@ -0,0 +1,185 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::error;
+use std::fs::{self, OpenOptions};
+use std::io::Read;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::asset::{AssetPath, AssetPathBuf};
+
+/// Records which files every asset was built from, together
+/// with the content hashes of those files at build time.
+///
+/// Answers two questions:
+/// - "What depends on this file?" (`changed_files` and `dependents_of`, used for hot reloading)
+/// - "Is this asset stale?" (`is_stale`, used by the converters)
+///
+/// All paths are stored as absolute asset paths.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename = "AssetManifest")]
+pub struct AssetDependencyGraph {
+	nodes: BTreeMap<AssetPathBuf, AssetNode>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AssetNode {
+	/// Hash of the asset file itself at the time it was recorded (`None` if it had no backing file)
+	pub content_hash: Option<ContentHash>,
+	pub dependencies: Vec<AssetDependency>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AssetDependency {
+	pub path: AssetPathBuf,
+	/// Hash of the dependency at the time the asset was built (`None` if the file was missing)
+	pub content_hash: Option<ContentHash>,
+}
+
+impl AssetDependencyGraph {
+	/// Inserts or replaces the node of an asset.
+	pub fn insert(&mut self, asset: &AssetPath, node: AssetNode) {
+		self.nodes.insert(asset.to_absolute(), node);
+	}
+	
+	pub fn remove(&mut self, asset: &AssetPath) -> Option<AssetNode> {
+		self.nodes.remove(&asset.to_absolute())
+	}
+	
+	pub fn node(&self, asset: &AssetPath) -> Option<&AssetNode> {
+		self.nodes.get(&asset.to_absolute())
+	}
+	
+	/// Returns the direct dependencies of an asset.
+	pub fn dependencies_of(&self, asset: &AssetPath) -> Vec<AssetPathBuf> {
+		self.node(asset).map_or(Vec::new(), |n| n.dependencies.iter().map(|d| d.path.clone()).collect())
+	}
+	
+	/// Returns all assets that depend on the given file, directly or transitively.
+	/// Direct dependents come first, the file itself is not included.
+	pub fn dependents_of(&self, file: &AssetPath) -> Vec<AssetPathBuf> {
+		let mut result = Vec::new();
+		let mut visited = HashSet::new();
+		let mut queue = VecDeque::new();
+		
+		let start = file.to_absolute();
+		visited.insert(start.clone());
+		queue.push_back(start);
+		
+		while let Some(current) = queue.pop_front() {
+			for (asset, node) in &self.nodes {
+				if !visited.contains(asset) && node.dependencies.iter().any(|d| d.path == current) {
+					visited.insert(asset.clone());
+					result.push(asset.clone());
+					queue.push_back(asset.clone());
+				}
+			}
+		}
+		
+		result
+	}
+	
+	/// Returns all recorded files, assets and dependencies, whose content changed since they
+	/// were recorded, sorted by path. `current_hash` works like for `is_stale`.
+	///
+	/// Dependencies with a node of their own are only checked through that node.
+	pub fn changed_files<F>(&self, current_hash: F) -> Vec<AssetPathBuf> where F: Fn(&AssetPath) -> Option<ContentHash> {
+		let mut changed = BTreeSet::new();
+		
+		for (asset, node) in &self.nodes {
+			if node.content_hash.is_some() && current_hash(&asset.as_path()) != node.content_hash {
+				changed.insert(asset.clone());
+			}
+			
+			for dependency in &node.dependencies {
+				if !changed.contains(&dependency.path) && !self.nodes.contains_key(&dependency.path) && current_hash(&dependency.path.as_path()) != dependency.content_hash {
+					changed.insert(dependency.path.clone());
+				}
+			}
+		}
+		
+		changed.into_iter().collect()
+	}
+	
+	/// Checks if an asset has to be rebuilt.
+	///
+	/// An asset is stale if it was never recorded, if its own file changed since, or if any
+	/// of its dependencies changed or are stale themselves. `current_hash` should return the
+	/// current content hash of a file, or `None` if it doesn't exist. Dependencies with a node of
+	/// their own are checked through that node, the hash recorded for the dependency is ignored.
+	pub fn is_stale<F>(&self, asset: &AssetPath, current_hash: F) -> bool where F: Fn(&AssetPath) -> Option<ContentHash> {
+		let mut visited = HashSet::new();
+		self.is_stale_recursive(&asset.to_absolute(), &current_hash, &mut visited)
+	}
+	
+	fn is_stale_recursive<F>(&self, asset: &AssetPathBuf, current_hash: &F, visited: &mut HashSet<AssetPathBuf>) -> bool where F: Fn(&AssetPath) -> Option<ContentHash> {
+		// Dependency cycles can't make anything stale on their own
+		if !visited.insert(asset.clone()) {
+			return false;
+		}
+		
+		let node = match self.nodes.get(asset) {
+			Some(node) => node,
+			None => return true,
+		};
+		
+		// Check the asset file itself
+		if node.content_hash.is_some() && current_hash(&asset.as_path()) != node.content_hash {
+			return true;
+		}
+		
+		// Check the dependencies
+		node.dependencies.iter().any(|d| {
+			if self.nodes.contains_key(&d.path) {
+				self.is_stale_recursive(&d.path, current_hash, visited)
+			}
+			else {
+				current_hash(&d.path.as_path()) != d.content_hash
+			}
+		})
+	}
+	
+	pub fn len(&self) -> usize {
+		self.nodes.len()
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.nodes.is_empty()
+	}
+	
+	pub fn save_manifest(&self, fs_path: &Path) -> Result<(), Box<dyn error::Error>> {
+		let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+		fs::write(fs_path, contents)?;
+		Ok(())
+	}
+	
+	pub fn load_manifest(fs_path: &Path) -> Result<AssetDependencyGraph, Box<dyn error::Error>> {
+		let mut file = OpenOptions::new().read(true).open(fs_path)?;
+		let mut buffer = String::new();
+		file.read_to_string(&mut buffer)?;
+		
+		Ok(ron::de::from_str::<AssetDependencyGraph>(&buffer)?)
+	}
+	
+	pub fn new() -> Self {
+		Self {
+			nodes: BTreeMap::new(),
+		}
+	}
+}
+
+/// Stable 64 bit FNV-1a hash of a file's contents.
+///
+/// This has to stay the same across runs and builds since it ends up in
+/// the manifest, so `std::collections::hash_map::DefaultHasher` can't be used.
+pub type ContentHash = u64;
+
+pub fn hash_content(data: &[u8]) -> ContentHash {
+	const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+	const FNV_PRIME: u64 = 0x100000001b3;
+	
+	let mut hash = FNV_OFFSET_BASIS;
+	for byte in data {
+		hash ^= *byte as u64;
+		hash = hash.wrapping_mul(FNV_PRIME);
+	}
+	hash
+}
@@ -0,0 +1,252 @@
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+use tokio::runtime::{Builder, Runtime};
+
+/// Error type used by the decode and upload stages of an asset load.
+/// Needs to be `Send` because decode errors travel back from the worker threads.
+pub type AssetLoadError = Box<dyn error::Error + Send + Sync>;
+
+/// Loads assets asynchronously in two stages.
+///
+/// The first stage (decoding, parsing, any other pure cpu work) runs
+/// as a task on the tokio worker threads. Once it finishes, the decoded
+/// data is put into the upload queue and the second stage (creating the
+/// gl objects) is run on the main thread by `process_uploads` which
+/// is called once per frame with a time budget.
+pub struct AssetLoadService {
+	runtime: Runtime,
+	upload_queue: VecDeque<Box<dyn QueuedUpload>>,
+}
+
+impl AssetLoadService {
+	/// Queues a new asynchronous load and returns a handle to the future asset.
+	///
+	/// `decode` is run on a worker thread, `upload` is later run on the main
+	/// thread (with the gl context current) inside of `process_uploads`.
+	pub fn load<D, T, F, U>(&mut self, decode: F, upload: U) -> LoadHandle<T>
+			where D: Send + 'static, T: 'static, F: FnOnce() -> Result<D, AssetLoadError> + Send + 'static, U: FnOnce(D) -> Result<T, AssetLoadError> + 'static {
+		let handle = LoadHandle::new_pending();
+		self.load_into(&handle, decode, upload);
+		handle
+	}
+	
+	/// Like `load` but completes the given (pending) handle instead of creating a new one.
+	pub fn load_into<D, T, F, U>(&mut self, handle: &LoadHandle<T>, decode: F, upload: U)
+			where D: Send + 'static, T: 'static, F: FnOnce() -> Result<D, AssetLoadError> + Send + 'static, U: FnOnce(D) -> Result<T, AssetLoadError> + 'static {
+		let (sender, receiver) = mpsc::channel();
+		
+		// Spawn the decode task
+		self.runtime.spawn(async move {
+			// The receiver may already be gone if the service was dropped, so ignore send errors
+			let _ = sender.send(decode());
+		});
+		
+		// Queue the upload
+		self.upload_queue.push_back(Box::new(PendingUpload {
+			receiver,
+			decoded: None,
+			upload: Some(upload),
+			handle: handle.clone(),
+		}));
+	}
+	
+	/// Runs the upload stage of all loads whose decode stage has finished,
+	/// until the given time budget is used up. At least one upload is always
+	/// processed (if one is ready) so loading never stalls completely.
+	///
+	/// Returns the number of processed uploads.
+	pub fn process_uploads(&mut self, time_budget: Duration) -> usize {
+		let start_time = Instant::now();
+		let mut num_processed = 0;
+		
+		let mut i = 0;
+		while i < self.upload_queue.len() {
+			// Check the budget
+			if num_processed > 0 && start_time.elapsed() >= time_budget {
+				break;
+			}
+			
+			if self.upload_queue[i].poll_decoded() {
+				// Index is in bounds
+				let upload = self.upload_queue.remove(i).unwrap();
+				upload.execute();
+				num_processed += 1;
+			}
+			else {
+				i += 1;
+			}
+		}
+		
+		num_processed
+	}
+	
+	/// Returns the number of loads that haven't finished their upload yet.
+	pub fn num_pending(&self) -> usize {
+		self.upload_queue.len()
+	}
+	
+	pub fn new() -> Result<Self, io::Error> {
+		let runtime = Builder::new()
+			.threaded_scheduler()
+			.thread_name("asset-loader")
+			.build()?;
+		
+		Ok(Self {
+			runtime,
+			upload_queue: VecDeque::new(),
+		})
+	}
+}
+
+trait QueuedUpload {
+	/// Checks if the decode stage has finished (successfully or not).
+	fn poll_decoded(&mut self) -> bool;
+	
+	/// Runs the upload stage and completes the handle.
+	fn execute(self: Box<Self>);
+}
+
+struct PendingUpload<D, T, U> {
+	receiver: Receiver<Result<D, AssetLoadError>>,
+	decoded: Option<Result<D, AssetLoadError>>,
+	upload: Option<U>,
+	handle: LoadHandle<T>,
+}
+
+impl<D, T, U> QueuedUpload for PendingUpload<D, T, U> where U: FnOnce(D) -> Result<T, AssetLoadError> {
+	fn poll_decoded(&mut self) -> bool {
+		if self.decoded.is_none() {
+			match self.receiver.try_recv() {
+				Ok(result) => self.decoded = Some(result),
+				Err(TryRecvError::Empty) => {},
+				Err(TryRecvError::Disconnected) => {
+					// The decode task died without sending anything (it most likely panicked)
+					self.decoded = Some(Err(AssetLoadError::from("Asset decode task terminated unexpectedly")));
+				}
+			}
+		}
+		self.decoded.is_some()
+	}
+	
+	fn execute(mut self: Box<Self>) {
+		let decoded = self.decoded.take().expect("Upload executed before decode finished");
+		
+		let result = match decoded {
+			Ok(data) => (self.upload.take().unwrap())(data),
+			Err(err) => Err(err),
+		};
+		
+		match result {
+			Ok(asset) => self.handle.set_state(LoadState::Ready(asset)),
+			Err(err) => {
+				println!("Asset load failed: {}", err);
+				self.handle.set_state(LoadState::Failed(err));
+			}
+		}
+	}
+}
+
+pub enum LoadState<T> {
+	Pending,
+	Ready(T),
+	Failed(AssetLoadError),
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum LoadStatus {
+	Pending,
+	Ready,
+	Failed,
+}
+
+/// Shared handle to an asset that is (possibly) still being loaded.
+/// Handles are main thread only, only the decode stage ever leaves it.
+pub struct LoadHandle<T> {
+	state: Rc<RefCell<LoadState<T>>>,
+}
+
+impl<T> LoadHandle<T> {
+	pub fn status(&self) -> LoadStatus {
+		match &*self.state.borrow() {
+			LoadState::Pending => LoadStatus::Pending,
+			LoadState::Ready(_) => LoadStatus::Ready,
+			LoadState::Failed(_) => LoadStatus::Failed,
+		}
+	}
+	
+	pub fn is_ready(&self) -> bool {
+		self.status() == LoadStatus::Ready
+	}
+	
+	/// Borrows the loaded asset, or returns `None` if it is not ready (yet).
+	pub fn get(&self) -> Option<Ref<'_, T>> {
+		let state = self.state.borrow();
+		if let LoadState::Ready(_) = &*state {
+			Some(Ref::map(state, |s| match s {
+				LoadState::Ready(asset) => asset,
+				_ => unreachable!(),
+			}))
+		}
+		else {
+			None
+		}
+	}
+	
+	/// Mutably borrows the loaded asset, or returns `None` if it is not ready (yet).
+	pub fn get_mut(&self) -> Option<RefMut<'_, T>> {
+		let state = self.state.borrow_mut();
+		if let LoadState::Ready(_) = &*state {
+			Some(RefMut::map(state, |s| match s {
+				LoadState::Ready(asset) => asset,
+				_ => unreachable!(),
+			}))
+		}
+		else {
+			None
+		}
+	}
+	
+	/// Returns the error message if the load failed.
+	pub fn error_message(&self) -> Option<String> {
+		match &*self.state.borrow() {
+			LoadState::Failed(err) => Some(err.to_string()),
+			_ => None,
+		}
+	}
+	
+	pub fn set_state(&self, state: LoadState<T>) {
+		*self.state.borrow_mut() = state;
+	}
+	
+	pub fn new_pending() -> Self {
+		Self {
+			state: Rc::new(RefCell::new(LoadState::Pending)),
+		}
+	}
+	
+	pub fn new_ready(asset: T) -> Self {
+		Self {
+			state: Rc::new(RefCell::new(LoadState::Ready(asset))),
+		}
+	}
+}
+
+impl<T> Clone for LoadHandle<T> {
+	fn clone(&self) -> Self {
+		Self {
+			state: Rc::clone(&self.state),
+		}
+	}
+}
+
+impl<T> fmt::Debug for LoadHandle<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		write!(f, "LoadHandle({:?})", self.status())
+	}
+}
@@ -1,15 +1,33 @@
+use std::fs;
+use std::io;
 use std::path::{PathBuf, Path};
-use crate::asset::AssetPath;
+use crate::asset::{AssetPath, AssetPathBuf, AssetDependencyGraph, AssetNode, AssetDependency, ContentHash, hash_content};
 
 pub static mut ASSET_MANAGER_INSTANCE: AssetManager = AssetManager::new();
 
+/// File name of the dependency manifest, relative to the asset root
+const MANIFEST_FILE_NAME: &str = "asset_manifest.ron";
+
 pub struct AssetManager {
 	asset_root: Option<PathBuf>,
+	dependency_graph: Option<AssetDependencyGraph>,
 }
 
 impl AssetManager {
 	pub fn init(&mut self, asset_root: PathBuf) {
 		self.asset_root = Some(asset_root);
+		
+		// Load the dependency manifest from the last run
+		let manifest_path = self.manifest_fs_path();
+		self.dependency_graph = Some(if manifest_path.exists() {
+			AssetDependencyGraph::load_manifest(&manifest_path).unwrap_or_else(|err| {
+				println!("Failed to load asset manifest, starting with an empty one: {}", err);
+				AssetDependencyGraph::new()
+			})
+		}
+		else {
+			AssetDependencyGraph::new()
+		});
 	}
 	
 	pub fn resolve_asset_fs_path(&self, asset_path: &AssetPath) -> PathBuf {
@@ -24,9 +42,93 @@ impl AssetManager {
 		self.asset_root.as_ref().unwrap().as_path().join(relative_asset_path)
 	}
 	
+	/// Finds all assets whose file name ends with `suffix` (e.g. `".program"`), sorted by path.
+	pub fn find_assets(&self, suffix: &str) -> Result<Vec<AssetPathBuf>, io::Error> {
+		fn walk(dir: &Path, relative_dir: &str, suffix: &str, found: &mut Vec<AssetPathBuf>) -> Result<(), io::Error> {
+			for entry in fs::read_dir(dir)? {
+				let entry = entry?;
+				let file_name = entry.file_name().to_string_lossy().into_owned();
+				let relative_path = format!("{}/{}", relative_dir, file_name);
+				
+				if entry.file_type()?.is_dir() {
+					walk(&entry.path(), &relative_path, suffix, found)?;
+				}
+				else if file_name.ends_with(suffix) {
+					found.push(AssetPathBuf::from_owned(relative_path));
+				}
+			}
+			Ok(())
+		}
+		
+		let mut found = Vec::new();
+		walk(self.asset_root.as_ref().expect("Asset manager not initialized yet"), "", suffix, &mut found)?;
+		found.sort();
+		Ok(found)
+	}
+	
+	pub fn dependency_graph(&self) -> &AssetDependencyGraph {
+		self.dependency_graph.as_ref().expect("Asset manager not initialized yet")
+	}
+	
+	pub fn dependency_graph_mut(&mut self) -> &mut AssetDependencyGraph {
+		self.dependency_graph.as_mut().expect("Asset manager not initialized yet")
+	}
+	
+	/// Returns the current content hash of an asset file, or `None` if it can't be read.
+	pub fn hash_asset_file(&self, asset_path: &AssetPath) -> Option<ContentHash> {
+		fs::read(self.resolve_asset_fs_path(asset_path)).ok().map(|data| hash_content(&data))
+	}
+	
+	/// Records that `asset` was built from the given files, hashing all of them as they are now.
+	pub fn record_dependencies(&mut self, asset: &AssetPath, dependencies: &[AssetPathBuf]) {
+		let node = AssetNode {
+			content_hash: self.hash_asset_file(asset),
+			dependencies: dependencies.iter().map(|path| AssetDependency {
+				path: path.as_path().to_absolute(),
+				content_hash: self.hash_asset_file(&path.as_path()),
+			}).collect(),
+		};
+		self.dependency_graph_mut().insert(asset, node);
+	}
+	
+	/// Records an asset with a content hash its loader computed from the bytes it already read, so the
+	/// file isn't read again. Dependencies are expected to be assets themselves, which are checked
+	/// through their own nodes, so they aren't hashed either (their hash is taken from the node if recorded).
+	pub fn record_asset(&mut self, asset: &AssetPath, content_hash: Option<ContentHash>, dependencies: &[AssetPathBuf]) {
+		let node = AssetNode {
+			content_hash,
+			dependencies: dependencies.iter().map(|path| AssetDependency {
+				path: path.as_path().to_absolute(),
+				content_hash: self.dependency_graph().node(&path.as_path()).and_then(|n| n.content_hash),
+			}).collect(),
+		};
+		self.dependency_graph_mut().insert(asset, node);
+	}
+	
+	/// Checks if an asset has to be rebuilt because it or any of its recorded dependencies changed.
+	pub fn is_asset_stale(&self, asset: &AssetPath) -> bool {
+		self.dependency_graph().is_stale(asset, |path| self.hash_asset_file(path))
+	}
+	
+	/// Returns the recorded files whose content changed since they were recorded.
+	pub fn changed_files(&self) -> Vec<AssetPathBuf> {
+		self.dependency_graph().changed_files(|path| self.hash_asset_file(path))
+	}
+	
+	pub fn manifest_fs_path(&self) -> PathBuf {
+		self.resolve_asset_fs_path(&AssetPath::from_str(MANIFEST_FILE_NAME))
+	}
+	
+	pub fn save_manifest(&self) {
+		if let Err(err) = self.dependency_graph().save_manifest(&self.manifest_fs_path()) {
+			println!("Failed to save asset manifest: {}", err);
+		}
+	}
+	
 	pub const fn new() -> Self {
 		Self {
 			asset_root: None,
+			dependency_graph: None,
 		}
 	}
 }
@@ -1,5 +1,8 @@
 use std::fmt::{self, Debug};
+use serde::{Serialize, Deserialize};
 
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct AssetPathBuf {
 	inner_path: String,
 }
@@ -38,6 +41,17 @@ impl<'a> AssetPath<'a> {
 		!self.is_absolute()
 	}
 	
+	/// Returns the path as absolute path buf (relative paths are
+	/// treated as relative to the asset root).
+	pub fn to_absolute(&self) -> AssetPathBuf {
+		if self.is_absolute() {
+			AssetPathBuf::from(self.inner_path_slice)
+		}
+		else {
+			AssetPathBuf::from_owned(format!("/{}", self.inner_path_slice))
+		}
+	}
+	
 	pub fn join(&self, other_relative: &AssetPath) -> Option<AssetPathBuf> {
 		// If `other` is absolute joining doesn't make sense
 		if other_relative.is_absolute() {
@@ -94,6 +108,12 @@ impl<'a> AssetPath<'a> {
 	}
 }
 
+impl Debug for AssetPathBuf {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		write!(f, "AssetPathBuf(\"{}\")", self.inner_path)
+	}
+}
+
 impl<'a> Debug for AssetPath<'a> {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
 		write!(f, "AssetPath(\"{}\")", self.inner_path_slice)
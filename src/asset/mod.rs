@@ -1,2 +1,5 @@
 mod asset_manager; pub use asset_manager::*;
 mod asset_path; pub use asset_path::*;
+mod asset_load_service; pub use asset_load_service::*;
+mod asset_cache; pub use asset_cache::*;
+mod asset_dependency_graph; pub use asset_dependency_graph::*;
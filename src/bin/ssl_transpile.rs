@@ -0,0 +1,223 @@
+//! Transpiles the ssl shaders of `.program` assets to glsl without starting the demo.
+//!
+//! ```text
+//! ssl_transpile [options] <program asset path>...
+//!
+//!     --asset-root <dir>         Asset folder (default: `assets`)
+//!     --all                      Transpile every `.program` in the asset folder
+//!     --out <dir>                Write `<dir>/<program path>.<stage>.glsl` files instead of printing to stdout
+//!     --check <dir>              Compare the output with the files in `<dir>` instead (snapshot check)
+//!     --permutation <NAME=VALUE> Transpile the given permutation instead of the default one (repeatable)
+//!     --keep-unused              Don't strip unused exported functions
+//!     --validate                 Also validate the glsl of every permutation (needs the `glsl-validation` feature)
+//! ```
+//!
+//! Diagnostics go to stderr. Exits with 1 if any program failed or didn't match its snapshot.
+//! The snapshots in `tests/snapshots/ssl` are updated with `ssl_transpile --all --out tests/snapshots/ssl`.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use render_demo::asset::{AssetPathBuf, ASSET_MANAGER_INSTANCE};
+use render_demo::render::shader::managed::TranspiledProgram;
+
+enum OutputMode {
+	Stdout,
+	Write(PathBuf),
+	Check(PathBuf),
+}
+
+struct Options {
+	asset_root: PathBuf,
+	all_programs: bool,
+	output_mode: OutputMode,
+	permutation: Vec<(String, String)>,
+	keep_unused: bool,
+	validate: bool,
+	programs: Vec<AssetPathBuf>,
+}
+
+fn parse_args() -> Result<Options, String> {
+	let mut options = Options {
+		asset_root: PathBuf::from("assets"),
+		all_programs: false,
+		output_mode: OutputMode::Stdout,
+		permutation: Vec::new(),
+		keep_unused: false,
+		validate: false,
+		programs: Vec::new(),
+	};
+	
+	let mut args = env::args().skip(1);
+	while let Some(arg) = args.next() {
+		let mut value = |name: &str| args.next().ok_or_else(|| format!("Missing value for {}", name));
+		
+		match arg.as_str() {
+			"--asset-root" => options.asset_root = PathBuf::from(value("--asset-root")?),
+			"--all" => options.all_programs = true,
+			"--out" => options.output_mode = OutputMode::Write(PathBuf::from(value("--out")?)),
+			"--check" => options.output_mode = OutputMode::Check(PathBuf::from(value("--check")?)),
+			"--permutation" => {
+				let permutation = value("--permutation")?;
+				let mut parts = permutation.splitn(2, '=');
+				match (parts.next(), parts.next()) {
+					(Some(name), Some(value)) => options.permutation.push((String::from(name), String::from(value))),
+					_ => return Err(format!("Expected NAME=VALUE for --permutation, got {}", permutation)),
+				}
+			}
+			"--keep-unused" => options.keep_unused = true,
+			"--validate" => options.validate = true,
+			"--help" | "-h" => return Err(String::new()),
+			_ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+			_ => options.programs.push(AssetPathBuf::from(&arg)),
+		}
+	}
+	
+	if !options.all_programs && options.programs.is_empty() {
+		return Err(String::from("No programs given"));
+	}
+	if options.validate && !cfg!(feature = "glsl-validation") {
+		return Err(String::from("--validate needs the glsl-validation feature"));
+	}
+	Ok(options)
+}
+
+fn main() {
+	let options = match parse_args() {
+		Ok(options) => options,
+		Err(message) => {
+			if !message.is_empty() {
+				eprintln!("error: {}", message);
+			}
+			eprintln!("usage: ssl_transpile [--asset-root <dir>] [--all] [--out <dir> | --check <dir>] [--permutation <NAME=VALUE>]... [--keep-unused] [--validate] <program>...");
+			process::exit(2);
+		}
+	};
+	
+	let asset_manager = unsafe {&mut ASSET_MANAGER_INSTANCE};
+	asset_manager.init(options.asset_root.clone());
+	
+	// Collect the programs
+	let mut programs = options.programs.clone();
+	if options.all_programs {
+		match asset_manager.find_assets(".program") {
+			Ok(found) => programs.extend(found),
+			Err(err) => {
+				eprintln!("error: Failed to search the asset folder {}: {}", options.asset_root.display(), err);
+				process::exit(1);
+			}
+		}
+	}
+	
+	let mut num_failed = 0;
+	for program_path in &programs {
+		let program = match TranspiledProgram::load(&program_path.as_path(), !options.keep_unused) {
+			Ok(program) => program,
+			Err(err) => {
+				eprintln!("{:?}: {}", program_path, err);
+				num_failed += 1;
+				continue;
+			}
+		};
+		
+		// Permutation values that don't exist in this program are ignored when transpiling many programs
+		let mut key = program.permutation_layout.default_key();
+		for (name, value) in &options.permutation {
+			match program.permutation_layout.with_value(key, name, value) {
+				Some(new_key) => key = new_key,
+				None if options.all_programs => {},
+				None => {
+					eprintln!("{:?}: Unknown permutation value {} = {}", program_path, name, value);
+					num_failed += 1;
+				}
+			}
+		}
+		
+		for (stage, _) in &program.shaders {
+			let source_code = program.variant_source(*stage, key).unwrap().source_code;
+			let file_name = program.stage_file_name(*stage);
+			
+			match &options.output_mode {
+				OutputMode::Stdout => {
+					println!("// ======== {} ========", file_name);
+					print!("{}", source_code);
+				}
+				OutputMode::Write(out_dir) => {
+					let out_path = out_dir.join(&file_name);
+					let result = fs::create_dir_all(out_path.parent().unwrap()).and_then(|_| fs::write(&out_path, &source_code));
+					if let Err(err) = result {
+						eprintln!("error: Failed to write {}: {}", out_path.display(), err);
+						num_failed += 1;
+					}
+				}
+				OutputMode::Check(snapshot_dir) => {
+					let snapshot_path = snapshot_dir.join(&file_name);
+					match fs::read_to_string(&snapshot_path) {
+						Ok(snapshot) if snapshot == source_code => {},
+						Ok(_) => {
+							eprintln!("{}: transpiled output differs from the snapshot", snapshot_path.display());
+							num_failed += 1;
+						}
+						Err(err) => {
+							eprintln!("{}: failed to read snapshot: {}", snapshot_path.display(), err);
+							num_failed += 1;
+						}
+					}
+				}
+			}
+		}
+		
+		if options.validate {
+			num_failed += validate_program(&program);
+		}
+	}
+	
+	if num_failed > 0 {
+		eprintln!("{} error(s)", num_failed);
+		process::exit(1);
+	}
+}
+
+/// Validates every permutation of every stage and returns the number of failed shaders.
+#[cfg(feature = "glsl-validation")]
+fn validate_program(program: &TranspiledProgram) -> u32 {
+	use render_demo::structured_shader_language::{GlslValidator, GlslValidation};
+	
+	let mut validator = GlslValidator::new();
+	let mut num_failed = 0;
+	
+	for (stage, _) in &program.shaders {
+		for key in program.permutation_layout.all_keys() {
+			let source = program.variant_source(*stage, key).unwrap();
+			let defines = program.permutation_layout.defines(key).iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>();
+			
+			match validator.validate(&source, *stage) {
+				Ok(GlslValidation::Valid) => {},
+				Ok(GlslValidation::Skipped(warning)) => {
+					eprintln!("{}", warning);
+					// All variants would be skipped for the same reason
+					break;
+				}
+				Err(diagnostics) => {
+					if defines.is_empty() {
+						eprintln!("{}: validation failed", program.stage_file_name(*stage));
+					} else {
+						eprintln!("{} [{}]: validation failed", program.stage_file_name(*stage), defines.join(" "));
+					}
+					for diagnostic in &diagnostics {
+						eprintln!("{}", diagnostic);
+					}
+					num_failed += 1;
+				}
+			}
+		}
+	}
+	
+	num_failed
+}
+
+#[cfg(not(feature = "glsl-validation"))]
+fn validate_program(_program: &TranspiledProgram) -> u32 {
+	0
+}
@@ -1,22 +1,22 @@
 use std::cell::RefCell;
 use std::error;
 use std::ffi::CStr;
-use std::fs::OpenOptions;
 use std::panic;
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::{self, Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use byte_slice_cast::*;
-use byteorder::{ByteOrder, LittleEndian};
-use cgmath::{Deg, InnerSpace, Quaternion, Rad, Rotation, vec2, vec3, Vector2, Vector3};
+use cgmath::{Deg, Quaternion, Rad, Rotation, vec3, Vector3};
 use gl_bindings::gl;
 use glfw::{SwapInterval, WindowEvent};
-use crate::asset::{ASSET_MANAGER_INSTANCE, AssetPath};
+use crate::asset::{ASSET_MANAGER_INSTANCE, AssetLoadService, AssetPath, Handle};
 use crate::camera::{Camera, OrbitAngles, PerspectiveProjection};
 use crate::camera::utils::fovx_to_fovy;
-use crate::model::ply::{PlyMeshLoader, PlyReadError, PullEvent};
-use crate::render::{ImageFormat, RenderGlobal, TestVertexBuffer, Texture};
+use crate::render::{GraphicsSetting, GraphicsSettingsTable, Mesh, QualityPreset, RenderGlobal, TestVertexBuffer, UpscaleFilter};
+use crate::render::material::Material;
+use crate::render::clustered::PointLight;
+use crate::render::shader::managed::PROGRAM_BINARY_CACHE_INSTANCE;
 use crate::render::separable_sss::{DEFAULT_HUMAN_SKIN_FALLOFF_FACTORS, DEFAULT_HUMAN_SKIN_STRENGTH_FACTORS, SubsurfaceKernelGenerator};
 use crate::utils::lazy_option::Lazy;
 use crate::utils::option_overwrite::OptionOverwrite;
@@ -24,6 +24,18 @@ use crate::windowing::{GlfwContext, Window};
 
 pub static mut DEMO_INSTANCE: Option<Demo> = None;
 
+/// Max time spent per frame creating gl objects for loaded assets
+const ASSET_UPLOAD_TIME_BUDGET: Duration = Duration::from_millis(4);
+
+/// Initial size of the main window, it can be resized afterwards
+const INITIAL_WINDOW_SIZE: (u32, u32) = (1600, 900);
+
+/// The user's graphics settings, relative to the working directory
+const GRAPHICS_SETTINGS_FILE_NAME: &str = "graphics_settings.ron";
+
+/// Directory of the program binary cache, relative to the asset root
+const PROGRAM_BINARY_CACHE_DIR: &str = "shader_cache";
+
 pub fn start() {
 	// Init the demo object
 	let demo = Demo::init().expect("Failed to init demo");
@@ -49,6 +61,8 @@ pub struct Demo {
 //	pub window_channel: Option<Receiver<(f64, WindowEvent)>>,
 	
 	pub render_global: RenderGlobal,
+	pub asset_load_service: AssetLoadService,
+	pub graphics_settings: GraphicsSettingsTable,
 	
 	pub test_teapot_vbo: Option<TestVertexBuffer>,
 	pub test_head_model: Option<TestHeadModel>,
@@ -75,6 +89,9 @@ impl Demo {
 		// Init glfw
 		let glfw_context = GlfwContext::init()?;
 		
+		// Start asset loader threads
+		let asset_load_service = AssetLoadService::new()?;
+		
 		// Make instance and return
 		Ok(Self {
 			asset_folder,
@@ -85,6 +102,8 @@ impl Demo {
 //			window_channel: None,
 			
 			render_global: RenderGlobal::new(),
+			asset_load_service,
+			graphics_settings: GraphicsSettingsTable::new(),
 			
 			test_teapot_vbo: None,
 			test_head_model: None,
@@ -96,8 +115,18 @@ impl Demo {
 	}
 	
 	pub fn run(&mut self) {
-		//let resolution = (1280, 720);
-		let resolution = (1600, 900);
+		{// Load the user's graphics settings
+			let settings_path = std::env::current_dir().unwrap().join(GRAPHICS_SETTINGS_FILE_NAME);
+			if settings_path.exists() {
+				match GraphicsSettingsTable::load(&settings_path) {
+					Ok(settings) => self.graphics_settings = settings,
+					Err(err) => println!("Failed to load graphics settings, using the defaults: {}", err),
+				}
+			}
+			
+			let configuration = self.graphics_settings.resolve().expect("Loaded graphics settings are validated");
+			self.render_global.apply_configuration(configuration).unwrap();
+		}
 		
 		{// Create the main window
 			let window = Window::new(Rc::clone(&self.glfw_context));
@@ -106,7 +135,7 @@ impl Demo {
 			
 			window.init();
 			window.set_title(String::from("Render Demo"));
-			window.resize(resolution.0, resolution.1);
+			window.resize(INITIAL_WINDOW_SIZE.0, INITIAL_WINDOW_SIZE.1);
 			
 			// Center window
 			window.center_on_screen();
@@ -127,7 +156,7 @@ impl Demo {
 			
 			// Make context current
 			gl_context.make_current();
-			gl_context.set_swap_interval(SwapInterval::Sync(1));
+			gl_context.set_swap_interval(if self.render_global.configuration().vsync {SwapInterval::Sync(1)} else {SwapInterval::None});
 		}
 		
 //		// Init glfw
@@ -161,6 +190,10 @@ impl Demo {
 			println!("  RENDERER: \"{}\"", renderer);
 			println!("  VERSION: \"{}\"", version);
 			println!("  GLSL_VERSION: \"{}\"", glsl_version);
+			
+			// Program binaries are only valid for the exact driver they came from
+			let cache_dir = unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(&AssetPath::from_str(PROGRAM_BINARY_CACHE_DIR));
+			unsafe {&mut PROGRAM_BINARY_CACHE_INSTANCE}.init(cache_dir, &[&vendor, &renderer, &version]);
 		}
 		
 		// Setup gl debug output
@@ -185,128 +218,23 @@ impl Demo {
 			println!("}};");
 		}
 		
-		{// Load test model (lee head)
+		{// Queue test model loading (lee head)
 			// Log
 			println!("Loading lee head model");
 			
-//			let mut file = OpenOptions::new().read(true).open(r"C:\Users\Jan\Desktop\Lee Head\Lee Head.ply").expect("Failed to load test lee head model");
-			let mut file = OpenOptions::new().read(true).open(unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(&AssetPath::from_str("models/free_head/head.ply"))).expect("Failed to load test free head model");
-			
-			let loader = PlyMeshLoader::new(&mut file);
-			let mut puller = loader.parse_header().unwrap();
-			
-			// Get vertex and index num
-			let mut num_vertices = 0u32;
-			let mut num_indices = 0u32;
-			
-			for e in &puller.header().elements {
-				let name = e.name.as_str();
-				if name.eq("vertex") {
-					num_vertices = e.num_entries;
-				}
-				else if name.eq("face") {
-					num_indices = e.num_entries * 3;
-				}
-			}
-			
-			// Allocate data buffers
-			let mut vertex_data_buffer = vec![0u8; (num_vertices as usize) * 4*8];
-			let mut index_data_buffer = vec![0u8; (num_indices as usize) * 4];
-			
-			loop {
-//				let mut borrowed_puller = puller.borrow_mut();
-				match puller.next_event() {
-					PullEvent::Element(mut parser) => {
-						let elem_name = parser.element_descriptor().name.as_str();
-						if elem_name.eq("vertex") {
-							let mut read_buffer = [0u8; 32];
-							
-							let mut vertex_data_pos = 0usize;
-							'vertex_entry_loop: loop {
-								let res = parser.read_entry(&mut read_buffer);
-								
-								if let Err(PlyReadError::BufferTooSmall {min_buffer_size}) = res {
-									panic!("Buffer too small! (min {})", min_buffer_size);
-								}
-								else if let Err(PlyReadError::NoMoreEntries) = res {
-									break 'vertex_entry_loop;
-								}
-								else if let Ok(_) = res {
-									// Copy into vertex buffer
-									let final_pos = vertex_data_pos + 4 * 8;
-									vertex_data_buffer[vertex_data_pos..final_pos].copy_from_slice(&read_buffer);
-									vertex_data_pos = final_pos;
-								}
-							}
-						}
-						else if elem_name.eq("face") {
-							let mut read_buffer = [0u8; 1+3*4];
-							
-							let mut index_data_pos = 0usize;
-							'index_entry_loop: loop {
-								let res = parser.read_entry(&mut read_buffer);
-								
-								if let Err(PlyReadError::BufferTooSmall {min_buffer_size}) = res {
-									panic!("Buffer too small! (min {})", min_buffer_size);
-								}
-								else if let Err(PlyReadError::NoMoreEntries) = res {
-									break 'index_entry_loop;
-								}
-								else if let Ok(_) = res {
-									// Copy into index buffer
-									index_data_buffer[index_data_pos+0..index_data_pos+4].copy_from_slice(&read_buffer[1..5]);
-									index_data_buffer[index_data_pos+4..index_data_pos+8].copy_from_slice(&read_buffer[5..9]);
-									index_data_buffer[index_data_pos+8..index_data_pos+12].copy_from_slice(&read_buffer[9..13]);
-									index_data_pos += 3*4;
-								}
-							}
-						}
-					}
-					PullEvent::End => break,
+			let service = &mut self.asset_load_service;
+			let render_global = &mut self.render_global;
+			
+			self.test_head_model = match Material::load(&AssetPath::from_str("surfaces/free_head.surface"), &mut render_global.program_cache, &mut render_global.texture_cache, service) {
+				Ok(material) => Some(TestHeadModel {
+					mesh: render_global.mesh_cache.load_async(&AssetPath::from_str("models/free_head/head.ply"), service),
+					material,
+				}),
+				Err(err) => {
+					println!("Failed to load head surface: {}", err);
+					None
 				}
-			}
-			
-			// Generate tangents
-			let (index_data_buffer, vertex_data_buffer) = calculate_mesh_tangents(num_indices, index_data_buffer, num_vertices, vertex_data_buffer);
-			
-			// Allocate vertex buffers
-			let (vertex_buffer_gl, index_buffer_gl) = unsafe {
-				let mut buffers = [0 as gl::uint, 2];
-				gl::CreateBuffers(2 as gl::sizei, buffers.as_mut_ptr());
-				(buffers[0], buffers[1])
 			};
-			
-			// Upload data
-			unsafe {
-				gl::NamedBufferStorage(vertex_buffer_gl, vertex_data_buffer.len() as gl::sizeiptr, vertex_data_buffer.as_ptr() as *const gl::void, 0);
-				gl::NamedBufferStorage(index_buffer_gl, index_data_buffer.len() as gl::sizeiptr, index_data_buffer.as_ptr() as *const gl::void, 0);
-			}
-			
-			// Load textures
-			println!("Loading lee head textures");
-			
-//			let tex_albedo = Texture::load_png_from_path(Path::new(r"C:/Users/Jan/Desktop/Lee Head/lee_head_albedo.png"), ImageFormat::get(gl::SRGB8_ALPHA8)).expect("Failed to load albedo texture");
-//			let tex_normal = Texture::load_png_from_path(Path::new(r"C:/Users/Jan/Desktop/Lee Head/lee_head_normal.png"), ImageFormat::get(gl::RGBA8)).expect("Failed to load normal texture");
-//			let tex_transmission = Texture::load_png_from_path(Path::new(r"C:/Users/Jan/Desktop/Lee Head/lee_head_transmission.png"), ImageFormat::get(gl::RGBA8)).expect("Failed to load transmission texture");
-			
-			let tex_albedo = Texture::load_ktx_from_path(unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(&AssetPath::from_str("models/free_head/head_albedo_bc7.ktx")).as_path(), ImageFormat::get(gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM_ARB)).expect("Failed to load albedo texture");
-			let tex_normal = Texture::load_ktx_from_path(unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(&AssetPath::from_str("models/free_head/head_normal_bc1.ktx")).as_path(), ImageFormat::get(gl::COMPRESSED_RGB_S3TC_DXT1_EXT)).expect("Failed to load normal texture");
-//			let tex_albedo = Texture::load_png_from_path(unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(&AssetPath::from_str("models/free_head/head_albedo.png")).as_path(), ImageFormat::get(gl::SRGB8_ALPHA8)).expect("Failed to load albedo texture");
-//			let tex_normal = Texture::load_png_from_path(unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(&AssetPath::from_str("models/free_head/head_normal.png")).as_path(), ImageFormat::get(gl::RGBA8)).expect("Failed to load normal texture");
-			let tex_transmission = Texture::load_png_from_path(unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(&AssetPath::from_str("models/free_head/head_translucency.png")).as_path(), ImageFormat::get(gl::RGBA8)).expect("Failed to load transmission texture");
-			
-//			let tex_albedo = Texture::new(16, 16, 1, ImageFormat::get(gl::RGBA8));
-//			let tex_normal = Texture::new(16, 16, 1, ImageFormat::get(gl::RGBA8));
-//			let tex_transmission = Texture::new(16, 16, 1, ImageFormat::get(gl::RGBA8));
-			
-			self.test_head_model = Some(TestHeadModel {
-				vertex_buffer_gl,
-				index_buffer_gl,
-				num_indices,
-				tex_albedo,
-				tex_normal,
-				tex_transmission,
-			});
 		}
 		
 		// Create test teapot vbo
@@ -316,26 +244,39 @@ impl Demo {
 			buffer
 		});
 		
+		// Render at the actual size of the default framebuffer
+		let resolution = self.main_window.need().borrow().get_framebuffer_size();
+		
 		// Create main camera
 		self.test_active_camera = Some(Arc::new(Mutex::new({
-			let fovy = fovx_to_fovy(Rad::from(Deg(65.0)), resolution.0 as f32 /resolution.1 as f32);
+			let fovy = fovx_to_fovy(Rad::from(Deg(65.0)), INITIAL_WINDOW_SIZE.0 as f32 / INITIAL_WINDOW_SIZE.1 as f32);
 			let projection = PerspectiveProjection::new(fovy, 1.0/256.0, 4096.0, true, true);
 			let mut cam = Camera::new(Box::new(projection));
 			cam.translation = Vector3 {x: 0.0, y: 1.0, z: 4.0};
+			cam.resize_viewport(resolution);
 			cam
 		})));
 		
 		// Initialize render global
 		self.render_global.initialize(resolution).expect("Failed to init render global");
 		
+		// Add the key light, its intensity is the squared distance to the head so the head is lit with about 1
+		self.render_global.lights_mut().point_lights.push(PointLight::new(Vector3::new(-3.0, 3.0, 4.0), Vector3::new(34.0, 34.0, 34.0), 64.0));
+		
 		// Main loop
 		'main_loop: loop {
 			{// Update window and poll messages
 				let mut window_borrow = self.main_window.need().borrow_mut();
+				let mut settings_change = None;
+				let mut framebuffer_resize = None;
 				
 				// Poll window events
 				for (_, event) in window_borrow.poll_messages() {
 					match event {
+						WindowEvent::FramebufferSize(width, height) => {
+							// Only the last size of a drag matters
+							framebuffer_resize = Some((width as u32, height as u32));
+						}
 						WindowEvent::Scroll(_scroll_x, scroll_y) => {
 							self.test_camera_orbit.distance = f32::min(f32::max(self.test_camera_orbit.distance - ((scroll_y as f32 * 0.1) * self.test_camera_orbit.distance), 2.0), 16.0);
 						}
@@ -343,6 +284,77 @@ impl Demo {
 							if key == glfw::Key::R && action == glfw::Action::Press {
 								// Reload shaders
 								self.render_global.queue_shader_reload();
+								
+								// Reload the head surface if it or anything it's built from changed
+								if let Some(head_model) = self.test_head_model.as_mut() {
+									if unsafe {&ASSET_MANAGER_INSTANCE}.is_asset_stale(&head_model.material.surface_asset_path()) {
+										match Material::load(&head_model.material.surface_asset_path(), &mut self.render_global.program_cache, &mut self.render_global.texture_cache, &mut self.asset_load_service) {
+											Ok(material) => head_model.material = material,
+											Err(err) => println!("Failed to reload head surface: {}", err),
+										}
+									}
+								}
+							}
+							if key == glfw::Key::S && action == glfw::Action::Press {
+								// Toggle subsurface scattering
+								let subsystems = self.render_global.subsystems_mut();
+								let enabled = subsystems.is_enabled("separable_sss").unwrap_or(false);
+								subsystems.set_enabled("separable_sss", !enabled);
+							}
+							if key == glfw::Key::B && action == glfw::Action::Press {
+								// Toggle bloom
+								let subsystems = self.render_global.subsystems_mut();
+								let enabled = subsystems.is_enabled("bloom").unwrap_or(false);
+								subsystems.set_enabled("bloom", !enabled);
+							}
+							if action == glfw::Action::Press {
+								// Switch quality presets
+								let preset = match key {
+									glfw::Key::F1 => Some(QualityPreset::Low),
+									glfw::Key::F2 => Some(QualityPreset::Medium),
+									glfw::Key::F3 => Some(QualityPreset::High),
+									glfw::Key::F4 => Some(QualityPreset::Ultra),
+									_ => None,
+								};
+								if let Some(preset) = preset {
+									let mut settings = self.graphics_settings.clone();
+									settings.set_preset(preset);
+									settings_change = Some(settings);
+								}
+							}
+							if key == glfw::Key::V && action == glfw::Action::Press {
+								// Toggle vsync
+								let mut configuration = self.render_global.configuration().clone();
+								configuration.vsync = !configuration.vsync;
+								
+								let mut settings = self.graphics_settings.clone();
+								settings.set_configuration(&configuration);
+								settings_change = Some(settings);
+							}
+							if key == glfw::Key::D && action == glfw::Action::Press {
+								// Toggle dynamic resolution
+								let mut configuration = self.render_global.configuration().clone();
+								configuration.dynamic_resolution = !configuration.dynamic_resolution;
+								
+								let mut settings = self.graphics_settings.clone();
+								settings.set_configuration(&configuration);
+								settings_change = Some(settings);
+							}
+							if key == glfw::Key::P && action == glfw::Action::Press {
+								// Print the frame timings
+								print!("{}", self.render_global.profiler().report());
+							}
+							if key == glfw::Key::U && action == glfw::Action::Press {
+								// Switch the upscale filter
+								let mut configuration = self.render_global.configuration().clone();
+								configuration.upscale_filter = match configuration.upscale_filter {
+									UpscaleFilter::Bilinear => UpscaleFilter::Sharpen,
+									UpscaleFilter::Sharpen => UpscaleFilter::Bilinear,
+								};
+								
+								let mut settings = self.graphics_settings.clone();
+								settings.set_configuration(&configuration);
+								settings_change = Some(settings);
 							}
 						}
 						_ => {},
@@ -356,8 +368,21 @@ impl Demo {
 				
 				// Ensure window RefMut is dropped
 				drop(window_borrow);
+				
+				// Resize the pipeline, a minimized window has no framebuffer to render to
+				if let Some(new_size) = framebuffer_resize.filter(|&(w, h)| w > 0 && h > 0) {
+					self.resize_pipeline(new_size);
+				}
+				
+				// Apply changed settings
+				if let Some(settings) = settings_change {
+					self.apply_graphics_settings(settings);
+				}
 			}
 			
+			// Upload finished asset loads
+			self.asset_load_service.process_uploads(ASSET_UPLOAD_TIME_BUDGET);
+			
 			// Tick frame
 			self.do_tick_frame();
 			
@@ -375,6 +400,51 @@ impl Demo {
 		
 //		// Close window
 //		self.window.take().unwrap().close();
+		
+		// Save the asset dependency manifest for the next run
+		unsafe {&ASSET_MANAGER_INSTANCE}.save_manifest();
+		
+		// Save the last used times of the cached program binaries
+		unsafe {&mut PROGRAM_BINARY_CACHE_INSTANCE}.save_index();
+		
+		// Save the graphics settings
+		if let Err(err) = self.graphics_settings.save(&std::env::current_dir().unwrap().join(GRAPHICS_SETTINGS_FILE_NAME)) {
+			println!("Failed to save graphics settings: {}", err);
+		}
+	}
+	
+	/// Reconfigures the pipeline and the camera for a new framebuffer size.
+	pub fn resize_pipeline(&mut self, new_size: (u32, u32)) {
+		if let Err(err) = self.render_global.do_reconfigure_pipeline(new_size, true) {
+			println!("Failed to resize the pipeline: {}", err);
+		}
+		
+		// Update camera aspect ratio
+		if let Some(camera) = &self.test_active_camera {
+			camera.lock().unwrap().resize_viewport(new_size);
+		}
+	}
+	
+	/// Applies the settings to the pipeline, only the parts affected by a changed setting are reconfigured.
+	pub fn apply_graphics_settings(&mut self, settings: GraphicsSettingsTable) {
+		let result = settings.resolve()
+			.map_err(|e| e.into())
+			.and_then(|configuration| self.render_global.apply_configuration(configuration));
+		
+		match result {
+			Ok(changed_settings) => {
+				self.graphics_settings = settings;
+				
+				// Vsync is a property of the window's context
+				if changed_settings.contains(&GraphicsSetting::Vsync) {
+					let vsync = self.render_global.configuration().vsync;
+					let window = self.main_window.need().borrow();
+					let mut gl_context = RefCell::borrow_mut(window.gl_context().unwrap());
+					gl_context.set_swap_interval(if vsync {SwapInterval::Sync(1)} else {SwapInterval::None});
+				}
+			}
+			Err(err) => println!("Failed to apply graphics settings: {}", err),
+		}
 	}
 	
 	pub fn do_tick_frame(&mut self) {
@@ -432,11 +502,6 @@ impl Demo {
 		{// Update cam
 			let mut cam = self.test_active_camera.need().lock().unwrap();
 			
-			// Update camera viewport size
-			let window = self.main_window.need().borrow();
-			let window_size = window.get_size();
-			cam.viewport_size = (window_size.0 as u32, window_size.1 as u32);
-			
 			// Update camera transform
 			let orbit = &self.test_camera_orbit;
 //			orbit.center = vec3(0.0, 0.5, 0.0);
@@ -467,99 +532,15 @@ pub extern "system" fn gl_debug_callback(_source: gl::enuma, _message_type: gl::
 }
 
 pub struct TestHeadModel {
-	pub vertex_buffer_gl: gl::uint,
-	pub index_buffer_gl: gl::uint,
-	pub num_indices: u32,
-	
-	pub tex_albedo: Texture,
-	pub tex_normal: Texture,
-	pub tex_transmission: Texture,
-//	pub tex_roughness: Texture,
+	pub mesh: Handle<Mesh>,
+	pub material: Material,
 }
 
-pub fn calculate_mesh_tangents(num_indices: u32, index_data: Vec<u8>, num_vertices: u32, vertex_data: Vec<u8>) -> (Vec<u8>, Vec<u8>) {
-	// https://www.marti.works/calculating-tangents-for-your-mesh/
-	
-	let mut tangent_list = vec![vec3(0.0, 0.0, 0.0); num_vertices as usize];
-	let mut bitangent_list = tangent_list.clone();
-	
-	fn read_vec3_f32(buffer: &[u8]) -> Vector3<f32> {
-		vec3(LittleEndian::read_f32(&buffer[0..4]), LittleEndian::read_f32(&buffer[4..8]), LittleEndian::read_f32(&buffer[8..12]))
-	}
-	fn read_vec2_f32(buffer: &[u8]) -> Vector2<f32> {
-		vec2(LittleEndian::read_f32(&buffer[0..4]), LittleEndian::read_f32(&buffer[4..8]))
+impl TestHeadModel {
+	/// Checks if the mesh and the material finished loading.
+	pub fn is_ready(&self) -> bool {
+		self.mesh.is_ready() && self.material.is_ready()
 	}
-	
-	for i in (0..num_indices as usize).step_by(3) {
-		let index_base = i*4;
-		let index0 = LittleEndian::read_u32(&index_data[(index_base)..(index_base+4)]) as usize;
-		let index1 = LittleEndian::read_u32(&index_data[(index_base+4)..(index_base+8)]) as usize;
-		let index2 = LittleEndian::read_u32(&index_data[(index_base+8)..(index_base+12)]) as usize;
-		
-		let vertex0 = read_vec3_f32(&vertex_data[(index0*32)..(index0*32+12)]);
-		let vertex1 = read_vec3_f32(&vertex_data[(index1*32)..(index1*32+12)]);
-		let vertex2 = read_vec3_f32(&vertex_data[(index2*32)..(index2*32+12)]);
-		
-		let uv0 = read_vec2_f32(&vertex_data[(index0*32+24)..(index0*32+32)]);
-		let uv1 = read_vec2_f32(&vertex_data[(index1*32+24)..(index1*32+32)]);
-		let uv2 = read_vec2_f32(&vertex_data[(index2*32+24)..(index2*32+32)]);
-		
-		let edge1: Vector3<f32> = vertex1 - vertex0;
-		let edge2: Vector3<f32> = vertex2 - vertex0;
-		
-		let uv_edge1: Vector2<f32> = uv1 - uv0;
-		let uv_edge2: Vector2<f32> = uv2 - uv0;
-		
-		let r = 1.0 / (uv_edge1.x * uv_edge2.y - uv_edge1.y * uv_edge2.x);
-		
-		let tangent = vec3(
-			((edge1.x * uv_edge2.y) - (edge2.x * uv_edge1.y)) * r,
-			((edge1.y * uv_edge2.y) - (edge2.y * uv_edge1.y)) * r,
-			((edge1.z * uv_edge2.y) - (edge2.z * uv_edge1.y)) * r
-		);
-		let bitangent = vec3(
-			((edge1.x * uv_edge2.x) - (edge2.x * uv_edge1.x)) * r,
-			((edge1.y * uv_edge2.x) - (edge2.y * uv_edge1.x)) * r,
-			((edge1.z * uv_edge2.x) - (edge2.z * uv_edge1.x)) * r
-		);
-		
-		tangent_list[index0] += tangent;
-		tangent_list[index1] += tangent;
-		tangent_list[index2] += tangent;
-		
-		bitangent_list[index0] += bitangent;
-		bitangent_list[index1] += bitangent;
-		bitangent_list[index2] += bitangent;
-	}
-	
-	let mut new_vertex_data = vec![0; num_vertices as usize * 48];
-	
-	for i in 0..num_vertices as usize {
-		let n = read_vec3_f32(&vertex_data[(i*32+12)..(i*32+24)]);
-		let t0 = tangent_list[i];
-		let t1 = bitangent_list[i];
-		
-		let t = Vector3::normalize(t0 - (n * Vector3::dot(n, t0)));
-		
-		let c = Vector3::cross(n, t0);
-		
-		// Calculate handedness: Needed for calculating the binormal in the right direction
-		let w = if Vector3::dot(c, t1) < 0.0 {-1.0} else {1.0};
-		
-		let final_tangent = t.extend(w);
-		
-		new_vertex_data[i*48..i*48+32].copy_from_slice(&vertex_data[i*32..i*32+32]);
-		
-		let mut tangent_buffer = [0u8; 16];
-		LittleEndian::write_f32(&mut tangent_buffer[0..4], final_tangent.x);
-		LittleEndian::write_f32(&mut tangent_buffer[4..8], final_tangent.y);
-		LittleEndian::write_f32(&mut tangent_buffer[8..12], final_tangent.z);
-		LittleEndian::write_f32(&mut tangent_buffer[12..16], final_tangent.w);
-		
-		new_vertex_data[i*48+32..i*48+48].copy_from_slice(&tangent_buffer);
-	}
-	
-	(index_data, new_vertex_data)
 }
 
 /*
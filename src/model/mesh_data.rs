@@ -0,0 +1,208 @@
+use std::error;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use byteorder::{ByteOrder, LittleEndian};
+use cgmath::{InnerSpace, vec2, vec3, Vector2, Vector3};
+use crate::model::ply::{PlyError, PlyMeshLoader, PlyReadError, PullEvent};
+
+/// Cpu side mesh data, ready to be uploaded to the gpu.
+///
+/// Vertices are tightly packed f32 little endian with the layout
+/// `position (3) | normal (3) | texcoord (2)` and optionally followed
+/// by a `tangent (4)` (see `has_tangents`). Indices are u32 triangles.
+pub struct MeshData {
+	pub num_vertices: u32,
+	pub num_indices: u32,
+	pub vertex_data: Vec<u8>,
+	pub index_data: Vec<u8>,
+	pub has_tangents: bool,
+}
+
+impl MeshData {
+	pub fn vertex_stride(&self) -> u32 {
+		if self.has_tangents {12*4} else {8*4}
+	}
+	
+	/// Generates per-vertex tangents, widening the vertex stride from 32 to 48 bytes.
+	pub fn generate_tangents(&mut self) {
+		if !self.has_tangents {
+			let vertex_data = std::mem::replace(&mut self.vertex_data, Vec::new());
+			let index_data = std::mem::replace(&mut self.index_data, Vec::new());
+			
+			let (index_data, vertex_data) = calculate_mesh_tangents(self.num_indices, index_data, self.num_vertices, vertex_data);
+			self.index_data = index_data;
+			self.vertex_data = vertex_data;
+			self.has_tangents = true;
+		}
+	}
+	
+	pub fn load_ply_from_path(path: &Path) -> Result<MeshData, Box<dyn error::Error>> {
+		MeshData::load_ply(&fs::read(path)?)
+	}
+	
+	/// Like `load_ply_from_path`, for the contents of a ply file that were already read.
+	pub fn load_ply(file_data: &[u8]) -> Result<MeshData, Box<dyn error::Error>> {
+		let mut cursor = Cursor::new(file_data);
+		
+		let loader = PlyMeshLoader::new(&mut cursor);
+		let mut puller = loader.parse_header()?;
+		
+		// Get vertex and index num
+		let mut num_vertices = 0u32;
+		let mut num_indices = 0u32;
+		
+		for e in &puller.header().elements {
+			let name = e.name.as_str();
+			if name.eq("vertex") {
+				num_vertices = e.num_entries;
+			}
+			else if name.eq("face") {
+				num_indices = e.num_entries * 3;
+			}
+		}
+		
+		// Allocate data buffers
+		let mut vertex_data_buffer = vec![0u8; (num_vertices as usize) * 4*8];
+		let mut index_data_buffer = vec![0u8; (num_indices as usize) * 4];
+		
+		loop {
+			match puller.next_event() {
+				PullEvent::Element(mut parser) => {
+					let elem_name = parser.element_descriptor().name.as_str();
+					if elem_name.eq("vertex") {
+						let mut read_buffer = [0u8; 32];
+						
+						let mut vertex_data_pos = 0usize;
+						'vertex_entry_loop: loop {
+							match parser.read_entry(&mut read_buffer) {
+								Err(PlyReadError::NoMoreEntries) => break 'vertex_entry_loop,
+								Err(PlyReadError::BufferTooSmall {..}) => return Err(Box::new(PlyError::new("Unsupported vertex layout: Vertex entry is larger than 32 bytes"))),
+								Err(PlyReadError::Other(err)) => return Err(err),
+								Ok(_) => {
+									// Copy into vertex buffer
+									let final_pos = vertex_data_pos + 4 * 8;
+									vertex_data_buffer[vertex_data_pos..final_pos].copy_from_slice(&read_buffer);
+									vertex_data_pos = final_pos;
+								}
+							}
+						}
+					}
+					else if elem_name.eq("face") {
+						let mut read_buffer = [0u8; 1+3*4];
+						
+						let mut index_data_pos = 0usize;
+						'index_entry_loop: loop {
+							match parser.read_entry(&mut read_buffer) {
+								Err(PlyReadError::NoMoreEntries) => break 'index_entry_loop,
+								Err(PlyReadError::BufferTooSmall {..}) => return Err(Box::new(PlyError::new("Unsupported face layout: Only triangles with uint indices are supported"))),
+								Err(PlyReadError::Other(err)) => return Err(err),
+								Ok(_) => {
+									// Copy into index buffer
+									index_data_buffer[index_data_pos+0..index_data_pos+4].copy_from_slice(&read_buffer[1..5]);
+									index_data_buffer[index_data_pos+4..index_data_pos+8].copy_from_slice(&read_buffer[5..9]);
+									index_data_buffer[index_data_pos+8..index_data_pos+12].copy_from_slice(&read_buffer[9..13]);
+									index_data_pos += 3*4;
+								}
+							}
+						}
+					}
+				}
+				PullEvent::End => break,
+			}
+		}
+		
+		Ok(MeshData {
+			num_vertices,
+			num_indices,
+			vertex_data: vertex_data_buffer,
+			index_data: index_data_buffer,
+			has_tangents: false,
+		})
+	}
+}
+
+pub fn calculate_mesh_tangents(num_indices: u32, index_data: Vec<u8>, num_vertices: u32, vertex_data: Vec<u8>) -> (Vec<u8>, Vec<u8>) {
+	// https://www.marti.works/calculating-tangents-for-your-mesh/
+	
+	let mut tangent_list = vec![vec3(0.0, 0.0, 0.0); num_vertices as usize];
+	let mut bitangent_list = tangent_list.clone();
+	
+	fn read_vec3_f32(buffer: &[u8]) -> Vector3<f32> {
+		vec3(LittleEndian::read_f32(&buffer[0..4]), LittleEndian::read_f32(&buffer[4..8]), LittleEndian::read_f32(&buffer[8..12]))
+	}
+	fn read_vec2_f32(buffer: &[u8]) -> Vector2<f32> {
+		vec2(LittleEndian::read_f32(&buffer[0..4]), LittleEndian::read_f32(&buffer[4..8]))
+	}
+	
+	for i in (0..num_indices as usize).step_by(3) {
+		let index_base = i*4;
+		let index0 = LittleEndian::read_u32(&index_data[(index_base)..(index_base+4)]) as usize;
+		let index1 = LittleEndian::read_u32(&index_data[(index_base+4)..(index_base+8)]) as usize;
+		let index2 = LittleEndian::read_u32(&index_data[(index_base+8)..(index_base+12)]) as usize;
+		
+		let vertex0 = read_vec3_f32(&vertex_data[(index0*32)..(index0*32+12)]);
+		let vertex1 = read_vec3_f32(&vertex_data[(index1*32)..(index1*32+12)]);
+		let vertex2 = read_vec3_f32(&vertex_data[(index2*32)..(index2*32+12)]);
+		
+		let uv0 = read_vec2_f32(&vertex_data[(index0*32+24)..(index0*32+32)]);
+		let uv1 = read_vec2_f32(&vertex_data[(index1*32+24)..(index1*32+32)]);
+		let uv2 = read_vec2_f32(&vertex_data[(index2*32+24)..(index2*32+32)]);
+		
+		let edge1: Vector3<f32> = vertex1 - vertex0;
+		let edge2: Vector3<f32> = vertex2 - vertex0;
+		
+		let uv_edge1: Vector2<f32> = uv1 - uv0;
+		let uv_edge2: Vector2<f32> = uv2 - uv0;
+		
+		let r = 1.0 / (uv_edge1.x * uv_edge2.y - uv_edge1.y * uv_edge2.x);
+		
+		let tangent = vec3(
+			((edge1.x * uv_edge2.y) - (edge2.x * uv_edge1.y)) * r,
+			((edge1.y * uv_edge2.y) - (edge2.y * uv_edge1.y)) * r,
+			((edge1.z * uv_edge2.y) - (edge2.z * uv_edge1.y)) * r
+		);
+		let bitangent = vec3(
+			((edge1.x * uv_edge2.x) - (edge2.x * uv_edge1.x)) * r,
+			((edge1.y * uv_edge2.x) - (edge2.y * uv_edge1.x)) * r,
+			((edge1.z * uv_edge2.x) - (edge2.z * uv_edge1.x)) * r
+		);
+		
+		tangent_list[index0] += tangent;
+		tangent_list[index1] += tangent;
+		tangent_list[index2] += tangent;
+		
+		bitangent_list[index0] += bitangent;
+		bitangent_list[index1] += bitangent;
+		bitangent_list[index2] += bitangent;
+	}
+	
+	let mut new_vertex_data = vec![0; num_vertices as usize * 48];
+	
+	for i in 0..num_vertices as usize {
+		let n = read_vec3_f32(&vertex_data[(i*32+12)..(i*32+24)]);
+		let t0 = tangent_list[i];
+		let t1 = bitangent_list[i];
+		
+		let t = Vector3::normalize(t0 - (n * Vector3::dot(n, t0)));
+		
+		let c = Vector3::cross(n, t0);
+		
+		// Calculate handedness: Needed for calculating the binormal in the right direction
+		let w = if Vector3::dot(c, t1) < 0.0 {-1.0} else {1.0};
+		
+		let final_tangent = t.extend(w);
+		
+		new_vertex_data[i*48..i*48+32].copy_from_slice(&vertex_data[i*32..i*32+32]);
+		
+		let mut tangent_buffer = [0u8; 16];
+		LittleEndian::write_f32(&mut tangent_buffer[0..4], final_tangent.x);
+		LittleEndian::write_f32(&mut tangent_buffer[4..8], final_tangent.y);
+		LittleEndian::write_f32(&mut tangent_buffer[8..12], final_tangent.z);
+		LittleEndian::write_f32(&mut tangent_buffer[12..16], final_tangent.w);
+		
+		new_vertex_data[i*48+32..i*48+48].copy_from_slice(&tangent_buffer);
+	}
+	
+	(index_data, new_vertex_data)
+}
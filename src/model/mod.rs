@@ -1,2 +1,3 @@
 pub mod obj;
 pub mod ply;
+mod mesh_data; pub use mesh_data::*;
@@ -1,23 +1,165 @@
-use crate::render::{RenderSubsystem, ReconfigureEvent, Framebuffer};
+use gl_bindings::gl;
+use crate::render::{GraphicsSetting, RenderSubsystem, ReconfigureEvent, FrameResources, ImageFormat};
+use crate::render::render_graph::{RenderGraph, RenderPassContext, ResourceId, TextureDesc};
+use crate::render::shader::managed::ManagedProgram;
+use crate::asset::{AssetCache, AssetPath, Handle};
+
+/// Octaves smaller than this (in either dimension) aren't worth a pass
+pub const BLOOM_MIN_OCTAVE_SIZE: u32 = 2;
+/// Weight of an octave relative to the next finer one
+pub const BLOOM_OCTAVE_FALLOFF: f32 = 0.85;
 
 pub struct BloomSubsystem {
+	pub program_downsample: Handle<ManagedProgram>,
+	pub program_upsample: Handle<ManagedProgram>,
 	pub max_octaves: u32,
-	
-	pub framebuffer_bloom_temp: Framebuffer,
-	pub framebuffers_bloom_levels: Vec<Framebuffer>,
+	/// Brightness below which the scene doesn't bloom
+	pub threshold: f32,
 }
 
 impl BloomSubsystem {
-	pub fn new() -> BloomSubsystem {
+	/// Downsamples the scene into a chain of octaves and upsamples them back up to the first one.
+	/// Returns the bloom at half the scene resolution or `None` if the scene is too small
+	/// or a program failed to load.
+	pub fn add_bloom_passes<'a>(&'a self, graph: &mut RenderGraph<'a>, scene_hdr: ResourceId) -> Option<ResourceId> {
+		let scene_desc = graph.resource(scene_hdr).desc;
+		let octave_sizes = bloom_octave_sizes((scene_desc.width, scene_desc.height), self.max_octaves);
+		if octave_sizes.is_empty() {
+			return None;
+		}
+		let octave_weights = bloom_octave_weights(octave_sizes.len() as u32, BLOOM_OCTAVE_FALLOFF);
+		
+		// Compile shaders, skip bloom if a program failed to load
+		let (karis_key, downsample_key) = {
+			let mut program = self.program_downsample.get_mut()?;
+			if program.needs_recompile() {
+				program.do_recompile();
+			}
+			
+			// The first downsample uses the karis average to suppress fireflies
+			let layout = program.permutation_layout();
+			let downsample_key = layout.default_key();
+			let karis_key = layout.with_flag(downsample_key, "BLOOM_KARIS_AVERAGE", true).unwrap_or(downsample_key);
+			program.program_variant(karis_key);
+			program.program_variant(downsample_key);
+			(karis_key, downsample_key)
+		};
+		let upsample_key = {
+			let mut program = self.program_upsample.get_mut()?;
+			if program.needs_recompile() {
+				program.do_recompile();
+			}
+			
+			let key = program.permutation_layout().default_key();
+			program.program_variant(key);
+			key
+		};
+		
+		let fullscreen_pass = |context: &RenderPassContext<'_>, program_gl: gl::uint, sources: &[ResourceId]| unsafe {
+			// Setup pipeline
+			gl::Disable(gl::DEPTH_TEST);
+			gl::Disable(gl::CULL_FACE);
+			gl::Disable(gl::BLEND);
+			
+			gl::DisableVertexAttribArray(0);
+			
+			gl::UseProgram(program_gl);
+			for (unit, &source) in sources.iter().enumerate() {
+				context.bind_texture(unit as u32, source);
+			}
+			
+			// Render screen triangle
+			gl::DrawArrays(gl::TRIANGLES, 0, 3);
+		};
+		
+		// Do downsample passes
+		let mut octaves = Vec::with_capacity(octave_sizes.len());
+		let mut source = scene_hdr;
+		for (i, &(width, height)) in octave_sizes.iter().enumerate() {
+			let octave = graph.create_texture(&format!("bloom_downsample{}", i), TextureDesc::new(width, height, ImageFormat::get(gl::R11F_G11F_B10F)));
+			let is_first = i == 0;
+			let key = if is_first {karis_key} else {downsample_key};
+			let threshold = self.threshold;
+			let program_downsample = &self.program_downsample;
+			
+			graph.add_pass(&format!("bloom_downsample{}", i))
+				.read(source)
+				.color_attachment(0, octave)
+				.execute(move |context| {
+					// Skip the pass if the program has nothing to draw with
+					let program = match program_downsample.get() {
+						Some(program) => program,
+						None => return,
+					};
+					let (shader, shader_gl) = match program.cached_variant(key).and_then(|s| Some((s, s.program_gl()?))) {
+						Some(shader) => shader,
+						None => return,
+					};
+					if is_first {
+						let _ = shader.set_uniform("uThreshold", threshold);
+					}
+					
+					fullscreen_pass(context, shader_gl, &[source]);
+				});
+			
+			octaves.push(octave);
+			source = octave;
+		}
+		
+		// Do upsample passes, each adds the tent filtered lower octave onto its own
+		let mut lower = octaves[octaves.len() - 1];
+		for i in (0..octaves.len() - 1).rev() {
+			let (width, height) = octave_sizes[i];
+			let target = graph.create_texture(&format!("bloom_upsample{}", i), TextureDesc::new(width, height, ImageFormat::get(gl::R11F_G11F_B10F)));
+			let current = octaves[i];
+			let current_weight = octave_weights[i];
+			
+			// The weights of the finer octaves are already applied to the upsampled ones
+			let lower_weight = if i == octaves.len() - 2 {octave_weights[i + 1]} else {1.0};
+			let program_upsample = &self.program_upsample;
+			
+			graph.add_pass(&format!("bloom_upsample{}", i))
+				.read(current)
+				.read(lower)
+				.color_attachment(0, target)
+				.execute(move |context| {
+					// Skip the pass if the program has nothing to draw with
+					let program = match program_upsample.get() {
+						Some(program) => program,
+						None => return,
+					};
+					let (shader, shader_gl) = match program.cached_variant(upsample_key).and_then(|s| Some((s, s.program_gl()?))) {
+						Some(shader) => shader,
+						None => return,
+					};
+					let _ = shader.set_uniform("uCurrentWeight", current_weight);
+					let _ = shader.set_uniform("uLowerWeight", lower_weight);
+					
+					fullscreen_pass(context, shader_gl, &[current, lower]);
+				});
+			
+			lower = target;
+		}
+		
+		// A single octave has a weight of 1 and needs no upsampling
+		Some(lower)
+	}
+	
+	pub fn new(program_cache: &mut AssetCache<ManagedProgram>) -> BloomSubsystem {
 		BloomSubsystem {
+			program_downsample: program_cache.load(&AssetPath::from_str("/shaders/bloom_downsample.program")),
+			program_upsample: program_cache.load(&AssetPath::from_str("/shaders/bloom_upsample.program")),
 			max_octaves: 6,
-			framebuffer_bloom_temp: Framebuffer::new(0, 0),
-			framebuffers_bloom_levels: Vec::with_capacity(6),
+			threshold: 1.0,
 		}
 	}
 }
 
 impl RenderSubsystem for BloomSubsystem {
+	fn name(&self) -> &'static str {
+		"bloom"
+	}
+	
 	fn initialize(&mut self) {
 		// Do nothing
 	}
@@ -27,17 +169,48 @@ impl RenderSubsystem for BloomSubsystem {
 	}
 	
 	fn reconfigure(&mut self, event: ReconfigureEvent<'_>) {
-		let base_width = event.resolution.0;
-		let base_height = event.resolution.1;
-		
-		// Resize framebuffers
-		self.framebuffer_bloom_temp.resize(base_width, base_height);
-		if !self.framebuffer_bloom_temp.is_allocated() {
-			self.framebuffer_bloom_temp.allocate();
+		// The render targets are transient, the intensity is applied by the post composite
+		self.max_octaves = event.configuration.bloom_octaves;
+		self.threshold = event.configuration.bloom_threshold;
+	}
+	
+	fn affected_by(&self, setting: GraphicsSetting) -> bool {
+		setting == GraphicsSetting::BloomOctaves || setting == GraphicsSetting::BloomThreshold
+	}
+	
+	/// Bloom is applied to the final hdr scene
+	fn run_after(&self) -> &[&'static str] {
+		&["separable_sss"]
+	}
+	
+	fn add_passes<'a>(&'a self, graph: &mut RenderGraph<'a>, frame: &mut FrameResources) {
+		frame.bloom = self.add_bloom_passes(graph, frame.scene_hdr);
+	}
+}
+
+/// Sizes of the octaves of the mip chain, starting at half the scene resolution.
+/// Stops before an octave would get smaller than `BLOOM_MIN_OCTAVE_SIZE`.
+pub fn bloom_octave_sizes(scene_resolution: (u32, u32), max_octaves: u32) -> Vec<(u32, u32)> {
+	let mut sizes = Vec::with_capacity(max_octaves as usize);
+	let (mut width, mut height) = scene_resolution;
+	while (sizes.len() as u32) < max_octaves {
+		width /= 2;
+		height /= 2;
+		if width < BLOOM_MIN_OCTAVE_SIZE || height < BLOOM_MIN_OCTAVE_SIZE {
+			break;
 		}
+		sizes.push((width, height));
 	}
+	sizes
 }
 
-pub struct BloomOctave {
-	
+/// Weight of each octave in the final bloom, each weighs `falloff` times the next finer one.
+/// The weights add up to 1 so bloom doesn't add energy beyond its intensity.
+pub fn bloom_octave_weights(octaves: u32, falloff: f32) -> Vec<f32> {
+	let mut weights: Vec<f32> = (0..octaves).map(|i| falloff.powi(i as i32)).collect();
+	let sum: f32 = weights.iter().sum();
+	for weight in &mut weights {
+		*weight /= sum;
+	}
+	weights
 }
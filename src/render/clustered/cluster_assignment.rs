@@ -0,0 +1,113 @@
+use cgmath::{Matrix4, Vector3, InnerSpace};
+use crate::render::clustered::{ClusterGrid, LightList, CLUSTER_MAX_LIGHTS};
+
+/// The lights assigned to the clusters of a grid, laid out like the lists the gpu builds.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ClusterAssignment {
+	/// Offset into `light_indices` and the number of point, spot and area lights of every cluster
+	pub cluster_lights: Vec<[u32; 4]>,
+	/// The point lights of each cluster, followed by its spot and area lights
+	pub light_indices: Vec<u32>,
+}
+
+impl ClusterAssignment {
+	pub fn point_lights(&self, cluster_index: u32) -> &[u32] {
+		let [offset, num_points, _, _] = self.cluster_lights[cluster_index as usize];
+		&self.light_indices[(offset as usize)..((offset + num_points) as usize)]
+	}
+	
+	pub fn spot_lights(&self, cluster_index: u32) -> &[u32] {
+		let [offset, num_points, num_spots, _] = self.cluster_lights[cluster_index as usize];
+		let start = offset + num_points;
+		&self.light_indices[(start as usize)..((start + num_spots) as usize)]
+	}
+	
+	pub fn area_lights(&self, cluster_index: u32) -> &[u32] {
+		let [offset, num_points, num_spots, num_areas] = self.cluster_lights[cluster_index as usize];
+		let start = offset + num_points + num_spots;
+		&self.light_indices[(start as usize)..((start + num_areas) as usize)]
+	}
+}
+
+/// CPU reference of the cluster assignment compute passes, assigns the lights exactly like
+/// `cluster_light_assign.comp.ssl` and compacts the lists like the prefix sum passes do.
+pub fn assign_lights(grid: &ClusterGrid, cluster_view: &Matrix4<f32>, lights: &LightList) -> ClusterAssignment {
+	let point_lights = lights.point_light_data();
+	let spot_lights = lights.spot_light_data();
+	let area_lights = lights.area_light_data();
+	
+	let mut cluster_lights = Vec::with_capacity(grid.num_clusters() as usize);
+	let mut light_indices = Vec::new();
+	
+	for cluster_index in 0..grid.num_clusters() {
+		let (bounds_min, bounds_max) = grid.cluster_bounds(grid.cluster_coord(cluster_index));
+		let offset = light_indices.len() as u32;
+		let mut num_assigned = 0;
+		
+		// Assign point lights
+		for (i, light) in point_lights.iter().enumerate() {
+			if num_assigned == CLUSTER_MAX_LIGHTS {
+				break;
+			}
+			
+			let center = (cluster_view * light.position_and_radius.truncate().extend(1.0)).truncate();
+			if sphere_intersects_bounds(center, light.position_and_radius.w, bounds_min, bounds_max) {
+				light_indices.push(i as u32);
+				num_assigned += 1;
+			}
+		}
+		let num_point_lights = num_assigned;
+		
+		// Assign spot lights, culled by the sphere around their cone
+		for (i, light) in spot_lights.iter().enumerate() {
+			if num_assigned == CLUSTER_MAX_LIGHTS {
+				break;
+			}
+			
+			let center = (cluster_view * light.position_and_radius.truncate().extend(1.0)).truncate();
+			if sphere_intersects_bounds(center, light.position_and_radius.w, bounds_min, bounds_max) {
+				light_indices.push(i as u32);
+				num_assigned += 1;
+			}
+		}
+		let num_spot_lights = num_assigned - num_point_lights;
+		
+		// Assign area lights, they only light what is in front of them
+		for (i, light) in area_lights.iter().enumerate() {
+			if num_assigned == CLUSTER_MAX_LIGHTS {
+				break;
+			}
+			
+			let center = (cluster_view * light.center_and_plane_width.truncate().extend(1.0)).truncate();
+			let normal = (cluster_view * light.normal_and_cos_outer_angle.truncate().extend(0.0)).truncate();
+			if sphere_intersects_bounds(center, light.tangent_and_radius.w, bounds_min, bounds_max) && bounds_in_front_of_plane(center, normal, bounds_min, bounds_max) {
+				light_indices.push(i as u32);
+				num_assigned += 1;
+			}
+		}
+		let num_area_lights = num_assigned - num_point_lights - num_spot_lights;
+		
+		cluster_lights.push([offset, num_point_lights, num_spot_lights, num_area_lights]);
+	}
+	
+	ClusterAssignment {
+		cluster_lights,
+		light_indices,
+	}
+}
+
+fn sphere_intersects_bounds(center: Vector3<f32>, radius: f32, bounds_min: Vector3<f32>, bounds_max: Vector3<f32>) -> bool {
+	let closest_point = Vector3::new(
+		center.x.max(bounds_min.x).min(bounds_max.x),
+		center.y.max(bounds_min.y).min(bounds_max.y),
+		center.z.max(bounds_min.z).min(bounds_max.z),
+	);
+	(closest_point - center).magnitude2() <= radius * radius
+}
+
+fn bounds_in_front_of_plane(plane_point: Vector3<f32>, plane_normal: Vector3<f32>, bounds_min: Vector3<f32>, bounds_max: Vector3<f32>) -> bool {
+	let bounds_center = (bounds_min + bounds_max) * 0.5;
+	let bounds_extents = (bounds_max - bounds_min) * 0.5;
+	let abs_normal = Vector3::new(plane_normal.x.abs(), plane_normal.y.abs(), plane_normal.z.abs());
+	(bounds_center - plane_point).dot(plane_normal) + bounds_extents.dot(abs_normal) >= 0.0
+}
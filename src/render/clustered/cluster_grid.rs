@@ -0,0 +1,104 @@
+use cgmath::{Matrix4, SquareMatrix, Vector2, Vector3};
+
+/// Clusters along x, y and the depth, the tiles fit the 16:9 default window
+pub const CLUSTER_GRID_DIMENSIONS: (u32, u32, u32) = (16, 9, 24);
+/// Most lights assigned to a single cluster, further lights are dropped.
+/// Has to match `CLUSTER_MAX_LIGHTS` in `clustered_lighting/cluster_grid.incl.ssl`.
+pub const CLUSTER_MAX_LIGHTS: u32 = 64;
+
+/// Partitions the view frustum into screen space tiles and exponentially distributed depth slices.
+///
+/// The clusters are located in cluster space, that is view space with the clip space w as
+/// depth (see `cluster_view_matrix`). Mirrors the functions in `cluster_grid.incl.ssl`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ClusterGrid {
+	pub dimensions: (u32, u32, u32),
+	/// Half extents of the view frustum at depth 1
+	pub tile_scale: Vector2<f32>,
+	/// Depth range covered by the slices, depths outside fall into the first or last slice
+	pub depth_planes: (f32, f32),
+}
+
+impl ClusterGrid {
+	pub fn num_clusters(&self) -> u32 {
+		self.dimensions.0 * self.dimensions.1 * self.dimensions.2
+	}
+	
+	pub fn cluster_index(&self, cluster: (u32, u32, u32)) -> u32 {
+		cluster.0 + (cluster.1 + cluster.2 * self.dimensions.1) * self.dimensions.0
+	}
+	
+	pub fn cluster_coord(&self, index: u32) -> (u32, u32, u32) {
+		(index % self.dimensions.0, (index / self.dimensions.0) % self.dimensions.1, index / (self.dimensions.0 * self.dimensions.1))
+	}
+	
+	pub fn depth_slice(&self, depth: f32) -> u32 {
+		let (near, far) = self.depth_planes;
+		let slice = (depth.max(near) / near).ln() / (far / near).ln() * self.dimensions.2 as f32;
+		u32::min(slice as u32, self.dimensions.2 - 1)
+	}
+	
+	/// Near and far depth of a slice.
+	pub fn slice_depth_range(&self, slice: u32) -> (f32, f32) {
+		let (near, far) = self.depth_planes;
+		let depth_ratio = far / near;
+		(
+			near * depth_ratio.powf(slice as f32 / self.dimensions.2 as f32),
+			near * depth_ratio.powf((slice + 1) as f32 / self.dimensions.2 as f32),
+		)
+	}
+	
+	/// Cluster containing the fragment at `screen_tex_coord` (0..1 over the render target,
+	/// origin at the bottom left) and `depth`.
+	pub fn cluster_at(&self, screen_tex_coord: Vector2<f32>, depth: f32) -> (u32, u32, u32) {
+		let tile_x = u32::min((screen_tex_coord.x.max(0.0) * self.dimensions.0 as f32) as u32, self.dimensions.0 - 1);
+		let tile_y = u32::min((screen_tex_coord.y.max(0.0) * self.dimensions.1 as f32) as u32, self.dimensions.1 - 1);
+		(tile_x, tile_y, self.depth_slice(depth))
+	}
+	
+	/// Axis aligned bounds of a cluster in cluster space, as min and max.
+	pub fn cluster_bounds(&self, cluster: (u32, u32, u32)) -> (Vector3<f32>, Vector3<f32>) {
+		let (near_depth, far_depth) = self.slice_depth_range(cluster.2);
+		
+		// The tile in ndc, scaled to the frustum at depth 1
+		let tile_ndc = |x: u32, y: u32| Vector2::new(
+			(x as f32 / self.dimensions.0 as f32 * 2.0 - 1.0) * self.tile_scale.x,
+			(y as f32 / self.dimensions.1 as f32 * 2.0 - 1.0) * self.tile_scale.y,
+		);
+		let tile_min = tile_ndc(cluster.0, cluster.1);
+		let tile_max = tile_ndc(cluster.0 + 1, cluster.1 + 1);
+		
+		// The frustum widens with the depth, so the tile at both depths spans the bounds
+		(
+			Vector3::new(f32::min(tile_min.x * near_depth, tile_min.x * far_depth), f32::min(tile_min.y * near_depth, tile_min.y * far_depth), near_depth),
+			Vector3::new(f32::max(tile_max.x * near_depth, tile_max.x * far_depth), f32::max(tile_max.y * near_depth, tile_max.y * far_depth), far_depth),
+		)
+	}
+	
+	/// Takes the tile scale from a symmetric perspective projection.
+	pub fn from_projection(dimensions: (u32, u32, u32), projection: &Matrix4<f32>, depth_planes: (f32, f32)) -> ClusterGrid {
+		Self::new(dimensions, Vector2::new(1.0 / projection.x.x, 1.0 / projection.y.y), depth_planes)
+	}
+	
+	pub fn new(dimensions: (u32, u32, u32), tile_scale: Vector2<f32>, depth_planes: (f32, f32)) -> ClusterGrid {
+		ClusterGrid {
+			dimensions,
+			tile_scale,
+			depth_planes,
+		}
+	}
+}
+
+/// Transforms world space into cluster space: view space x and y with the clip space w
+/// (the linear depth) as z. The depth grows away from the camera whatever conventions
+/// the projection and view matrix follow.
+pub fn cluster_view_matrix(projection: &Matrix4<f32>, view: &Matrix4<f32>) -> Matrix4<f32> {
+	// Replace the z row with the w row of the projection
+	let mut depth_matrix = Matrix4::identity();
+	depth_matrix.x.z = projection.x.w;
+	depth_matrix.y.z = projection.y.w;
+	depth_matrix.z.z = projection.z.w;
+	depth_matrix.w.z = projection.w.w;
+	
+	depth_matrix * view
+}
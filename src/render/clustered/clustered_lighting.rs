@@ -0,0 +1,240 @@
+use cgmath::{Matrix4, Vector2, Vector4};
+use gl_bindings::gl;
+use crate::gpu_block;
+use crate::render::{GpuBuffer, GpuArrayBuffer};
+use crate::render::clustered::{ClusterGrid, LightList, PointLightData, SpotLightData, AreaLightData, CLUSTER_GRID_DIMENSIONS, CLUSTER_MAX_LIGHTS};
+use crate::render::render_graph::RenderGraph;
+use crate::render::shader::managed::ManagedProgram;
+use crate::asset::{AssetCache, AssetPath, Handle};
+
+// Binding points, have to match the includes in `shaders/clustered_lighting`
+pub const SSBO_POINT_LIGHT_DATA_BINDING: gl::uint = 8;
+pub const SSBO_SPOT_LIGHT_DATA_BINDING: gl::uint = 9;
+pub const SSBO_AREA_LIGHT_DATA_BINDING: gl::uint = 10;
+pub const SSBO_CLUSTER_LIGHT_GRID_BINDING: gl::uint = 11;
+pub const SSBO_CLUSTER_LIGHT_INDICES_BINDING: gl::uint = 12;
+pub const SSBO_CLUSTER_ASSIGNMENT_SCRATCH_BINDING: gl::uint = 13;
+pub const UBO_CLUSTER_GRID_BINDING: gl::uint = 14;
+
+/// Work group size of the assign and compact passes
+const CLUSTER_PASS_GROUP_SIZE: u32 = 64;
+
+gpu_block! {
+	/// Mirrors `ubClusterGrid` in `clustered_lighting/cluster_grid.incl.ssl`.
+	pub struct ClusterGridBlock {
+		pub cluster_view: Matrix4<f32>,
+		pub dimensions: Vector4<u32>,
+		pub light_counts: Vector4<u32>,
+		pub tile_scale: Vector2<f32>,
+		pub depth_planes: Vector2<f32>,
+	}
+}
+
+/// Clustered forward lighting: assigns the lights to the clusters of a `ClusterGrid` on the gpu,
+/// the forward shaders then only shade the lights in the list of the fragment's cluster.
+///
+/// Unlike the `RenderSubsystem`s this is owned by `RenderGlobal` directly, its passes have to run
+/// before the scene pass and the graph doesn't know about the buffers the scene pass reads.
+pub struct ClusteredLighting {
+	pub program_assign: Handle<ManagedProgram>,
+	pub program_size_accum: Handle<ManagedProgram>,
+	pub program_compact: Handle<ManagedProgram>,
+	
+	/// The lights of the scene, uploaded every frame
+	pub lights: LightList,
+	grid: ClusterGrid,
+	/// Whether all programs were available in `prepare_frame`, the light lists stay empty otherwise
+	programs_available: bool,
+	
+	grid_buffer: GpuBuffer<ClusterGridBlock>,
+	/// Programs the grid block was already bound to, a mismatch is only logged once per link
+	grid_block_programs: Vec<gl::uint>,
+	point_light_buffer: GpuArrayBuffer<PointLightData>,
+	spot_light_buffer: GpuArrayBuffer<SpotLightData>,
+	area_light_buffer: GpuArrayBuffer<AreaLightData>,
+	
+	/// Offset and number of point, spot and area lights of every cluster
+	cluster_light_grid_buffer: GpuArrayBuffer<Vector4<u32>>,
+	/// `CLUSTER_MAX_LIGHTS` slots per cluster the assignment writes to
+	cluster_assignment_scratch_buffer: GpuArrayBuffer<u32>,
+	/// The compacted lists of all clusters
+	cluster_light_indices_buffer: GpuArrayBuffer<u32>,
+}
+
+impl ClusteredLighting {
+	pub fn grid(&self) -> &ClusterGrid {
+		&self.grid
+	}
+	
+	/// Compiles the programs and uploads the lights and the grid for this frame. Needs a current gl context.
+	/// If a program is unavailable the lists aren't built this frame, shading sees empty ones instead.
+	pub fn prepare_frame(&mut self, grid: ClusterGrid, cluster_view: Matrix4<f32>) {
+		let mut programs_available = true;
+		for program in &[&self.program_assign, &self.program_size_accum, &self.program_compact] {
+			let mut program = match program.get_mut() {
+				Some(program) => program,
+				None => {
+					programs_available = false;
+					continue;
+				}
+			};
+			if program.needs_recompile() {
+				program.do_recompile();
+			}
+			
+			// The error program doesn't write the lists
+			let key = program.permutation_layout().default_key();
+			programs_available &= program.program_variant(key).program_gl().is_some() && !program.is_using_error_program(key);
+		}
+		self.programs_available = programs_available;
+		
+		// Upload the lights
+		self.point_light_buffer.upload(&self.lights.point_light_data());
+		self.spot_light_buffer.upload(&self.lights.spot_light_data());
+		self.area_light_buffer.upload(&self.lights.area_light_data());
+		
+		// Upload the grid
+		self.grid = grid;
+		self.grid_buffer.upload(&ClusterGridBlock {
+			cluster_view,
+			dimensions: Vector4::new(grid.dimensions.0, grid.dimensions.1, grid.dimensions.2, grid.num_clusters()),
+			light_counts: Vector4::new(self.lights.point_lights.len() as u32, self.lights.spot_lights.len() as u32, self.lights.area_lights.len() as u32, 0),
+			tile_scale: grid.tile_scale,
+			depth_planes: Vector2::new(grid.depth_planes.0, grid.depth_planes.1),
+		});
+		
+		// Bind the grid block to newly linked programs, which checks its layout against theirs
+		for program in &[&self.program_assign, &self.program_size_accum, &self.program_compact] {
+			let program = match program.get() {
+				Some(program) => program,
+				None => continue,
+			};
+			let shader = match program.cached_variant(program.permutation_layout().default_key()) {
+				Some(shader) => shader,
+				None => continue,
+			};
+			
+			if let Some(program_gl) = shader.program_gl().filter(|p| !self.grid_block_programs.contains(p)) {
+				if let Err(err) = self.grid_buffer.bind_to_block(shader, "ubClusterGrid") {
+					println!("Cluster grid block doesn't match {}: {}", program.program_asset_path().map_or("<unnamed program>", |p| p.inner_path_slice), err);
+				}
+				self.grid_block_programs.push(program_gl);
+			}
+		}
+		
+		// Every cluster can hold the same number of lights so the compacted lists always fit
+		let num_clusters = grid.num_clusters() as usize;
+		self.cluster_light_grid_buffer.allocate(num_clusters);
+		self.cluster_assignment_scratch_buffer.allocate(num_clusters * CLUSTER_MAX_LIGHTS as usize);
+		self.cluster_light_indices_buffer.allocate(num_clusters * CLUSTER_MAX_LIGHTS as usize);
+		
+		// Without the programs no cluster gets any lights
+		if !self.programs_available {
+			self.cluster_light_grid_buffer.upload(&vec![Vector4::new(0, 0, 0, 0); num_clusters]);
+		}
+	}
+	
+	/// Adds the compute passes building the cluster light lists. They have to be added
+	/// before the passes that shade with the lists, the graph orders them by declaration.
+	/// Nothing is added if a program was unavailable in `prepare_frame`.
+	pub fn add_passes<'a>(&'a self, graph: &mut RenderGraph<'a>) {
+		if !self.programs_available {
+			return;
+		}
+		
+		let num_groups = (self.grid.num_clusters() + CLUSTER_PASS_GROUP_SIZE - 1) / CLUSTER_PASS_GROUP_SIZE;
+		
+		let dispatch = move |program: &Handle<ManagedProgram>, num_groups: u32| unsafe {
+			// Skip the dispatch if the program has nothing to run
+			let program = match program.get() {
+				Some(program) => program,
+				None => return,
+			};
+			let shader_gl = match program.cached_variant(program.permutation_layout().default_key()).and_then(|s| s.program_gl()) {
+				Some(shader_gl) => shader_gl,
+				None => return,
+			};
+			
+			self.grid_buffer.bind(UBO_CLUSTER_GRID_BINDING);
+			gl::UseProgram(shader_gl);
+			gl::DispatchCompute(num_groups, 1, 1);
+			
+			// The graph only places barriers for textures
+			gl::MemoryBarrier(gl::SHADER_STORAGE_BARRIER_BIT);
+		};
+		
+		graph.add_pass("cluster_light_assign")
+			.side_effects()
+			.execute(move |_| {
+				self.point_light_buffer.bind(SSBO_POINT_LIGHT_DATA_BINDING);
+				self.spot_light_buffer.bind(SSBO_SPOT_LIGHT_DATA_BINDING);
+				self.area_light_buffer.bind(SSBO_AREA_LIGHT_DATA_BINDING);
+				self.cluster_light_grid_buffer.bind(SSBO_CLUSTER_LIGHT_GRID_BINDING);
+				self.cluster_assignment_scratch_buffer.bind(SSBO_CLUSTER_ASSIGNMENT_SCRATCH_BINDING);
+				
+				dispatch(&self.program_assign, num_groups);
+			});
+		
+		// A single work group does the whole prefix sum
+		graph.add_pass("cluster_size_accum")
+			.side_effects()
+			.execute(move |_| {
+				self.cluster_light_grid_buffer.bind(SSBO_CLUSTER_LIGHT_GRID_BINDING);
+				
+				dispatch(&self.program_size_accum, 1);
+			});
+		
+		graph.add_pass("cluster_light_compact")
+			.side_effects()
+			.execute(move |_| {
+				self.cluster_light_grid_buffer.bind(SSBO_CLUSTER_LIGHT_GRID_BINDING);
+				self.cluster_assignment_scratch_buffer.bind(SSBO_CLUSTER_ASSIGNMENT_SCRATCH_BINDING);
+				self.cluster_light_indices_buffer.bind(SSBO_CLUSTER_LIGHT_INDICES_BINDING);
+				
+				dispatch(&self.program_compact, num_groups);
+			});
+	}
+	
+	/// Binds the lights and the cluster light lists for shading.
+	pub fn bind_for_shading(&self) {
+		self.grid_buffer.bind(UBO_CLUSTER_GRID_BINDING);
+		self.point_light_buffer.bind(SSBO_POINT_LIGHT_DATA_BINDING);
+		self.spot_light_buffer.bind(SSBO_SPOT_LIGHT_DATA_BINDING);
+		self.area_light_buffer.bind(SSBO_AREA_LIGHT_DATA_BINDING);
+		self.cluster_light_grid_buffer.bind(SSBO_CLUSTER_LIGHT_GRID_BINDING);
+		self.cluster_light_indices_buffer.bind(SSBO_CLUSTER_LIGHT_INDICES_BINDING);
+	}
+	
+	pub fn dispose(&mut self) {
+		self.grid_buffer.dispose();
+		self.grid_block_programs.clear();
+		self.point_light_buffer.dispose();
+		self.spot_light_buffer.dispose();
+		self.area_light_buffer.dispose();
+		self.cluster_light_grid_buffer.dispose();
+		self.cluster_assignment_scratch_buffer.dispose();
+		self.cluster_light_indices_buffer.dispose();
+	}
+	
+	pub fn new(program_cache: &mut AssetCache<ManagedProgram>) -> ClusteredLighting {
+		ClusteredLighting {
+			program_assign: program_cache.load(&AssetPath::from_str("/shaders/clustered_lighting/cluster_light_assign.program")),
+			program_size_accum: program_cache.load(&AssetPath::from_str("/shaders/clustered_lighting/cluster_size_accum.program")),
+			program_compact: program_cache.load(&AssetPath::from_str("/shaders/clustered_lighting/cluster_light_compact.program")),
+			
+			lights: LightList::new(),
+			grid: ClusterGrid::new(CLUSTER_GRID_DIMENSIONS, Vector2::new(1.0, 1.0), (0.1, 1000.0)),
+			programs_available: false,
+			
+			grid_buffer: GpuBuffer::new_uniform(),
+			grid_block_programs: Vec::new(),
+			point_light_buffer: GpuArrayBuffer::new(),
+			spot_light_buffer: GpuArrayBuffer::new(),
+			area_light_buffer: GpuArrayBuffer::new(),
+			
+			cluster_light_grid_buffer: GpuArrayBuffer::new(),
+			cluster_assignment_scratch_buffer: GpuArrayBuffer::new(),
+			cluster_light_indices_buffer: GpuArrayBuffer::new(),
+		}
+	}
+}
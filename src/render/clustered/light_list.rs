@@ -0,0 +1,175 @@
+use cgmath::{Rad, Vector2, Vector3, Vector4, Angle};
+use crate::gpu_block;
+
+gpu_block! {
+	/// Mirrors `PointLightData` in `clustered_lighting/light_data.incl.ssl`.
+	#[derive(Copy, Clone, PartialEq, Debug)]
+	pub struct PointLightData {
+		pub position_and_radius: Vector4<f32>,
+		pub intensities_and_nothing: Vector4<f32>,
+	}
+}
+
+gpu_block! {
+	/// Mirrors `SpotLightData` in `clustered_lighting/light_data.incl.ssl`.
+	#[derive(Copy, Clone, PartialEq, Debug)]
+	pub struct SpotLightData {
+		pub position_and_radius: Vector4<f32>,
+		pub intensities_and_cos_inner_angle: Vector4<f32>,
+		pub direction_and_cos_outer_angle: Vector4<f32>,
+	}
+}
+
+gpu_block! {
+	/// Mirrors `AreaLightData` in `clustered_lighting/light_data.incl.ssl`.
+	#[derive(Copy, Clone, PartialEq, Debug)]
+	pub struct AreaLightData {
+		pub center_and_plane_width: Vector4<f32>,
+		pub intensities_and_plane_height: Vector4<f32>,
+		pub normal_and_cos_outer_angle: Vector4<f32>,
+		pub tangent_and_radius: Vector4<f32>,
+	}
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PointLight {
+	pub position: Vector3<f32>,
+	pub intensities: Vector3<f32>,
+	/// Distance at which the light is cut off, it isn't assigned to clusters further away
+	pub radius: f32,
+}
+
+impl PointLight {
+	pub fn light_data(&self) -> PointLightData {
+		PointLightData {
+			position_and_radius: self.position.extend(self.radius),
+			intensities_and_nothing: self.intensities.extend(0.0),
+		}
+	}
+	
+	pub fn new(position: Vector3<f32>, intensities: Vector3<f32>, radius: f32) -> PointLight {
+		PointLight {
+			position,
+			intensities,
+			radius,
+		}
+	}
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SpotLight {
+	pub position: Vector3<f32>,
+	/// Normalized direction the cone points to
+	pub direction: Vector3<f32>,
+	pub intensities: Vector3<f32>,
+	pub radius: f32,
+	/// Half angle of the cone lit at full intensity
+	pub inner_angle: Rad<f32>,
+	/// Half angle of the cone, the light fades out between the inner and outer angle
+	pub outer_angle: Rad<f32>,
+}
+
+impl SpotLight {
+	pub fn light_data(&self) -> SpotLightData {
+		SpotLightData {
+			position_and_radius: self.position.extend(self.radius),
+			intensities_and_cos_inner_angle: self.intensities.extend(self.inner_angle.cos()),
+			direction_and_cos_outer_angle: self.direction.extend(self.outer_angle.cos()),
+		}
+	}
+	
+	pub fn new(position: Vector3<f32>, direction: Vector3<f32>, intensities: Vector3<f32>, radius: f32, inner_angle: Rad<f32>, outer_angle: Rad<f32>) -> SpotLight {
+		SpotLight {
+			position,
+			direction,
+			intensities,
+			radius,
+			inner_angle,
+			outer_angle,
+		}
+	}
+}
+
+/// A rectangular light, lighting the side its normal points to.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct AreaLight {
+	pub center: Vector3<f32>,
+	/// Normalized
+	pub normal: Vector3<f32>,
+	/// Normalized and orthogonal to the normal, the width is measured along it
+	pub tangent: Vector3<f32>,
+	/// Width and height of the rectangle
+	pub size: Vector2<f32>,
+	pub intensities: Vector3<f32>,
+	/// Distance from the center at which the light is cut off
+	pub radius: f32,
+	/// Angle to the normal the light falls off to, 90 degrees give a lambertian emitter
+	pub outer_angle: Rad<f32>,
+}
+
+impl AreaLight {
+	pub fn light_data(&self) -> AreaLightData {
+		AreaLightData {
+			center_and_plane_width: self.center.extend(self.size.x),
+			intensities_and_plane_height: self.intensities.extend(self.size.y),
+			normal_and_cos_outer_angle: self.normal.extend(self.outer_angle.cos()),
+			tangent_and_radius: self.tangent.extend(self.radius),
+		}
+	}
+	
+	pub fn new(center: Vector3<f32>, normal: Vector3<f32>, tangent: Vector3<f32>, size: Vector2<f32>, intensities: Vector3<f32>, radius: f32, outer_angle: Rad<f32>) -> AreaLight {
+		AreaLight {
+			center,
+			normal,
+			tangent,
+			size,
+			intensities,
+			radius,
+			outer_angle,
+		}
+	}
+}
+
+/// The lights of a scene, in world space.
+#[derive(Clone, PartialEq, Debug)]
+pub struct LightList {
+	pub point_lights: Vec<PointLight>,
+	pub spot_lights: Vec<SpotLight>,
+	pub area_lights: Vec<AreaLight>,
+}
+
+impl LightList {
+	pub fn len(&self) -> usize {
+		self.point_lights.len() + self.spot_lights.len() + self.area_lights.len()
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+	
+	pub fn clear(&mut self) {
+		self.point_lights.clear();
+		self.spot_lights.clear();
+		self.area_lights.clear();
+	}
+	
+	pub fn point_light_data(&self) -> Vec<PointLightData> {
+		self.point_lights.iter().map(PointLight::light_data).collect()
+	}
+	
+	pub fn spot_light_data(&self) -> Vec<SpotLightData> {
+		self.spot_lights.iter().map(SpotLight::light_data).collect()
+	}
+	
+	pub fn area_light_data(&self) -> Vec<AreaLightData> {
+		self.area_lights.iter().map(AreaLight::light_data).collect()
+	}
+	
+	pub fn new() -> LightList {
+		LightList {
+			point_lights: Vec::new(),
+			spot_lights: Vec::new(),
+			area_lights: Vec::new(),
+		}
+	}
+}
@@ -0,0 +1,4 @@
+mod light_list; pub use light_list::*;
+mod cluster_grid; pub use cluster_grid::*;
+mod cluster_assignment; pub use cluster_assignment::*;
+mod clustered_lighting; pub use clustered_lighting::*;
@@ -0,0 +1,114 @@
+/// Smoothing factor of the exponential moving average over the gpu frametimes
+pub const DYNAMIC_RESOLUTION_SMOOTHING: f32 = 0.1;
+/// Relative distance to the target frametime in which the scale isn't changed
+pub const DYNAMIC_RESOLUTION_TOLERANCE: f32 = 0.1;
+/// Scales are rounded to multiples of this, every change reallocates the scene targets
+pub const DYNAMIC_RESOLUTION_SCALE_STEP: f32 = 0.05;
+/// Frames to wait after a change before the scale is changed again
+pub const DYNAMIC_RESOLUTION_COOLDOWN_FRAMES: u32 = 30;
+/// The lowest scale dynamic resolution goes down to
+pub const DYNAMIC_RESOLUTION_MIN_SCALE: f32 = 0.5;
+
+/// Picks the internal render scale from the measured gpu frametime so that it stays near a
+/// target. Knows nothing about the gpu, it is only fed frametimes.
+#[derive(Clone, Debug)]
+pub struct DynamicResolutionController {
+	min_scale: f32,
+	max_scale: f32,
+	target_frametime_ms: f32,
+	
+	current_scale: f32,
+	smoothed_frametime_ms: Option<f32>,
+	frames_since_change: u32,
+}
+
+impl DynamicResolutionController {
+	pub fn current_scale(&self) -> f32 {
+		self.current_scale
+	}
+	
+	pub fn smoothed_frametime_ms(&self) -> Option<f32> {
+		self.smoothed_frametime_ms
+	}
+	
+	pub fn min_scale(&self) -> f32 {
+		self.min_scale
+	}
+	
+	pub fn max_scale(&self) -> f32 {
+		self.max_scale
+	}
+	
+	/// Changes the limits, the current scale is clamped into them and the history is dropped.
+	pub fn set_limits(&mut self, min_scale: f32, max_scale: f32, target_frametime_ms: f32) {
+		self.min_scale = f32::min(min_scale, max_scale);
+		self.max_scale = max_scale;
+		self.target_frametime_ms = target_frametime_ms;
+		
+		self.current_scale = self.current_scale.max(self.min_scale).min(self.max_scale);
+		self.reset();
+	}
+	
+	/// Drops the frametime history, e.g. after the window was resized.
+	pub fn reset(&mut self) {
+		self.smoothed_frametime_ms = None;
+		self.frames_since_change = 0;
+	}
+	
+	/// Feeds the gpu time of a finished frame. Returns the scale to render the next frames at.
+	pub fn update(&mut self, gpu_frametime_ms: f32) -> f32 {
+		if !(gpu_frametime_ms > 0.0) || !gpu_frametime_ms.is_finite() {
+			return self.current_scale;
+		}
+		
+		// Smooth out single slow frames
+		let smoothed = match self.smoothed_frametime_ms {
+			Some(smoothed) => smoothed + (gpu_frametime_ms - smoothed) * DYNAMIC_RESOLUTION_SMOOTHING,
+			None => gpu_frametime_ms,
+		};
+		self.smoothed_frametime_ms = Some(smoothed);
+		
+		// Wait for the last change to show in the frametimes
+		self.frames_since_change = self.frames_since_change.saturating_add(1);
+		if self.frames_since_change < DYNAMIC_RESOLUTION_COOLDOWN_FRAMES {
+			return self.current_scale;
+		}
+		
+		let ratio = self.target_frametime_ms / smoothed;
+		if (ratio - 1.0).abs() <= DYNAMIC_RESOLUTION_TOLERANCE {
+			return self.current_scale;
+		}
+		
+		// The frametime roughly scales with the pixel count, that is the scale squared
+		let ideal_scale = self.current_scale * ratio.sqrt();
+		
+		// Drop straight to the ideal scale but only climb back one step at a time
+		let mut new_scale = (ideal_scale / DYNAMIC_RESOLUTION_SCALE_STEP).round() * DYNAMIC_RESOLUTION_SCALE_STEP;
+		if new_scale > self.current_scale {
+			new_scale = new_scale.min(self.current_scale + DYNAMIC_RESOLUTION_SCALE_STEP);
+		}
+		let new_scale = new_scale.max(self.min_scale).min(self.max_scale);
+		
+		if (new_scale - self.current_scale).abs() > std::f32::EPSILON {
+			self.current_scale = new_scale;
+			self.frames_since_change = 0;
+			
+			// The old frametimes were measured at a different scale
+			self.smoothed_frametime_ms = None;
+		}
+		self.current_scale
+	}
+	
+	/// Starts at the maximum scale.
+	pub fn new(min_scale: f32, max_scale: f32, target_frametime_ms: f32) -> Self {
+		DynamicResolutionController {
+			min_scale: f32::min(min_scale, max_scale),
+			max_scale,
+			target_frametime_ms,
+			
+			current_scale: max_scale,
+			smoothed_frametime_ms: None,
+			frames_since_change: 0,
+		}
+	}
+}
@@ -72,54 +72,17 @@ impl Framebuffer {
 				buffer
 			};
 			
-			let mut draw_buffer_table: [gl::enuma; 16] = [gl::NONE; 16];
-			
-			// Create depth attachment
+			// Allocate the textures
 			if let Some(depth_attachment) = &mut self.depth_attachment {
-				// Allocate texture
 				depth_attachment.allocate(self.width, self.height);
-				
-				// Attach texture to framebuffer
-				unsafe {
-					gl::NamedFramebufferTexture(self.handle_gl, AttachmentPoint::Depth.as_gl_enum().unwrap(), depth_attachment.texture.borrow().texture_gl(), depth_attachment.level as gl::int);
-				}
 			}
-			
-			// Create color attachment
 			for attachment in self.color_attachments.iter_mut() {
 				if let Some(attachment) = attachment.as_mut() {
-					// Allocate color texture
 					attachment.allocate(self.width, self.height);
-					
-					// Attach texture to framebuffer
-					unsafe {
-						gl::NamedFramebufferTexture(self.handle_gl, attachment.attachment_point.as_gl_enum().unwrap(), attachment.texture.borrow().texture_gl(), attachment.level as gl::int);
-					}
-					
-					// Update draw buffer table
-					let index = match attachment.attachment_point {
-						AttachmentPoint::Color(index) => index,
-						_ => panic!(),
-					};
-					draw_buffer_table[index as usize] = attachment.attachment_point.as_gl_enum().unwrap();
 				}
 			}
 			
-			// Set drawbuffers
-			unsafe {
-				// TODO: Actually set the draw buffers based on how many attachments we have
-				// Somehow the above doesn't work and generate an GL_INVALID_VALUE: Draw buffer is invalid error
-				let debug_draw_buffers = [gl::COLOR_ATTACHMENT0, gl::COLOR_ATTACHMENT1, gl::COLOR_ATTACHMENT2];
-				gl::NamedFramebufferDrawBuffers(self.handle_gl, debug_draw_buffers.len() as gl::sizei, debug_draw_buffers.as_ptr());
-			}
-			
-			// Check framebuffer status
-			let status = unsafe {
-				gl::CheckNamedFramebufferStatus(self.handle_gl, gl::FRAMEBUFFER)
-			};
-			if status != gl::FRAMEBUFFER_COMPLETE {
-				eprintln!("Framebuffer is incomplete: {}", status);
-			}
+			self.attach_textures();
 			return true;
 		}
 		else {
@@ -127,26 +90,93 @@ impl Framebuffer {
 		}
 	}
 	
+	/// Attaches the textures to the framebuffer object, has to be redone
+	/// whenever the textures are recreated.
+	fn attach_textures(&mut self) {
+		let mut draw_buffer_table: [gl::enuma; 16] = [gl::NONE; 16];
+		
+		// Attach depth texture
+		if let Some(depth_attachment) = &self.depth_attachment {
+			unsafe {
+				gl::NamedFramebufferTexture(self.handle_gl, AttachmentPoint::Depth.as_gl_enum().unwrap(), depth_attachment.texture.borrow().texture_gl(), depth_attachment.level as gl::int);
+			}
+		}
+		
+		// Attach color textures
+		for attachment in self.color_attachments.iter() {
+			if let Some(attachment) = attachment.as_ref() {
+				unsafe {
+					gl::NamedFramebufferTexture(self.handle_gl, attachment.attachment_point.as_gl_enum().unwrap(), attachment.texture.borrow().texture_gl(), attachment.level as gl::int);
+				}
+				
+				// Update draw buffer table
+				let index = match attachment.attachment_point {
+					AttachmentPoint::Color(index) => index,
+					_ => panic!(),
+				};
+				draw_buffer_table[index as usize] = attachment.attachment_point.as_gl_enum().unwrap();
+			}
+		}
+		
+		// Set drawbuffers
+		unsafe {
+			// TODO: Actually set the draw buffers based on how many attachments we have
+			// Somehow the above doesn't work and generate an GL_INVALID_VALUE: Draw buffer is invalid error
+			let debug_draw_buffers = [gl::COLOR_ATTACHMENT0, gl::COLOR_ATTACHMENT1, gl::COLOR_ATTACHMENT2];
+			gl::NamedFramebufferDrawBuffers(self.handle_gl, debug_draw_buffers.len() as gl::sizei, debug_draw_buffers.as_ptr());
+		}
+		
+		// Check framebuffer status
+		let status = unsafe {
+			gl::CheckNamedFramebufferStatus(self.handle_gl, gl::FRAMEBUFFER)
+		};
+		if status != gl::FRAMEBUFFER_COMPLETE {
+			eprintln!("Framebuffer is incomplete: {}", status);
+		}
+	}
+	
+	/// Recreates the textures with the new size and attaches them again.
+	/// Returns false if the size didn't change.
 	pub fn resize(&mut self, new_width: u32, new_height: u32) -> bool {
-		fn resize_attachment(attachment: &mut FramebufferAttachment, new_width: u32, new_height: u32) {
-			attachment.resize(new_width, new_height);
+		if (new_width, new_height) == (self.width, self.height) {
+			return false;
+		}
+		self.width = new_width;
+		self.height = new_height;
+		
+		// Not allocated yet, the textures get the new size when it is
+		if !self.is_allocated {
+			return true;
 		}
 		
 		// Resize depth attachment
 		if let Some(depth) = &mut self.depth_attachment {
-			resize_attachment(depth, new_width, new_height);
+			depth.resize(new_width, new_height);
 		}
 		
 		// Resize color attachments
 		for attachment in &mut self.color_attachments.iter_mut() {
 			if let Some(color) = attachment.as_mut() {
-				resize_attachment(color, new_width, new_height);
+				color.resize(new_width, new_height);
 			}
 		}
 		
+		// The textures got new gl names
+		self.attach_textures();
 		true
 	}
 	
+	/// Deletes the gl framebuffer object. The attached textures are left alone.
+	pub fn dispose(&mut self) {
+		if self.handle_gl != 0 {
+			unsafe {
+				gl::DeleteFramebuffers(1, &self.handle_gl);
+			}
+			self.handle_gl = 0;
+		}
+		self.is_allocated = false;
+	}
+	
 	pub fn new(width: u32, height: u32) -> Framebuffer {
 		Framebuffer {
 			width,
@@ -174,15 +204,7 @@ impl FramebufferAttachment {
 	
 	pub fn resize(&mut self, width: u32, height: u32) {
 		// Delete old texture
-		let tex = RefCell::borrow_mut(&mut self.texture);
-		
-		if tex.texture_gl() != 0 {
-			let tex_gl: gl::uint = tex.texture_gl(); 
-			unsafe {
-				gl::DeleteTextures(1, &tex_gl);
-			}
-		}
-		drop(tex);
+		RefCell::borrow_mut(&self.texture).dispose();
 		
 		// Reallocate
 		self.allocate(width, height);
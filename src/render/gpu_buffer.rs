@@ -0,0 +1,218 @@
+use std::marker::PhantomData;
+use gl_bindings::gl;
+use crate::render::shader::{GpuBlock, GpuBlockMember, BlockLayoutStd, ShaderProgram, array_stride, array_to_bytes};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum GpuBufferTarget {
+	Uniform,
+	Storage,
+}
+
+impl GpuBufferTarget {
+	pub fn target_gl(&self) -> gl::enuma {
+		match self {
+			GpuBufferTarget::Uniform => gl::UNIFORM_BUFFER,
+			GpuBufferTarget::Storage => gl::SHADER_STORAGE_BUFFER,
+		}
+	}
+}
+
+/// A gl buffer holding a single `gpu_block!` struct, laid out
+/// std140 for uniform buffers and std430 for storage buffers.
+pub struct GpuBuffer<T: GpuBlock> {
+	buffer_gl: gl::uint,
+	target: GpuBufferTarget,
+	std: BlockLayoutStd,
+	/// Programs the layout was already checked against
+	checked_programs: Vec<gl::uint>,
+	_block: PhantomData<T>,
+}
+
+impl<T: GpuBlock> GpuBuffer<T> {
+	pub fn buffer_gl(&self) -> gl::uint {
+		self.buffer_gl
+	}
+	
+	pub fn target(&self) -> GpuBufferTarget {
+		self.target
+	}
+	
+	pub fn layout_std(&self) -> BlockLayoutStd {
+		self.std
+	}
+	
+	/// Uploads the value, allocating the buffer on first use. Needs a current gl context.
+	pub fn upload(&mut self, value: &T) {
+		let bytes = value.to_bytes(self.std);
+		
+		unsafe {
+			if self.buffer_gl == 0 {
+				gl::CreateBuffers(1, &mut self.buffer_gl);
+				gl::NamedBufferStorage(self.buffer_gl, bytes.len() as gl::sizeiptr, bytes.as_ptr() as *const gl::void, gl::DYNAMIC_STORAGE_BIT);
+			}
+			else {
+				gl::NamedBufferSubData(self.buffer_gl, 0, bytes.len() as gl::intptr, bytes.as_ptr() as *const gl::void);
+			}
+		}
+	}
+	
+	/// Binds the buffer to the binding point of `block_name` in `program`.
+	///
+	/// The first time a program is used the layout is checked against the
+	/// reflected block, a mismatch is returned as error and the buffer isn't bound.
+	pub fn bind_to_block(&mut self, program: &ShaderProgram, block_name: &str) -> Result<(), String> {
+		let program_gl = program.program_gl().ok_or("Program is not linked")?;
+		let reflection = program.reflection().ok_or("Program is not linked")?;
+		
+		let block = match self.target {
+			GpuBufferTarget::Uniform => reflection.uniform_block(block_name),
+			GpuBufferTarget::Storage => reflection.storage_block(block_name),
+		}.ok_or_else(|| format!("No active block named {}", block_name))?;
+		
+		// Check the layout on first use
+		if !self.checked_programs.contains(&program_gl) {
+			T::layout(self.std).check(block)?;
+			self.checked_programs.push(program_gl);
+		}
+		
+		self.bind(block.binding);
+		Ok(())
+	}
+	
+	/// Binds the buffer to a binding point without any checks.
+	pub fn bind(&self, binding: gl::uint) {
+		unsafe {
+			gl::BindBufferBase(self.target.target_gl(), binding, self.buffer_gl);
+		}
+	}
+	
+	pub fn dispose(&mut self) {
+		if self.buffer_gl != 0 {
+			unsafe {
+				gl::DeleteBuffers(1, &self.buffer_gl);
+			}
+			self.buffer_gl = 0;
+		}
+		self.checked_programs.clear();
+	}
+	
+	pub fn new_uniform() -> Self {
+		Self::new(GpuBufferTarget::Uniform, BlockLayoutStd::Std140)
+	}
+	
+	pub fn new_storage() -> Self {
+		Self::new(GpuBufferTarget::Storage, BlockLayoutStd::Std430)
+	}
+	
+	pub fn new(target: GpuBufferTarget, std: BlockLayoutStd) -> Self {
+		Self {
+			buffer_gl: 0,
+			target,
+			std,
+			checked_programs: Vec::new(),
+			_block: PhantomData,
+		}
+	}
+}
+
+impl<T: GpuBlock> Drop for GpuBuffer<T> {
+	fn drop(&mut self) {
+		self.dispose();
+	}
+}
+
+/// A storage buffer holding a runtime sized array of `T`, laid out std430.
+/// The buffer only ever grows, so it can be refilled every frame without reallocating.
+pub struct GpuArrayBuffer<T: GpuBlockMember> {
+	buffer_gl: gl::uint,
+	/// Number of elements the buffer has room for
+	capacity: usize,
+	len: usize,
+	_element: PhantomData<T>,
+}
+
+impl<T: GpuBlockMember> GpuArrayBuffer<T> {
+	pub fn buffer_gl(&self) -> gl::uint {
+		self.buffer_gl
+	}
+	
+	pub fn len(&self) -> usize {
+		self.len
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+	
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+	
+	/// Uploads the elements, growing the buffer if needed. Needs a current gl context.
+	pub fn upload(&mut self, elements: &[T]) {
+		self.reserve(elements.len());
+		self.len = elements.len();
+		
+		let bytes = array_to_bytes(elements, BlockLayoutStd::Std430);
+		if !bytes.is_empty() {
+			unsafe {
+				gl::NamedBufferSubData(self.buffer_gl, 0, bytes.len() as gl::intptr, bytes.as_ptr() as *const gl::void);
+			}
+		}
+	}
+	
+	/// Makes room for `len` elements written by shaders. The contents are undefined after the buffer grew.
+	pub fn allocate(&mut self, len: usize) {
+		self.reserve(len);
+		self.len = len;
+	}
+	
+	fn reserve(&mut self, len: usize) {
+		if self.buffer_gl != 0 && len <= self.capacity {
+			return;
+		}
+		self.dispose();
+		
+		// Binding an empty buffer is an error, so there is always room for one element
+		self.capacity = len.max(1).next_power_of_two();
+		let size = array_stride::<T>(BlockLayoutStd::Std430) * self.capacity;
+		
+		unsafe {
+			gl::CreateBuffers(1, &mut self.buffer_gl);
+			gl::NamedBufferStorage(self.buffer_gl, size as gl::sizeiptr, std::ptr::null(), gl::DYNAMIC_STORAGE_BIT);
+		}
+	}
+	
+	/// Binds the buffer to a storage buffer binding point.
+	pub fn bind(&self, binding: gl::uint) {
+		unsafe {
+			gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, self.buffer_gl);
+		}
+	}
+	
+	pub fn dispose(&mut self) {
+		if self.buffer_gl != 0 {
+			unsafe {
+				gl::DeleteBuffers(1, &self.buffer_gl);
+			}
+			self.buffer_gl = 0;
+		}
+		self.capacity = 0;
+		self.len = 0;
+	}
+	
+	pub fn new() -> Self {
+		Self {
+			buffer_gl: 0,
+			capacity: 0,
+			len: 0,
+			_element: PhantomData,
+		}
+	}
+}
+
+impl<T: GpuBlockMember> Drop for GpuArrayBuffer<T> {
+	fn drop(&mut self) {
+		self.dispose();
+	}
+}
@@ -1,10 +1,405 @@
+use std::error::{self, Error};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Read;
+use std::path::Path;
+use crate::render::GraphicsSettingsSchema::{GraphicsSettingsDef, QualityPresetDef, AntiAliasingDef, UpscaleFilterDef};
 
-pub struct GraphicsSettingsTable {
+/// Kernel sizes the sss resolve shader has precomputed kernels for
+pub const SUPPORTED_SSS_KERNEL_SIZES: [u32; 3] = [7, 11, 21];
+pub const MIN_RENDER_SCALE: f32 = 0.25;
+pub const MAX_RENDER_SCALE: f32 = 2.0;
+pub const MAX_BLOOM_OCTAVES: u32 = 8;
+pub const MAX_BLOOM_INTENSITY: f32 = 1.0;
+pub const MIN_TARGET_FRAMETIME_MS: f32 = 1.0;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum QualityPreset {
+	Low,
+	Medium,
+	High,
+	Ultra,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AntiAliasingMode {
+	None,
+	/// Edge heuristic aa, the scene writes the edges and the post composite pass resolves them
+	Ehaa,
+}
+
+/// How the scene is scaled to the window resolution by the post composite pass.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum UpscaleFilter {
+	Bilinear,
+	/// Bilinear with contrast adaptive sharpening, sharpens less along edges to avoid halos
+	Sharpen,
+}
+
+/// A single setting, used to find the parts of the pipeline a change affects.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum GraphicsSetting {
+	RenderScale,
+	DynamicResolution,
+	UpscaleFilter,
+	SssKernelSize,
+	BloomOctaves,
+	BloomIntensity,
+	BloomThreshold,
+	AntiAliasing,
+	Vsync,
+	Tessellation,
+}
+
+impl GraphicsSetting {
+	pub fn all() -> &'static [GraphicsSetting] {
+		&[
+			GraphicsSetting::RenderScale,
+			GraphicsSetting::DynamicResolution,
+			GraphicsSetting::UpscaleFilter,
+			GraphicsSetting::SssKernelSize,
+			GraphicsSetting::BloomOctaves,
+			GraphicsSetting::BloomIntensity,
+			GraphicsSetting::BloomThreshold,
+			GraphicsSetting::AntiAliasing,
+			GraphicsSetting::Vsync,
+			GraphicsSetting::Tessellation,
+		]
+	}
+}
+
+/// The resolved settings the pipeline runs with.
+#[derive(Clone, PartialEq, Debug)]
+pub struct GraphicsConfiguration {
+	/// Internal scene resolution relative to the window, the upper bound with dynamic resolution
+	pub render_scale: f32,
+	/// Lowers the render scale when the gpu frametime exceeds the target
+	pub dynamic_resolution: bool,
+	pub target_frametime_ms: f32,
+	pub upscale_filter: UpscaleFilter,
+	pub sss_kernel_size: u32,
+	pub bloom_octaves: u32,
+	/// Amount of bloom added onto the scene
+	pub bloom_intensity: f32,
+	/// Brightness below which the scene doesn't bloom, 0 lets the whole scene bloom
+	pub bloom_threshold: f32,
+	pub anti_aliasing: AntiAliasingMode,
+	pub vsync: bool,
+	pub tessellation: bool,
+}
+
+impl GraphicsConfiguration {
+	pub fn validate(&self) -> Result<(), GraphicsSettingsError> {
+		if !(self.render_scale >= MIN_RENDER_SCALE && self.render_scale <= MAX_RENDER_SCALE) {
+			return Err(GraphicsSettingsError::RenderScaleOutOfRange {render_scale: self.render_scale});
+		}
+		if !(self.target_frametime_ms >= MIN_TARGET_FRAMETIME_MS) {
+			return Err(GraphicsSettingsError::InvalidTargetFrametime {target_frametime_ms: self.target_frametime_ms});
+		}
+		if !SUPPORTED_SSS_KERNEL_SIZES.contains(&self.sss_kernel_size) {
+			return Err(GraphicsSettingsError::UnsupportedSssKernelSize {kernel_size: self.sss_kernel_size});
+		}
+		if self.bloom_octaves == 0 || self.bloom_octaves > MAX_BLOOM_OCTAVES {
+			return Err(GraphicsSettingsError::BloomOctavesOutOfRange {octaves: self.bloom_octaves});
+		}
+		if !(self.bloom_intensity >= 0.0 && self.bloom_intensity <= MAX_BLOOM_INTENSITY) {
+			return Err(GraphicsSettingsError::BloomIntensityOutOfRange {intensity: self.bloom_intensity});
+		}
+		if !(self.bloom_threshold >= 0.0 && self.bloom_threshold.is_finite()) {
+			return Err(GraphicsSettingsError::InvalidBloomThreshold {threshold: self.bloom_threshold});
+		}
+		Ok(())
+	}
+	
+	/// The settings that differ between the two configurations.
+	pub fn changed_settings(&self, other: &GraphicsConfiguration) -> Vec<GraphicsSetting> {
+		let mut changed = Vec::new();
+		if self.render_scale != other.render_scale {
+			changed.push(GraphicsSetting::RenderScale);
+		}
+		if self.dynamic_resolution != other.dynamic_resolution || self.target_frametime_ms != other.target_frametime_ms {
+			changed.push(GraphicsSetting::DynamicResolution);
+		}
+		if self.upscale_filter != other.upscale_filter {
+			changed.push(GraphicsSetting::UpscaleFilter);
+		}
+		if self.sss_kernel_size != other.sss_kernel_size {
+			changed.push(GraphicsSetting::SssKernelSize);
+		}
+		if self.bloom_octaves != other.bloom_octaves {
+			changed.push(GraphicsSetting::BloomOctaves);
+		}
+		if self.bloom_intensity != other.bloom_intensity {
+			changed.push(GraphicsSetting::BloomIntensity);
+		}
+		if self.bloom_threshold != other.bloom_threshold {
+			changed.push(GraphicsSetting::BloomThreshold);
+		}
+		if self.anti_aliasing != other.anti_aliasing {
+			changed.push(GraphicsSetting::AntiAliasing);
+		}
+		if self.vsync != other.vsync {
+			changed.push(GraphicsSetting::Vsync);
+		}
+		if self.tessellation != other.tessellation {
+			changed.push(GraphicsSetting::Tessellation);
+		}
+		changed
+	}
+	
+	/// Internal scene resolution for a window resolution at the fixed render scale.
+	pub fn scaled_resolution(&self, resolution: (u32, u32)) -> (u32, u32) {
+		scale_resolution(resolution, self.render_scale)
+	}
+	
+	pub fn from_preset(preset: QualityPreset) -> GraphicsConfiguration {
+		match preset {
+			QualityPreset::Low => GraphicsConfiguration {
+				render_scale: 0.5,
+				dynamic_resolution: false,
+				target_frametime_ms: 16.0,
+				upscale_filter: UpscaleFilter::Sharpen,
+				sss_kernel_size: 7,
+				bloom_octaves: 4,
+				bloom_intensity: 0.3,
+				bloom_threshold: 1.0,
+				anti_aliasing: AntiAliasingMode::None,
+				vsync: true,
+				tessellation: false,
+			},
+			QualityPreset::Medium => GraphicsConfiguration {
+				render_scale: 0.75,
+				dynamic_resolution: false,
+				target_frametime_ms: 16.0,
+				upscale_filter: UpscaleFilter::Sharpen,
+				sss_kernel_size: 11,
+				bloom_octaves: 5,
+				bloom_intensity: 0.3,
+				bloom_threshold: 1.0,
+				anti_aliasing: AntiAliasingMode::Ehaa,
+				vsync: true,
+				tessellation: false,
+			},
+			QualityPreset::High => GraphicsConfiguration {
+				render_scale: 1.0,
+				dynamic_resolution: false,
+				target_frametime_ms: 16.0,
+				upscale_filter: UpscaleFilter::Bilinear,
+				sss_kernel_size: 21,
+				bloom_octaves: 6,
+				bloom_intensity: 0.3,
+				bloom_threshold: 1.0,
+				anti_aliasing: AntiAliasingMode::Ehaa,
+				vsync: true,
+				tessellation: true,
+			},
+			QualityPreset::Ultra => GraphicsConfiguration {
+				render_scale: 1.5,
+				dynamic_resolution: false,
+				target_frametime_ms: 16.0,
+				upscale_filter: UpscaleFilter::Bilinear,
+				sss_kernel_size: 21,
+				bloom_octaves: 8,
+				bloom_intensity: 0.3,
+				bloom_threshold: 1.0,
+				anti_aliasing: AntiAliasingMode::Ehaa,
+				vsync: true,
+				tessellation: true,
+			},
+		}
+	}
 	
+	pub fn new() -> GraphicsConfiguration {
+		GraphicsConfiguration::from_preset(QualityPreset::High)
+	}
+}
+
+/// The user's settings: a quality preset and the settings overridden from it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct GraphicsSettingsTable {
+	pub preset: QualityPreset,
+	pub render_scale: Option<f32>,
+	pub dynamic_resolution: Option<bool>,
+	pub target_frametime_ms: Option<f32>,
+	pub upscale_filter: Option<UpscaleFilter>,
+	pub sss_kernel_size: Option<u32>,
+	pub bloom_octaves: Option<u32>,
+	pub bloom_intensity: Option<f32>,
+	pub bloom_threshold: Option<f32>,
+	pub anti_aliasing: Option<AntiAliasingMode>,
+	pub vsync: Option<bool>,
+	pub tessellation: Option<bool>,
 }
 
 impl GraphicsSettingsTable {
+	/// Applies the overrides on top of the preset and validates the result.
+	pub fn resolve(&self) -> Result<GraphicsConfiguration, GraphicsSettingsError> {
+		let preset = GraphicsConfiguration::from_preset(self.preset);
+		let configuration = GraphicsConfiguration {
+			render_scale: self.render_scale.unwrap_or(preset.render_scale),
+			dynamic_resolution: self.dynamic_resolution.unwrap_or(preset.dynamic_resolution),
+			target_frametime_ms: self.target_frametime_ms.unwrap_or(preset.target_frametime_ms),
+			upscale_filter: self.upscale_filter.unwrap_or(preset.upscale_filter),
+			sss_kernel_size: self.sss_kernel_size.unwrap_or(preset.sss_kernel_size),
+			bloom_octaves: self.bloom_octaves.unwrap_or(preset.bloom_octaves),
+			bloom_intensity: self.bloom_intensity.unwrap_or(preset.bloom_intensity),
+			bloom_threshold: self.bloom_threshold.unwrap_or(preset.bloom_threshold),
+			anti_aliasing: self.anti_aliasing.unwrap_or(preset.anti_aliasing),
+			vsync: self.vsync.unwrap_or(preset.vsync),
+			tessellation: self.tessellation.unwrap_or(preset.tessellation),
+		};
+		
+		configuration.validate()?;
+		Ok(configuration)
+	}
+	
+	/// Switches to a preset, all overrides are dropped.
+	pub fn set_preset(&mut self, preset: QualityPreset) {
+		*self = GraphicsSettingsTable::from_preset(preset);
+	}
+	
+	/// Stores the settings of `configuration` that differ from the current preset as overrides.
+	pub fn set_configuration(&mut self, configuration: &GraphicsConfiguration) {
+		let preset = GraphicsConfiguration::from_preset(self.preset);
+		fn differing<T: PartialEq>(value: T, preset_value: T) -> Option<T> {
+			if value != preset_value {Some(value)} else {None}
+		}
+		
+		self.render_scale = differing(configuration.render_scale, preset.render_scale);
+		self.dynamic_resolution = differing(configuration.dynamic_resolution, preset.dynamic_resolution);
+		self.target_frametime_ms = differing(configuration.target_frametime_ms, preset.target_frametime_ms);
+		self.upscale_filter = differing(configuration.upscale_filter, preset.upscale_filter);
+		self.sss_kernel_size = differing(configuration.sss_kernel_size, preset.sss_kernel_size);
+		self.bloom_octaves = differing(configuration.bloom_octaves, preset.bloom_octaves);
+		self.bloom_intensity = differing(configuration.bloom_intensity, preset.bloom_intensity);
+		self.bloom_threshold = differing(configuration.bloom_threshold, preset.bloom_threshold);
+		self.anti_aliasing = differing(configuration.anti_aliasing, preset.anti_aliasing);
+		self.vsync = differing(configuration.vsync, preset.vsync);
+		self.tessellation = differing(configuration.tessellation, preset.tessellation);
+	}
+	
+	pub fn load(fs_path: &Path) -> Result<GraphicsSettingsTable, Box<dyn error::Error>> {
+		let mut file = OpenOptions::new().read(true).open(fs_path)?;
+		let mut buffer = String::new();
+		file.read_to_string(&mut buffer)?;
+		
+		let settings = GraphicsSettingsTable::from_def(&ron::de::from_str::<GraphicsSettingsDef>(&buffer)?);
+		settings.resolve()?;
+		Ok(settings)
+	}
+	
+	pub fn save(&self, fs_path: &Path) -> Result<(), Box<dyn error::Error>> {
+		let contents = ron::ser::to_string_pretty(&self.to_def(), ron::ser::PrettyConfig::default())?;
+		fs::write(fs_path, contents)?;
+		Ok(())
+	}
+	
+	pub fn to_def(&self) -> GraphicsSettingsDef {
+		GraphicsSettingsDef {
+			preset: match self.preset {
+				QualityPreset::Low => QualityPresetDef::Low,
+				QualityPreset::Medium => QualityPresetDef::Medium,
+				QualityPreset::High => QualityPresetDef::High,
+				QualityPreset::Ultra => QualityPresetDef::Ultra,
+			},
+			render_scale: self.render_scale,
+			dynamic_resolution: self.dynamic_resolution,
+			target_frametime_ms: self.target_frametime_ms,
+			upscale_filter: self.upscale_filter.map(|filter| match filter {
+				UpscaleFilter::Bilinear => UpscaleFilterDef::Bilinear,
+				UpscaleFilter::Sharpen => UpscaleFilterDef::Sharpen,
+			}),
+			sss_kernel_size: self.sss_kernel_size,
+			bloom_octaves: self.bloom_octaves,
+			bloom_intensity: self.bloom_intensity,
+			bloom_threshold: self.bloom_threshold,
+			anti_aliasing: self.anti_aliasing.map(|mode| match mode {
+				AntiAliasingMode::None => AntiAliasingDef::None,
+				AntiAliasingMode::Ehaa => AntiAliasingDef::Ehaa,
+			}),
+			vsync: self.vsync,
+			tessellation: self.tessellation,
+		}
+	}
+	
+	pub fn from_def(def: &GraphicsSettingsDef) -> GraphicsSettingsTable {
+		GraphicsSettingsTable {
+			preset: match def.preset {
+				QualityPresetDef::Low => QualityPreset::Low,
+				QualityPresetDef::Medium => QualityPreset::Medium,
+				QualityPresetDef::High => QualityPreset::High,
+				QualityPresetDef::Ultra => QualityPreset::Ultra,
+			},
+			render_scale: def.render_scale,
+			dynamic_resolution: def.dynamic_resolution,
+			target_frametime_ms: def.target_frametime_ms,
+			upscale_filter: def.upscale_filter.map(|filter| match filter {
+				UpscaleFilterDef::Bilinear => UpscaleFilter::Bilinear,
+				UpscaleFilterDef::Sharpen => UpscaleFilter::Sharpen,
+			}),
+			sss_kernel_size: def.sss_kernel_size,
+			bloom_octaves: def.bloom_octaves,
+			bloom_intensity: def.bloom_intensity,
+			bloom_threshold: def.bloom_threshold,
+			anti_aliasing: def.anti_aliasing.map(|mode| match mode {
+				AntiAliasingDef::None => AntiAliasingMode::None,
+				AntiAliasingDef::Ehaa => AntiAliasingMode::Ehaa,
+			}),
+			vsync: def.vsync,
+			tessellation: def.tessellation,
+		}
+	}
+	
+	pub fn from_preset(preset: QualityPreset) -> GraphicsSettingsTable {
+		GraphicsSettingsTable {
+			preset,
+			render_scale: None,
+			dynamic_resolution: None,
+			target_frametime_ms: None,
+			upscale_filter: None,
+			sss_kernel_size: None,
+			bloom_octaves: None,
+			bloom_intensity: None,
+			bloom_threshold: None,
+			anti_aliasing: None,
+			vsync: None,
+			tessellation: None,
+		}
+	}
+	
 	pub fn new() -> GraphicsSettingsTable {
-		GraphicsSettingsTable {}
+		GraphicsSettingsTable::from_preset(QualityPreset::High)
+	}
+}
+
+/// Scales a resolution, the result is at least 1x1.
+pub fn scale_resolution(resolution: (u32, u32), scale: f32) -> (u32, u32) {
+	let scale = |x: u32| u32::max((x as f32 * scale).round() as u32, 1);
+	(scale(resolution.0), scale(resolution.1))
+}
+
+#[derive(Debug)]
+pub enum GraphicsSettingsError {
+	RenderScaleOutOfRange {render_scale: f32},
+	InvalidTargetFrametime {target_frametime_ms: f32},
+	/// There is no precomputed kernel of that size
+	UnsupportedSssKernelSize {kernel_size: u32},
+	BloomOctavesOutOfRange {octaves: u32},
+	BloomIntensityOutOfRange {intensity: f32},
+	InvalidBloomThreshold {threshold: f32},
+}
+
+impl Error for GraphicsSettingsError {}
+
+impl fmt::Display for GraphicsSettingsError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		match self {
+			GraphicsSettingsError::RenderScaleOutOfRange {render_scale} => write!(f, "Render scale {} is not within {} and {}", render_scale, MIN_RENDER_SCALE, MAX_RENDER_SCALE),
+			GraphicsSettingsError::InvalidTargetFrametime {target_frametime_ms} => write!(f, "Target frametime {} ms is below {} ms", target_frametime_ms, MIN_TARGET_FRAMETIME_MS),
+			GraphicsSettingsError::UnsupportedSssKernelSize {kernel_size} => write!(f, "Unsupported sss kernel size {}, supported are {:?}", kernel_size, SUPPORTED_SSS_KERNEL_SIZES),
+			GraphicsSettingsError::BloomOctavesOutOfRange {octaves} => write!(f, "Bloom octaves {} is not within 1 and {}", octaves, MAX_BLOOM_OCTAVES),
+			GraphicsSettingsError::BloomIntensityOutOfRange {intensity} => write!(f, "Bloom intensity {} is not within 0 and {}", intensity, MAX_BLOOM_INTENSITY),
+			GraphicsSettingsError::InvalidBloomThreshold {threshold} => write!(f, "Bloom threshold {} is negative", threshold),
+		}
 	}
 }
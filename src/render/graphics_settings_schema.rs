@@ -0,0 +1,58 @@
+
+#[allow(non_snake_case)]
+pub mod GraphicsSettingsSchema {
+	use serde::{Deserialize, Serialize};
+	
+	/// The user's graphics settings file. Settings that aren't given use the value of the preset.
+	#[derive(Serialize, Deserialize, Clone, Debug)]
+	#[serde(rename = "GraphicsSettings")]
+	pub struct GraphicsSettingsDef {
+		pub preset: self::QualityPresetDef,
+		
+		#[serde(default)]
+		pub render_scale: Option<f32>,
+		#[serde(default)]
+		pub dynamic_resolution: Option<bool>,
+		#[serde(default)]
+		pub target_frametime_ms: Option<f32>,
+		#[serde(default)]
+		pub upscale_filter: Option<self::UpscaleFilterDef>,
+		#[serde(default)]
+		pub sss_kernel_size: Option<u32>,
+		#[serde(default)]
+		pub bloom_octaves: Option<u32>,
+		#[serde(default)]
+		pub bloom_intensity: Option<f32>,
+		#[serde(default)]
+		pub bloom_threshold: Option<f32>,
+		#[serde(default)]
+		pub anti_aliasing: Option<self::AntiAliasingDef>,
+		#[serde(default)]
+		pub vsync: Option<bool>,
+		#[serde(default)]
+		pub tessellation: Option<bool>,
+	}
+	
+	#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+	#[serde(rename = "QualityPreset")]
+	pub enum QualityPresetDef {
+		Low,
+		Medium,
+		High,
+		Ultra,
+	}
+	
+	#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+	#[serde(rename = "AntiAliasing")]
+	pub enum AntiAliasingDef {
+		None,
+		Ehaa,
+	}
+	
+	#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+	#[serde(rename = "UpscaleFilter")]
+	pub enum UpscaleFilterDef {
+		Bilinear,
+		Sharpen,
+	}
+}
@@ -0,0 +1,173 @@
+use std::cell::Ref;
+use std::error;
+use std::fs::OpenOptions;
+use std::io::Read;
+use cgmath::{Vector2, Vector3, Vector4};
+use gl_bindings::gl;
+use crate::asset::{AssetCache, AssetLoadService, AssetPath, AssetPathBuf, Handle, ASSET_MANAGER_INSTANCE, hash_content};
+use crate::render::{GraphicsConfiguration, Texture};
+use crate::render::material::{RenderState, Sampler};
+use crate::render::material::SurfaceAssetSchema::{self, ParameterValueDef};
+use crate::render::shader::ShaderProgram;
+use crate::render::shader::managed::ManagedProgram;
+
+/// Runtime version of a `.surface` file.
+pub struct Material {
+	surface_asset_path: AssetPathBuf,
+	program: Handle<ManagedProgram>,
+	permutation: Vec<(String, String)>,
+	textures: Vec<MaterialTexture>,
+	parameters: Vec<(String, ParameterValueDef)>,
+	render_state: RenderState,
+}
+
+struct MaterialTexture {
+	uniform: String,
+	texture: Handle<Texture>,
+	unit: Option<u32>,
+	sampler: Option<Sampler>,
+}
+
+impl Material {
+	pub fn surface_asset_path<'a>(&'a self) -> AssetPath<'a> {
+		self.surface_asset_path.as_path()
+	}
+	
+	pub fn program(&self) -> &Handle<ManagedProgram> {
+		&self.program
+	}
+	
+	pub fn render_state(&self) -> &RenderState {
+		&self.render_state
+	}
+	
+	/// Checks if the program and all textures finished loading.
+	pub fn is_ready(&self) -> bool {
+		self.program.is_ready() && self.textures.iter().all(|t| t.texture.is_ready())
+	}
+	
+	/// Binds the program variant, render state, textures and parameters.
+	///
+	/// Returns the bound program so the caller can set its per draw uniforms,
+	/// `None` if the material isn't ready yet (nothing is bound then).
+	pub fn bind(&self, configuration: &GraphicsConfiguration) -> Option<Ref<'_, ShaderProgram>> {
+		if !self.is_ready() {
+			return None;
+		}
+		
+		// Build the variant if needed
+		let key = {
+			let mut managed_program = self.program.get_mut()?;
+			let layout = managed_program.permutation_layout();
+			let values = self.permutation.iter().map(|(n, v)| (n.as_str(), v.as_str())).collect::<Vec<_>>();
+			
+			// The layout may have changed since the material was loaded
+			let key = layout.key(&values).unwrap_or_else(|_| layout.default_key());
+			managed_program.program_variant(key);
+			key
+		};
+		
+		let program = Ref::map(self.program.get()?, |p| p.cached_variant(key).unwrap());
+		let program_gl = program.program_gl()?;
+		
+		unsafe {
+			gl::UseProgram(program_gl);
+		}
+		self.render_state.apply(configuration.tessellation);
+		
+		// Bind textures
+		for texture in &self.textures {
+			// Use the unit from the surface or else the one from the shader's layout
+			let unit = texture.unit.or_else(|| {
+				program.reflection()?.uniform(&texture.uniform)?.binding.map(|b| b as u32)
+			});
+			
+			if let Some(unit) = unit {
+				unsafe {
+					gl::BindTextureUnit(unit, texture.texture.get().unwrap().texture_gl());
+					gl::BindSampler(unit, texture.sampler.as_ref().map_or(0, |s| s.sampler_gl()));
+				}
+				
+				if texture.unit.is_some() {
+					let _ = program.set_uniform(&texture.uniform, unit as i32);
+				}
+			}
+		}
+		
+		// Upload parameters
+		for (name, value) in &self.parameters {
+			let _ = match *value {
+				ParameterValueDef::Float(x) => program.set_uniform(name, x),
+				ParameterValueDef::Vec2(x, y) => program.set_uniform(name, Vector2::new(x, y)),
+				ParameterValueDef::Vec3(x, y, z) => program.set_uniform(name, Vector3::new(x, y, z)),
+				ParameterValueDef::Vec4(x, y, z, w) => program.set_uniform(name, Vector4::new(x, y, z, w)),
+				ParameterValueDef::Int(x) => program.set_uniform(name, x),
+				ParameterValueDef::UInt(x) => program.set_uniform(name, x),
+				ParameterValueDef::Bool(x) => program.set_uniform(name, x),
+			};
+		}
+		
+		Some(program)
+	}
+	
+	/// Loads a `.surface` file. The program is loaded right away, textures are queued on the load service.
+	/// Only errors in the surface itself fail the load, a broken program is retried on the next shader reload.
+	pub fn load(surface_asset_path: &AssetPath, program_cache: &mut AssetCache<ManagedProgram>, texture_cache: &mut AssetCache<Texture>, service: &mut AssetLoadService) -> Result<Material, Box<dyn error::Error>> {
+		let surface_asset_path = surface_asset_path.to_absolute();
+		
+		// Read the surface asset
+		let surface_asset_contents = {
+			let real_path = unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(&surface_asset_path.as_path());
+			
+			let mut file = OpenOptions::new().read(true).open(real_path)?;
+			let mut buffer = String::new();
+			file.read_to_string(&mut buffer)?;
+			
+			buffer
+		};
+		
+		// Deserialize the surface def
+		let surface_def = ron::de::from_str::<SurfaceAssetSchema::SurfaceDef>(&surface_asset_contents)?;
+		
+		// Relativize a referenced asset path (if not absolute)
+		let resolve_path = |path: &str| -> AssetPathBuf {
+			let asset_path = AssetPathBuf::from(path);
+			if asset_path.as_path().is_absolute() {
+				asset_path
+			}
+			else {
+				surface_asset_path.as_path().parent().unwrap().join(&asset_path.as_path()).unwrap()
+			}
+		};
+		
+		// Load the program
+		let program = program_cache.load(&resolve_path(&surface_def.program).as_path());
+		if let Some(managed_program) = program.get().filter(|p| p.has_sources()) {// Make sure the permutation exists
+			let values = surface_def.permutation.iter().map(|(n, v)| (n.as_str(), v.as_str())).collect::<Vec<_>>();
+			managed_program.permutation_layout().key(&values)?;
+		}
+		
+		// Queue the textures
+		let textures = surface_def.textures.iter().map(|def| MaterialTexture {
+			uniform: def.uniform.clone(),
+			texture: texture_cache.load_async(&resolve_path(&def.texture).as_path(), service),
+			unit: def.unit,
+			sampler: def.sampler.as_ref().map(Sampler::new),
+		}).collect();
+		
+		// Record the program and textures as dependencies, they are checked through their own nodes
+		// so only the surface contents that were already read are hashed
+		let mut dependencies = vec![resolve_path(&surface_def.program)];
+		dependencies.extend(surface_def.textures.iter().map(|def| resolve_path(&def.texture)));
+		unsafe {&mut ASSET_MANAGER_INSTANCE}.record_asset(&surface_asset_path.as_path(), Some(hash_content(surface_asset_contents.as_bytes())), &dependencies);
+		
+		Ok(Material {
+			surface_asset_path,
+			program,
+			permutation: surface_def.permutation,
+			textures,
+			parameters: surface_def.parameters.into_iter().map(|p| (p.name, p.value)).collect(),
+			render_state: RenderState::from_def(&surface_def.render_state),
+		})
+	}
+}
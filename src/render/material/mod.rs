@@ -0,0 +1,4 @@
+mod surface_asset_schema; pub use surface_asset_schema::*;
+mod render_state; pub use render_state::*;
+mod sampler; pub use sampler::*;
+mod material; pub use material::*;
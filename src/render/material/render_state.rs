@@ -0,0 +1,115 @@
+use gl_bindings::gl;
+use crate::render::material::SurfaceAssetSchema::{RenderStateDef, CullModeDef, BlendModeDef, DepthFuncDef, TessellationDef};
+
+/// Fixed function state a material is drawn with.
+#[derive(Clone, Debug)]
+pub struct RenderState {
+	/// Face to cull, `None` disables culling
+	pub cull_face: Option<gl::enuma>,
+	pub blend: BlendMode,
+	/// `None` disables the depth test
+	pub depth_func: Option<gl::enuma>,
+	pub depth_write: bool,
+	pub tessellation: Option<TessellationState>,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BlendMode {
+	Opaque,
+	/// Premultiplied alpha
+	Alpha,
+	Additive,
+}
+
+#[derive(Clone, Debug)]
+pub struct TessellationState {
+	pub patch_vertices: u32,
+	pub outer_level: [f32; 4],
+	pub inner_level: [f32; 2],
+}
+
+impl RenderState {
+	/// Sets all of the state, so nothing leaks from the previous draw.
+	///
+	/// With tessellation disabled the patches still go through the tessellation
+	/// stages but aren't subdivided.
+	pub fn apply(&self, tessellation_enabled: bool) {
+		unsafe {
+			match self.cull_face {
+				Some(face) => {
+					gl::Enable(gl::CULL_FACE);
+					gl::CullFace(face);
+				}
+				None => gl::Disable(gl::CULL_FACE),
+			}
+			
+			match self.blend {
+				BlendMode::Opaque => gl::Disable(gl::BLEND),
+				BlendMode::Alpha => {
+					gl::Enable(gl::BLEND);
+					gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+				}
+				BlendMode::Additive => {
+					gl::Enable(gl::BLEND);
+					gl::BlendFunc(gl::ONE, gl::ONE);
+				}
+			}
+			
+			match self.depth_func {
+				Some(func) => {
+					gl::Enable(gl::DEPTH_TEST);
+					gl::DepthFunc(func);
+				}
+				None => gl::Disable(gl::DEPTH_TEST),
+			}
+			gl::DepthMask(if self.depth_write {gl::TRUE} else {gl::FALSE});
+			
+			if let Some(tessellation) = &self.tessellation {
+				gl::PatchParameteri(gl::PATCH_VERTICES, tessellation.patch_vertices as gl::int);
+				if tessellation_enabled {
+					gl::PatchParameterfv(gl::PATCH_DEFAULT_OUTER_LEVEL, tessellation.outer_level.as_ptr());
+					gl::PatchParameterfv(gl::PATCH_DEFAULT_INNER_LEVEL, tessellation.inner_level.as_ptr());
+				}
+				else {
+					gl::PatchParameterfv(gl::PATCH_DEFAULT_OUTER_LEVEL, [1.0f32; 4].as_ptr());
+					gl::PatchParameterfv(gl::PATCH_DEFAULT_INNER_LEVEL, [1.0f32; 2].as_ptr());
+				}
+			}
+		}
+	}
+	
+	/// The primitive type to draw triangles with, `PATCHES` if tessellated.
+	pub fn primitive_type(&self) -> gl::enuma {
+		if self.tessellation.is_some() {gl::PATCHES} else {gl::TRIANGLES}
+	}
+	
+	pub fn from_def(def: &RenderStateDef) -> RenderState {
+		RenderState {
+			cull_face: match def.cull {
+				CullModeDef::None => None,
+				CullModeDef::Front => Some(gl::FRONT),
+				CullModeDef::Back => Some(gl::BACK),
+			},
+			blend: match def.blend {
+				BlendModeDef::Opaque => BlendMode::Opaque,
+				BlendModeDef::Alpha => BlendMode::Alpha,
+				BlendModeDef::Additive => BlendMode::Additive,
+			},
+			depth_func: def.depth_test.map(|func| match func {
+				DepthFuncDef::Never => gl::NEVER,
+				DepthFuncDef::Less => gl::LESS,
+				DepthFuncDef::LessEqual => gl::LEQUAL,
+				DepthFuncDef::Equal => gl::EQUAL,
+				DepthFuncDef::Greater => gl::GREATER,
+				DepthFuncDef::GreaterEqual => gl::GEQUAL,
+				DepthFuncDef::Always => gl::ALWAYS,
+			}),
+			depth_write: def.depth_write,
+			tessellation: def.tessellation.map(|t: TessellationDef| TessellationState {
+				patch_vertices: t.patch_vertices,
+				outer_level: [t.outer_level.0, t.outer_level.1, t.outer_level.2, t.outer_level.3],
+				inner_level: [t.inner_level.0, t.inner_level.1],
+			}),
+		}
+	}
+}
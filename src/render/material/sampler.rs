@@ -0,0 +1,76 @@
+use gl_bindings::gl;
+use crate::render::material::SurfaceAssetSchema::{SamplerDef, FilterDef, WrapDef};
+
+/// Core in gl 4.6 (and EXT_texture_filter_anisotropic), not in our 4.5 bindings
+const TEXTURE_MAX_ANISOTROPY: gl::enuma = 0x84FE;
+
+/// A gl sampler object, overrides the sampling parameters of the texture bound to the same unit.
+pub struct Sampler {
+	sampler_gl: gl::uint,
+}
+
+impl Sampler {
+	pub fn sampler_gl(&self) -> gl::uint {
+		self.sampler_gl
+	}
+	
+	pub fn bind(&self, unit: u32) {
+		unsafe {
+			gl::BindSampler(unit, self.sampler_gl);
+		}
+	}
+	
+	pub fn dispose(&mut self) {
+		if self.sampler_gl != 0 {
+			unsafe {
+				gl::DeleteSamplers(1, &self.sampler_gl);
+			}
+			self.sampler_gl = 0;
+		}
+	}
+	
+	/// Creates the sampler object. Needs a current gl context.
+	pub fn new(def: &SamplerDef) -> Sampler {
+		fn filter_gl(filter: FilterDef) -> gl::int {
+			(match filter {
+				FilterDef::Nearest => gl::NEAREST,
+				FilterDef::Linear => gl::LINEAR,
+				FilterDef::NearestMipmapNearest => gl::NEAREST_MIPMAP_NEAREST,
+				FilterDef::LinearMipmapNearest => gl::LINEAR_MIPMAP_NEAREST,
+				FilterDef::NearestMipmapLinear => gl::NEAREST_MIPMAP_LINEAR,
+				FilterDef::LinearMipmapLinear => gl::LINEAR_MIPMAP_LINEAR,
+			}) as gl::int
+		}
+		
+		let wrap = (match def.wrap {
+			WrapDef::Repeat => gl::REPEAT,
+			WrapDef::MirroredRepeat => gl::MIRRORED_REPEAT,
+			WrapDef::ClampToEdge => gl::CLAMP_TO_EDGE,
+		}) as gl::int;
+		
+		let sampler_gl = unsafe {
+			let mut sampler: gl::uint = 0;
+			gl::CreateSamplers(1, &mut sampler);
+			gl::SamplerParameteri(sampler, gl::TEXTURE_MIN_FILTER, filter_gl(def.min_filter));
+			gl::SamplerParameteri(sampler, gl::TEXTURE_MAG_FILTER, filter_gl(def.mag_filter));
+			gl::SamplerParameteri(sampler, gl::TEXTURE_WRAP_S, wrap);
+			gl::SamplerParameteri(sampler, gl::TEXTURE_WRAP_T, wrap);
+			gl::SamplerParameteri(sampler, gl::TEXTURE_WRAP_R, wrap);
+			
+			if let Some(max_anisotropy) = def.max_anisotropy {
+				gl::SamplerParameterf(sampler, TEXTURE_MAX_ANISOTROPY, max_anisotropy);
+			}
+			sampler
+		};
+		
+		Sampler {
+			sampler_gl,
+		}
+	}
+}
+
+impl Drop for Sampler {
+	fn drop(&mut self) {
+		self.dispose();
+	}
+}
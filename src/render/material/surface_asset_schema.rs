@@ -0,0 +1,178 @@
+
+#[allow(non_snake_case)]
+pub mod SurfaceAssetSchema {
+	use serde::{Deserialize};
+	
+	/// A `.surface` file, describes everything needed to draw a mesh with a program.
+	#[derive(Deserialize)]
+	#[serde(rename = "Surface")]
+	pub struct SurfaceDef {
+		/// The `.program`, relative to the surface file or absolute
+		pub program: String,
+		/// Permutation values, permutations not listed keep their default
+		#[serde(default)]
+		pub permutation: Vec<(String, String)>,
+		#[serde(default)]
+		pub textures: Vec<self::TextureBindingDef>,
+		#[serde(default)]
+		pub parameters: Vec<self::ParameterDef>,
+		#[serde(default)]
+		pub render_state: self::RenderStateDef,
+	}
+	
+	#[derive(Deserialize)]
+	#[serde(rename = "Texture")]
+	pub struct TextureBindingDef {
+		/// Name of the sampler uniform
+		pub uniform: String,
+		/// The texture asset, relative to the surface file or absolute
+		pub texture: String,
+		/// Texture unit, taken from the sampler's `layout(binding = N)` if not given
+		#[serde(default)]
+		pub unit: Option<u32>,
+		/// Sampler state, the texture's own parameters are used if not given
+		#[serde(default)]
+		pub sampler: Option<self::SamplerDef>,
+	}
+	
+	#[derive(Deserialize, Clone)]
+	#[serde(rename = "Sampler")]
+	#[serde(default)]
+	pub struct SamplerDef {
+		pub min_filter: self::FilterDef,
+		pub mag_filter: self::FilterDef,
+		pub wrap: self::WrapDef,
+		pub max_anisotropy: Option<f32>,
+	}
+	
+	impl Default for SamplerDef {
+		fn default() -> Self {
+			Self {
+				min_filter: FilterDef::LinearMipmapLinear,
+				mag_filter: FilterDef::Linear,
+				wrap: WrapDef::Repeat,
+				max_anisotropy: None,
+			}
+		}
+	}
+	
+	#[derive(Deserialize, Copy, Clone)]
+	#[serde(rename = "Filter")]
+	pub enum FilterDef {
+		Nearest,
+		Linear,
+		NearestMipmapNearest,
+		LinearMipmapNearest,
+		NearestMipmapLinear,
+		LinearMipmapLinear,
+	}
+	
+	#[derive(Deserialize, Copy, Clone)]
+	#[serde(rename = "Wrap")]
+	pub enum WrapDef {
+		Repeat,
+		MirroredRepeat,
+		ClampToEdge,
+	}
+	
+	#[derive(Deserialize, Clone)]
+	#[serde(rename = "Parameter")]
+	pub struct ParameterDef {
+		/// Name of the uniform
+		pub name: String,
+		pub value: self::ParameterValueDef,
+	}
+	
+	#[derive(Deserialize, Copy, Clone, Debug)]
+	#[serde(rename = "Value")]
+	pub enum ParameterValueDef {
+		Float(f32),
+		Vec2(f32, f32),
+		Vec3(f32, f32, f32),
+		Vec4(f32, f32, f32, f32),
+		Int(i32),
+		UInt(u32),
+		Bool(bool),
+	}
+	
+	#[derive(Deserialize, Clone)]
+	#[serde(rename = "RenderState")]
+	#[serde(default)]
+	pub struct RenderStateDef {
+		pub cull: self::CullModeDef,
+		pub blend: self::BlendModeDef,
+		/// `None` disables the depth test
+		pub depth_test: Option<self::DepthFuncDef>,
+		pub depth_write: bool,
+		/// Drawn as patches if set
+		pub tessellation: Option<self::TessellationDef>,
+	}
+	
+	/// Opaque with reverse-z depth testing
+	impl Default for RenderStateDef {
+		fn default() -> Self {
+			Self {
+				cull: CullModeDef::Back,
+				blend: BlendModeDef::Opaque,
+				depth_test: Some(DepthFuncDef::Greater),
+				depth_write: true,
+				tessellation: None,
+			}
+		}
+	}
+	
+	#[derive(Deserialize, Copy, Clone)]
+	#[serde(rename = "Cull")]
+	pub enum CullModeDef {
+		None,
+		Front,
+		Back,
+	}
+	
+	#[derive(Deserialize, Copy, Clone)]
+	#[serde(rename = "Blend")]
+	pub enum BlendModeDef {
+		Opaque,
+		/// Premultiplied alpha
+		Alpha,
+		Additive,
+	}
+	
+	#[derive(Deserialize, Copy, Clone)]
+	#[serde(rename = "DepthFunc")]
+	pub enum DepthFuncDef {
+		Never,
+		Less,
+		LessEqual,
+		Equal,
+		Greater,
+		GreaterEqual,
+		Always,
+	}
+	
+	#[derive(Deserialize, Copy, Clone)]
+	#[serde(rename = "Tessellation")]
+	pub struct TessellationDef {
+		#[serde(default = "TessellationDef::default_patch_vertices")]
+		pub patch_vertices: u32,
+		/// Used if the program has no tessellation control shader
+		#[serde(default = "TessellationDef::default_outer_level")]
+		pub outer_level: (f32, f32, f32, f32),
+		#[serde(default = "TessellationDef::default_inner_level")]
+		pub inner_level: (f32, f32),
+	}
+	
+	impl TessellationDef {
+		pub fn default_patch_vertices() -> u32 {
+			3
+		}
+		
+		pub fn default_outer_level() -> (f32, f32, f32, f32) {
+			(1.0, 1.0, 1.0, 1.0)
+		}
+		
+		pub fn default_inner_level() -> (f32, f32) {
+			(1.0, 1.0)
+		}
+	}
+}
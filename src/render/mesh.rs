@@ -0,0 +1,106 @@
+use gl_bindings::gl;
+use crate::asset::{ASSET_MANAGER_INSTANCE, AssetPath, AssetLoader, AssetLoadError, AssetLoadService, LoadHandle, ContentHash, hash_content};
+use crate::model::MeshData;
+
+/// Gpu side mesh, owns its vertex and index buffer.
+pub struct Mesh {
+	vertex_buffer_gl: gl::uint,
+	index_buffer_gl: gl::uint,
+	num_indices: u32,
+	vertex_stride: u32,
+}
+
+impl Mesh {
+	pub fn vertex_buffer_gl(&self) -> gl::uint {
+		self.vertex_buffer_gl
+	}
+	
+	pub fn index_buffer_gl(&self) -> gl::uint {
+		self.index_buffer_gl
+	}
+	
+	pub fn num_indices(&self) -> u32 {
+		self.num_indices
+	}
+	
+	pub fn vertex_stride(&self) -> u32 {
+		self.vertex_stride
+	}
+	
+	pub fn dispose(&mut self) {
+		if self.vertex_buffer_gl != 0 {
+			unsafe {
+				let buffers = [self.vertex_buffer_gl, self.index_buffer_gl];
+				gl::DeleteBuffers(2, buffers.as_ptr());
+			}
+			self.vertex_buffer_gl = 0;
+			self.index_buffer_gl = 0;
+		}
+	}
+	
+	/// Creates the gl buffers and uploads the mesh data. Needs a current gl context.
+	pub fn upload(mesh_data: &MeshData) -> Mesh {
+		// Allocate buffers
+		let (vertex_buffer_gl, index_buffer_gl) = unsafe {
+			let mut buffers = [0 as gl::uint; 2];
+			gl::CreateBuffers(2 as gl::sizei, buffers.as_mut_ptr());
+			(buffers[0], buffers[1])
+		};
+		
+		// Upload data
+		unsafe {
+			gl::NamedBufferStorage(vertex_buffer_gl, mesh_data.vertex_data.len() as gl::sizeiptr, mesh_data.vertex_data.as_ptr() as *const gl::void, 0);
+			gl::NamedBufferStorage(index_buffer_gl, mesh_data.index_data.len() as gl::sizeiptr, mesh_data.index_data.as_ptr() as *const gl::void, 0);
+		}
+		
+		Mesh {
+			vertex_buffer_gl,
+			index_buffer_gl,
+			num_indices: mesh_data.num_indices,
+			vertex_stride: mesh_data.vertex_stride(),
+		}
+	}
+}
+
+impl Drop for Mesh {
+	fn drop(&mut self) {
+		self.dispose();
+	}
+}
+
+/// Loads `.ply` meshes (with generated tangents) for the mesh `AssetCache`.
+pub struct MeshLoader;
+
+impl MeshLoader {
+	/// Reads and decodes a mesh file, also hashing its contents for the dependency graph.
+	fn decode(fs_path: &std::path::Path) -> Result<(MeshData, ContentHash), AssetLoadError> {
+		let (mut mesh_data, content_hash) = std::fs::read(fs_path).map_err(Box::<dyn std::error::Error>::from)
+			.and_then(|file_data| Ok((MeshData::load_ply(&file_data)?, hash_content(&file_data))))
+			.map_err(|e| AssetLoadError::from(format!("Failed to load mesh {}: {}", fs_path.display(), e)))?;
+		
+		// Generate tangents
+		mesh_data.generate_tangents();
+		Ok((mesh_data, content_hash))
+	}
+}
+
+impl AssetLoader<Mesh> for MeshLoader {
+	fn load(&self, path: &AssetPath) -> Result<Mesh, AssetLoadError> {
+		let fs_path = unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(path);
+		let (mesh_data, content_hash) = Self::decode(&fs_path)?;
+		unsafe {&mut ASSET_MANAGER_INSTANCE}.record_asset(path, Some(content_hash), &[]);
+		
+		Ok(Mesh::upload(&mesh_data))
+	}
+	
+	fn load_async(&self, path: &AssetPath, service: &mut AssetLoadService, handle: &LoadHandle<Mesh>) {
+		let fs_path = unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(path);
+		let path = path.to_absolute();
+		
+		// Hash on the worker from the bytes it reads anyway, record once uploaded
+		service.load_into(handle, move || Self::decode(&fs_path), move |(mesh_data, content_hash)| {
+			unsafe {&mut ASSET_MANAGER_INSTANCE}.record_asset(&path.as_path(), Some(content_hash), &[]);
+			Ok(Mesh::upload(&mesh_data))
+		});
+	}
+}
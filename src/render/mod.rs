@@ -1,11 +1,18 @@
 mod render_global; pub use render_global::*;
 mod graphics_settings; pub use graphics_settings::*;
+mod graphics_settings_schema; pub use graphics_settings_schema::*;
+mod dynamic_resolution; pub use dynamic_resolution::*;
 mod texture; pub use texture::*;
 mod image_format; pub use image_format::*;
 mod framebuffer; pub use framebuffer::*;
 mod test_vertex_buffer; pub use test_vertex_buffer::*;
+mod mesh; pub use mesh::*;
+mod gpu_buffer; pub use gpu_buffer::*;
 mod render_subsystem; pub use render_subsystem::*;
+mod render_subsystem_registry; pub use render_subsystem_registry::*;
 pub mod shader;
+pub mod material;
+pub mod render_graph;
 pub mod separable_sss;
 pub mod bloom;
 pub mod clustered;
@@ -0,0 +1,173 @@
+use gl_bindings::gl;
+
+/// Frames whose timestamp queries are in flight at once. The results of a frame are
+/// read once the gpu finished it, at the latest when its queries are reused.
+pub const GPU_TIMER_FRAMES_IN_FLIGHT: usize = 4;
+
+/// A scope measured by two timestamps
+struct GpuTimerScope {
+	timer: usize,
+	begin_query: usize,
+	end_query: Option<usize>,
+}
+
+/// The queries of one frame
+struct GpuTimerFrame {
+	queries_gl: Vec<gl::uint>,
+	num_used_queries: usize,
+	scopes: Vec<GpuTimerScope>,
+	is_pending: bool,
+}
+
+impl GpuTimerFrame {
+	/// Whether the gpu wrote all timestamps of the frame. They complete in order, so checking the last one is enough.
+	fn is_available(&self) -> bool {
+		if self.num_used_queries == 0 {
+			return true;
+		}
+		
+		let mut available: gl::int = 0;
+		unsafe {
+			gl::GetQueryObjectiv(self.queries_gl[self.num_used_queries - 1], gl::QUERY_RESULT_AVAILABLE, &mut available);
+		}
+		available != 0
+	}
+	
+	/// Reads back the duration of every closed scope as the timer and milliseconds.
+	fn read_results(&self, resolved: &mut Vec<(usize, f32)>) {
+		let timestamps = self.queries_gl[..self.num_used_queries].iter().map(|&query| {
+			let mut timestamp: u64 = 0;
+			unsafe {
+				gl::GetQueryObjectui64v(query, gl::QUERY_RESULT, &mut timestamp);
+			}
+			timestamp
+		}).collect::<Vec<_>>();
+		
+		for scope in &self.scopes {
+			if let Some(end_query) = scope.end_query {
+				let elapsed_ns = timestamps[end_query].saturating_sub(timestamps[scope.begin_query]);
+				resolved.push((scope.timer, (elapsed_ns as f64 / 1e6) as f32));
+			}
+		}
+	}
+	
+	fn reset(&mut self) {
+		self.num_used_queries = 0;
+		self.scopes.clear();
+		self.is_pending = false;
+	}
+	
+	fn next_query(&mut self) -> usize {
+		if self.num_used_queries == self.queries_gl.len() {
+			let mut query: gl::uint = 0;
+			unsafe {
+				gl::CreateQueries(gl::TIMESTAMP, 1, &mut query);
+			}
+			self.queries_gl.push(query);
+		}
+		
+		let index = self.num_used_queries;
+		self.num_used_queries += 1;
+		
+		unsafe {
+			gl::QueryCounter(self.queries_gl[index], gl::TIMESTAMP);
+		}
+		index
+	}
+}
+
+/// Times scopes on the gpu with timestamp queries, without ever waiting for a result.
+/// Every frame records into its own set of queries, the sets are reused in a ring.
+pub struct GpuTimestampQueries {
+	frames: Vec<GpuTimerFrame>,
+	current_frame: usize,
+	dropped_frames: u64,
+}
+
+impl GpuTimestampQueries {
+	/// Frames whose results weren't available before their queries had to be reused
+	pub fn dropped_frames(&self) -> u64 {
+		self.dropped_frames
+	}
+	
+	/// Starts recording a new frame. The durations of the scopes of earlier frames the gpu
+	/// has finished since are appended to `resolved` as the timer and milliseconds.
+	pub fn begin_frame(&mut self, resolved: &mut Vec<(usize, f32)>) {
+		self.current_frame = (self.current_frame + 1) % self.frames.len();
+		
+		// Read back the finished frames, oldest first. The current frame is the oldest one.
+		let num_frames = self.frames.len();
+		for offset in 0..num_frames {
+			let frame = &mut self.frames[(self.current_frame + offset) % num_frames];
+			if !frame.is_pending {
+				continue;
+			}
+			if !frame.is_available() {
+				break;
+			}
+			
+			frame.read_results(resolved);
+			frame.reset();
+		}
+		
+		// Reuse the queries of the current frame, waiting for them would stall
+		let frame = &mut self.frames[self.current_frame];
+		if frame.is_pending {
+			self.dropped_frames += 1;
+		}
+		frame.reset();
+		frame.is_pending = true;
+	}
+	
+	/// Records the begin timestamp of a scope. Returns the handle to end it with.
+	pub fn begin_scope(&mut self, timer: usize) -> usize {
+		let frame = &mut self.frames[self.current_frame];
+		let begin_query = frame.next_query();
+		
+		frame.scopes.push(GpuTimerScope {
+			timer,
+			begin_query,
+			end_query: None,
+		});
+		frame.scopes.len() - 1
+	}
+	
+	pub fn end_scope(&mut self, scope: usize) {
+		let frame = &mut self.frames[self.current_frame];
+		let end_query = frame.next_query();
+		
+		frame.scopes[scope].end_query = Some(end_query);
+	}
+	
+	pub fn dispose(&mut self) {
+		for frame in &mut self.frames {
+			if !frame.queries_gl.is_empty() {
+				unsafe {
+					gl::DeleteQueries(frame.queries_gl.len() as gl::sizei, frame.queries_gl.as_ptr());
+				}
+			}
+			frame.queries_gl.clear();
+			frame.reset();
+		}
+	}
+	
+	/// The queries are created when first used. Needs a current gl context from then on.
+	pub fn new() -> GpuTimestampQueries {
+		GpuTimestampQueries {
+			frames: (0..GPU_TIMER_FRAMES_IN_FLIGHT).map(|_| GpuTimerFrame {
+				queries_gl: Vec::new(),
+				num_used_queries: 0,
+				scopes: Vec::new(),
+				is_pending: false,
+			}).collect(),
+			current_frame: 0,
+			dropped_frames: 0,
+		}
+	}
+}
+
+impl Drop for GpuTimestampQueries {
+	fn drop(&mut self) {
+		self.dispose();
+	}
+}
@@ -1,2 +1,4 @@
 mod performance_profiler; pub use performance_profiler::*;
 mod performance_timer; pub use performance_timer::*;
+mod timing_history; pub use timing_history::*;
+mod gpu_timestamp_queries; pub use gpu_timestamp_queries::*;
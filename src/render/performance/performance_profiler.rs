@@ -1,11 +1,207 @@
-//use crate::render::performance::PerformanceTimer;
+use std::time::Instant;
+use std::fmt::Write;
+use crate::render::performance::{PerformanceTimer, GpuTimestampQueries, TimingStatistics};
 
+/// Name of the scope spanning a whole frame
+pub const PERFORMANCE_FRAME_SCOPE: &str = "frame";
+
+struct OpenScope {
+	timer: usize,
+	cpu_start: Instant,
+	gpu_scope: Option<usize>,
+}
+
+/// Times named, nested scopes on the cpu and optionally on the gpu. Every scope gets a
+/// `PerformanceTimer` keeping the recent samples, found by its path like `frame/scene`.
+///
+/// The gpu times of a frame come in a few frames later, without stalling on the queries.
 pub struct PerformanceProfiler {
+	/// Parents always come before their children
+	timers: Vec<PerformanceTimer>,
+	open_scopes: Vec<OpenScope>,
+	history_length: usize,
 	
+	gpu_queries: Option<GpuTimestampQueries>,
+	/// Gpu time of the latest frame resolved by `begin_frame`
+	resolved_gpu_frametime_ms: Option<f32>,
+	num_frames: u64,
 }
 
 impl PerformanceProfiler {
-//	pub fn make_timer() -> PerformanceTimer {
-//		
-//	}
+	/// All timers in the order their scopes were first opened, parents before their children
+	pub fn timers(&self) -> &[PerformanceTimer] {
+		&self.timers
+	}
+	
+	/// The timer of the scope at `path`, the names of the nested scopes separated by `/`.
+	pub fn timer(&self, path: &str) -> Option<&PerformanceTimer> {
+		self.timers.iter().find(|t| t.path() == path)
+	}
+	
+	pub fn cpu_statistics(&self, path: &str) -> Option<TimingStatistics> {
+		self.timer(path)?.cpu.statistics()
+	}
+	
+	pub fn gpu_statistics(&self, path: &str) -> Option<TimingStatistics> {
+		self.timer(path)?.gpu.statistics()
+	}
+	
+	/// Timers of the scopes directly nested in the scope of `timer`
+	pub fn children(&self, timer: usize) -> impl Iterator<Item = (usize, &PerformanceTimer)> + '_ {
+		self.timers.iter().enumerate().filter(move |(_, t)| t.parent() == Some(timer))
+	}
+	
+	/// Gpu time of the last frame whose timestamps came in with the latest `begin_frame`, if any did
+	pub fn resolved_gpu_frametime_ms(&self) -> Option<f32> {
+		self.resolved_gpu_frametime_ms
+	}
+	
+	pub fn num_frames(&self) -> u64 {
+		self.num_frames
+	}
+	
+	pub fn is_gpu_timing_enabled(&self) -> bool {
+		self.gpu_queries.is_some()
+	}
+	
+	/// Also times the scopes on the gpu from the next frame on. Needs a current gl context.
+	pub fn enable_gpu_timing(&mut self) {
+		if self.gpu_queries.is_none() {
+			self.gpu_queries = Some(GpuTimestampQueries::new());
+		}
+	}
+	
+	pub fn disable_gpu_timing(&mut self) {
+		self.gpu_queries = None;
+	}
+	
+	/// Frames whose gpu timestamps were dropped because the gpu fell too far behind
+	pub fn dropped_gpu_frames(&self) -> u64 {
+		self.gpu_queries.as_ref().map_or(0, |q| q.dropped_frames())
+	}
+	
+	/// Opens the frame scope, the scopes opened until `end_frame` are nested in it.
+	/// Collects the gpu times of earlier frames the gpu has finished since.
+	pub fn begin_frame(&mut self) {
+		// Close the scopes of the last frame if it wasn't ended
+		if !self.open_scopes.is_empty() {
+			self.end_frame();
+		}
+		
+		// Collect the resolved gpu times
+		self.resolved_gpu_frametime_ms = None;
+		if let Some(gpu_queries) = self.gpu_queries.as_mut() {
+			let mut resolved = Vec::new();
+			gpu_queries.begin_frame(&mut resolved);
+			
+			for (timer, elapsed_ms) in resolved {
+				self.timers[timer].gpu.push(elapsed_ms);
+				
+				if self.timers[timer].parent().is_none() && self.timers[timer].name() == PERFORMANCE_FRAME_SCOPE {
+					self.resolved_gpu_frametime_ms = Some(elapsed_ms);
+				}
+			}
+		}
+		
+		self.begin_scope(PERFORMANCE_FRAME_SCOPE);
+	}
+	
+	/// Closes the frame scope and any scope still open in it.
+	pub fn end_frame(&mut self) {
+		while !self.open_scopes.is_empty() {
+			self.end_scope();
+		}
+		self.num_frames += 1;
+	}
+	
+	/// Opens a scope nested in the innermost open scope.
+	pub fn begin_scope(&mut self, name: &str) {
+		let parent = self.open_scopes.last().map(|s| s.timer);
+		let timer = match self.timers.iter().position(|t| t.parent() == parent && t.name() == name) {
+			Some(timer) => timer,
+			None => {
+				let timer = PerformanceTimer::new(name, parent.map(|p| (p, &self.timers[p])), self.history_length);
+				self.timers.push(timer);
+				self.timers.len() - 1
+			}
+		};
+		
+		let gpu_scope = self.gpu_queries.as_mut().map(|q| q.begin_scope(timer));
+		self.open_scopes.push(OpenScope {
+			timer,
+			cpu_start: Instant::now(),
+			gpu_scope,
+		});
+	}
+	
+	/// Closes the innermost open scope.
+	pub fn end_scope(&mut self) {
+		let scope = match self.open_scopes.pop() {
+			Some(scope) => scope,
+			None => {
+				println!("Profiler scope ended without being begun");
+				return;
+			}
+		};
+		
+		let elapsed = scope.cpu_start.elapsed();
+		self.timers[scope.timer].cpu.push((elapsed.as_secs_f64() * 1e3) as f32);
+		
+		if let (Some(gpu_queries), Some(gpu_scope)) = (self.gpu_queries.as_mut(), scope.gpu_scope) {
+			gpu_queries.end_scope(gpu_scope);
+		}
+	}
+	
+	/// Times `f` as a scope nested in the innermost open scope.
+	pub fn scope<R>(&mut self, name: &str, f: impl FnOnce(&mut PerformanceProfiler) -> R) -> R {
+		self.begin_scope(name);
+		let result = f(self);
+		self.end_scope();
+		result
+	}
+	
+	/// Drops the samples of all timers, e.g. after the pipeline was reconfigured.
+	pub fn reset(&mut self) {
+		for timer in &mut self.timers {
+			timer.cpu.clear();
+			timer.gpu.clear();
+		}
+	}
+	
+	/// The scope tree with the averages and 95th percentiles, one scope per line.
+	pub fn report(&self) -> String {
+		let mut report = format!("{:<40} {:>18} {:>18}\n", "scope", "cpu avg / p95 ms", "gpu avg / p95 ms");
+		
+		let format_statistics = |statistics: Option<TimingStatistics>| match statistics {
+			Some(s) => format!("{:.3} / {:.3}", s.average_ms, s.p95_ms),
+			None => String::from("-"),
+		};
+		
+		// Depth first, so the children are listed below their parent
+		let mut stack = self.timers.iter().enumerate().filter(|(_, t)| t.parent().is_none()).map(|(i, _)| i).collect::<Vec<_>>();
+		stack.reverse();
+		
+		while let Some(index) = stack.pop() {
+			let timer = &self.timers[index];
+			let label = format!("{}{}", "  ".repeat(timer.depth()), timer.name());
+			let _ = writeln!(report, "{:<40} {:>18} {:>18}", label, format_statistics(timer.cpu.statistics()), format_statistics(timer.gpu.statistics()));
+			
+			let mut children = self.children(index).map(|(i, _)| i).collect::<Vec<_>>();
+			children.reverse();
+			stack.extend(children);
+		}
+		report
+	}
+	
+	pub fn new(history_length: usize) -> PerformanceProfiler {
+		PerformanceProfiler {
+			timers: Vec::new(),
+			open_scopes: Vec::new(),
+			history_length,
+			
+			gpu_queries: None,
+			resolved_gpu_frametime_ms: None,
+			num_frames: 0,
+		}
+	}
 }
@@ -1,5 +1,51 @@
+use crate::render::performance::TimingHistory;
 
-/// Measures frametime (gpu) and processtime (cpu)
+/// Measures frametime (gpu) and processtime (cpu) of one named scope of the profiler.
+#[derive(Clone, Debug)]
 pub struct PerformanceTimer {
+	name: String,
+	/// Names of the enclosing scopes and this one, separated by `/`
+	path: String,
+	parent: Option<usize>,
+	depth: usize,
 	
+	pub cpu: TimingHistory,
+	/// Stays empty while gpu timing is disabled
+	pub gpu: TimingHistory,
+}
+
+impl PerformanceTimer {
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+	
+	pub fn path(&self) -> &str {
+		&self.path
+	}
+	
+	/// Index of the enclosing scope's timer in `PerformanceProfiler::timers`
+	pub fn parent(&self) -> Option<usize> {
+		self.parent
+	}
+	
+	/// Number of enclosing scopes
+	pub fn depth(&self) -> usize {
+		self.depth
+	}
+	
+	/// `parent` is the timer of the enclosing scope and its index.
+	pub fn new(name: &str, parent: Option<(usize, &PerformanceTimer)>, history_length: usize) -> PerformanceTimer {
+		PerformanceTimer {
+			name: String::from(name),
+			path: match parent {
+				Some((_, parent)) => format!("{}/{}", parent.path, name),
+				None => String::from(name),
+			},
+			parent: parent.map(|(index, _)| index),
+			depth: parent.map_or(0, |(_, parent)| parent.depth + 1),
+			
+			cpu: TimingHistory::new(history_length),
+			gpu: TimingHistory::new(history_length),
+		}
+	}
 }
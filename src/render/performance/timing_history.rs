@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+
+/// Number of samples the profiler keeps per timing
+pub const PERFORMANCE_HISTORY_LENGTH: usize = 240;
+
+/// Statistics over the samples of a `TimingHistory`, in milliseconds.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TimingStatistics {
+	pub num_samples: usize,
+	pub latest_ms: f32,
+	pub average_ms: f32,
+	pub min_ms: f32,
+	pub max_ms: f32,
+	pub median_ms: f32,
+	pub p95_ms: f32,
+	pub p99_ms: f32,
+}
+
+/// Rolling window over the last samples of a timing, in milliseconds.
+#[derive(Clone, Debug)]
+pub struct TimingHistory {
+	/// Oldest first
+	samples: VecDeque<f32>,
+	capacity: usize,
+	total_samples: u64,
+}
+
+impl TimingHistory {
+	pub fn len(&self) -> usize {
+		self.samples.len()
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.samples.is_empty()
+	}
+	
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+	
+	/// Number of samples ever pushed, including the ones that fell out of the window
+	pub fn total_samples(&self) -> u64 {
+		self.total_samples
+	}
+	
+	/// The samples in the window, oldest first
+	pub fn samples(&self) -> impl Iterator<Item = f32> + '_ {
+		self.samples.iter().copied()
+	}
+	
+	pub fn latest(&self) -> Option<f32> {
+		self.samples.back().copied()
+	}
+	
+	/// Adds a sample, dropping the oldest one if the window is full. Samples that aren't finite are ignored.
+	pub fn push(&mut self, sample_ms: f32) {
+		if !sample_ms.is_finite() {
+			return;
+		}
+		
+		if self.samples.len() == self.capacity {
+			self.samples.pop_front();
+		}
+		self.samples.push_back(sample_ms);
+		self.total_samples += 1;
+	}
+	
+	pub fn clear(&mut self) {
+		self.samples.clear();
+	}
+	
+	pub fn average(&self) -> Option<f32> {
+		if self.samples.is_empty() {
+			return None;
+		}
+		
+		// Sum in f64 so long windows of small samples stay exact
+		let sum = self.samples.iter().fold(0.0f64, |sum, &s| sum + s as f64);
+		Some((sum / self.samples.len() as f64) as f32)
+	}
+	
+	pub fn min(&self) -> Option<f32> {
+		self.samples.iter().copied().fold(None, |min, s| Some(min.map_or(s, |m: f32| m.min(s))))
+	}
+	
+	pub fn max(&self) -> Option<f32> {
+		self.samples.iter().copied().fold(None, |max, s| Some(max.map_or(s, |m: f32| m.max(s))))
+	}
+	
+	/// Nearest rank percentile of the window, `percentile` goes from 0 to 100.
+	pub fn percentile(&self, percentile: f32) -> Option<f32> {
+		let sorted = self.sorted_samples();
+		Self::nearest_rank(&sorted, percentile)
+	}
+	
+	/// All statistics at once, sorting the window only once.
+	pub fn statistics(&self) -> Option<TimingStatistics> {
+		let sorted = self.sorted_samples();
+		if sorted.is_empty() {
+			return None;
+		}
+		
+		Some(TimingStatistics {
+			num_samples: sorted.len(),
+			latest_ms: self.latest()?,
+			average_ms: self.average()?,
+			min_ms: sorted[0],
+			max_ms: sorted[sorted.len() - 1],
+			median_ms: Self::nearest_rank(&sorted, 50.0)?,
+			p95_ms: Self::nearest_rank(&sorted, 95.0)?,
+			p99_ms: Self::nearest_rank(&sorted, 99.0)?,
+		})
+	}
+	
+	fn sorted_samples(&self) -> Vec<f32> {
+		let mut sorted = self.samples.iter().copied().collect::<Vec<_>>();
+		sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+		sorted
+	}
+	
+	fn nearest_rank(sorted: &[f32], percentile: f32) -> Option<f32> {
+		if sorted.is_empty() {
+			return None;
+		}
+		
+		let rank = (percentile.max(0.0).min(100.0) / 100.0 * sorted.len() as f32).ceil() as usize;
+		Some(sorted[rank.max(1).min(sorted.len()) - 1])
+	}
+	
+	pub fn new(capacity: usize) -> TimingHistory {
+		let capacity = capacity.max(1);
+		
+		TimingHistory {
+			samples: VecDeque::with_capacity(capacity),
+			capacity,
+			total_samples: 0,
+		}
+	}
+}
@@ -1,47 +1,76 @@
 use std::error;
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
 use std::ops::Deref;
 use std::sync::Mutex;
 use gl_bindings::gl;
-use cgmath::{Matrix4, SquareMatrix, vec3, Point3, Rad};
+use cgmath::{Matrix4, SquareMatrix, vec3, Point3, Rad, Vector2};
 use crate::demo;
-use crate::utils::lazy_option::Lazy;
-use crate::render::{Framebuffer, FramebufferAttachment, AttachmentPoint, ImageFormat, RenderSubsystem};
+use crate::render::{AntiAliasingMode, UpscaleFilter, GraphicsConfiguration, GraphicsSetting, DynamicResolutionController, DYNAMIC_RESOLUTION_MIN_SCALE, scale_resolution, ImageFormat, RenderSubsystem, RenderSubsystemRegistry, FrameResources, Texture, TextureLoader, Mesh, MeshLoader};
+use crate::render::render_graph::{RenderGraph, RenderGraphExecutor, TextureDesc};
 use crate::render::separable_sss::SeparableSSSSubsystem;
-use crate::render::shader::managed::ManagedProgram;
-use crate::asset::AssetPathBuf;
+use crate::render::bloom::BloomSubsystem;
+use crate::render::clustered::{ClusteredLighting, ClusterGrid, LightList, CLUSTER_GRID_DIMENSIONS, cluster_view_matrix};
+use crate::render::performance::{PerformanceProfiler, PERFORMANCE_HISTORY_LENGTH};
+use crate::render::shader::managed::{ManagedProgram, ProgramLoader};
+use crate::asset::{ASSET_MANAGER_INSTANCE, AssetCache, AssetPath, Handle};
 
 pub struct RenderGlobal {
 	current_configuration: Rc<RefCell<GraphicsConfiguration>>,
 	current_resolution: (u32, u32),
 	
-	separable_sss_system: SeparableSSSSubsystem,
+	pub program_cache: AssetCache<ManagedProgram>,
+	pub texture_cache: AssetCache<Texture>,
+	pub mesh_cache: AssetCache<Mesh>,
 	
-	framebuffer_scene_hdr_ehaa: Option<Rc<RefCell<Framebuffer>>>,
+	subsystems: RenderSubsystemRegistry,
+	is_initialized: bool,
 	
-	program_ehaa_scene: ManagedProgram,
-	program_post_composite: ManagedProgram,
+	clustered_lighting: ClusteredLighting,
 	
-	frametime_query_object_gl: gl::uint,
+	render_graph_executor: RenderGraphExecutor,
+	
+	program_ehaa_scene: Handle<ManagedProgram>,
+	program_post_composite: Handle<ManagedProgram>,
+	
+	profiler: PerformanceProfiler,
+	dynamic_resolution: DynamicResolutionController,
 	
 	queued_shader_reload: bool,
 }
 
 impl RenderGlobal {
 	pub fn new() -> RenderGlobal {
+		let mut program_cache = AssetCache::new(Box::new(ProgramLoader::new()));
+		
 		RenderGlobal {
 			current_configuration: Rc::new(RefCell::new(GraphicsConfiguration::new())),
 			current_resolution: (0, 0),
 			
-			separable_sss_system: SeparableSSSSubsystem::new(),
+			subsystems: {
+				let mut subsystems = RenderSubsystemRegistry::new();
+				subsystems.register(Box::new(SeparableSSSSubsystem::new(&mut program_cache)), true).unwrap();
+				subsystems.register(Box::new(BloomSubsystem::new(&mut program_cache)), true).unwrap();
+				subsystems
+			},
+			is_initialized: false,
+			
+			clustered_lighting: ClusteredLighting::new(&mut program_cache),
 			
-			framebuffer_scene_hdr_ehaa: None,
+			render_graph_executor: RenderGraphExecutor::new(),
 			
-			program_ehaa_scene: ManagedProgram::new(Some(AssetPathBuf::from("/shaders/legacy/main_scene_forward.program"))),
-			program_post_composite: ManagedProgram::new(Some(AssetPathBuf::from("/shaders/post_composite.program"))),
+			program_ehaa_scene: program_cache.load(&AssetPath::from_str("/shaders/legacy/main_scene_forward.program")),
+			program_post_composite: program_cache.load(&AssetPath::from_str("/shaders/post_composite.program")),
 			
-			frametime_query_object_gl: 0,
+			program_cache,
+			texture_cache: AssetCache::new(Box::new(TextureLoader::new())),
+			mesh_cache: AssetCache::new(Box::new(MeshLoader)),
+			
+			profiler: PerformanceProfiler::new(PERFORMANCE_HISTORY_LENGTH),
+			dynamic_resolution: {
+				let configuration = GraphicsConfiguration::new();
+				DynamicResolutionController::new(DYNAMIC_RESOLUTION_MIN_SCALE, configuration.render_scale, configuration.target_frametime_ms)
+			},
 			
 			queued_shader_reload: false,
 		}
@@ -52,7 +81,8 @@ impl RenderGlobal {
 		self.current_resolution = resolution;
 		
 		// Init subsystems
-		self.separable_sss_system.initialize();
+		self.subsystems.initialize_all();
+		self.is_initialized = true;
 		
 		// Do initial reconfiguration
 		self.do_reconfigure_pipeline(self.current_resolution, false)?;
@@ -63,103 +93,112 @@ impl RenderGlobal {
 	pub fn do_reconfigure_pipeline(&mut self, new_resolution: (u32, u32), only_resize: bool) -> Result<(), Box<dyn error::Error>> {
 		// Update state
 		self.current_resolution = new_resolution;
+		self.dynamic_resolution.reset();
 		
 		let config = RefCell::borrow(&self.current_configuration);
 		let event = ReconfigureEvent {
 			configuration: config.deref(),
 			resolution: new_resolution,
 			only_resize,
+			changed_settings: if only_resize {&[]} else {GraphicsSetting::all()},
 		};
 		
-		// Configure main fbo
-		if let Some(t) = &mut self.framebuffer_scene_hdr_ehaa {
-			let mut fbo = RefCell::borrow_mut(t);
-			fbo.resize(event.resolution.0, event.resolution.1);
-		}
-		else {
-			// Create fbo
-			self.framebuffer_scene_hdr_ehaa = Some(Rc::new(RefCell::new({
-				let mut fbo = Framebuffer::new(event.resolution.0, event.resolution.1);
-				
-				fbo.add_attachment(FramebufferAttachment::from_new_texture(AttachmentPoint::Depth, ImageFormat::get(gl::DEPTH_COMPONENT32F)));
-				fbo.add_attachment(FramebufferAttachment::from_new_texture(AttachmentPoint::Color(0), ImageFormat::get(gl::R11F_G11F_B10F)));
-				fbo.add_attachment(FramebufferAttachment::from_new_texture(AttachmentPoint::Color(1), ImageFormat::get(gl::RGB8)));
-				fbo.add_attachment(FramebufferAttachment::from_new_texture(AttachmentPoint::Color(2), ImageFormat::get(gl::RGB8)));
-//				fbo.add_attachment(FramebufferAttachment::from_new_texture(AttachmentPoint::Color(1), ImageFormat::get(gl::RGBA8)));
-//				fbo.add_attachment(FramebufferAttachment::from_new_texture(AttachmentPoint::Color(1), ImageFormat::get(gl::RG16_SNORM)));
-				
-				fbo.allocate();
-				fbo
-			})));
-		}
-		
 		// Reconfigure subsystems
-		self.separable_sss_system.reconfigure(event);
+		self.subsystems.reconfigure_all(event);
 		
 		// Drop config for now
 		drop(config);
 		
-		// Create query object
-		if self.frametime_query_object_gl == 0 {
-			self.frametime_query_object_gl = unsafe {
-				let mut query: gl::uint = 0;
-				gl::CreateQueries(gl::TIME_ELAPSED, 1, &mut query);
-				query
-			};
-		}
+		// Time the frames on the gpu too, the timestamp queries need the context
+		self.profiler.enable_gpu_timing();
 		
-		// Load shaders
-		self.reload_shaders();
+		// Load shaders, a resize doesn't change them
+		if !only_resize {
+			self.reload_shaders();
+		}
 		
 		Ok(())
 	}
 	
+	/// Switches to a new configuration, only the subsystems affected by the changed settings
+	/// are reconfigured. Returns the changed settings.
+	pub fn apply_configuration(&mut self, configuration: GraphicsConfiguration) -> Result<Vec<GraphicsSetting>, Box<dyn error::Error>> {
+		configuration.validate()?;
+		
+		let changed_settings = RefCell::borrow(&self.current_configuration).changed_settings(&configuration);
+		if changed_settings.is_empty() {
+			return Ok(changed_settings);
+		}
+		
+		// The fixed render scale is the upper bound of dynamic resolution
+		self.dynamic_resolution.set_limits(DYNAMIC_RESOLUTION_MIN_SCALE, configuration.render_scale, configuration.target_frametime_ms);
+		*RefCell::borrow_mut(&self.current_configuration) = configuration;
+		
+		// Reconfigure affected subsystems, the rest of the pipeline reads the configuration every frame
+		if self.is_initialized {
+			let config = RefCell::borrow(&self.current_configuration);
+			self.subsystems.reconfigure_affected(ReconfigureEvent {
+				configuration: config.deref(),
+				resolution: self.current_resolution,
+				only_resize: false,
+				changed_settings: &changed_settings,
+			});
+		}
+		Ok(changed_settings)
+	}
+	
+	pub fn configuration(&self) -> Ref<'_, GraphicsConfiguration> {
+		RefCell::borrow(&self.current_configuration)
+	}
+	
+	/// The lights of the scene, assigned to the clusters every frame
+	pub fn lights(&self) -> &LightList {
+		&self.clustered_lighting.lights
+	}
+	
+	pub fn lights_mut(&mut self) -> &mut LightList {
+		&mut self.clustered_lighting.lights
+	}
+	
+	pub fn dynamic_resolution(&self) -> &DynamicResolutionController {
+		&self.dynamic_resolution
+	}
+	
+	/// Cpu and gpu times of the frames and their passes
+	pub fn profiler(&self) -> &PerformanceProfiler {
+		&self.profiler
+	}
+	
+	pub fn profiler_mut(&mut self) -> &mut PerformanceProfiler {
+		&mut self.profiler
+	}
+	
 	fn reload_shaders(&mut self) {
 //		let asset_folder = demo::demo_instance().asset_folder.as_mut().unwrap();
 		
 		// Log
 		println!("Reloading shaders!");
 		
-		// Reload shaders from asset
-		self.program_ehaa_scene.reload_from_asset().expect("Failed to reload scene shader from asset");
-		self.program_post_composite.reload_from_asset().expect("Failed to reload post composite shader from asset");
-		
-//		// Delete old shaders
-//		if let Some(program) = self.program_ehaa_scene.take() {
-//			let mut program = RefCell::borrow_mut(&program);
-//			program.delete();
-//		}
-//		if let Some(program) = self.program_post_resolve.take() {
-//			let mut program = RefCell::borrow_mut(&program);
-//			program.delete();
-//		}
-		
-		// Reload shader from assets
-		
-		
-//		// Load shaders
-//		self.program_ehaa_scene = Some({
-//			let mut s = ShaderProgram::new_from_file(
-//				&asset_folder.join("shaders/scene_ehaa.vert.glsl"),
-//				&asset_folder.join("shaders/scene_ehaa.frag.glsl"),
-//				Some(&asset_folder.join("shaders/scene_ehaa.tesseval.glsl"))
-////				None
-//			);
-//			s.compile();
-//			Rc::new(RefCell::new(s))
-//		});
-//		self.program_post_resolve = Some({
-//			let mut s = ShaderProgram::new_from_file(
-//				&asset_folder.join("shaders/post_resolve.vert.glsl"),
-//				&asset_folder.join("shaders/post_resolve.frag.glsl"),
-//				None
-//			);
-//			s.compile();
-//			Rc::new(RefCell::new(s))
-//		});
-
-		// Reload subsystem shaders
-		self.separable_sss_system.reload_shaders();
+		// Reload the cached assets whose files changed
+		self.reload_changed_assets();
+		self.subsystems.reload_shaders_all(&mut self.program_cache);
+		
+		// Drop the programs nothing uses anymore, e.g. of subsystems that were disabled
+		self.unload_unused_assets();
+	}
+	
+	/// Reloads the cached assets built from files that changed since they were loaded, directly or
+	/// through other assets like the includes of a program, and retries the ones that failed to load.
+	/// Returns the number of reloaded assets.
+	pub fn reload_changed_assets(&mut self) -> usize {
+		let changed_files = unsafe {&ASSET_MANAGER_INSTANCE}.changed_files();
+		
+		self.program_cache.reload_affected_by(&changed_files) + self.texture_cache.reload_affected_by(&changed_files) + self.mesh_cache.reload_affected_by(&changed_files)
+	}
+	
+	/// Removes the cached assets no handle refers to anymore. Returns the number of unloaded assets.
+	pub fn unload_unused_assets(&mut self) -> usize {
+		self.program_cache.unload_unused() + self.texture_cache.unload_unused() + self.mesh_cache.unload_unused()
 	}
 	
 	pub fn do_render_frame(&mut self) {
@@ -206,12 +245,17 @@ impl RenderGlobal {
 		let viewprojection_matrix = cam_state.projection_matrix * cam_state.view_matrix;
 		
 		// Recompile shaders
-		if self.program_ehaa_scene.needs_recompile() {
-			self.program_ehaa_scene.do_recompile();
-		}
-		if self.program_post_composite.needs_recompile() {
-			self.program_post_composite.do_recompile();
+		{// Scene program borrow has to end before the materials bind it
+			// Nothing to recompile if the program failed to load, a reload retries it
+			if let Some(mut program_ehaa_scene) = self.program_ehaa_scene.get_mut() {
+				if program_ehaa_scene.needs_recompile() {
+					program_ehaa_scene.do_recompile();
+				}
+			}
 		}
+		let configuration = RefCell::borrow(&self.current_configuration).clone();
+		let ehaa_enabled = configuration.anti_aliasing == AntiAliasingMode::Ehaa;
+		let upscale_sharpen = configuration.upscale_filter == UpscaleFilter::Sharpen;
 		
 		unsafe {
 			gl::Disable(gl::FRAMEBUFFER_SRGB);
@@ -222,69 +266,84 @@ impl RenderGlobal {
 			gl::CullFace(gl::FRONT); // For some reason we need to cull FRONT. This might be due to reverse-z flipping the winding order?
 			
 			gl::Enable(gl::DEPTH_TEST);
+			gl::DepthMask(gl::TRUE);
 			
 			// Setup NDC z axis for reverse float depth
 			gl::DepthFunc(gl::GREATER);
 			gl::ClearDepth(0.0); // 0.0 is far with reverse z
 			gl::ClipControl(gl::LOWER_LEFT, gl::ZERO_TO_ONE);
 			gl::DepthRange(0.0, 1.0); // Standard (non-inversed) depth range, we use a reverse-z projection matrix instead
+		}
+		
+		// Start the frame scope, this also collects the gpu times of the last frames
+		self.profiler.begin_frame();
+		
+		// Pick the render scale of the next frames
+		if let Some(gpu_frametime_ms) = self.profiler.resolved_gpu_frametime_ms().filter(|_| configuration.dynamic_resolution) {
+			self.dynamic_resolution.update(gpu_frametime_ms);
+		}
+		
+		{// Upload the lights and the cluster grid of this frame
+			self.profiler.begin_scope("prepare_lights");
 			
-			// Use scene shader
-			let scene_shader = self.program_ehaa_scene.program().unwrap();
-			let scene_shader_gl = scene_shader.program_gl().unwrap();
-			gl::UseProgram(scene_shader_gl);
-			
-			// Bind scene framebuffer
-			let scene_fbo = RefCell::borrow(self.framebuffer_scene_hdr_ehaa.need());
-			gl::BindFramebuffer(gl::FRAMEBUFFER, scene_fbo.handle_gl());
+			let cluster_grid = ClusterGrid::from_projection(CLUSTER_GRID_DIMENSIONS, &cam_state.projection_matrix, (camera_near_z, camera_far_z));
+			let cluster_view = cluster_view_matrix(&cam_state.projection_matrix, &cam_state.view_matrix);
+			self.clustered_lighting.prepare_frame(cluster_grid, cluster_view);
 			
-			// Set the viewport
-			gl::Viewport(0, 0, self.current_resolution.0 as gl::sizei, self.current_resolution.1 as gl::sizei);
+			self.profiler.end_scope();
+		}
+		
+		// Declare the frame, the scene is rendered at the scaled resolution
+		let resolution = self.current_resolution;
+		let scene_resolution = if configuration.dynamic_resolution {
+			scale_resolution(resolution, self.dynamic_resolution.current_scale())
+		}
+		else {
+			configuration.scaled_resolution(resolution)
+		};
+		let mut graph = RenderGraph::new();
+		
+		let scene_hdr = graph.create_texture("scene_hdr", TextureDesc::new(scene_resolution.0, scene_resolution.1, ImageFormat::get(gl::R11F_G11F_B10F)));
+		let scene_edge_heuristics = if ehaa_enabled {
+			Some((
+				graph.create_texture("scene_edge_heuristic", TextureDesc::new(scene_resolution.0, scene_resolution.1, ImageFormat::get(gl::RGB8))),
+				graph.create_texture("scene_edge_heuristic2", TextureDesc::new(scene_resolution.0, scene_resolution.1, ImageFormat::get(gl::RGB8))),
+			))
+		}
+		else {
+			None
+		};
+		let scene_depth = graph.create_texture("scene_depth", TextureDesc::new(scene_resolution.0, scene_resolution.1, ImageFormat::get(gl::DEPTH_COMPONENT32F)));
+		let backbuffer = graph.import_backbuffer("backbuffer", resolution);
+		
+		// Assign the lights to the clusters before the scene is shaded
+		self.clustered_lighting.add_passes(&mut graph);
+		
+		{// Scene pass
+			let view_matrix = cam_state.view_matrix;
+			let configuration = configuration.clone();
+			let clustered_lighting = &self.clustered_lighting;
 			
-			gl::ClearColor(0.0, 0.0, 0.0, 0.0);
-			gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+			let mut scene_pass = graph.add_pass("scene");
 			
-			{// Upload matrices
-				let model_matrix = Matrix4::from_scale(1.0);
-				
-				let model_matrix_arr: [[f32; 4]; 4] = model_matrix.into();
-				gl::UniformMatrix4fv(gl::GetUniformLocation(scene_shader_gl, "uMatrixModel\0".as_ptr() as *const gl::char), 1, gl::FALSE, model_matrix_arr.as_ptr() as *const gl::float);
-				
-				let view_matrix_arr: [[f32; 4]; 4] = cam_state.view_matrix.into();
-				gl::UniformMatrix4fv(gl::GetUniformLocation(scene_shader_gl, "uMatrixView\0".as_ptr() as *const gl::char), 1, gl::FALSE, view_matrix_arr.as_ptr() as *const gl::float);
-				
-				let viewprojection_matrix_arr: [[f32; 4]; 4] = viewprojection_matrix.into();
-				gl::UniformMatrix4fv(gl::GetUniformLocation(scene_shader_gl, "uMatrixViewProjection\0".as_ptr() as *const gl::char), 1, gl::FALSE, viewprojection_matrix_arr.as_ptr() as *const gl::float);
+			// Without ehaa the edge outputs of the shader are discarded
+			if let Some((scene_edge_heuristic, scene_edge_heuristic2)) = scene_edge_heuristics {
+				scene_pass
+					.color_attachment(1, scene_edge_heuristic)
+					.color_attachment(2, scene_edge_heuristic2);
 			}
 			
-			let start_frametimer = {// Start frametime timer
-				let mut elapsed_frametime: u64 = std::u64::MAX;
-				gl::GetQueryObjectui64v(self.frametime_query_object_gl, gl::QUERY_RESULT_NO_WAIT, &mut elapsed_frametime);
-				
-				if elapsed_frametime != std::u64::MAX {
-					let _float_frametime = (elapsed_frametime as f64) / 1e6;
+			scene_pass
+				.color_attachment(0, scene_hdr)
+				.depth_attachment(scene_depth)
+				.execute(move |_| unsafe {
+					gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+					gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 					
-//					let title = format!("EHAA Demo ~ Frametime {} ms", float_frametime);
-//					self.window.need_mut().set_title(title.as_str());
-					
-					// Restart query
-					gl::BeginQuery(gl::TIME_ELAPSED, self.frametime_query_object_gl);
-					true
-				}
-				else {
-					false
-				}
-			};
-			
-			// Set tessellation state
-			gl::PatchParameteri(gl::PATCH_VERTICES, 3);
-			gl::PatchParameterfv(gl::PATCH_DEFAULT_OUTER_LEVEL, [1.0f32, 1.0f32, 1.0f32, 1.0f32].as_ptr());
-			gl::PatchParameterfv(gl::PATCH_DEFAULT_INNER_LEVEL, [1.0f32, 1.0f32].as_ptr());
-			
-			gl::EnableVertexAttribArray(0);
+					gl::EnableVertexAttribArray(0);
 //			gl::EnableVertexAttribArray(1);
 //			gl::EnableVertexAttribArray(2);
-			
+					
 			/*
 			{// Draw teapot
 				let test_teapot_vbo = demo::demo_instance().test_teapot_vbo.need();
@@ -295,40 +354,55 @@ impl RenderGlobal {
 				gl::DrawArrays(gl::PATCHES, 0, (crate::render::teapot::TEAPOT_VERTEX_DATA.len() / 3) as gl::sizei);
 			}
 			*/
-			
-//			/*
-			{// Draw head model
-				let test_head_model = demo::demo_instance().test_head_model.need();
-				
-				// Bind textures
-				gl::BindTextureUnit(1, test_head_model.tex_albedo.texture_gl());
-				gl::BindTextureUnit(2, test_head_model.tex_normal.texture_gl());
-				gl::BindTextureUnit(4, test_head_model.tex_transmission.texture_gl());
-				
-				gl::BindBuffer(gl::ARRAY_BUFFER, test_head_model.vertex_buffer_gl);
-//				let stride = 8*4;
-				let stride = 12*4;
-				gl::EnableVertexAttribArray(0);
-				gl::EnableVertexAttribArray(1);
-				gl::EnableVertexAttribArray(2);
-				gl::EnableVertexAttribArray(3);
-				gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, 0 as *const gl::void); // vertex
-				gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, stride, (3*4 + 3*4) as *const gl::void); // texcoord
-				gl::VertexAttribPointer(2, 3, gl::FLOAT, gl::FALSE, stride, (3*4) as *const gl::void); // normal
-				gl::VertexAttribPointer(3, 4, gl::FLOAT, gl::FALSE, stride, (3*4 + 3*4 + 2*4) as *const gl::void); // tangent
-				
-				gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, test_head_model.index_buffer_gl);
-				
-				gl::DrawElements(gl::PATCHES, test_head_model.num_indices as gl::sizei, gl::UNSIGNED_INT, 0 as *const gl::void);
-//				gl::DrawElements(gl::TRIANGLES, self.test_head_model.need().num_indices as gl::GLsizei, gl::UNSIGNED_INT, 0 as *const std::ffi::c_void);
-				
-				gl::DisableVertexAttribArray(0);
-				gl::DisableVertexAttribArray(1);
-				gl::DisableVertexAttribArray(2);
-				gl::DisableVertexAttribArray(3);
-			}
-//			*/
-			
+					
+//					/*
+					{// Draw head model
+						// Skip the model until all of its parts finished loading
+						if let Some(test_head_model) = demo::demo_instance().test_head_model.as_ref().filter(|m| m.is_ready()) {
+							let mesh = test_head_model.mesh.get().unwrap();
+							let material = &test_head_model.material;
+							
+							// Bind the material (program, render state and textures), skip the draw if its program has nothing to draw with
+							let scene_shader = match material.bind(&configuration) {
+								Some(scene_shader) => scene_shader,
+								None => return,
+							};
+							
+							{// Upload matrices
+								let model_matrix = Matrix4::from_scale(1.0);
+								
+								let _ = scene_shader.set_uniform("uMatrixModel", model_matrix);
+								let _ = scene_shader.set_uniform("uMatrixView", view_matrix);
+								let _ = scene_shader.set_uniform("uMatrixViewProjection", viewprojection_matrix);
+							}
+							
+							// Size of the render targets, used by the edge heuristic and to find the cluster
+							let _ = scene_shader.set_uniform("uScreenSize", Vector2::new(scene_resolution.0 as f32, scene_resolution.1 as f32));
+							clustered_lighting.bind_for_shading();
+							
+							gl::BindBuffer(gl::ARRAY_BUFFER, mesh.vertex_buffer_gl());
+							let stride = mesh.vertex_stride() as gl::sizei;
+							gl::EnableVertexAttribArray(0);
+							gl::EnableVertexAttribArray(1);
+							gl::EnableVertexAttribArray(2);
+							gl::EnableVertexAttribArray(3);
+							gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, 0 as *const gl::void); // vertex
+							gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, stride, (3*4 + 3*4) as *const gl::void); // texcoord
+							gl::VertexAttribPointer(2, 3, gl::FLOAT, gl::FALSE, stride, (3*4) as *const gl::void); // normal
+							gl::VertexAttribPointer(3, 4, gl::FLOAT, gl::FALSE, stride, (3*4 + 3*4 + 2*4) as *const gl::void); // tangent
+							
+							gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, mesh.index_buffer_gl());
+							
+							gl::DrawElements(material.render_state().primitive_type(), mesh.num_indices() as gl::sizei, gl::UNSIGNED_INT, 0 as *const gl::void);
+							
+							gl::DisableVertexAttribArray(0);
+							gl::DisableVertexAttribArray(1);
+							gl::DisableVertexAttribArray(2);
+							gl::DisableVertexAttribArray(3);
+						}
+					}
+//					*/
+					
 			/*
 			{// Draw debug triangles
 				gl::Begin(gl::PATCHES);
@@ -359,72 +433,147 @@ impl RenderGlobal {
 				gl::End();
 			}
 			*/
-			
-			{// Resolve separable sss
-				let main_fbo = RefCell::borrow(self.framebuffer_scene_hdr_ehaa.need());
-				let scene_hdr_rt = RefCell::borrow(&main_fbo.get_attachment(AttachmentPoint::Color(0)).unwrap().texture);
-				let scene_depth_rt = RefCell::borrow(&main_fbo.get_attachment(AttachmentPoint::Depth).unwrap().texture);
-				
-				// Render ssss
-				self.separable_sss_system.do_resolve_sss(&scene_hdr_rt, &scene_depth_rt, camera_fovy, (camera_near_z, camera_far_z));
+				});
+		}
+		
+		// Let the subsystems add their passes
+		let mut frame = FrameResources {
+			resolution: scene_resolution,
+			scene_hdr,
+			scene_depth,
+			camera: cam_state,
+			camera_fovy,
+			camera_depth_planes: (camera_near_z, camera_far_z),
+			bloom: None,
+		};
+		self.subsystems.add_passes(&mut graph, &mut frame);
+		let scene_final = frame.scene_hdr;
+		let bloom = frame.bloom;
+		
+		// The permutation depends on what the subsystems produced
+		let post_composite_key = self.program_post_composite.get_mut().map(|mut program_post_composite| {
+			if program_post_composite.needs_recompile() {
+				program_post_composite.do_recompile();
 			}
 			
-			{// Do ehaa resolve pass
-				let post_resolve_shader = self.program_post_composite.program().unwrap();
-				
-// 				// DEBUG: Blit framebuffer
-//				gl::BlitNamedFramebuffer(self.framebuffer_scene_hdr_ehaa.need().handle_gl(), 0, 0, 0, 1280, 720, 0, 0, 1280, 720, gl::COLOR_BUFFER_BIT, gl::NEAREST);
-				
-				gl::Disable(gl::DEPTH_TEST);
-				
-				// Bind resolve shader
-				gl::UseProgram(post_resolve_shader.program_gl().unwrap());
-				
-				// Bind shaders
-				let main_fbo = RefCell::borrow(self.framebuffer_scene_hdr_ehaa.need());
-//				gl::BindTextureUnit(0, RefCell::borrow(&main_fbo.get_attachment(AttachmentPoint::Color(0)).unwrap().texture).texture_gl());
- 				gl::BindTextureUnit(0, RefCell::borrow(&self.separable_sss_system.fbo_resolve_final.get_attachment(AttachmentPoint::Color(0)).unwrap().texture).texture_gl());
-				gl::BindTextureUnit(1, RefCell::borrow(&main_fbo.get_attachment(AttachmentPoint::Color(1)).unwrap().texture).texture_gl());
-				gl::BindTextureUnit(2, RefCell::borrow(&main_fbo.get_attachment(AttachmentPoint::Color(2)).unwrap().texture).texture_gl());
-				
-				// Bind back buffer
-				gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
-				
-				// Draw oversized fullscreen triangles
-				gl::DisableVertexAttribArray(0);
-				gl::DisableVertexAttribArray(1);
-				gl::DisableVertexAttribArray(2);
-				gl::DrawArrays(gl::TRIANGLES, 0, 3);
+			// Build the variant if needed
+			let layout = program_post_composite.permutation_layout();
+			let key = layout.with_flag(layout.default_key(), "EHAA_ENABLE", ehaa_enabled)
+				.and_then(|key| layout.with_flag(key, "UPSCALE_SHARPEN", upscale_sharpen))
+				.and_then(|key| layout.with_flag(key, "BLOOM_ENABLE", bloom.is_some()))
+				.unwrap_or_else(|| layout.default_key());
+			program_post_composite.program_variant(key);
+			key
+		}).unwrap_or_default();
+		
+		{// Do ehaa resolve pass
+			let program_post_composite = &self.program_post_composite;
+			
+			let mut composite_pass = graph.add_pass("post_composite");
+			if let Some((scene_edge_heuristic, scene_edge_heuristic2)) = scene_edge_heuristics {
+				composite_pass
+					.read(scene_edge_heuristic)
+					.read(scene_edge_heuristic2);
 			}
 			
-			// End frametimer query
-			if start_frametimer {
-				gl::EndQuery(gl::TIME_ELAPSED);
+			if let Some(bloom) = bloom {
+				composite_pass.read(bloom);
 			}
+			
+			let bloom_intensity = configuration.bloom_intensity;
+			composite_pass
+				.read(scene_final)
+				.color_attachment(0, backbuffer)
+				.execute(move |context| unsafe {
+					// Skip the pass if the program failed to load, broken sources already fall back to the error program
+					let program_post_composite = match program_post_composite.get() {
+						Some(program_post_composite) => program_post_composite,
+						None => return,
+					};
+					let (post_resolve_shader, post_resolve_shader_gl) = match program_post_composite.cached_variant(post_composite_key).and_then(|s| Some((s, s.program_gl()?))) {
+						Some(shader) => shader,
+						None => return,
+					};
+					
+					gl::Disable(gl::DEPTH_TEST);
+					
+					// Bind resolve shader
+					gl::UseProgram(post_resolve_shader_gl);
+					
+					// Bind textures
+//					context.bind_texture(0, scene_hdr);
+					context.bind_texture(0, scene_final);
+					if let Some((scene_edge_heuristic, scene_edge_heuristic2)) = scene_edge_heuristics {
+						context.bind_texture(1, scene_edge_heuristic);
+						context.bind_texture(2, scene_edge_heuristic2);
+					}
+					if let Some(bloom) = bloom {
+						context.bind_texture(3, bloom);
+						let _ = post_resolve_shader.set_uniform("uBloomIntensity", bloom_intensity);
+					}
+					
+					// Draw oversized fullscreen triangles
+					gl::DisableVertexAttribArray(0);
+					gl::DisableVertexAttribArray(1);
+					gl::DisableVertexAttribArray(2);
+					gl::DrawArrays(gl::TRIANGLES, 0, 3);
+				});
+		}
+		
+		// Compile and execute the frame, the passes are timed by the executor
+		let compiled = self.profiler.scope("compile_render_graph", |_| graph.compile());
+		match compiled {
+			Ok(compiled) => self.render_graph_executor.execute(&mut graph, &compiled, &mut self.profiler),
+			Err(err) => println!("Failed to compile render graph: {}", err),
 		}
+		
+		// End the frame scope
+		self.profiler.end_frame();
 	}
 	
-	pub fn queue_shader_reload(&mut self) {
-		self.queued_shader_reload = true;
+	/// Registers a subsystem, it's initialized right away if the pipeline already is.
+	pub fn register_subsystem(&mut self, subsystem: Box<dyn RenderSubsystem>, enabled: bool) -> Result<(), Box<dyn error::Error>> {
+		let name = subsystem.name();
+		self.subsystems.register(subsystem, enabled)?;
+		
+		if self.is_initialized {
+			// Only the new subsystem isn't initialized yet
+			self.subsystems.initialize_all();
+			
+			let config = RefCell::borrow(&self.current_configuration);
+			self.subsystems.reconfigure(name, ReconfigureEvent {
+				configuration: config.deref(),
+				resolution: self.current_resolution,
+				only_resize: false,
+				changed_settings: GraphicsSetting::all(),
+			});
+		}
+		Ok(())
 	}
-}
-
-pub struct GraphicsConfiguration {
 	
-}
-
-impl GraphicsConfiguration {
-	pub fn new() -> GraphicsConfiguration {
-		GraphicsConfiguration {}
+	pub fn subsystems(&self) -> &RenderSubsystemRegistry {
+		&self.subsystems
+	}
+	
+	pub fn subsystems_mut(&mut self) -> &mut RenderSubsystemRegistry {
+		&mut self.subsystems
+	}
+	
+	pub fn queue_shader_reload(&mut self) {
+		self.queued_shader_reload = true;
 	}
 }
 
+#[derive(Copy, Clone)]
 pub struct ReconfigureEvent<'a> {
 	pub configuration: &'a GraphicsConfiguration,
 	pub resolution: (u32, u32),
 	pub only_resize: bool,
+	/// Settings that changed since the last reconfiguration, all of them initially
+	pub changed_settings: &'a [GraphicsSetting],
 }
 
+#[derive(Copy, Clone)]
 pub struct RenderCameraState {
 	pub projection_matrix: Matrix4<f32>,
 	pub view_matrix: Matrix4<f32>,
@@ -0,0 +1,2 @@
+mod render_graph; pub use render_graph::*;
+mod render_graph_executor; pub use render_graph_executor::*;
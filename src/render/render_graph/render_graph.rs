@@ -0,0 +1,531 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use gl_bindings::gl;
+use crate::render::{ImageFormat, Texture};
+use crate::render::render_graph::RenderPassContext;
+
+/// Identifies a resource of a `RenderGraph`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ResourceId(pub usize);
+
+/// Identifies a pass of a `RenderGraph`, in declaration order.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct PassId(pub usize);
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TextureDesc {
+	pub width: u32,
+	pub height: u32,
+	pub image_format: ImageFormat,
+}
+
+impl TextureDesc {
+	pub fn new(width: u32, height: u32, image_format: ImageFormat) -> TextureDesc {
+		TextureDesc {
+			width,
+			height,
+			image_format,
+		}
+	}
+}
+
+pub enum ResourceKind {
+	/// Allocated (and possibly aliased) by the graph, only valid while the graph executes
+	Transient,
+	/// Owned by someone else, its contents live on after the frame
+	Imported(Rc<RefCell<Texture>>),
+	/// The default framebuffer
+	Backbuffer,
+}
+
+pub struct ResourceDecl {
+	pub name: String,
+	pub desc: TextureDesc,
+	pub kind: ResourceKind,
+}
+
+/// How a pass accesses a resource.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum ResourceAccess {
+	/// Read through a sampler
+	Sampled,
+	ColorAttachment(u32),
+	DepthAttachment,
+	/// Read through an image unit
+	StorageRead,
+	/// Written through an image unit
+	StorageWrite,
+}
+
+impl ResourceAccess {
+	pub fn is_write(&self) -> bool {
+		match self {
+			ResourceAccess::ColorAttachment(_) | ResourceAccess::DepthAttachment | ResourceAccess::StorageWrite => true,
+			ResourceAccess::Sampled | ResourceAccess::StorageRead => false,
+		}
+	}
+	
+	pub fn is_attachment(&self) -> bool {
+		match self {
+			ResourceAccess::ColorAttachment(_) | ResourceAccess::DepthAttachment => true,
+			_ => false,
+		}
+	}
+}
+
+pub type RenderPassCallback<'a> = Box<dyn FnMut(&RenderPassContext<'_>) + 'a>;
+
+pub struct RenderPassDecl<'a> {
+	pub name: String,
+	pub accesses: Vec<(ResourceId, ResourceAccess)>,
+	/// Never culled, even if nothing reads what it writes
+	pub has_side_effects: bool,
+	pub callback: Option<RenderPassCallback<'a>>,
+}
+
+impl<'a> RenderPassDecl<'a> {
+	pub fn attachments<'s>(&'s self) -> impl Iterator<Item = (ResourceId, ResourceAccess)> + 's {
+		self.accesses.iter().cloned().filter(|(_, a)| a.is_attachment())
+	}
+}
+
+/// The passes of a frame and the resources they read and write.
+///
+/// Passes are declared in the order they'd be executed in without a graph, every read sees
+/// the last write declared before it. `compile` derives the actual order, culls passes nobody
+/// depends on and decides which transient textures share memory. It doesn't touch gl at all.
+pub struct RenderGraph<'a> {
+	pub resources: Vec<ResourceDecl>,
+	pub passes: Vec<RenderPassDecl<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+	/// A texture that's allocated by the graph. It may share memory with other
+	/// transient textures so its contents are undefined until a pass writes it.
+	pub fn create_texture(&mut self, name: &str, desc: TextureDesc) -> ResourceId {
+		self.add_resource(name, desc, ResourceKind::Transient)
+	}
+	
+	/// A texture owned outside of the graph. Passes writing it are never culled.
+	/// Replace the `Rc` instead of reallocating the texture in place when it changes,
+	/// the executor drops its framebuffers when the imported textures differ from the last frame.
+	pub fn import_texture(&mut self, name: &str, texture: Rc<RefCell<Texture>>) -> ResourceId {
+		let (width, height) = texture.borrow().size();
+		let image_format = texture.borrow().image_format();
+		self.add_resource(name, TextureDesc::new(width, height, image_format), ResourceKind::Imported(texture))
+	}
+	
+	/// The default framebuffer, can only be used as the sole color attachment `0` of a pass.
+	pub fn import_backbuffer(&mut self, name: &str, size: (u32, u32)) -> ResourceId {
+		self.add_resource(name, TextureDesc::new(size.0, size.1, ImageFormat::get(gl::RGBA8)), ResourceKind::Backbuffer)
+	}
+	
+	fn add_resource(&mut self, name: &str, desc: TextureDesc, kind: ResourceKind) -> ResourceId {
+		self.resources.push(ResourceDecl {
+			name: String::from(name),
+			desc,
+			kind,
+		});
+		ResourceId(self.resources.len() - 1)
+	}
+	
+	pub fn add_pass<'g>(&'g mut self, name: &str) -> RenderPassBuilder<'g, 'a> {
+		self.passes.push(RenderPassDecl {
+			name: String::from(name),
+			accesses: Vec::new(),
+			has_side_effects: false,
+			callback: None,
+		});
+		let pass = PassId(self.passes.len() - 1);
+		
+		RenderPassBuilder {
+			graph: self,
+			pass,
+		}
+	}
+	
+	pub fn resource(&self, id: ResourceId) -> &ResourceDecl {
+		&self.resources[id.0]
+	}
+	
+	pub fn pass(&self, id: PassId) -> &RenderPassDecl<'a> {
+		&self.passes[id.0]
+	}
+	
+	/// Orders the passes, culls unused ones and assigns transient textures to physical textures.
+	pub fn compile(&self) -> Result<CompiledRenderGraph, RenderGraphError> {
+		let num_passes = self.passes.len();
+		let num_resources = self.resources.len();
+		
+		// Validate the attachments
+		for (pass_index, pass) in self.passes.iter().enumerate() {
+			self.validate_attachments(PassId(pass_index), pass)?;
+		}
+		
+		// Find the passes each pass needs the output of, and the ones it just has to run after
+		let mut data_dependencies = vec![Vec::<usize>::new(); num_passes];
+		let mut order_dependencies = vec![Vec::<usize>::new(); num_passes];
+		{
+			let mut last_writer = vec![None::<usize>; num_resources];
+			let mut readers_since_write = vec![Vec::<usize>::new(); num_resources];
+			
+			for (pass_index, pass) in self.passes.iter().enumerate() {
+				for &(resource, access) in &pass.accesses {
+					let writer = last_writer[resource.0];
+					
+					// Reads (and attachment writes, which blend or load) need the previous write
+					if writer.is_none() && !access.is_write() {
+						if let ResourceKind::Transient = self.resources[resource.0].kind {
+							return Err(RenderGraphError::ReadBeforeWrite {
+								pass: pass.name.clone(),
+								resource: self.resources[resource.0].name.clone(),
+							});
+						}
+					}
+					if let Some(writer) = writer {
+						if writer != pass_index {
+							data_dependencies[pass_index].push(writer);
+						}
+					}
+					
+					if access.is_write() {
+						// Writes also have to wait for everyone still reading the old contents
+						for &reader in &readers_since_write[resource.0] {
+							if reader != pass_index {
+								order_dependencies[pass_index].push(reader);
+							}
+						}
+						readers_since_write[resource.0].clear();
+					}
+				}
+				
+				// Update the state after all accesses of the pass, a pass can read and write the same resource
+				for &(resource, access) in &pass.accesses {
+					if access.is_write() {
+						last_writer[resource.0] = Some(pass_index);
+					}
+					else {
+						readers_since_write[resource.0].push(pass_index);
+					}
+				}
+			}
+			
+			for (data_deps, order_deps) in data_dependencies.iter().zip(order_dependencies.iter_mut()) {
+				order_deps.extend(data_deps.iter().cloned());
+				order_deps.sort();
+				order_deps.dedup();
+			}
+		}
+		
+		// Cull passes whose output nothing needed reads
+		let mut is_needed = vec![false; num_passes];
+		{
+			let mut stack = Vec::<usize>::new();
+			for (pass_index, pass) in self.passes.iter().enumerate() {
+				let writes_external = pass.accesses.iter().any(|(r, a)| {
+					a.is_write() && match self.resources[r.0].kind {
+						ResourceKind::Transient => false,
+						_ => true,
+					}
+				});
+				if pass.has_side_effects || writes_external {
+					stack.push(pass_index);
+				}
+			}
+			
+			while let Some(pass_index) = stack.pop() {
+				if !is_needed[pass_index] {
+					is_needed[pass_index] = true;
+					stack.extend(data_dependencies[pass_index].iter().cloned());
+				}
+			}
+		}
+		
+		// Order the needed passes topologically, earlier declared passes first
+		let order = {
+			let mut order = Vec::<PassId>::with_capacity(num_passes);
+			let mut is_scheduled = vec![false; num_passes];
+			
+			while order.len() < is_needed.iter().filter(|n| **n).count() {
+				let next = (0..num_passes).find(|&p| {
+					is_needed[p] && !is_scheduled[p] && order_dependencies[p].iter().all(|&d| is_scheduled[d] || !is_needed[d])
+				});
+				
+				match next {
+					Some(p) => {
+						is_scheduled[p] = true;
+						order.push(PassId(p));
+					}
+					None => {
+						// Can't happen with the dependencies derived from declaration order
+						let pass = (0..num_passes).find(|&p| is_needed[p] && !is_scheduled[p]).unwrap();
+						return Err(RenderGraphError::Cycle {pass: self.passes[pass].name.clone()});
+					}
+				}
+			}
+			order
+		};
+		
+		// Find the lifetime of each resource as range of indices into the order
+		let mut lifetimes = vec![None::<ResourceLifetime>; num_resources];
+		for (order_index, pass) in order.iter().enumerate() {
+			for &(resource, _) in &self.passes[pass.0].accesses {
+				let lifetime = lifetimes[resource.0].get_or_insert(ResourceLifetime {first_use: order_index, last_use: order_index});
+				lifetime.last_use = order_index;
+			}
+		}
+		
+		// Alias transient textures with equal descs and disjoint lifetimes
+		let mut physical_slots = vec![None::<usize>; num_resources];
+		let mut slots = Vec::<PhysicalTextureSlot>::new();
+		{
+			let mut transients = (0..num_resources)
+				.filter(|&r| match self.resources[r].kind {ResourceKind::Transient => true, _ => false})
+				.filter_map(|r| lifetimes[r].map(|l| (r, l)))
+				.collect::<Vec<_>>();
+			transients.sort_by_key(|(r, l)| (l.first_use, *r));
+			
+			for (resource, lifetime) in transients {
+				let desc = self.resources[resource].desc;
+				
+				let free_slot = slots.iter().position(|s| s.desc == desc && s.last_use < lifetime.first_use);
+				let slot_index = match free_slot {
+					Some(index) => index,
+					None => {
+						slots.push(PhysicalTextureSlot {desc, last_use: 0});
+						slots.len() - 1
+					}
+				};
+				slots[slot_index].last_use = lifetime.last_use;
+				physical_slots[resource] = Some(slot_index);
+			}
+		}
+		
+		// Derive the barriers from consecutive accesses of the same resource
+		let mut barriers = vec![Vec::<PassBarrier>::new(); order.len()];
+		{
+			let mut last_access = vec![None::<ResourceAccess>; num_resources];
+			for (order_index, pass) in order.iter().enumerate() {
+				let accesses = &self.passes[pass.0].accesses;
+				
+				for &(resource, access) in accesses {
+					if let Some(previous) = last_access[resource.0] {
+						if previous.is_write() || access.is_write() {
+							let barrier = PassBarrier {resource, from: previous, to: access};
+							if !barriers[order_index].contains(&barrier) {
+								barriers[order_index].push(barrier);
+							}
+						}
+					}
+				}
+				for &(resource, access) in accesses {
+					last_access[resource.0] = Some(access);
+				}
+			}
+		}
+		
+		Ok(CompiledRenderGraph {
+			order,
+			culled: (0..num_passes).filter(|&p| !is_needed[p]).map(PassId).collect(),
+			lifetimes,
+			physical_slots,
+			slot_descs: slots.into_iter().map(|s| s.desc).collect(),
+			barriers,
+		})
+	}
+	
+	fn validate_attachments(&self, pass_id: PassId, pass: &RenderPassDecl<'a>) -> Result<(), RenderGraphError> {
+		let invalid = |reason: &str| RenderGraphError::InvalidAttachments {
+			pass: pass.name.clone(),
+			reason: String::from(reason),
+		};
+		
+		let mut size = None;
+		let mut points = HashSet::new();
+		let mut has_backbuffer = false;
+		let mut num_attachments = 0;
+		
+		for (resource, access) in self.pass(pass_id).attachments() {
+			let decl = &self.resources[resource.0];
+			num_attachments += 1;
+			
+			if !points.insert(access) {
+				return Err(invalid("Attachment point is used twice"));
+			}
+			if let ResourceKind::Backbuffer = decl.kind {
+				has_backbuffer = true;
+				if access != ResourceAccess::ColorAttachment(0) {
+					return Err(invalid("Backbuffer can only be color attachment 0"));
+				}
+			}
+			match size {
+				None => size = Some((decl.desc.width, decl.desc.height)),
+				Some(size) if size != (decl.desc.width, decl.desc.height) => return Err(invalid("Attachments differ in size")),
+				_ => {},
+			}
+		}
+		
+		if has_backbuffer && num_attachments > 1 {
+			return Err(invalid("Backbuffer can't be combined with other attachments"));
+		}
+		Ok(())
+	}
+	
+	pub fn new() -> RenderGraph<'a> {
+		RenderGraph {
+			resources: Vec::new(),
+			passes: Vec::new(),
+		}
+	}
+}
+
+pub struct RenderPassBuilder<'g, 'a> {
+	graph: &'g mut RenderGraph<'a>,
+	pass: PassId,
+}
+
+impl<'g, 'a> RenderPassBuilder<'g, 'a> {
+	pub fn id(&self) -> PassId {
+		self.pass
+	}
+	
+	fn access(&mut self, resource: ResourceId, access: ResourceAccess) -> &mut Self {
+		self.graph.passes[self.pass.0].accesses.push((resource, access));
+		self
+	}
+	
+	pub fn read(&mut self, resource: ResourceId) -> &mut Self {
+		self.access(resource, ResourceAccess::Sampled)
+	}
+	
+	pub fn read_storage(&mut self, resource: ResourceId) -> &mut Self {
+		self.access(resource, ResourceAccess::StorageRead)
+	}
+	
+	pub fn write_storage(&mut self, resource: ResourceId) -> &mut Self {
+		self.access(resource, ResourceAccess::StorageWrite)
+	}
+	
+	pub fn color_attachment(&mut self, index: u32, resource: ResourceId) -> &mut Self {
+		self.access(resource, ResourceAccess::ColorAttachment(index))
+	}
+	
+	pub fn depth_attachment(&mut self, resource: ResourceId) -> &mut Self {
+		self.access(resource, ResourceAccess::DepthAttachment)
+	}
+	
+	pub fn side_effects(&mut self) -> &mut Self {
+		self.graph.passes[self.pass.0].has_side_effects = true;
+		self
+	}
+	
+	/// Called with the pass' framebuffer bound and the viewport set to its size.
+	pub fn execute<F>(&mut self, callback: F) -> &mut Self where F: FnMut(&RenderPassContext<'_>) + 'a {
+		self.graph.passes[self.pass.0].callback = Some(Box::new(callback));
+		self
+	}
+}
+
+/// First and last use of a resource, as indices into `CompiledRenderGraph::pass_order`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ResourceLifetime {
+	pub first_use: usize,
+	pub last_use: usize,
+}
+
+/// A hazard between two consecutive accesses of a resource.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct PassBarrier {
+	pub resource: ResourceId,
+	pub from: ResourceAccess,
+	pub to: ResourceAccess,
+}
+
+impl PassBarrier {
+	/// The `glMemoryBarrier` bits needed, zero if gl already orders the accesses
+	/// (everything except incoherent image stores).
+	pub fn memory_barrier_bits(&self) -> gl::bitfield {
+		if self.from != ResourceAccess::StorageWrite {
+			return 0;
+		}
+		
+		match self.to {
+			ResourceAccess::Sampled => gl::TEXTURE_FETCH_BARRIER_BIT,
+			ResourceAccess::StorageRead | ResourceAccess::StorageWrite => gl::SHADER_IMAGE_ACCESS_BARRIER_BIT,
+			ResourceAccess::ColorAttachment(_) | ResourceAccess::DepthAttachment => gl::FRAMEBUFFER_BARRIER_BIT,
+		}
+	}
+}
+
+struct PhysicalTextureSlot {
+	desc: TextureDesc,
+	last_use: usize,
+}
+
+/// The result of `RenderGraph::compile`.
+pub struct CompiledRenderGraph {
+	order: Vec<PassId>,
+	culled: Vec<PassId>,
+	lifetimes: Vec<Option<ResourceLifetime>>,
+	physical_slots: Vec<Option<usize>>,
+	slot_descs: Vec<TextureDesc>,
+	barriers: Vec<Vec<PassBarrier>>,
+}
+
+impl CompiledRenderGraph {
+	/// The passes to execute, in order. Culled passes aren't in here.
+	pub fn pass_order(&self) -> &[PassId] {
+		&self.order
+	}
+	
+	pub fn culled_passes(&self) -> &[PassId] {
+		&self.culled
+	}
+	
+	pub fn is_culled(&self, pass: PassId) -> bool {
+		self.culled.contains(&pass)
+	}
+	
+	/// `None` if no executed pass uses the resource.
+	pub fn lifetime(&self, resource: ResourceId) -> Option<ResourceLifetime> {
+		self.lifetimes[resource.0]
+	}
+	
+	/// The physical texture a transient resource lives in, transient resources with the same slot alias.
+	pub fn physical_slot(&self, resource: ResourceId) -> Option<usize> {
+		self.physical_slots[resource.0]
+	}
+	
+	/// Descs of the physical textures needed to execute the graph.
+	pub fn physical_slots(&self) -> &[TextureDesc] {
+		&self.slot_descs
+	}
+	
+	/// Barriers to issue before the pass at `order_index` in `pass_order`.
+	pub fn barriers(&self, order_index: usize) -> &[PassBarrier] {
+		&self.barriers[order_index]
+	}
+}
+
+#[derive(Debug)]
+pub enum RenderGraphError {
+	/// A pass reads a transient resource before any pass wrote it
+	ReadBeforeWrite {pass: String, resource: String},
+	InvalidAttachments {pass: String, reason: String},
+	Cycle {pass: String},
+}
+
+impl Error for RenderGraphError {}
+
+impl fmt::Display for RenderGraphError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		match self {
+			RenderGraphError::ReadBeforeWrite {pass, resource} => write!(f, "Pass {} reads {} before anything wrote it", pass, resource),
+			RenderGraphError::InvalidAttachments {pass, reason} => write!(f, "Invalid attachments in pass {}: {}", pass, reason),
+			RenderGraphError::Cycle {pass} => write!(f, "Pass {} is part of a dependency cycle", pass),
+		}
+	}
+}
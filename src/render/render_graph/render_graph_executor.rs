@@ -0,0 +1,214 @@
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use gl_bindings::gl;
+use crate::render::{Framebuffer, FramebufferAttachment, AttachmentPoint, Texture};
+use crate::render::render_graph::{RenderGraph, CompiledRenderGraph, ResourceId, ResourceKind, ResourceAccess, TextureDesc};
+use crate::render::performance::PerformanceProfiler;
+
+/// What a pass callback gets to look up the textures of its resources.
+pub struct RenderPassContext<'r> {
+	textures: &'r [Option<Rc<RefCell<Texture>>>],
+	framebuffer_gl: gl::uint,
+	size: (u32, u32),
+}
+
+impl<'r> RenderPassContext<'r> {
+	pub fn texture(&self, resource: ResourceId) -> Ref<'_, Texture> {
+		RefCell::borrow(self.textures[resource.0].as_ref().expect("Resource has no texture (the backbuffer or unused)"))
+	}
+	
+	pub fn texture_gl(&self, resource: ResourceId) -> gl::uint {
+		self.texture(resource).texture_gl()
+	}
+	
+	pub fn bind_texture(&self, unit: u32, resource: ResourceId) {
+		unsafe {
+			gl::BindTextureUnit(unit, self.texture_gl(resource));
+		}
+	}
+	
+	/// The framebuffer bound for the pass, `0` for the backbuffer or if the pass has no attachments.
+	pub fn framebuffer_gl(&self) -> gl::uint {
+		self.framebuffer_gl
+	}
+	
+	/// Size of the attachments (and the viewport)
+	pub fn size(&self) -> (u32, u32) {
+		self.size
+	}
+}
+
+struct PooledTexture {
+	desc: TextureDesc,
+	texture: Rc<RefCell<Texture>>,
+}
+
+/// Executes compiled render graphs. Keeps the physical textures and
+/// framebuffers around so they can be reused by the next frame.
+pub struct RenderGraphExecutor {
+	texture_pool: Vec<PooledTexture>,
+	/// Keyed by the attachment points and textures
+	framebuffer_cache: HashMap<Vec<(gl::enuma, gl::uint)>, Framebuffer>,
+	/// The imported textures of the last execute and their gl names
+	imported_textures: Vec<(Rc<RefCell<Texture>>, gl::uint)>,
+}
+
+impl RenderGraphExecutor {
+	/// Executes the passes in order, each one is timed as a profiler scope named like the pass.
+	pub fn execute(&mut self, graph: &mut RenderGraph<'_>, compiled: &CompiledRenderGraph, profiler: &mut PerformanceProfiler) {
+		// Get a physical texture for each slot
+		let slot_textures = self.acquire_textures(compiled.physical_slots());
+		
+		// Resolve the texture of every resource
+		let textures = graph.resources.iter().enumerate().map(|(index, resource)| {
+			match &resource.kind {
+				ResourceKind::Transient => compiled.physical_slot(ResourceId(index)).map(|slot| Rc::clone(&slot_textures[slot])),
+				ResourceKind::Imported(texture) => Some(Rc::clone(texture)),
+				ResourceKind::Backbuffer => None,
+			}
+		}).collect::<Vec<_>>();
+		
+		// Only the gl names of pooled textures are known to stay valid, an imported texture may be
+		// freed and its name reused by another one. So the framebuffers go when the imports change.
+		let imported_textures = graph.resources.iter().filter_map(|resource| match &resource.kind {
+			ResourceKind::Imported(texture) => Some((Rc::clone(texture), texture.borrow().texture_gl())),
+			_ => None,
+		}).collect::<Vec<_>>();
+		let imports_changed = imported_textures.len() != self.imported_textures.len()
+			|| imported_textures.iter().zip(&self.imported_textures).any(|((texture, texture_gl), (last, last_gl))| !Rc::ptr_eq(texture, last) || texture_gl != last_gl);
+		if imports_changed {
+			self.clear_framebuffers();
+			self.imported_textures = imported_textures;
+		}
+		
+		for (order_index, pass_id) in compiled.pass_order().iter().enumerate() {
+			// Issue barriers
+			let barrier_bits = compiled.barriers(order_index).iter().fold(0, |bits, b| bits | b.memory_barrier_bits());
+			if barrier_bits != 0 {
+				unsafe {
+					gl::MemoryBarrier(barrier_bits);
+				}
+			}
+			
+			// Find the framebuffer
+			let attachments = graph.pass(*pass_id).attachments().collect::<Vec<_>>();
+			let size = attachments.first().map_or((0, 0), |(r, _)| (graph.resources[r.0].desc.width, graph.resources[r.0].desc.height));
+			let is_backbuffer = attachments.iter().any(|(r, _)| textures[r.0].is_none());
+			
+			let framebuffer_gl = if attachments.is_empty() || is_backbuffer {
+				0
+			}
+			else {
+				let key = attachments.iter().map(|(r, a)| (Self::attachment_point(*a).as_gl_enum().unwrap(), textures[r.0].as_ref().unwrap().borrow().texture_gl())).collect::<Vec<_>>();
+				
+				self.framebuffer_cache.entry(key).or_insert_with(|| {
+					let mut fbo = Framebuffer::new(size.0, size.1);
+					for (resource, access) in &attachments {
+						fbo.add_attachment(FramebufferAttachment::from_texture(Self::attachment_point(*access), Rc::clone(textures[resource.0].as_ref().unwrap()), 0));
+					}
+					fbo.allocate();
+					fbo
+				}).handle_gl()
+			};
+			
+			unsafe {
+				gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer_gl);
+				if !attachments.is_empty() {
+					gl::Viewport(0, 0, size.0 as gl::sizei, size.1 as gl::sizei);
+				}
+			}
+			
+			// Execute the pass
+			let pass = &mut graph.passes[pass_id.0];
+			if let Some(callback) = pass.callback.as_mut() {
+				let context = RenderPassContext {
+					textures: &textures,
+					framebuffer_gl,
+					size,
+				};
+				
+				profiler.begin_scope(&pass.name);
+				callback(&context);
+				profiler.end_scope();
+			}
+		}
+	}
+	
+	fn attachment_point(access: ResourceAccess) -> AttachmentPoint {
+		match access {
+			ResourceAccess::ColorAttachment(index) => AttachmentPoint::Color(index),
+			ResourceAccess::DepthAttachment => AttachmentPoint::Depth,
+			_ => panic!("Not an attachment"),
+		}
+	}
+	
+	/// Takes a pooled texture for every slot, allocating the missing ones.
+	/// Pooled textures not needed anymore (e.g. after a resize) are freed.
+	fn acquire_textures(&mut self, slots: &[TextureDesc]) -> Vec<Rc<RefCell<Texture>>> {
+		let mut is_taken = vec![false; self.texture_pool.len()];
+		let mut textures = Vec::with_capacity(slots.len());
+		
+		for desc in slots {
+			let pooled = (0..self.texture_pool.len()).find(|&i| !is_taken[i] && self.texture_pool[i].desc == *desc);
+			let texture = match pooled {
+				Some(index) => {
+					is_taken[index] = true;
+					Rc::clone(&self.texture_pool[index].texture)
+				}
+				None => {
+					let mut texture = Texture::new(desc.width, desc.height, 1, desc.image_format);
+					texture.allocate();
+					let texture = Rc::new(RefCell::new(texture));
+					
+					self.texture_pool.push(PooledTexture {desc: *desc, texture: Rc::clone(&texture)});
+					is_taken.push(true);
+					texture
+				}
+			};
+			textures.push(texture);
+		}
+		
+		// Free the textures nobody took
+		if is_taken.iter().any(|t| !*t) {
+			let mut index = 0;
+			self.texture_pool.retain(|pooled| {
+				let keep = is_taken[index];
+				index += 1;
+				
+				if !keep {
+					pooled.texture.borrow_mut().dispose();
+				}
+				keep
+			});
+			
+			// The gl names of freed textures may be reused so the framebuffers have to go
+			self.clear_framebuffers();
+		}
+		
+		textures
+	}
+	
+	fn clear_framebuffers(&mut self) {
+		for (_, mut fbo) in self.framebuffer_cache.drain() {
+			fbo.dispose();
+		}
+	}
+	
+	/// Frees all pooled textures and framebuffers.
+	pub fn dispose(&mut self) {
+		self.clear_framebuffers();
+		self.imported_textures.clear();
+		for pooled in self.texture_pool.drain(..) {
+			pooled.texture.borrow_mut().dispose();
+		}
+	}
+	
+	pub fn new() -> RenderGraphExecutor {
+		RenderGraphExecutor {
+			texture_pool: Vec::new(),
+			framebuffer_cache: HashMap::new(),
+			imported_textures: Vec::new(),
+		}
+	}
+}
@@ -1,7 +1,52 @@
-use crate::render::ReconfigureEvent;
+use cgmath::Rad;
+use crate::asset::AssetCache;
+use crate::render::{GraphicsSetting, ReconfigureEvent, RenderCameraState};
+use crate::render::render_graph::{RenderGraph, ResourceId};
+use crate::render::shader::managed::ManagedProgram;
 
+/// A part of the pipeline that can be plugged into `RenderGlobal`.
 pub trait RenderSubsystem {
+	/// Unique name, used for ordering constraints and to enable/disable the subsystem.
+	fn name(&self) -> &'static str;
+	
 	fn initialize(&mut self);
 	fn deinitialize(&mut self);
 	fn reconfigure(&mut self, event: ReconfigureEvent<'_>);
+	
+	/// Whether a change of the setting needs the subsystem to be reconfigured.
+	fn affected_by(&self, _setting: GraphicsSetting) -> bool {
+		false
+	}
+	
+	/// Called after all cached programs were reloaded from their assets.
+	fn reload_shaders(&mut self, _program_cache: &mut AssetCache<ManagedProgram>) {
+		// Do nothing
+	}
+	
+	/// Names of subsystems whose passes have to be added before ours (if they're registered).
+	fn run_after(&self) -> &[&'static str] {
+		&[]
+	}
+	
+	/// Names of subsystems whose passes have to be added after ours (if they're registered).
+	fn run_before(&self) -> &[&'static str] {
+		&[]
+	}
+	
+	/// Adds the passes of the subsystem for this frame. Passes may replace the shared
+	/// resources in `frame`, e.g. with their post processed version of the scene.
+	fn add_passes<'a>(&'a self, graph: &mut RenderGraph<'a>, frame: &mut FrameResources);
+}
+
+/// Resources and state of the frame that are shared between the subsystems.
+pub struct FrameResources {
+	pub resolution: (u32, u32),
+	/// The lit scene, in linear hdr
+	pub scene_hdr: ResourceId,
+	pub scene_depth: ResourceId,
+	pub camera: RenderCameraState,
+	pub camera_fovy: Rad<f32>,
+	pub camera_depth_planes: (f32, f32),
+	/// Bloom of the scene, added onto it by the post composite
+	pub bloom: Option<ResourceId>,
 }
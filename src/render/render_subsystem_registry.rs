@@ -0,0 +1,200 @@
+use std::error;
+use crate::asset::AssetCache;
+use crate::render::{RenderSubsystem, FrameResources, ReconfigureEvent};
+use crate::render::render_graph::RenderGraph;
+use crate::render::shader::managed::ManagedProgram;
+
+struct RegisteredSubsystem {
+	subsystem: Box<dyn RenderSubsystem>,
+	enabled: bool,
+	initialized: bool,
+}
+
+/// The subsystems of the pipeline, in an order satisfying their `run_after`/`run_before` constraints.
+pub struct RenderSubsystemRegistry {
+	subsystems: Vec<RegisteredSubsystem>,
+	/// Indices into `subsystems`
+	order: Vec<usize>,
+}
+
+impl RenderSubsystemRegistry {
+	/// Adds a subsystem. Fails if the name is taken or the ordering constraints can't be satisfied anymore.
+	/// It isn't initialized until the next `initialize_all`.
+	pub fn register(&mut self, subsystem: Box<dyn RenderSubsystem>, enabled: bool) -> Result<(), Box<dyn error::Error>> {
+		if self.index_of(subsystem.name()).is_some() {
+			return Err(format!("Render subsystem {} is already registered", subsystem.name()).into());
+		}
+		
+		self.subsystems.push(RegisteredSubsystem {
+			subsystem,
+			enabled,
+			initialized: false,
+		});
+		
+		match self.sort() {
+			Ok(order) => {
+				self.order = order;
+				Ok(())
+			}
+			Err(err) => {
+				self.subsystems.pop();
+				Err(err)
+			}
+		}
+	}
+	
+	/// Removes a subsystem, it is deinitialized first if it was initialized.
+	pub fn unregister(&mut self, name: &str) -> Option<Box<dyn RenderSubsystem>> {
+		let index = self.index_of(name)?;
+		let mut registered = self.subsystems.remove(index);
+		if registered.initialized {
+			registered.subsystem.deinitialize();
+		}
+		
+		// Removing a subsystem can't introduce a cycle
+		self.order = self.sort().unwrap();
+		Some(registered.subsystem)
+	}
+	
+	fn index_of(&self, name: &str) -> Option<usize> {
+		self.subsystems.iter().position(|s| s.subsystem.name() == name)
+	}
+	
+	/// Orders the subsystems topologically, earlier registered ones first.
+	fn sort(&self) -> Result<Vec<usize>, Box<dyn error::Error>> {
+		let num_subsystems = self.subsystems.len();
+		
+		// Collect the edges, constraints on subsystems that aren't registered are ignored
+		let mut run_after = vec![Vec::<usize>::new(); num_subsystems];
+		for (index, registered) in self.subsystems.iter().enumerate() {
+			for name in registered.subsystem.run_after() {
+				if let Some(other) = self.index_of(name) {
+					run_after[index].push(other);
+				}
+			}
+			for name in registered.subsystem.run_before() {
+				if let Some(other) = self.index_of(name) {
+					run_after[other].push(index);
+				}
+			}
+		}
+		
+		let mut order = Vec::with_capacity(num_subsystems);
+		let mut is_sorted = vec![false; num_subsystems];
+		while order.len() < num_subsystems {
+			let next = (0..num_subsystems).find(|&i| !is_sorted[i] && run_after[i].iter().all(|&d| is_sorted[d]));
+			match next {
+				Some(index) => {
+					is_sorted[index] = true;
+					order.push(index);
+				}
+				None => {
+					let stuck = (0..num_subsystems).filter(|&i| !is_sorted[i]).map(|i| self.subsystems[i].subsystem.name()).collect::<Vec<_>>();
+					return Err(format!("Render subsystems have cyclic ordering constraints: {}", stuck.join(", ")).into());
+				}
+			}
+		}
+		Ok(order)
+	}
+	
+	/// Names of all subsystems in execution order.
+	pub fn names(&self) -> Vec<&'static str> {
+		self.order.iter().map(|&i| self.subsystems[i].subsystem.name()).collect()
+	}
+	
+	pub fn is_enabled(&self, name: &str) -> Option<bool> {
+		self.index_of(name).map(|i| self.subsystems[i].enabled)
+	}
+	
+	/// Returns false if there is no subsystem with that name.
+	pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+		match self.index_of(name) {
+			Some(index) => {
+				self.subsystems[index].enabled = enabled;
+				true
+			}
+			None => false,
+		}
+	}
+	
+	/// Initializes the subsystems that aren't initialized yet, e.g. the ones registered since the last call.
+	pub fn initialize_all(&mut self) {
+		for &index in &self.order {
+			let registered = &mut self.subsystems[index];
+			if !registered.initialized {
+				registered.subsystem.initialize();
+				registered.initialized = true;
+			}
+		}
+	}
+	
+	pub fn deinitialize_all(&mut self) {
+		for &index in self.order.iter().rev() {
+			let registered = &mut self.subsystems[index];
+			if registered.initialized {
+				registered.subsystem.deinitialize();
+				registered.initialized = false;
+			}
+		}
+	}
+	
+	/// Returns `None` if there is no subsystem with that name.
+	pub fn is_initialized(&self, name: &str) -> Option<bool> {
+		self.index_of(name).map(|i| self.subsystems[i].initialized)
+	}
+	
+	/// Returns false if there is no subsystem with that name.
+	pub fn reconfigure(&mut self, name: &str, event: ReconfigureEvent<'_>) -> bool {
+		match self.index_of(name) {
+			Some(index) => {
+				self.subsystems[index].subsystem.reconfigure(event);
+				true
+			}
+			None => false,
+		}
+	}
+	
+	/// Disabled subsystems are reconfigured too so they're ready when they get enabled.
+	pub fn reconfigure_all(&mut self, event: ReconfigureEvent<'_>) {
+		for &index in &self.order {
+			self.subsystems[index].subsystem.reconfigure(event);
+		}
+	}
+	
+	/// Reconfigures only the subsystems affected by one of the event's changed settings,
+	/// returns their names.
+	pub fn reconfigure_affected(&mut self, event: ReconfigureEvent<'_>) -> Vec<&'static str> {
+		let mut reconfigured = Vec::new();
+		for &index in &self.order {
+			let subsystem = &mut self.subsystems[index].subsystem;
+			if event.changed_settings.iter().any(|&s| subsystem.affected_by(s)) {
+				subsystem.reconfigure(event);
+				reconfigured.push(subsystem.name());
+			}
+		}
+		reconfigured
+	}
+	
+	pub fn reload_shaders_all(&mut self, program_cache: &mut AssetCache<ManagedProgram>) {
+		for &index in &self.order {
+			self.subsystems[index].subsystem.reload_shaders(program_cache);
+		}
+	}
+	
+	/// Adds the passes of all enabled subsystems in order.
+	pub fn add_passes<'a>(&'a self, graph: &mut RenderGraph<'a>, frame: &mut FrameResources) {
+		for &index in &self.order {
+			let registered = &self.subsystems[index];
+			if registered.enabled {
+				registered.subsystem.add_passes(graph, frame);
+			}
+		}
+	}
+	
+	pub fn new() -> RenderSubsystemRegistry {
+		RenderSubsystemRegistry {
+			subsystems: Vec::new(),
+			order: Vec::new(),
+		}
+	}
+}
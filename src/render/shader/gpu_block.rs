@@ -0,0 +1,311 @@
+use cgmath::{Matrix3, Matrix4, Vector2, Vector3, Vector4};
+use crate::render::shader::BlockInfo;
+
+/// Memory layout rules of a uniform or storage block.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BlockLayoutStd {
+	/// Default for uniform blocks, arrays and structs are aligned to 16 bytes
+	Std140,
+	/// Only for storage blocks, arrays and structs are aligned like their members
+	Std430,
+}
+
+/// A type that can be a member of a `gpu_block!` struct.
+pub trait GpuBlockMember {
+	/// Base alignment in bytes
+	fn align(std: BlockLayoutStd) -> usize;
+	
+	/// Size in bytes, without trailing padding up to the next member
+	fn size(std: BlockLayoutStd) -> usize;
+	
+	/// Writes the value into `out`, which is exactly `size(std)` bytes long.
+	fn write(&self, std: BlockLayoutStd, out: &mut [u8]);
+}
+
+/// A struct defined with `gpu_block!`, uploaded as a whole uniform or storage block.
+pub trait GpuBlock: GpuBlockMember {
+	fn layout(std: BlockLayoutStd) -> BlockLayout;
+	
+	/// Returns the value laid out according to `std`, ready to upload.
+	fn to_bytes(&self, std: BlockLayoutStd) -> Vec<u8> {
+		let mut bytes = vec![0u8; Self::layout(std).size];
+		self.write(std, &mut bytes);
+		bytes
+	}
+}
+
+/// Offsets of the members of a block.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct BlockLayout {
+	pub members: Vec<BlockLayoutMember>,
+	pub align: usize,
+	/// Size including the padding at the end
+	pub size: usize,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct BlockLayoutMember {
+	pub name: &'static str,
+	pub offset: usize,
+	pub size: usize,
+}
+
+impl BlockLayout {
+	/// Lays out members given as `(name, align, size)` in order.
+	pub fn compute(std: BlockLayoutStd, members: &[(&'static str, usize, usize)]) -> BlockLayout {
+		let mut layout_members = Vec::with_capacity(members.len());
+		let mut offset = 0;
+		let mut max_align = 1;
+		
+		for (name, align, size) in members {
+			offset = align_up(offset, *align);
+			layout_members.push(BlockLayoutMember {
+				name,
+				offset,
+				size: *size,
+			});
+			offset += size;
+			max_align = max_align.max(*align);
+		}
+		
+		// Structs are aligned like vec4s in std140
+		let align = match std {
+			BlockLayoutStd::Std140 => align_up(max_align, 16),
+			BlockLayoutStd::Std430 => max_align,
+		};
+		
+		BlockLayout {
+			members: layout_members,
+			align,
+			size: align_up(offset, align),
+		}
+	}
+	
+	/// Compares the layout with the layout the driver reflected for the block.
+	/// Structs and arrays are located by their first active element.
+	pub fn check(&self, block: &BlockInfo) -> Result<(), String> {
+		let mut errors = Vec::<String>::new();
+		
+		// Members of blocks with an instance name are reflected as `instance.member`
+		let instance_prefix = block.members.first()
+			.and_then(|m| m.name.find('.').map(|dot| &m.name[..=dot]))
+			.filter(|prefix| !prefix.contains('['))
+			.filter(|prefix| !self.members.iter().any(|m| m.name == &prefix[..(prefix.len() - 1)]))
+			.filter(|prefix| block.members.iter().all(|m| m.name.starts_with(prefix)))
+			.unwrap_or("");
+		
+		for member in &self.members {
+			let reflected = block.members.iter()
+				.filter(|m| is_reflected_member(&m.name[instance_prefix.len()..], member.name))
+				.map(|m| m.offset as usize)
+				.min();
+			
+			match reflected {
+				Some(offset) if offset != member.offset => errors.push(format!("{} is at offset {} but at {} in the shader", member.name, member.offset, offset)),
+				Some(_) => {},
+				// Inactive in this program
+				None => {},
+			}
+		}
+		
+		if (block.data_size as usize) > self.size {
+			errors.push(format!("Block is {} bytes but {} bytes in the shader", self.size, block.data_size));
+		}
+		
+		if errors.is_empty() {
+			Ok(())
+		} else {
+			Err(format!("Layout of block {} doesn't match the shader: {}", block.name, errors.join(", ")))
+		}
+	}
+}
+
+/// Matches `name`, `name[0]`, `name.x` and `name[0].x`.
+fn is_reflected_member(reflected_name: &str, name: &str) -> bool {
+	reflected_name.strip_prefix(name).map_or(false, |rest| rest.is_empty() || rest.starts_with('[') || rest.starts_with('.'))
+}
+
+pub fn align_up(offset: usize, align: usize) -> usize {
+	(offset + align - 1) / align * align
+}
+
+/// Defines a struct that can be uploaded as a uniform or storage block.
+///
+/// ```ignore
+/// gpu_block! {
+/// 	pub struct CameraBlock {
+/// 		pub view: Matrix4<f32>,
+/// 		pub depth_planes: Vector2<f32>,
+/// 	}
+/// }
+/// ```
+///
+/// Members can be any `GpuBlockMember`, including arrays and other `gpu_block!` structs.
+#[macro_export]
+macro_rules! gpu_block {
+	(
+		$(#[$attr:meta])*
+		$vis:vis struct $name:ident {
+			$($(#[$field_attr:meta])* $field_vis:vis $field:ident : $field_type:ty),* $(,)?
+		}
+	) => {
+		$(#[$attr])*
+		$vis struct $name {
+			$($(#[$field_attr])* $field_vis $field: $field_type),*
+		}
+		
+		impl $crate::render::shader::GpuBlockMember for $name {
+			fn align(std: $crate::render::shader::BlockLayoutStd) -> usize {
+				<Self as $crate::render::shader::GpuBlock>::layout(std).align
+			}
+			
+			fn size(std: $crate::render::shader::BlockLayoutStd) -> usize {
+				<Self as $crate::render::shader::GpuBlock>::layout(std).size
+			}
+			
+			fn write(&self, std: $crate::render::shader::BlockLayoutStd, out: &mut [u8]) {
+				let layout = <Self as $crate::render::shader::GpuBlock>::layout(std);
+				let mut members = layout.members.iter();
+				$(
+					let member = members.next().unwrap();
+					$crate::render::shader::GpuBlockMember::write(&self.$field, std, &mut out[member.offset..(member.offset + member.size)]);
+				)*
+			}
+		}
+		
+		impl $crate::render::shader::GpuBlock for $name {
+			fn layout(std: $crate::render::shader::BlockLayoutStd) -> $crate::render::shader::BlockLayout {
+				$crate::render::shader::BlockLayout::compute(std, &[
+					$((
+						stringify!($field),
+						<$field_type as $crate::render::shader::GpuBlockMember>::align(std),
+						<$field_type as $crate::render::shader::GpuBlockMember>::size(std),
+					)),*
+				])
+			}
+		}
+	};
+}
+
+macro_rules! impl_scalar_member {
+	($type:ty) => {
+		impl GpuBlockMember for $type {
+			fn align(_std: BlockLayoutStd) -> usize {4}
+			fn size(_std: BlockLayoutStd) -> usize {4}
+			
+			fn write(&self, _std: BlockLayoutStd, out: &mut [u8]) {
+				out.copy_from_slice(&self.to_le_bytes());
+			}
+		}
+	};
+}
+
+impl_scalar_member!(f32);
+impl_scalar_member!(i32);
+impl_scalar_member!(u32);
+
+/// Bools are 4 bytes in glsl
+impl GpuBlockMember for bool {
+	fn align(_std: BlockLayoutStd) -> usize {4}
+	fn size(_std: BlockLayoutStd) -> usize {4}
+	
+	fn write(&self, std: BlockLayoutStd, out: &mut [u8]) {
+		(*self as u32).write(std, out);
+	}
+}
+
+macro_rules! impl_vector_member {
+	($type:ident, $num_components:expr, $align:expr, [$($component:ident),*]) => {
+		impl<T: GpuBlockMember> GpuBlockMember for $type<T> {
+			fn align(std: BlockLayoutStd) -> usize {$align * T::align(std)}
+			fn size(std: BlockLayoutStd) -> usize {$num_components * T::size(std)}
+			
+			fn write(&self, std: BlockLayoutStd, out: &mut [u8]) {
+				let component_size = T::size(std);
+				for (i, component) in [$(&self.$component),*].iter().enumerate() {
+					component.write(std, &mut out[(i * component_size)..((i + 1) * component_size)]);
+				}
+			}
+		}
+	};
+}
+
+// vec3 is aligned like a vec4
+impl_vector_member!(Vector2, 2, 2, [x, y]);
+impl_vector_member!(Vector3, 3, 4, [x, y, z]);
+impl_vector_member!(Vector4, 4, 4, [x, y, z, w]);
+
+/// Matrices are laid out as arrays of column vectors, so their columns are
+/// aligned like vec4s in both layouts (there is no mat3 packing).
+impl GpuBlockMember for Matrix3<f32> {
+	fn align(_std: BlockLayoutStd) -> usize {16}
+	fn size(_std: BlockLayoutStd) -> usize {3 * 16}
+	
+	fn write(&self, std: BlockLayoutStd, out: &mut [u8]) {
+		for (i, column) in [self.x, self.y, self.z].iter().enumerate() {
+			column.write(std, &mut out[(i * 16)..(i * 16 + 12)]);
+		}
+	}
+}
+
+impl GpuBlockMember for Matrix4<f32> {
+	fn align(_std: BlockLayoutStd) -> usize {16}
+	fn size(_std: BlockLayoutStd) -> usize {4 * 16}
+	
+	fn write(&self, std: BlockLayoutStd, out: &mut [u8]) {
+		for (i, column) in [self.x, self.y, self.z, self.w].iter().enumerate() {
+			column.write(std, &mut out[(i * 16)..((i + 1) * 16)]);
+		}
+	}
+}
+
+/// Distance between two elements of an array of `T`.
+pub fn array_stride<T: GpuBlockMember>(std: BlockLayoutStd) -> usize {
+	let align = array_align::<T>(std);
+	align_up(T::size(std), align)
+}
+
+fn array_align<T: GpuBlockMember>(std: BlockLayoutStd) -> usize {
+	match std {
+		BlockLayoutStd::Std140 => align_up(T::align(std), 16),
+		BlockLayoutStd::Std430 => T::align(std),
+	}
+}
+
+macro_rules! impl_array_member {
+	($($len:expr),*) => {
+		$(
+			impl<T: GpuBlockMember> GpuBlockMember for [T; $len] {
+				fn align(std: BlockLayoutStd) -> usize {
+					array_align::<T>(std)
+				}
+				
+				fn size(std: BlockLayoutStd) -> usize {
+					array_stride::<T>(std) * $len
+				}
+				
+				fn write(&self, std: BlockLayoutStd, out: &mut [u8]) {
+					let stride = array_stride::<T>(std);
+					let element_size = T::size(std);
+					for (i, element) in self.iter().enumerate() {
+						element.write(std, &mut out[(i * stride)..(i * stride + element_size)]);
+					}
+				}
+			}
+		)*
+	};
+}
+
+impl_array_member!(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 24, 32, 48, 64, 128, 256, 512, 1024);
+
+/// Lays out the elements like an array member of a block, e.g. for the runtime sized array of a storage block.
+pub fn array_to_bytes<T: GpuBlockMember>(elements: &[T], std: BlockLayoutStd) -> Vec<u8> {
+	let stride = array_stride::<T>(std);
+	let element_size = T::size(std);
+	
+	let mut bytes = vec![0u8; stride * elements.len()];
+	for (i, element) in elements.iter().enumerate() {
+		element.write(std, &mut bytes[(i * stride)..(i * stride + element_size)]);
+	}
+	bytes
+}
@@ -0,0 +1,104 @@
+use crate::render::shader::{ShaderProgram, Shader, ShaderCode, ShaderStage, ShaderCompileOptions, ShaderCompileStatus, ProgramLinkOptions, ProgramLinkStatus};
+
+/// Covers the screen with a triangle per three vertices so it shows up
+/// for mesh draws and fullscreen passes without a vertex buffer alike.
+/// The depth is in the middle so it passes the reverse-z depth test.
+const ERROR_VERTEX_SOURCE: &str = "#version 430 core
+void main() {
+	int index = gl_VertexID % 3;
+	vec2 position = vec2((index == 1) ? 3.0 : -1.0, (index == 2) ? 3.0 : -1.0);
+	gl_Position = vec4(position, 0.5, 1.0);
+}
+";
+
+const ERROR_TESS_CONTROL_SOURCE: &str = "#version 430 core
+layout(vertices = 3) out;
+void main() {
+	gl_out[gl_InvocationID].gl_Position = gl_in[gl_InvocationID].gl_Position;
+	gl_TessLevelOuter[0] = 1.0;
+	gl_TessLevelOuter[1] = 1.0;
+	gl_TessLevelOuter[2] = 1.0;
+	gl_TessLevelInner[0] = 1.0;
+}
+";
+
+const ERROR_TESS_EVAL_SOURCE: &str = "#version 430 core
+layout(triangles) in;
+void main() {
+	gl_Position = gl_TessCoord.x * gl_in[0].gl_Position + gl_TessCoord.y * gl_in[1].gl_Position + gl_TessCoord.z * gl_in[2].gl_Position;
+}
+";
+
+const ERROR_GEOMETRY_SOURCE: &str = "#version 430 core
+layout(triangles) in;
+layout(triangle_strip, max_vertices = 3) out;
+void main() {
+	for (int i = 0; i < 3; i++) {
+		gl_Position = gl_in[i].gl_Position;
+		EmitVertex();
+	}
+	EndPrimitive();
+}
+";
+
+const ERROR_FRAGMENT_SOURCE: &str = "#version 430 core
+layout(location = 0) out vec4 outColor;
+void main() {
+	outColor = vec4(1.0, 0.0, 1.0, 1.0);
+}
+";
+
+/// Does nothing, there is nothing to see for compute
+const ERROR_COMPUTE_SOURCE: &str = "#version 430 core
+layout(local_size_x = 1) in;
+void main() {}
+";
+
+/// Builds the magenta program used in place of a program that never compiled.
+///
+/// It has the same stages as the broken program (tessellation stages are just passed
+/// through) so it can be drawn with the same primitive type, e.g. `PATCHES`.
+pub fn build_error_program(stages: &[ShaderStage]) -> ShaderProgram {
+	let mut program = ShaderProgram::new();
+	
+	// Compute programs can't have any other stage
+	let stages = if stages.contains(&ShaderStage::Compute) {
+		vec![ShaderStage::Compute]
+	}
+	else {
+		let mut stages = Vec::from(stages);
+		for required_stage in &[ShaderStage::Vertex, ShaderStage::Fragment] {
+			if !stages.contains(required_stage) {
+				stages.push(*required_stage);
+			}
+		}
+		stages
+	};
+	
+	for stage in stages {
+		let source = match stage {
+			ShaderStage::Vertex => ERROR_VERTEX_SOURCE,
+			ShaderStage::TessellationControl => ERROR_TESS_CONTROL_SOURCE,
+			ShaderStage::TessellationEval => ERROR_TESS_EVAL_SOURCE,
+			ShaderStage::Geometry => ERROR_GEOMETRY_SOURCE,
+			ShaderStage::Fragment => ERROR_FRAGMENT_SOURCE,
+			ShaderStage::Compute => ERROR_COMPUTE_SOURCE,
+		};
+		
+		let mut shader = Shader::new(stage);
+		shader.attach_source(ShaderCode::new(Vec::from(source.as_bytes())));
+		
+		let compile_result = shader.compile(&ShaderCompileOptions::default());
+		if compile_result.status != ShaderCompileStatus::Success {
+			println!("Built-in error shader failed to compile ({:?}):\n{}", stage, compile_result.info_log.as_deref().unwrap_or("<< no infolog >>"));
+		}
+		program.attach_shader(shader);
+	}
+	
+	let link_result = program.link(&ProgramLinkOptions::default());
+	if link_result.status != ProgramLinkStatus::Success {
+		println!("Built-in error program failed to link:\n{}", link_result.info_log.as_deref().unwrap_or("<< no infolog >>"));
+	}
+	
+	program
+}
@@ -1,25 +1,130 @@
 use std::error;
-use std::fs::{OpenOptions};
-use std::io::{self, Read};
-use crate::asset::{AssetPathBuf, AssetPath, ASSET_MANAGER_INSTANCE};
+use std::collections::HashMap;
+use crate::asset::{AssetPathBuf, AssetPath, ASSET_MANAGER_INSTANCE, AssetLoader, AssetLoadError};
 use crate::render::shader::{ShaderProgram, Shader, ShaderCode, ProgramLinkOptions, ShaderStage, ShaderCompileOptions, ShaderCompileStatus, ProgramLinkStatus};
-use crate::render::shader::managed::ProgramAssetSchema;
-use crate::structured_shader_language::{SSLSourceParser, ParsedSource, SSLTranspiler};
+use crate::render::shader::managed::{PermutationLayout, PermutationKey, TranspiledProgram, PROGRAM_BINARY_CACHE_INSTANCE, ProgramBuildResult, ProgramBuildStatus, ShaderBuildResult, build_error_program};
+use crate::structured_shader_language::{SourceMap, TranspiledShaderSource};
 
 pub struct ManagedProgram {
 	program_asset_path: Option<AssetPathBuf>,
+	/// Leave out exported include functions the shaders never call
+	strip_unused_functions: bool,
+	/// Load variants from the program binary cache if possible instead of compiling them
+	use_binary_cache: bool,
 	
+	permutation_layout: PermutationLayout,
+	/// Transpiled source of every stage, shared by all variants (they only differ in the injected defines)
+	transpiled_shaders: Vec<(ShaderStage, TranspiledShaderSource)>,
+	/// Stages listed in the `.program` file, known even if their sources never loaded
+	stages: Vec<ShaderStage>,
+	/// Set while the sources fail to load, without earlier sources no variant can be built until they do
+	source_build_result: Option<ProgramBuildResult>,
+	
+	variants: HashMap<PermutationKey, ProgramVariant>,
+	needs_recompile: bool,
+	
+	/// Magenta program used for variants that never built successfully
+	error_program: Option<ShaderProgram>,
+}
+
+/// One permutation of a program
+struct ProgramVariant {
+	/// Final source of every stage with the permutation defines injected
+	sources: Vec<(ShaderStage, Vec<u8>)>,
+	/// Source maps of the variant's shaders, used to make the compile logs point to the ssl files
+	source_maps: Vec<(ShaderStage, SourceMap)>,
+	
+	/// The last program that built successfully, kept when a rebuild fails
 	program_object: Option<ShaderProgram>,
+	last_build_result: Option<ProgramBuildResult>,
 	needs_recompile: bool,
 }
 
 impl ManagedProgram {
+	/// The variant with all permutations at their default values.
+	///
+	/// This is the last program that built successfully, or the
+	/// magenta error program if it never did. `None` before the first `do_recompile`.
 	pub fn program(&self) -> Option<&ShaderProgram> {
-		self.program_object.as_ref()
+		self.cached_variant(self.permutation_layout.default_key())
 	}
 	
+	/// The last successfully built program of the default variant.
 	pub fn program_mut(&mut self) -> Option<&mut ShaderProgram> {
-		self.program_object.as_mut()
+		let default_key = self.permutation_layout.default_key();
+		self.variants.get_mut(&default_key).and_then(|v| v.program_object.as_mut())
+	}
+	
+	/// Sets if exported include functions the shaders never call are left out (on by default),
+	/// takes effect on the next `reload_from_asset`. Turn it off to see the full include sources.
+	pub fn set_strip_unused_functions(&mut self, strip: bool) {
+		self.strip_unused_functions = strip;
+	}
+	
+	/// Sets if variants are loaded from the program binary cache when possible (on by default).
+	/// Turn it off to always compile from source.
+	pub fn set_use_binary_cache(&mut self, use_binary_cache: bool) {
+		self.use_binary_cache = use_binary_cache;
+	}
+	
+	pub fn permutation_layout(&self) -> &PermutationLayout {
+		&self.permutation_layout
+	}
+	
+	/// Returns the variant for `key`, building and compiling it first if needed.
+	/// Falls back to the error program if the variant never built successfully.
+	pub fn program_variant(&mut self, key: PermutationKey) -> &ShaderProgram {
+		if !self.variants.contains_key(&key) && self.has_sources() {
+			let variant = self.build_variant(key);
+			self.variants.insert(key, variant);
+		}
+		
+		if let Some(variant) = self.variants.get_mut(&key) {
+			if variant.needs_recompile {
+				variant.compile(self.use_binary_cache);
+			}
+		}
+		self.ensure_error_program();
+		
+		self.cached_variant(key).unwrap()
+	}
+	
+	/// Returns the variant for `key` if it was built already (or the error program if it failed to,
+	/// or if the sources never loaded).
+	pub fn cached_variant(&self, key: PermutationKey) -> Option<&ShaderProgram> {
+		if self.is_using_error_program(key) {
+			return self.error_program.as_ref();
+		}
+		self.variants.get(&key)?.program_object.as_ref()
+	}
+	
+	/// Result of the last build of the variant, or the `SourceError` if the current sources
+	/// failed to load. `None` if it wasn't built yet.
+	pub fn build_result(&self, key: PermutationKey) -> Option<&ProgramBuildResult> {
+		self.source_build_result.as_ref().or_else(|| self.variants.get(&key)?.last_build_result.as_ref())
+	}
+	
+	/// Result of the last build of the default variant.
+	pub fn last_build_result(&self) -> Option<&ProgramBuildResult> {
+		self.build_result(self.permutation_layout.default_key())
+	}
+	
+	/// Whether the variant is drawn with the error program because it never built successfully,
+	/// or because the sources never loaded so it can't be built at all.
+	pub fn is_using_error_program(&self, key: PermutationKey) -> bool {
+		match self.variants.get(&key) {
+			Some(variant) => variant.last_build_result.is_some() && variant.program_object.is_none(),
+			None => !self.has_sources() && self.source_build_result.is_some(),
+		}
+	}
+	
+	/// Whether the sources loaded at least once, variants can only be built from those.
+	pub fn has_sources(&self) -> bool {
+		!self.transpiled_shaders.is_empty()
+	}
+	
+	pub fn num_cached_variants(&self) -> usize {
+		self.variants.len()
 	}
 	
 	pub fn program_asset_path<'a>(&'a self) -> Option<AssetPath<'a>> {
@@ -33,12 +138,19 @@ impl ManagedProgram {
 	
 	pub fn mark_recompile_needed(&mut self) {
 		self.needs_recompile = true;
+		for variant in self.variants.values_mut() {
+			variant.needs_recompile = true;
+		}
 	}
 	
 	pub fn needs_recompile(&self) -> bool {
 		self.needs_recompile
 	}
 	
+	pub fn source_map(&self, key: PermutationKey, stage: ShaderStage) -> Option<&SourceMap> {
+		self.variants.get(&key)?.source_maps.iter().find(|(s, _)| *s == stage).map(|(_, m)| m)
+	}
+	
 //	pub fn reload(&mut self) {
 //		// Create program
 //		if let None = self.program_object {
@@ -51,139 +163,105 @@ impl ManagedProgram {
 //		}
 //	}
 	
+	/// (Re-)compiles all cached variants, building the default variant if it doesn't exist yet.
 	pub fn do_recompile(&mut self) {
-		let program = self.program_object.get_or_insert_with(|| ShaderProgram::new());
-		
-		// (Re-)compile all shaders
-		for stage in ShaderStage::stages() {
-			if let Some(shader) = program.attached_shader_mut(stage) {
-				let mut compile_options = ShaderCompileOptions::default();
-				
-				let compile_result = shader.compile(compile_options.with_info_log(false, true));
-				
-				match compile_result.status {
-					ShaderCompileStatus::Success => {},
-					_ => {
-						println!("Shader compile failed (status {:?}):", compile_result.status);
-						println!("{}", compile_result.info_log.as_deref().unwrap_or("<< no infolog >>"));
-					}
-				}
-			}
+		let default_key = self.permutation_layout.default_key();
+		if !self.variants.contains_key(&default_key) && self.has_sources() {
+			let variant = self.build_variant(default_key);
+			self.variants.insert(default_key, variant);
 		}
 		
-		// Link the program
-		let link_result = program.link(ProgramLinkOptions::default().with_info_log(false, true));
-		match link_result.status {
-			ProgramLinkStatus::Success => {},
-			_ => {
-				println!("Program link failed (status {:?}):", link_result.status);
-				println!("{}", link_result.info_log.as_deref().unwrap_or("<< no infolog >>"));
+		for variant in self.variants.values_mut() {
+			if variant.needs_recompile {
+				variant.compile(self.use_binary_cache);
 			}
 		}
-	
+		self.ensure_error_program();
+		
 		// Reset flag
 		self.needs_recompile = false;
 	}
 	
-	pub fn reload_from_asset(&mut self) -> Result<(), Box<dyn error::Error>> {
-		// Open the program asset
-		let (program_asset_contents, program_asset_path) = {
-			let asset_path = self.program_asset_path.as_ref().unwrap().as_path();
-			
-			// Resolve the actual file path
-			let real_path = unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(&asset_path);
-			
-			// Read the program asset to string
-			let mut file = OpenOptions::new().read(true).open(real_path)?;
-			let mut buffer = String::new();
-			file.read_to_string(&mut buffer)?;
-			
-			(buffer, asset_path)
-		};
+	/// Builds the error program once any variant needs it.
+	fn ensure_error_program(&mut self) {
+		if self.error_program.is_some() {
+			return;
+		}
 		
-		// Deserialize the program def
-		let program_def = ron::de::from_str::<ProgramAssetSchema::ProgramDef>(&program_asset_contents)?;
+		let needs_error_program = self.variants.values().any(|v| v.last_build_result.is_some() && v.program_object.is_none())
+			|| (!self.has_sources() && self.source_build_result.is_some());
+		if needs_error_program {
+			self.error_program = Some(build_error_program(&self.stages));
+		}
+	}
+	
+	/// Creates a variant with the permutation defines injected into every shader, it is compiled lazily.
+	fn build_variant(&self, key: PermutationKey) -> ProgramVariant {
+		let defines = self.permutation_layout.defines(key);
 		
-		// Setup the ssl source parser
-		let mut source_parser = SSLSourceParser::new();
+		let mut sources = Vec::new();
+		let mut source_maps = Vec::new();
 		
-		fn load_asset_as_str(asset_path: &AssetPath, base_program_path: &AssetPath) -> Result<String, io::Error> {
-			// Relativize the asset path (if not absolute)
-			let joined_path_buf: AssetPathBuf;
-			let joined_path: AssetPath;
-			
-			let real_asset_path = if asset_path.is_absolute() {
-				asset_path
-			} else {
-				joined_path_buf = base_program_path.parent().unwrap().join(asset_path).unwrap();
-				joined_path = joined_path_buf.as_path();
-				&joined_path
-			};
-			
-			// Resolve the file path
-			let file_path = unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(real_asset_path);
+		for (stage, transpiled_code) in &self.transpiled_shaders {
+			let variant_code = transpiled_code.with_defines(&defines);
 			
-//			// DEBUG: Print file path of ssl source
-//			println!("Loading source file {:?}", file_path);
-			
-			// Read the file to buffer
-			let mut file = OpenOptions::new().read(true).open(file_path.as_path())?;
-			
-			let mut buffer = String::new();
-			file.read_to_string(&mut buffer)?;
+			sources.push((*stage, Vec::from(variant_code.source_code.as_bytes())));
+			source_maps.push((*stage, variant_code.source_map));
+		}
+		
+		ProgramVariant {
+			sources,
+			source_maps,
 			
-			Ok(buffer)
+			program_object: None,
+			last_build_result: None,
+			needs_recompile: true,
+		}
+	}
+	
+	pub fn reload_from_asset(&mut self) -> Result<(), Box<dyn error::Error>> {
+		let program_asset_path = self.program_asset_path.clone().unwrap();
+		let program_asset_path = program_asset_path.as_path();
+		
+		// Transpile all stages, the current sources are kept if that fails
+		let transpiled_program = match TranspiledProgram::load(&program_asset_path, self.strip_unused_functions) {
+			Ok(transpiled_program) => transpiled_program,
+			Err(err) => {
+				self.record_source_error(&program_asset_path, &*err);
+				return Err(err);
+			}
 		};
+		let TranspiledProgram {permutation_layout, shaders: transpiled_shaders, dependencies, ..} = transpiled_program;
 		
-		// Parse the includes
-//		let mut parsed_includes = HashMap::<String, ParsedSource>::new();
-		let mut parsed_includes = Vec::<ParsedSource>::new();
+		// Record dependencies
+		unsafe {&mut ASSET_MANAGER_INSTANCE}.record_dependencies(&program_asset_path, &dependencies);
 		
-//		if let Some(include_defs) = &program_def.includes {
-			for include_path in &program_def.includes /*include_defs*/ {
-				let asset_path = AssetPathBuf::from(include_path.as_str());
-				let source_code = load_asset_as_str(&asset_path.as_path(), &program_asset_path)?;
-				
-				let parsed_source = source_parser.parse_source(source_code);
-//				parsed_includes.insert(String::from(&parsed_source.namespace), parsed_source);
-				parsed_includes.push(parsed_source);
-			}
-//		}
+		// Replace the sources and rebuild all variants that were in use, they are compiled on the next recompile
+		let layout_changed = self.permutation_layout != permutation_layout;
+		let old_default_key = self.permutation_layout.default_key();
+		let mut old_variants = std::mem::replace(&mut self.variants, HashMap::new());
 		
-		// Create program object
-		let program = {
-			self.program_object = Some(ShaderProgram::new());
-			self.program_object.as_mut().unwrap() // SAFETY: Is always Some because it's replaced by the line above
-		};
+		self.permutation_layout = permutation_layout;
+		self.stages = transpiled_shaders.iter().map(|(stage, _)| *stage).collect();
+		self.transpiled_shaders = transpiled_shaders;
+		self.source_build_result = None;
 		
-		// Load the shaders
-		for shader_def in &program_def.shaders {
-			// Load the referenced code
-			let asset_path = AssetPathBuf::from(&shader_def.source);
-			let source_code = load_asset_as_str(&asset_path.as_path(), &program_asset_path)?;
-			
-			let parsed_source = source_parser.parse_source(source_code);
-			
-			// Setup the transpiler
-			let mut transpiler = SSLTranspiler::new();
-			
-			// Add the includes
-			for include in &parsed_includes {
-				transpiler.add_include(include);
-			}
-			
-			// Transpile the shader
-			let transpiled_code = transpiler.transpile(&parsed_source);
-			
-			// Create the shader object
-			let mut shader = Shader::new(shader_def.stage.as_engine_stage_enum());
-			
-			let shader_source = ShaderCode::new(Vec::from(transpiled_code.source_code.as_bytes()));
-			shader.attach_source(shader_source);
-			
-			// Attach shader
-			program.attach_shader(shader);
+		// Keep the last good programs in case the new sources don't compile.
+		// Keys of the old layout mean something different in a changed layout, so only the default variant survives that.
+		if layout_changed {
+			old_variants = old_variants.remove(&old_default_key)
+				.map(|v| (self.permutation_layout.default_key(), v))
+				.into_iter()
+				.collect();
 		}
+		for (key, old_variant) in old_variants {
+			let mut variant = self.build_variant(key);
+			variant.program_object = old_variant.program_object;
+			self.variants.insert(key, variant);
+		}
+		
+		// The stages might have changed
+		self.error_program = None;
 		
 		// Mark recompile needed
 		self.mark_recompile_needed();
@@ -192,12 +270,52 @@ impl ManagedProgram {
 		Ok(())
 	}
 	
+	/// Records why the sources failed to load as the build result. Without sources from an earlier load
+	/// the error program is used, with the stages from the `.program` file if that can still be read.
+	fn record_source_error(&mut self, program_asset_path: &AssetPath, err: &dyn error::Error) {
+		let program_def = TranspiledProgram::load_def(program_asset_path).ok();
+		
+		// Depend on the files the program lists and the ones it was built from before, so fixing them reloads it
+		let asset_manager = unsafe {&mut ASSET_MANAGER_INSTANCE};
+		let mut dependencies = asset_manager.dependency_graph().dependencies_of(program_asset_path);
+		if let Some(program_def) = &program_def {
+			let listed_paths = program_def.includes.iter().chain(program_def.shaders.iter().map(|s| &s.source));
+			for path in listed_paths {
+				let path = TranspiledProgram::resolve_source_path(&AssetPath::from_str(path), program_asset_path);
+				if !dependencies.contains(&path) {
+					dependencies.push(path);
+				}
+			}
+		}
+		asset_manager.record_dependencies(program_asset_path, &dependencies);
+		
+		// The stages might have changed
+		if !self.has_sources() {
+			self.stages = program_def.map_or(Vec::new(), |d| d.shaders.iter().map(|s| s.stage.as_engine_stage_enum()).collect());
+			self.error_program = None;
+		}
+		
+		let mut build_result = ProgramBuildResult::new(ProgramBuildStatus::SourceError);
+		build_result.source_error = Some(err.to_string());
+		self.source_build_result = Some(build_result);
+		self.needs_recompile = true;
+	}
+	
 	pub fn new(program_asset_path: Option<AssetPathBuf>) -> Self {
 		Self {
 			program_asset_path,
+			strip_unused_functions: true,
+			use_binary_cache: true,
 			
-			program_object: None,
+			permutation_layout: PermutationLayout::new(),
+			transpiled_shaders: Vec::new(),
+			stages: Vec::new(),
+			source_build_result: None,
+			
+			variants: HashMap::new(),
 			needs_recompile: false,
+			
+			error_program: None,
 		}
 	}
 	
@@ -218,3 +336,143 @@ impl ManagedProgram {
 //		program
 //	}
 }
+
+impl ProgramVariant {
+	/// Builds a new program from the sources. It only replaces the current program
+	/// if it built successfully, so a typo while editing a shader doesn't break anything.
+	fn compile(&mut self, use_binary_cache: bool) {
+		let binary_cache = unsafe {&mut PROGRAM_BINARY_CACHE_INSTANCE};
+		let use_binary_cache = use_binary_cache && binary_cache.is_enabled();
+		
+		// Key the binary by the final source of every stage
+		let binary_key = {
+			let sources = self.sources.iter()
+				.map(|(stage, source)| (*stage, source.as_slice()))
+				.collect::<Vec<_>>();
+			binary_cache.key(&sources)
+		};
+		
+		// Reset flag
+		self.needs_recompile = false;
+		
+		let mut program = ShaderProgram::new();
+		
+		// Try the cached binary first
+		if use_binary_cache {
+			if let Some((format, binary)) = binary_cache.load(binary_key) {
+				if program.load_binary(format, &binary) {
+					self.finish_build(program, ProgramBuildResult::new(ProgramBuildStatus::LoadedFromCache));
+					return;
+				}
+				
+				// The driver rejected the binary, compile from source and replace it
+				binary_cache.remove(binary_key);
+			}
+		}
+		
+		let mut build_result = ProgramBuildResult::new(ProgramBuildStatus::Success);
+		
+		// Compile all shaders
+		for (stage, source) in &self.sources {
+			let mut shader = Shader::new(*stage);
+			shader.attach_source(ShaderCode::new(source.clone()));
+			
+			let mut compile_options = ShaderCompileOptions::default();
+			let compile_result = shader.compile(compile_options.with_info_log(false, true));
+			
+			// Make the log point to the ssl files instead of the transpiled source
+			let info_log = match (compile_result.info_log, self.source_maps.iter().find(|(s, _)| s == stage)) {
+				(Some(info_log), Some((_, source_map))) => Some(source_map.rewrite_info_log(&info_log)),
+				(info_log, _) => info_log,
+			};
+			
+			if compile_result.status != ShaderCompileStatus::Success {
+				build_result.status = ProgramBuildStatus::CompileError;
+			}
+			build_result.shaders.push(ShaderBuildResult {
+				stage: *stage,
+				status: compile_result.status,
+				info_log,
+			});
+			
+			program.attach_shader(shader);
+		}
+		
+		// Link the program
+		if build_result.status == ProgramBuildStatus::Success {
+			let link_result = program.link(ProgramLinkOptions::default().with_info_log(false, true).with_binary_retrievable(use_binary_cache));
+			
+			if link_result.status == ProgramLinkStatus::Success {
+				// Cache the binary for the next run
+				if use_binary_cache {
+					if let Some((format, binary)) = program.get_binary() {
+						binary_cache.store(binary_key, format, &binary);
+					}
+				}
+			}
+			else {
+				build_result.status = ProgramBuildStatus::LinkError;
+			}
+			build_result.link_status = Some(link_result.status);
+			build_result.link_info_log = link_result.info_log;
+		}
+		
+		self.finish_build(program, build_result);
+	}
+	
+	fn finish_build(&mut self, program: ShaderProgram, build_result: ProgramBuildResult) {
+		if build_result.is_success() {
+			self.program_object = Some(program);
+		}
+		else {
+			println!("{}", build_result);
+			if self.program_object.is_some() {
+				println!("Keeping the last working program");
+			}
+			else {
+				println!("Using the error program instead");
+			}
+		}
+		
+		self.last_build_result = Some(build_result);
+	}
+}
+
+/// Loads `.program` assets for the program `AssetCache`.
+/// The program is only transpiled here, compiling happens lazily on first use.
+pub struct ProgramLoader {
+	/// Leave out exported include functions the shaders never call, see `ssl_transpile --keep-unused`
+	pub strip_unused_functions: bool,
+	/// Load programs from the program binary cache instead of compiling them if possible
+	pub use_binary_cache: bool,
+}
+
+impl ProgramLoader {
+	pub fn new() -> Self {
+		Self {
+			strip_unused_functions: true,
+			use_binary_cache: true,
+		}
+	}
+}
+
+impl AssetLoader<ManagedProgram> for ProgramLoader {
+	fn load(&self, path: &AssetPath) -> Result<ManagedProgram, AssetLoadError> {
+		let mut program = ManagedProgram::new(Some(AssetPathBuf::from(path.inner_path_slice)));
+		program.set_strip_unused_functions(self.strip_unused_functions);
+		program.set_use_binary_cache(self.use_binary_cache);
+		
+		// Keep the program even if its sources are broken, it's drawn with the error program until they are fixed
+		if let Err(err) = program.reload_from_asset() {
+			println!("Failed to load program {:?}: {}", path, err);
+		}
+		Ok(program)
+	}
+	
+	/// Reloads in place so the last working programs survive broken sources.
+	fn reload(&self, path: &AssetPath, program: &mut ManagedProgram) -> Result<(), AssetLoadError> {
+		program.set_strip_unused_functions(self.strip_unused_functions);
+		program.set_use_binary_cache(self.use_binary_cache);
+		program.reload_from_asset().map_err(|e| AssetLoadError::from(format!("Failed to reload program {:?}: {}", path, e)))
+	}
+}
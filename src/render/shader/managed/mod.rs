@@ -1,2 +1,7 @@
 mod managed_program; pub use managed_program::*;
 mod program_asset_schema; pub use program_asset_schema::*;
+mod program_permutation; pub use program_permutation::*;
+mod transpiled_program; pub use transpiled_program::*;
+mod program_binary_cache; pub use program_binary_cache::*;
+mod program_build_result; pub use program_build_result::*;
+mod error_program; pub use error_program::*;
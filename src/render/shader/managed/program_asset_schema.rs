@@ -11,6 +11,8 @@ pub mod ProgramAssetSchema {
 		
 		#[serde(default = "ProgramDef::default_includes")]
 		pub includes: Vec<String>,
+		#[serde(default = "ProgramDef::default_permutations")]
+		pub permutations: Vec<self::PermutationDef>,
 		pub shaders: Vec<self::ShaderDef>,
 	}
 	
@@ -18,6 +20,29 @@ pub mod ProgramAssetSchema {
 		pub fn default_includes() -> Vec<String> {
 			Vec::new()
 		}
+		
+		pub fn default_permutations() -> Vec<PermutationDef> {
+			Vec::new()
+		}
+	}
+	
+	/// A `#define` the program can be compiled with different values of.
+	/// Without explicit values it's a flag that's either `0` or `1`.
+	#[derive(Deserialize)]
+	#[serde(rename = "Permutation")]
+	pub struct PermutationDef {
+		pub name: String,
+		#[serde(default = "PermutationDef::default_values")]
+		pub values: Vec<String>,
+		/// Value used unless another one is requested, the first value if not given
+		#[serde(default)]
+		pub default: Option<String>,
+	}
+	
+	impl PermutationDef {
+		pub fn default_values() -> Vec<String> {
+			vec![String::from("0"), String::from("1")]
+		}
 	}
 	
 	#[derive(Deserialize)]
@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+use std::error;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use gl_bindings::gl;
+use serde::{Serialize, Deserialize};
+use crate::asset::hash_content;
+use crate::render::shader::ShaderStage;
+
+pub static mut PROGRAM_BINARY_CACHE_INSTANCE: ProgramBinaryCache = ProgramBinaryCache::new();
+
+/// File name of the cache index, relative to the cache directory
+const INDEX_FILE_NAME: &str = "index.ron";
+
+/// Once the cache grows above this, entries are evicted least recently used first
+pub const MAX_CACHE_SIZE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Entries not used for this long are evicted (mostly binaries of old driver versions)
+pub const MAX_ENTRY_AGE_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Magic at the start of every binary file
+const BINARY_FILE_MAGIC: &[u8; 4] = b"GLPB";
+
+pub type ProgramBinaryKey = u64;
+
+/// On-disk cache of linked program binaries (`glGetProgramBinary`/`glProgramBinary`).
+///
+/// Entries are keyed by a hash of the driver vendor/renderer/version and the final
+/// sources of all stages (so permutation defines are included). Binaries are only
+/// a hint though, the driver may still reject them, in which case the program is
+/// compiled from source and the entry replaced.
+pub struct ProgramBinaryCache {
+	cache_dir: Option<PathBuf>,
+	/// Identifies the driver, binaries of other drivers (or driver versions) can't be loaded
+	driver_id: String,
+	index: Option<ProgramBinaryCacheIndex>,
+	index_dirty: bool,
+	
+	max_size_bytes: u64,
+	max_entry_age_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename = "ProgramBinaryCacheIndex")]
+struct ProgramBinaryCacheIndex {
+	/// Entries by file name
+	entries: BTreeMap<String, CacheEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CacheEntry {
+	size: u64,
+	/// Unix time in seconds
+	last_used: u64,
+}
+
+impl ProgramBinaryCache {
+	/// Enables the cache. Has to be called with a current gl context as the cache stays
+	/// disabled if the driver doesn't support any binary formats.
+	pub fn init(&mut self, cache_dir: PathBuf, driver_info: &[&str]) {
+		let num_binary_formats = unsafe {
+			let mut num_formats: gl::int = 0;
+			gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut num_formats);
+			num_formats
+		};
+		if num_binary_formats <= 0 {
+			println!("Driver doesn't support program binaries, program binary cache disabled");
+			return;
+		}
+		
+		if let Err(err) = self.open(cache_dir.clone(), driver_info) {
+			println!("Failed to create program binary cache directory {}, cache disabled: {}", cache_dir.display(), err);
+		}
+	}
+	
+	/// Enables the cache in `cache_dir` without checking the driver, `driver_info`
+	/// identifies the driver the binaries are for.
+	pub fn open(&mut self, cache_dir: PathBuf, driver_info: &[&str]) -> Result<(), io::Error> {
+		fs::create_dir_all(&cache_dir)?;
+		
+		// Load the index from the last run
+		let index_path = cache_dir.join(INDEX_FILE_NAME);
+		let index = if index_path.exists() {
+			load_index(&index_path).unwrap_or_else(|err| {
+				println!("Failed to load program binary cache index, starting with an empty cache: {}", err);
+				ProgramBinaryCacheIndex::default()
+			})
+		}
+		else {
+			ProgramBinaryCacheIndex::default()
+		};
+		
+		self.driver_id = driver_info.join("\n");
+		self.index = Some(index);
+		self.cache_dir = Some(cache_dir);
+		
+		// Delete binaries that aren't in the index anymore (e.g. from a crashed run)
+		self.delete_orphaned_files();
+		self.evict();
+		Ok(())
+	}
+	
+	pub fn is_enabled(&self) -> bool {
+		self.cache_dir.is_some()
+	}
+	
+	/// Overrides `MAX_CACHE_SIZE_BYTES` and `MAX_ENTRY_AGE_SECS`, takes effect on the next eviction.
+	pub fn set_limits(&mut self, max_size_bytes: u64, max_entry_age_secs: u64) {
+		self.max_size_bytes = max_size_bytes;
+		self.max_entry_age_secs = max_entry_age_secs;
+	}
+	
+	/// Builds the key for a program from the sources of all its stages.
+	pub fn key(&self, sources: &[(ShaderStage, &[u8])]) -> ProgramBinaryKey {
+		let mut key_data = Vec::<u8>::with_capacity(sources.iter().map(|(_, s)| s.len() + 8).sum::<usize>() + self.driver_id.len() + 1);
+		key_data.extend_from_slice(self.driver_id.as_bytes());
+		key_data.push(0);
+		
+		for (stage, source) in sources {
+			key_data.extend_from_slice(stage.file_extension().as_bytes());
+			key_data.push(0);
+			key_data.extend_from_slice(source);
+			key_data.push(0);
+		}
+		
+		hash_content(&key_data)
+	}
+	
+	/// Returns the binary format and data of a cached program.
+	pub fn load(&mut self, key: ProgramBinaryKey) -> Option<(gl::enuma, Vec<u8>)> {
+		let file_name = entry_file_name(key);
+		let entry_size = self.index.as_ref()?.entries.get(&file_name)?.size;
+		
+		let data = match fs::read(self.cache_dir.as_ref()?.join(&file_name)) {
+			Ok(data) => data,
+			Err(_) => {
+				// The file is gone, forget about it
+				self.remove(key);
+				return None;
+			}
+		};
+		
+		// Check the header, and the size in case the file was only partially written
+		let header_len = BINARY_FILE_MAGIC.len() + 4 + 8;
+		if data.len() < header_len || data.len() as u64 != entry_size || &data[0..4] != BINARY_FILE_MAGIC || data[8..16] != key.to_le_bytes() {
+			self.remove(key);
+			return None;
+		}
+		
+		let mut format_bytes = [0u8; 4];
+		format_bytes.copy_from_slice(&data[4..8]);
+		let format = u32::from_le_bytes(format_bytes) as gl::enuma;
+		
+		// Mark as used
+		self.index.as_mut().unwrap().entries.get_mut(&file_name).unwrap().last_used = unix_time_secs();
+		self.index_dirty = true;
+		
+		Some((format, Vec::from(&data[header_len..])))
+	}
+	
+	pub fn store(&mut self, key: ProgramBinaryKey, format: gl::enuma, binary: &[u8]) {
+		let cache_dir = match &self.cache_dir {
+			Some(dir) => dir,
+			None => return,
+		};
+		let file_name = entry_file_name(key);
+		
+		// Write header and binary
+		let mut data = Vec::with_capacity(binary.len() + 16);
+		data.extend_from_slice(BINARY_FILE_MAGIC);
+		data.extend_from_slice(&(format as u32).to_le_bytes());
+		data.extend_from_slice(&key.to_le_bytes());
+		data.extend_from_slice(binary);
+		
+		if let Err(err) = fs::write(cache_dir.join(&file_name), &data) {
+			println!("Failed to write program binary {}: {}", file_name, err);
+			return;
+		}
+		
+		self.index.as_mut().unwrap().entries.insert(file_name, CacheEntry {
+			size: data.len() as u64,
+			last_used: unix_time_secs(),
+		});
+		self.index_dirty = true;
+		
+		self.evict();
+		self.save_index();
+	}
+	
+	/// Removes an entry, e.g. because the driver rejected the binary.
+	pub fn remove(&mut self, key: ProgramBinaryKey) {
+		let file_name = entry_file_name(key);
+		if let (Some(cache_dir), Some(index)) = (&self.cache_dir, &mut self.index) {
+			index.entries.remove(&file_name);
+			let _ = fs::remove_file(cache_dir.join(&file_name));
+			self.index_dirty = true;
+		}
+	}
+	
+	/// Evicts entries that weren't used for a long time and then
+	/// the least recently used ones until the cache fits its size limit.
+	fn evict(&mut self) {
+		let (cache_dir, index) = match (&self.cache_dir, &mut self.index) {
+			(Some(cache_dir), Some(index)) => (cache_dir, index),
+			_ => return,
+		};
+		
+		let now = unix_time_secs();
+		let mut by_last_used = index.entries.iter()
+			.map(|(name, entry)| (name.clone(), entry.clone()))
+			.collect::<Vec<_>>();
+		by_last_used.sort_by_key(|(_, entry)| entry.last_used);
+		
+		let mut total_size = by_last_used.iter().map(|(_, entry)| entry.size).sum::<u64>();
+		for (file_name, entry) in by_last_used {
+			let too_old = now.saturating_sub(entry.last_used) > self.max_entry_age_secs;
+			if !too_old && total_size <= self.max_size_bytes {
+				break;
+			}
+			
+			index.entries.remove(&file_name);
+			let _ = fs::remove_file(cache_dir.join(&file_name));
+			total_size -= entry.size;
+			self.index_dirty = true;
+		}
+	}
+	
+	fn delete_orphaned_files(&mut self) {
+		let (cache_dir, index) = match (&self.cache_dir, &self.index) {
+			(Some(cache_dir), Some(index)) => (cache_dir, index),
+			_ => return,
+		};
+		
+		if let Ok(dir_entries) = fs::read_dir(cache_dir) {
+			for dir_entry in dir_entries.filter_map(|e| e.ok()) {
+				let file_name = dir_entry.file_name().to_string_lossy().into_owned();
+				if file_name.ends_with(".bin") && !index.entries.contains_key(&file_name) {
+					let _ = fs::remove_file(dir_entry.path());
+				}
+			}
+		}
+	}
+	
+	/// Writes the index if it changed. Called after every store and when the demo exits
+	/// so the last used times survive.
+	pub fn save_index(&mut self) {
+		if !self.index_dirty {
+			return;
+		}
+		if let (Some(cache_dir), Some(index)) = (&self.cache_dir, &self.index) {
+			let result = ron::ser::to_string_pretty(index, ron::ser::PrettyConfig::default())
+				.map_err(|e| Box::new(e) as Box<dyn error::Error>)
+				.and_then(|contents| fs::write(cache_dir.join(INDEX_FILE_NAME), contents).map_err(|e| e.into()));
+			
+			match result {
+				Ok(_) => self.index_dirty = false,
+				Err(err) => println!("Failed to save program binary cache index: {}", err),
+			}
+		}
+	}
+	
+	pub const fn new() -> Self {
+		Self {
+			cache_dir: None,
+			driver_id: String::new(),
+			index: None,
+			index_dirty: false,
+			
+			max_size_bytes: MAX_CACHE_SIZE_BYTES,
+			max_entry_age_secs: MAX_ENTRY_AGE_SECS,
+		}
+	}
+}
+
+fn entry_file_name(key: ProgramBinaryKey) -> String {
+	format!("{:016x}.bin", key)
+}
+
+fn unix_time_secs() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn load_index(fs_path: &PathBuf) -> Result<ProgramBinaryCacheIndex, Box<dyn error::Error>> {
+	let mut file = OpenOptions::new().read(true).open(fs_path)?;
+	let mut buffer = String::new();
+	file.read_to_string(&mut buffer)?;
+	
+	Ok(ron::de::from_str::<ProgramBinaryCacheIndex>(&buffer)?)
+}
@@ -0,0 +1,76 @@
+use std::fmt;
+use crate::render::shader::{ShaderStage, ShaderCompileStatus, ProgramLinkStatus};
+
+/// Outcome of the last build of a program variant.
+#[derive(Clone, Debug)]
+pub struct ProgramBuildResult {
+	pub status: ProgramBuildStatus,
+	/// Compile results of every stage, empty if the program was loaded from the binary cache
+	pub shaders: Vec<ShaderBuildResult>,
+	/// `None` if linking wasn't attempted because a shader failed to compile
+	pub link_status: Option<ProgramLinkStatus>,
+	pub link_info_log: Option<String>,
+	/// Why the sources failed to load or transpile, only set with `SourceError`
+	pub source_error: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ShaderBuildResult {
+	pub stage: ShaderStage,
+	pub status: ShaderCompileStatus,
+	/// The compile log with the lines pointing to the ssl sources where possible
+	pub info_log: Option<String>,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ProgramBuildStatus {
+	Success,
+	/// Loaded from the program binary cache
+	LoadedFromCache,
+	CompileError,
+	LinkError,
+	/// The sources failed to load or transpile, nothing was compiled
+	SourceError,
+}
+
+impl ProgramBuildResult {
+	pub fn is_success(&self) -> bool {
+		match self.status {
+			ProgramBuildStatus::Success | ProgramBuildStatus::LoadedFromCache => true,
+			_ => false,
+		}
+	}
+	
+	/// The stages that failed to compile
+	pub fn failed_stages(&self) -> impl Iterator<Item = &ShaderBuildResult> {
+		self.shaders.iter().filter(|s| s.status != ShaderCompileStatus::Success)
+	}
+	
+	pub fn new(status: ProgramBuildStatus) -> Self {
+		Self {
+			status,
+			shaders: Vec::new(),
+			link_status: None,
+			link_info_log: None,
+			source_error: None,
+		}
+	}
+}
+
+impl fmt::Display for ProgramBuildResult {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		match self.status {
+			ProgramBuildStatus::Success => write!(f, "Program built successfully"),
+			ProgramBuildStatus::LoadedFromCache => write!(f, "Program loaded from the binary cache"),
+			ProgramBuildStatus::CompileError => {
+				write!(f, "Shader compile failed")?;
+				for shader in self.failed_stages() {
+					write!(f, "\n{:?} shader (status {:?}):\n{}", shader.stage, shader.status, shader.info_log.as_deref().unwrap_or("<< no infolog >>"))?;
+				}
+				Ok(())
+			}
+			ProgramBuildStatus::LinkError => write!(f, "Program link failed (status {:?}):\n{}", self.link_status.unwrap_or(ProgramLinkStatus::LinkageError), self.link_info_log.as_deref().unwrap_or("<< no infolog >>")),
+			ProgramBuildStatus::SourceError => write!(f, "Program sources failed to load:\n{}", self.source_error.as_deref().unwrap_or("<< no error >>")),
+		}
+	}
+}
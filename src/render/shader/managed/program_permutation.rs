@@ -0,0 +1,149 @@
+use std::error;
+use crate::render::shader::managed::ProgramAssetSchema::PermutationDef;
+
+/// Identifies one variant of a program.
+///
+/// Every permutation gets a few bits holding the index of its value,
+/// so looking up a variant is just hashing a `u64`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct PermutationKey(pub u64);
+
+/// Describes how the permutations of a program are packed into a `PermutationKey`.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct PermutationLayout {
+	permutations: Vec<PermutationSlot>,
+	default_key: PermutationKey,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+struct PermutationSlot {
+	name: String,
+	values: Vec<String>,
+	shift: u32,
+	mask: u64,
+}
+
+impl PermutationLayout {
+	pub fn from_defs(defs: &[PermutationDef]) -> Result<PermutationLayout, Box<dyn error::Error>> {
+		let mut layout = PermutationLayout::new();
+		let mut next_shift = 0u32;
+		
+		for def in defs {
+			if def.values.is_empty() {
+				return Err(format!("Permutation {} has no values", def.name).into());
+			}
+			if layout.slot(&def.name).is_some() {
+				return Err(format!("Duplicate permutation {}", def.name).into());
+			}
+			
+			// Number of bits needed to store the value index
+			let num_bits = 64 - ((def.values.len() - 1) as u64).leading_zeros();
+			if next_shift + num_bits > 64 {
+				return Err(format!("Too many permutations, {} doesn't fit into the 64 bit key anymore", def.name).into());
+			}
+			
+			let slot = PermutationSlot {
+				name: def.name.clone(),
+				values: def.values.clone(),
+				shift: if num_bits == 0 {0} else {next_shift},
+				mask: if num_bits == 0 {0} else {(!0u64 >> (64 - num_bits)) << next_shift},
+			};
+			next_shift += num_bits;
+			
+			// Put the default value into the default key
+			let default_index = match &def.default {
+				Some(default) => slot.values.iter().position(|v| v == default).ok_or_else(|| format!("Default value {} of permutation {} is not one of its values", default, def.name))?,
+				None => 0,
+			};
+			layout.default_key = slot.set(layout.default_key, default_index);
+			
+			layout.permutations.push(slot);
+		}
+		
+		Ok(layout)
+	}
+	
+	fn slot(&self, name: &str) -> Option<&PermutationSlot> {
+		self.permutations.iter().find(|p| p.name == name)
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.permutations.is_empty()
+	}
+	
+	/// The key with every permutation at its default value
+	pub fn default_key(&self) -> PermutationKey {
+		self.default_key
+	}
+	
+	/// Returns `key` with the permutation `name` set to `value`,
+	/// or `None` if there is no such permutation or value.
+	pub fn with_value(&self, key: PermutationKey, name: &str, value: &str) -> Option<PermutationKey> {
+		let slot = self.slot(name)?;
+		let index = slot.values.iter().position(|v| v == value)?;
+		Some(slot.set(key, index))
+	}
+	
+	/// Shorthand for `with_value` on flag permutations.
+	pub fn with_flag(&self, key: PermutationKey, name: &str, enabled: bool) -> Option<PermutationKey> {
+		self.with_value(key, name, if enabled {"1"} else {"0"})
+	}
+	
+	/// Builds a key from the default key and the given values.
+	pub fn key(&self, values: &[(&str, &str)]) -> Result<PermutationKey, Box<dyn error::Error>> {
+		let mut key = self.default_key;
+		for (name, value) in values {
+			key = self.with_value(key, name, value).ok_or_else(|| format!("Unknown permutation value {} = {}", name, value))?;
+		}
+		Ok(key)
+	}
+	
+	pub fn value_of(&self, key: PermutationKey, name: &str) -> Option<&str> {
+		self.slot(name).and_then(|s| s.get(key)).map(|v| v.as_str())
+	}
+	
+	/// Every possible key, starting with the default key.
+	pub fn all_keys(&self) -> Vec<PermutationKey> {
+		let mut keys = vec![self.default_key];
+		for slot in &self.permutations {
+			keys = keys.iter()
+				.flat_map(|key| (0..slot.values.len()).map(move |index| slot.set(*key, index)))
+				.collect();
+		}
+		
+		// Keep the default key in front
+		keys.retain(|k| *k != self.default_key);
+		keys.insert(0, self.default_key);
+		keys
+	}
+	
+	/// The `#define`s for a variant, in declaration order.
+	pub fn defines(&self, key: PermutationKey) -> Vec<(&str, &str)> {
+		self.permutations.iter()
+			.map(|s| (s.name.as_str(), s.get(key).map_or("", |v| v.as_str())))
+			.collect()
+	}
+	
+	pub fn new() -> Self {
+		Self {
+			permutations: Vec::new(),
+			default_key: PermutationKey(0),
+		}
+	}
+}
+
+impl PermutationSlot {
+	fn set(&self, key: PermutationKey, index: usize) -> PermutationKey {
+		PermutationKey((key.0 & !self.mask) | (((index as u64) << self.shift) & self.mask))
+	}
+	
+	fn get(&self, key: PermutationKey) -> Option<&String> {
+		self.values.get(((key.0 & self.mask) >> self.shift) as usize)
+	}
+}
+
+impl Default for PermutationKey {
+	fn default() -> Self {
+		PermutationKey(0)
+	}
+}
@@ -0,0 +1,160 @@
+use std::error;
+use std::fs::{OpenOptions};
+use std::io::{self, Read};
+use std::rc::Rc;
+use crate::asset::{AssetPathBuf, AssetPath, ASSET_MANAGER_INSTANCE};
+use crate::render::shader::ShaderStage;
+use crate::render::shader::managed::{ProgramAssetSchema, PermutationLayout, PermutationKey};
+use crate::structured_shader_language::{SSLSourceParser, SSLTranspiler, SslError, AssetIncludeResolver, TranspiledShaderSource};
+
+/// A `.program` asset with all stages transpiled to glsl.
+///
+/// Doesn't touch gl at all so it can also be built offline, e.g. by the `ssl_transpile` tool.
+pub struct TranspiledProgram {
+	pub program_asset_path: AssetPathBuf,
+	pub id: String,
+	pub permutation_layout: PermutationLayout,
+	/// Transpiled source of every stage, without any permutation defines
+	pub shaders: Vec<(ShaderStage, TranspiledShaderSource)>,
+	/// Every file the program is built from
+	pub dependencies: Vec<AssetPathBuf>,
+}
+
+impl TranspiledProgram {
+	/// Returns the source of a stage with the defines of the given permutation injected.
+	pub fn variant_source(&self, stage: ShaderStage, key: PermutationKey) -> Option<TranspiledShaderSource> {
+		let (_, source) = self.shaders.iter().find(|(s, _)| *s == stage)?;
+		Some(source.with_defines(&self.permutation_layout.defines(key)))
+	}
+	
+	/// Relative file name for dumping the glsl of a stage, e.g. `shaders/post_composite.frag.glsl`
+	/// for the fragment shader of `/shaders/post_composite.program`.
+	pub fn stage_file_name(&self, stage: ShaderStage) -> String {
+		let program_path = self.program_asset_path.as_path().inner_path_slice;
+		let program_path = program_path.strip_prefix("/").unwrap_or(program_path);
+		let program_path = program_path.strip_suffix(".program").unwrap_or(program_path);
+		
+		format!("{}.{}.glsl", program_path, stage.file_extension())
+	}
+	
+	/// Reads the program def of a `.program` asset without loading any of its sources,
+	/// so the stages and files of a program are known even if the sources are broken.
+	pub fn load_def(program_asset_path: &AssetPath) -> Result<ProgramAssetSchema::ProgramDef, Box<dyn error::Error>> {
+		// Open the program asset
+		let program_asset_contents = {
+			// Resolve the actual file path
+			let real_path = unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(program_asset_path);
+			
+			// Read the program asset to string
+			let mut file = OpenOptions::new().read(true).open(real_path)?;
+			let mut buffer = String::new();
+			file.read_to_string(&mut buffer)?;
+			
+			buffer
+		};
+		
+		// Deserialize the program def
+		Ok(ron::de::from_str::<ProgramAssetSchema::ProgramDef>(&program_asset_contents)?)
+	}
+	
+	/// Resolves a source path from a program def, relative paths are relative to the program.
+	pub fn resolve_source_path(asset_path: &AssetPath, base_program_path: &AssetPath) -> AssetPathBuf {
+		// Relativize the asset path (if not absolute)
+		if asset_path.is_absolute() {
+			asset_path.to_absolute()
+		} else {
+			base_program_path.parent().unwrap().join(asset_path).unwrap()
+		}
+	}
+	
+	pub fn load(program_asset_path: &AssetPath, strip_unused_functions: bool) -> Result<TranspiledProgram, Box<dyn error::Error>> {
+		let program_asset_path = program_asset_path.to_absolute();
+		let program_asset_path = program_asset_path.as_path();
+		
+		let program_def = TranspiledProgram::load_def(&program_asset_path)?;
+		
+		// Setup the ssl source parser
+		let mut source_parser = SSLSourceParser::new();
+		
+		fn load_asset_as_str(asset_path: &AssetPath, base_program_path: &AssetPath) -> Result<(String, AssetPathBuf), io::Error> {
+			let real_asset_path = TranspiledProgram::resolve_source_path(asset_path, base_program_path);
+			
+			// Resolve the file path
+			let file_path = unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(&real_asset_path.as_path());
+			
+//			// DEBUG: Print file path of ssl source
+//			println!("Loading source file {:?}", file_path);
+			
+			// Read the file to buffer
+			let mut file = OpenOptions::new().read(true).open(file_path.as_path())?;
+			
+			let mut buffer = String::new();
+			file.read_to_string(&mut buffer)?;
+			
+			Ok((buffer, real_asset_path))
+		}
+		
+		// Every file the program is built from
+		let mut dependencies = Vec::<AssetPathBuf>::new();
+		
+		// Setup the transpiler, includes not listed in the program are looked up
+		// next to the program first and then in the shared shader directory
+		let mut transpiler = SSLTranspiler::new();
+		transpiler.set_strip_unused_functions(strip_unused_functions);
+		transpiler.set_include_resolver(Box::new(AssetIncludeResolver::new(vec![
+			program_asset_path.parent().unwrap().to_absolute(),
+			AssetPathBuf::from("/shaders"),
+		])));
+		
+		// Parse the includes
+		for include_path in &program_def.includes {
+			let asset_path = AssetPathBuf::from(include_path.as_str());
+			let (source_code, source_path) = load_asset_as_str(&asset_path.as_path(), &program_asset_path)?;
+			
+			let parsed_source = source_parser.parse_source(source_path.as_path().inner_path_slice, &source_code).map_err(SslError::from)?;
+			dependencies.push(source_path);
+			
+			// Make sure it's actually an include
+			parsed_source.validate_shader_type(None).map_err(|d| SslError::from(vec![d]))?;
+			transpiler.add_include(Rc::new(parsed_source));
+		}
+		
+		// Setup the permutations
+		let permutation_layout = PermutationLayout::from_defs(&program_def.permutations)?;
+		
+		let mut shaders = Vec::new();
+		
+		// Load the shaders
+		for shader_def in &program_def.shaders {
+			// Load the referenced code
+			let asset_path = AssetPathBuf::from(&shader_def.source);
+			let (source_code, source_path) = load_asset_as_str(&asset_path.as_path(), &program_asset_path)?;
+			
+			let parsed_source = source_parser.parse_source(source_path.as_path().inner_path_slice, &source_code).map_err(SslError::from)?;
+			dependencies.push(source_path);
+			
+			// Make sure the source is meant for this stage
+			parsed_source.validate_shader_type(Some(shader_def.stage.as_engine_stage_enum())).map_err(|d| SslError::from(vec![d]))?;
+			
+			// Transpile the shader
+			let transpiled_code = transpiler.transpile(&parsed_source).map_err(SslError::from)?;
+			shaders.push((shader_def.stage.as_engine_stage_enum(), transpiled_code));
+		}
+		
+		// Add the includes found by the resolver to the dependencies
+		for include in transpiler.import_scope() {
+			let include_path = AssetPathBuf::from(include.file_name.as_str());
+			if !dependencies.contains(&include_path) {
+				dependencies.push(include_path);
+			}
+		}
+		
+		Ok(TranspiledProgram {
+			program_asset_path: program_asset_path.to_absolute(),
+			id: program_def.id,
+			permutation_layout,
+			shaders,
+			dependencies,
+		})
+	}
+}
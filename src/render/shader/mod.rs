@@ -1,5 +1,7 @@
 mod shader; pub use shader::*;
 mod shader_program; pub use shader_program::*;
 mod uniform; pub use uniform::*;
+mod program_reflection; pub use program_reflection::*;
+mod gpu_block; pub use gpu_block::*;
 
 pub mod managed;
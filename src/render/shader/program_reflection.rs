@@ -0,0 +1,307 @@
+use std::fmt;
+use gl_bindings::gl;
+
+/// Everything the linker kept active in a program, queried through the program interface api.
+#[derive(Clone, Debug, Default)]
+pub struct ProgramReflection {
+	/// Uniforms of the default block, including samplers and images
+	pub uniforms: Vec<UniformInfo>,
+	pub uniform_blocks: Vec<BlockInfo>,
+	pub storage_blocks: Vec<BlockInfo>,
+	pub vertex_inputs: Vec<VertexInputInfo>,
+}
+
+#[derive(Clone, Debug)]
+pub struct UniformInfo {
+	/// Name without the `[0]` suffix of arrays
+	pub name: String,
+	pub uniform_type: UniformType,
+	pub location: gl::int,
+	/// 1 for non-arrays
+	pub array_size: gl::int,
+	/// Texture or image unit for samplers and images
+	pub binding: Option<gl::int>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockInfo {
+	pub name: String,
+	pub binding: gl::uint,
+	pub data_size: gl::uint,
+	/// Active members in declaration order (arrays of structs are flattened by gl, like `lights[0].position`)
+	pub members: Vec<BlockMemberInfo>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockMemberInfo {
+	/// Name without the `[0]` suffix of arrays, prefixed with the block's instance name if it has one
+	pub name: String,
+	pub member_type: UniformType,
+	/// Byte offset from the start of the block
+	pub offset: gl::int,
+	/// 1 for non-arrays, 0 for unsized arrays
+	pub array_size: gl::int,
+	pub array_stride: gl::int,
+}
+
+#[derive(Clone, Debug)]
+pub struct VertexInputInfo {
+	pub name: String,
+	pub input_type: UniformType,
+	pub location: gl::int,
+}
+
+impl ProgramReflection {
+	/// Reflects a linked program.
+	pub fn query(program_gl: gl::uint) -> ProgramReflection {
+		let mut reflection = ProgramReflection::new();
+		
+		{// Uniform and storage blocks
+			let props = [gl::NAME_LENGTH, gl::BUFFER_BINDING, gl::BUFFER_DATA_SIZE];
+			for (interface, blocks) in [(gl::UNIFORM_BLOCK, &mut reflection.uniform_blocks), (gl::SHADER_STORAGE_BLOCK, &mut reflection.storage_blocks)].iter_mut() {
+				for index in 0..num_active_resources(program_gl, *interface) {
+					let values = resource_properties(program_gl, *interface, index, &props);
+					
+					blocks.push(BlockInfo {
+						name: resource_name(program_gl, *interface, index, values[0]),
+						binding: values[1] as gl::uint,
+						data_size: values[2] as gl::uint,
+						members: Vec::new(),
+					});
+				}
+			}
+		}
+		
+		{// Uniforms, block members go to their uniform block
+			let props = [gl::NAME_LENGTH, gl::TYPE, gl::LOCATION, gl::ARRAY_SIZE, gl::BLOCK_INDEX, gl::OFFSET, gl::ARRAY_STRIDE];
+			for index in 0..num_active_resources(program_gl, gl::UNIFORM) {
+				let values = resource_properties(program_gl, gl::UNIFORM, index, &props);
+				let name = resource_name(program_gl, gl::UNIFORM, index, values[0]);
+				let uniform_type = UniformType::from_gl(values[1] as gl::enuma);
+				
+				if values[4] != -1 {
+					if let Some(block) = reflection.uniform_blocks.get_mut(values[4] as usize) {
+						block.members.push(BlockMemberInfo {
+							name,
+							member_type: uniform_type,
+							offset: values[5],
+							array_size: values[3],
+							array_stride: values[6],
+						});
+					}
+					continue;
+				}
+				
+				let binding = if uniform_type.is_opaque() {
+					let mut unit: gl::int = 0;
+					unsafe {gl::GetUniformiv(program_gl, values[2], &mut unit)};
+					Some(unit)
+				} else {
+					None
+				};
+				
+				reflection.uniforms.push(UniformInfo {
+					name,
+					uniform_type,
+					location: values[2],
+					array_size: values[3],
+					binding,
+				});
+			}
+		}
+		
+		{// Storage block members
+			let props = [gl::NAME_LENGTH, gl::TYPE, gl::ARRAY_SIZE, gl::BLOCK_INDEX, gl::OFFSET, gl::ARRAY_STRIDE];
+			for index in 0..num_active_resources(program_gl, gl::BUFFER_VARIABLE) {
+				let values = resource_properties(program_gl, gl::BUFFER_VARIABLE, index, &props);
+				
+				if let Some(block) = reflection.storage_blocks.get_mut(values[3] as usize) {
+					block.members.push(BlockMemberInfo {
+						name: resource_name(program_gl, gl::BUFFER_VARIABLE, index, values[0]),
+						member_type: UniformType::from_gl(values[1] as gl::enuma),
+						offset: values[4],
+						array_size: values[2],
+						array_stride: values[5],
+					});
+				}
+			}
+		}
+		
+		// Gl doesn't guarantee any order
+		for block in reflection.uniform_blocks.iter_mut().chain(reflection.storage_blocks.iter_mut()) {
+			block.members.sort_by_key(|m| m.offset);
+		}
+		
+		{// Vertex inputs
+			let props = [gl::NAME_LENGTH, gl::TYPE, gl::LOCATION];
+			for index in 0..num_active_resources(program_gl, gl::PROGRAM_INPUT) {
+				let values = resource_properties(program_gl, gl::PROGRAM_INPUT, index, &props);
+				
+				// Builtins like gl_VertexID don't have a location
+				if values[2] == -1 {
+					continue;
+				}
+				
+				reflection.vertex_inputs.push(VertexInputInfo {
+					name: resource_name(program_gl, gl::PROGRAM_INPUT, index, values[0]),
+					input_type: UniformType::from_gl(values[1] as gl::enuma),
+					location: values[2],
+				});
+			}
+		}
+		
+		reflection
+	}
+	
+	pub fn uniform(&self, name: &str) -> Option<&UniformInfo> {
+		self.uniforms.iter().find(|u| u.name == name)
+	}
+	
+	pub fn uniform_block(&self, name: &str) -> Option<&BlockInfo> {
+		self.uniform_blocks.iter().find(|b| b.name == name)
+	}
+	
+	pub fn storage_block(&self, name: &str) -> Option<&BlockInfo> {
+		self.storage_blocks.iter().find(|b| b.name == name)
+	}
+	
+	pub fn vertex_input(&self, name: &str) -> Option<&VertexInputInfo> {
+		self.vertex_inputs.iter().find(|i| i.name == name)
+	}
+	
+	pub fn new() -> Self {
+		Self {
+			uniforms: Vec::new(),
+			uniform_blocks: Vec::new(),
+			storage_blocks: Vec::new(),
+			vertex_inputs: Vec::new(),
+		}
+	}
+}
+
+fn num_active_resources(program_gl: gl::uint, interface: gl::enuma) -> gl::uint {
+	let mut num_resources: gl::int = 0;
+	unsafe {gl::GetProgramInterfaceiv(program_gl, interface, gl::ACTIVE_RESOURCES, &mut num_resources)};
+	num_resources.max(0) as gl::uint
+}
+
+fn resource_properties(program_gl: gl::uint, interface: gl::enuma, index: gl::uint, props: &[gl::enuma]) -> Vec<gl::int> {
+	let mut values = vec![0 as gl::int; props.len()];
+	unsafe {
+		gl::GetProgramResourceiv(program_gl, interface, index, props.len() as gl::sizei, props.as_ptr(), values.len() as gl::sizei, std::ptr::null_mut(), values.as_mut_ptr());
+	}
+	values
+}
+
+/// Returns the name of a resource, without the `[0]` gl appends to arrays.
+fn resource_name(program_gl: gl::uint, interface: gl::enuma, index: gl::uint, name_length: gl::int) -> String {
+	let mut buffer = vec![0u8; name_length.max(1) as usize];
+	let mut length: gl::sizei = 0;
+	unsafe {
+		gl::GetProgramResourceName(program_gl, interface, index, buffer.len() as gl::sizei, &mut length, buffer.as_mut_ptr() as *mut gl::char);
+	}
+	buffer.truncate(length as usize);
+	
+	let name = String::from_utf8_lossy(&buffer).into_owned();
+	match name.strip_suffix("[0]") {
+		Some(name) => String::from(name),
+		None => name,
+	}
+}
+
+/// The glsl type of a uniform or vertex input.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum UniformType {
+	Float,
+	Vec2,
+	Vec3,
+	Vec4,
+	Int,
+	IVec2,
+	IVec3,
+	IVec4,
+	UInt,
+	UVec2,
+	UVec3,
+	UVec4,
+	Bool,
+	Mat2,
+	Mat3,
+	Mat4,
+	/// Any sampler type, with the gl type enum
+	Sampler(gl::enuma),
+	/// Any image type, with the gl type enum
+	Image(gl::enuma),
+	Other(gl::enuma),
+}
+
+impl UniformType {
+	pub fn from_gl(type_gl: gl::enuma) -> UniformType {
+		use UniformType::*;
+		match type_gl {
+			gl::FLOAT => Float,
+			gl::FLOAT_VEC2 => Vec2,
+			gl::FLOAT_VEC3 => Vec3,
+			gl::FLOAT_VEC4 => Vec4,
+			gl::INT => Int,
+			gl::INT_VEC2 => IVec2,
+			gl::INT_VEC3 => IVec3,
+			gl::INT_VEC4 => IVec4,
+			gl::UNSIGNED_INT => UInt,
+			gl::UNSIGNED_INT_VEC2 => UVec2,
+			gl::UNSIGNED_INT_VEC3 => UVec3,
+			gl::UNSIGNED_INT_VEC4 => UVec4,
+			gl::BOOL => Bool,
+			gl::FLOAT_MAT2 => Mat2,
+			gl::FLOAT_MAT3 => Mat3,
+			gl::FLOAT_MAT4 => Mat4,
+			
+			gl::SAMPLER_1D | gl::SAMPLER_2D | gl::SAMPLER_3D | gl::SAMPLER_CUBE
+			| gl::SAMPLER_2D_SHADOW | gl::SAMPLER_CUBE_SHADOW | gl::SAMPLER_2D_ARRAY | gl::SAMPLER_2D_ARRAY_SHADOW
+			| gl::SAMPLER_2D_MULTISAMPLE | gl::SAMPLER_BUFFER | gl::SAMPLER_CUBE_MAP_ARRAY
+			| gl::INT_SAMPLER_2D | gl::INT_SAMPLER_3D | gl::INT_SAMPLER_2D_ARRAY | gl::INT_SAMPLER_BUFFER
+			| gl::UNSIGNED_INT_SAMPLER_2D | gl::UNSIGNED_INT_SAMPLER_3D | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY | gl::UNSIGNED_INT_SAMPLER_BUFFER => Sampler(type_gl),
+			
+			gl::IMAGE_1D | gl::IMAGE_2D | gl::IMAGE_3D | gl::IMAGE_CUBE | gl::IMAGE_2D_ARRAY | gl::IMAGE_BUFFER
+			| gl::INT_IMAGE_2D | gl::INT_IMAGE_3D | gl::INT_IMAGE_BUFFER
+			| gl::UNSIGNED_INT_IMAGE_2D | gl::UNSIGNED_INT_IMAGE_3D | gl::UNSIGNED_INT_IMAGE_BUFFER => Image(type_gl),
+			
+			_ => Other(type_gl),
+		}
+	}
+	
+	/// Samplers and images, their value is the unit they are bound to.
+	pub fn is_opaque(&self) -> bool {
+		match self {
+			UniformType::Sampler(_) | UniformType::Image(_) => true,
+			_ => false,
+		}
+	}
+}
+
+impl fmt::Display for UniformType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		use UniformType::*;
+		match self {
+			Float => write!(f, "float"),
+			Vec2 => write!(f, "vec2"),
+			Vec3 => write!(f, "vec3"),
+			Vec4 => write!(f, "vec4"),
+			Int => write!(f, "int"),
+			IVec2 => write!(f, "ivec2"),
+			IVec3 => write!(f, "ivec3"),
+			IVec4 => write!(f, "ivec4"),
+			UInt => write!(f, "uint"),
+			UVec2 => write!(f, "uvec2"),
+			UVec3 => write!(f, "uvec3"),
+			UVec4 => write!(f, "uvec4"),
+			Bool => write!(f, "bool"),
+			Mat2 => write!(f, "mat2"),
+			Mat3 => write!(f, "mat3"),
+			Mat4 => write!(f, "mat4"),
+			Sampler(type_gl) => write!(f, "sampler (0x{:04X})", type_gl),
+			Image(type_gl) => write!(f, "image (0x{:04X})", type_gl),
+			Other(type_gl) => write!(f, "unknown type (0x{:04X})", type_gl),
+		}
+	}
+}
@@ -1,7 +1,7 @@
 use gl_bindings::gl;
 use std::mem;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum ShaderStage {
 	Vertex,
 	Fragment,
@@ -28,6 +28,19 @@ impl ShaderStage {
 		use ShaderStage::*;
 		&[Vertex, Fragment, TessellationControl, TessellationEval, Geometry, Compute]
 	}
+	
+	/// The usual file extension for glsl sources of this stage (as used by glslang)
+	pub fn file_extension(&self) -> &'static str {
+		use ShaderStage::*;
+		match self {
+			Vertex => "vert",
+			Fragment => "frag",
+			TessellationControl => "tesc",
+			TessellationEval => "tese",
+			Geometry => "geom",
+			Compute => "comp",
+		}
+	}
 }
 
 pub struct Shader {
@@ -73,18 +86,21 @@ impl Shader {
 		let info_log: Option<String>;
 		let successful: bool;
 		
+		// Delete the shader object of the last compile
+		self.dispose();
+		
+		// Get code
+		let code = if let Some(code) = &self.attached_source {
+			code
+		} else {
+			// We don't have source code, so return a missing code error
+			return ShaderCompileResult::new(ShaderCompileStatus::MissingSource, None);
+		};
+		
 		unsafe {
 			// Create shader object
 			self.shader_gl = gl::CreateShader(self.shader_stage.as_gl_enum());
 			
-			// Get code
-			let code = if let Some(code) = &self.attached_source {
-				code
-			} else {
-				// We don't have source code, so return a missing code error
-				return ShaderCompileResult::new(ShaderCompileStatus::MissingSource, None);
-			};
-			
 			// Add source code
 			let code_ptr = code.code.as_ptr() as *const gl::char;
 			let code_length = code.code.len() as gl::int;
@@ -124,8 +140,14 @@ impl Shader {
 			};
 		}
 		
+		// A failed shader object is of no use, so only keep it if it compiled
+		if !successful {
+			self.dispose();
+		}
+		
 		// Make result object
-		let compile_result = ShaderCompileResult::new(ShaderCompileStatus::Success, info_log);
+		let status = if successful {ShaderCompileStatus::Success} else {ShaderCompileStatus::CompileError};
+		let compile_result = ShaderCompileResult::new(status, info_log);
 		compile_result
 	}
 	
@@ -210,7 +232,7 @@ impl ShaderCompileResult {
 	}
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum ShaderCompileStatus {
 	Success,
 	MissingSource,
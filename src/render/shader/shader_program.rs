@@ -1,5 +1,7 @@
 use gl_bindings::gl;
-use crate::render::shader::{Shader, ShaderStage};
+use crate::render::shader::{Shader, ShaderStage, ProgramReflection, UniformType, UniformValue, UniformError};
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::mem;
 
 pub struct ShaderProgram {
@@ -11,6 +13,10 @@ pub struct ShaderProgram {
 	compute_shader: Option<Shader>,
 	
 	program_gl: gl::uint,
+	/// Active resources of the linked program
+	reflection: Option<ProgramReflection>,
+	/// Uniform names a failed `set_uniform` was already reported for
+	reported_uniform_errors: RefCell<HashSet<String>>,
 }
 
 impl ShaderProgram {
@@ -26,6 +32,41 @@ impl ShaderProgram {
 		}
 	}
 	
+	pub fn reflection(&self) -> Option<&ProgramReflection> {
+		self.reflection.as_ref()
+	}
+	
+	/// Sets a uniform of the default block by name, checking that it exists and has the type of `T`.
+	/// Errors are also printed, once per uniform name so they don't flood the log every frame.
+	pub fn set_uniform<T: UniformValue>(&self, name: &str, value: T) -> Result<(), UniformError> {
+		let result = self.try_set_uniform(name, &value);
+		
+		if let Err(err) = &result {
+			if self.reported_uniform_errors.borrow_mut().insert(String::from(name)) {
+				println!("Failed to set uniform: {}", err);
+			}
+		}
+		result
+	}
+	
+	fn try_set_uniform<T: UniformValue>(&self, name: &str, value: &T) -> Result<(), UniformError> {
+		let (program_gl, reflection) = match (self.program_gl(), &self.reflection) {
+			(Some(program_gl), Some(reflection)) => (program_gl, reflection),
+			_ => return Err(UniformError::NotLinked),
+		};
+		
+		let uniform = reflection.uniform(name).ok_or_else(|| UniformError::NotFound {name: String::from(name)})?;
+		
+		// Samplers and images are set with their unit
+		let type_matches = uniform.uniform_type == T::UNIFORM_TYPE || (uniform.uniform_type.is_opaque() && T::UNIFORM_TYPE == UniformType::Int);
+		if !type_matches {
+			return Err(UniformError::TypeMismatch {name: String::from(name), expected: uniform.uniform_type, actual: T::UNIFORM_TYPE});
+		}
+		
+		unsafe {value.upload(program_gl, uniform.location)};
+		Ok(())
+	}
+	
 	pub fn attached_shader(&self, stage: &ShaderStage) -> Option<&Shader> {
 		self.shader_slot(stage).map_or(None, |s| s.as_ref())
 	}
@@ -89,20 +130,31 @@ impl ShaderProgram {
 		let info_log: Option<String>;
 		let successful: bool;
 		
+		// Delete the program of the last link
+		self.dispose();
+		
+		// Check all shaders are compiled before creating anything
+		let any_uncompiled = ShaderStage::stages().iter()
+			.filter_map(|stage| self.attached_shader(stage))
+			.any(|shader| !shader.is_compiled());
+		if any_uncompiled {
+			// Shader is avaialable but uncompiled
+			return ProgramLinkResult::new(ProgramLinkStatus::UncompiledShader, None);
+		}
+		
 		unsafe {
 			// Create program object
 			self.program_gl = gl::CreateProgram();
 			
+			// Tell the driver we want to read the binary back
+			if options.binary_retrievable {
+				gl::ProgramParameteri(self.program_gl, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as gl::int);
+			}
+			
 			// Attach shaders
 			for stage in ShaderStage::stages() {
-				if let Some(shader) = self.attached_shader(stage) {
-					if let Some(shader_gl) = shader.shader_gl() {
-						gl::AttachShader(self.program_gl, shader_gl);
-					}
-					else {
-						// Shader is avaialable but uncompiled
-						return ProgramLinkResult::new(ProgramLinkStatus::UncompiledShader, None);
-					}
+				if let Some(shader_gl) = self.attached_shader(stage).and_then(|s| s.shader_gl()) {
+					gl::AttachShader(self.program_gl, shader_gl);
 				}
 			}
 			
@@ -150,12 +202,72 @@ impl ShaderProgram {
 			}
 		}
 		
+		// Reflect the active resources, a failed program is of no use so don't keep it around
+		if successful {
+			self.reflect();
+		} else {
+			self.dispose();
+		}
+		
 		// Make result object
 		let status = if successful {ProgramLinkStatus::Success} else {ProgramLinkStatus::LinkageError};
 		let result = ProgramLinkResult::new(status, info_log);
 		result
 	}
 	
+	/// Creates the program object from a binary previously returned by `get_binary`
+	/// instead of linking the attached shaders. Returns false if the driver rejected
+	/// the binary (e.g. after a driver update), the program is left unlinked then.
+	pub fn load_binary(&mut self, format: gl::enuma, binary: &[u8]) -> bool {
+		self.dispose();
+		
+		unsafe {
+			self.program_gl = gl::CreateProgram();
+			gl::ProgramBinary(self.program_gl, format, binary.as_ptr() as *const gl::void, binary.len() as gl::sizei);
+			
+			// Loading the binary sets the link status
+			let mut link_status: gl::int = 0;
+			gl::GetProgramiv(self.program_gl, gl::LINK_STATUS, &mut link_status);
+			
+			if link_status != gl::TRUE as gl::int {
+				self.dispose();
+				return false;
+			}
+		}
+		
+		self.reflect();
+		true
+	}
+	
+	fn reflect(&mut self) {
+		self.reflection = Some(ProgramReflection::query(self.program_gl));
+		self.reported_uniform_errors.borrow_mut().clear();
+	}
+	
+	/// Returns the binary format and data of the linked program, if the driver can provide it.
+	/// The program should be linked with `ProgramLinkOptions::binary_retrievable` set.
+	pub fn get_binary(&self) -> Option<(gl::enuma, Vec<u8>)> {
+		let program_gl = self.program_gl()?;
+		
+		unsafe {
+			// Query binary length
+			let mut binary_length: gl::int = 0;
+			gl::GetProgramiv(program_gl, gl::PROGRAM_BINARY_LENGTH, &mut binary_length);
+			if binary_length <= 0 {
+				return None;
+			}
+			
+			// Get the binary
+			let mut binary = vec![0u8; binary_length as usize];
+			let mut written_length: gl::sizei = 0;
+			let mut format: gl::enuma = 0;
+			gl::GetProgramBinary(program_gl, binary_length as gl::sizei, &mut written_length, &mut format, binary.as_mut_ptr() as *mut gl::void);
+			
+			binary.truncate(written_length as usize);
+			Some((format, binary))
+		}
+	}
+	
 	/// Disposes this programs OpenGL program object by deleting it.
 	pub fn dispose(&mut self) {
 		if self.program_gl != 0 {
@@ -165,6 +277,7 @@ impl ShaderProgram {
 				gl::DeleteProgram(name);
 			}
 		}
+		self.reflection = None;
 	}
 	
 	pub fn new() -> Self {
@@ -177,6 +290,8 @@ impl ShaderProgram {
 			compute_shader: None,
 			
 			program_gl: 0,
+			reflection: None,
+			reported_uniform_errors: RefCell::new(HashSet::new()),
 		}
 	}
 }
@@ -191,6 +306,8 @@ impl Drop for ShaderProgram {
 pub struct ProgramLinkOptions {
 	pub capture_success_info_log: bool,
 	pub capture_failure_info_log: bool,
+	/// Set the retrievable hint so `get_binary` can be used after linking
+	pub binary_retrievable: bool,
 }
 
 impl ProgramLinkOptions {
@@ -199,6 +316,11 @@ impl ProgramLinkOptions {
 		self.capture_failure_info_log = capture_on_failure;
 		self
 	}
+	
+	pub fn with_binary_retrievable(&mut self, retrievable: bool) -> &mut Self {
+		self.binary_retrievable = retrievable;
+		self
+	}
 }
 
 impl Default for ProgramLinkOptions {
@@ -206,6 +328,7 @@ impl Default for ProgramLinkOptions {
 		Self {
 			capture_success_info_log: false,
 			capture_failure_info_log: true,
+			binary_retrievable: false,
 		}
 	}
 }
@@ -232,7 +355,7 @@ impl ProgramLinkResult {
 	}
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum ProgramLinkStatus {
 	Success,
 	LinkageError,
@@ -1,16 +1,25 @@
 use gl_bindings::gl;
+use std::error::Error;
 use std::ffi::CString;
+use std::fmt;
+use cgmath::{Matrix3, Matrix4, Vector2, Vector3, Vector4};
+use crate::render::shader::UniformType;
 
+/// Caches the location of a single uniform by name.
+///
+/// Prefer `ShaderProgram::set_uniform` which uses the program's reflection data.
 pub struct UniformLocationCache {
 	uniform_name: CString,
 	
 	cached_program_gl: gl::uint,
+	/// `-1` if the program has no active uniform with that name
 	cached_location: gl::int,
 }
 
 impl UniformLocationCache {
 	pub fn get(&mut self, program_gl: gl::uint) -> Option<gl::int> {
-		if self.cached_program_gl != program_gl || self.cached_location == 0 {
+		// Location 0 is a perfectly valid location, so only requery when the program changed
+		if self.cached_program_gl != program_gl {
 			self.cached_program_gl = program_gl;
 			self.cached_location = unsafe {
 				let name_ptr = self.uniform_name.as_ptr() as *const gl::char;
@@ -18,7 +27,7 @@ impl UniformLocationCache {
 			};
 		}
 		
-		if self.cached_location != 0 {
+		if self.cached_location != -1 {
 			Some(self.cached_location)
 		} else {
 			None
@@ -30,11 +39,127 @@ impl UniformLocationCache {
 			uniform_name: CString::new(uniform_name).unwrap(),
 			
 			cached_program_gl: 0,
-			cached_location: 0,
+			cached_location: -1,
 		}
 	}
 }
 
-//pub struct Uniform<T: UniformValue> {
-//	
-//}
+/// A rust value that can be uploaded to a uniform of type `UNIFORM_TYPE`.
+pub trait UniformValue {
+	const UNIFORM_TYPE: UniformType;
+	
+	/// Uploads the value with the `glProgramUniform*` functions, so the program doesn't need to be bound.
+	unsafe fn upload(&self, program_gl: gl::uint, location: gl::int);
+}
+
+impl UniformValue for f32 {
+	const UNIFORM_TYPE: UniformType = UniformType::Float;
+	
+	unsafe fn upload(&self, program_gl: gl::uint, location: gl::int) {
+		gl::ProgramUniform1f(program_gl, location, *self);
+	}
+}
+
+impl UniformValue for Vector2<f32> {
+	const UNIFORM_TYPE: UniformType = UniformType::Vec2;
+	
+	unsafe fn upload(&self, program_gl: gl::uint, location: gl::int) {
+		gl::ProgramUniform2f(program_gl, location, self.x, self.y);
+	}
+}
+
+impl UniformValue for Vector3<f32> {
+	const UNIFORM_TYPE: UniformType = UniformType::Vec3;
+	
+	unsafe fn upload(&self, program_gl: gl::uint, location: gl::int) {
+		gl::ProgramUniform3f(program_gl, location, self.x, self.y, self.z);
+	}
+}
+
+impl UniformValue for Vector4<f32> {
+	const UNIFORM_TYPE: UniformType = UniformType::Vec4;
+	
+	unsafe fn upload(&self, program_gl: gl::uint, location: gl::int) {
+		gl::ProgramUniform4f(program_gl, location, self.x, self.y, self.z, self.w);
+	}
+}
+
+impl UniformValue for i32 {
+	const UNIFORM_TYPE: UniformType = UniformType::Int;
+	
+	unsafe fn upload(&self, program_gl: gl::uint, location: gl::int) {
+		gl::ProgramUniform1i(program_gl, location, *self);
+	}
+}
+
+impl UniformValue for Vector2<i32> {
+	const UNIFORM_TYPE: UniformType = UniformType::IVec2;
+	
+	unsafe fn upload(&self, program_gl: gl::uint, location: gl::int) {
+		gl::ProgramUniform2i(program_gl, location, self.x, self.y);
+	}
+}
+
+impl UniformValue for u32 {
+	const UNIFORM_TYPE: UniformType = UniformType::UInt;
+	
+	unsafe fn upload(&self, program_gl: gl::uint, location: gl::int) {
+		gl::ProgramUniform1ui(program_gl, location, *self);
+	}
+}
+
+impl UniformValue for Vector2<u32> {
+	const UNIFORM_TYPE: UniformType = UniformType::UVec2;
+	
+	unsafe fn upload(&self, program_gl: gl::uint, location: gl::int) {
+		gl::ProgramUniform2ui(program_gl, location, self.x, self.y);
+	}
+}
+
+impl UniformValue for bool {
+	const UNIFORM_TYPE: UniformType = UniformType::Bool;
+	
+	unsafe fn upload(&self, program_gl: gl::uint, location: gl::int) {
+		gl::ProgramUniform1i(program_gl, location, *self as gl::int);
+	}
+}
+
+impl UniformValue for Matrix3<f32> {
+	const UNIFORM_TYPE: UniformType = UniformType::Mat3;
+	
+	unsafe fn upload(&self, program_gl: gl::uint, location: gl::int) {
+		let matrix_arr: [[f32; 3]; 3] = (*self).into();
+		gl::ProgramUniformMatrix3fv(program_gl, location, 1, gl::FALSE, matrix_arr.as_ptr() as *const gl::float);
+	}
+}
+
+impl UniformValue for Matrix4<f32> {
+	const UNIFORM_TYPE: UniformType = UniformType::Mat4;
+	
+	unsafe fn upload(&self, program_gl: gl::uint, location: gl::int) {
+		let matrix_arr: [[f32; 4]; 4] = (*self).into();
+		gl::ProgramUniformMatrix4fv(program_gl, location, 1, gl::FALSE, matrix_arr.as_ptr() as *const gl::float);
+	}
+}
+
+#[derive(Clone, Debug)]
+pub enum UniformError {
+	/// The program isn't linked (yet)
+	NotLinked,
+	/// The program has no active uniform with that name. Note that uniforms
+	/// the shader doesn't use are optimized out and aren't active either.
+	NotFound {name: String},
+	TypeMismatch {name: String, expected: UniformType, actual: UniformType},
+}
+
+impl Error for UniformError {}
+
+impl fmt::Display for UniformError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		match self {
+			UniformError::NotLinked => write!(f, "Program is not linked"),
+			UniformError::NotFound {name} => write!(f, "No active uniform named {}", name),
+			UniformError::TypeMismatch {name, expected, actual} => write!(f, "Uniform {} is a {} but was set with a {}", name, expected, actual),
+		}
+	}
+}
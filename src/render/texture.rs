@@ -2,8 +2,10 @@ use gl_bindings::gl;
 use crate::render::ImageFormat;
 use std::path::Path;
 use std::error;
-use std::fs::OpenOptions;
+use std::fs;
+use lodepng::{ColorType, Image};
 use ktx::KtxInfo;
+use crate::asset::{ASSET_MANAGER_INSTANCE, AssetPath, AssetLoader, AssetLoadError, AssetLoadService, LoadHandle, ContentHash, hash_content};
 
 pub struct Texture {
 	width: u32,
@@ -49,10 +51,28 @@ impl Texture {
 		self.handle_gl != 0
 	}
 	
+	pub fn size(&self) -> (u32, u32) {
+		(self.width, self.height)
+	}
+	
+	pub fn image_format(&self) -> ImageFormat {
+		self.image_format
+	}
+	
 	pub fn texture_gl(&self) -> gl::uint {
 		self.handle_gl
 	}
 	
+	/// Deletes the gl texture. The texture can be allocated again afterwards.
+	pub fn dispose(&mut self) {
+		if self.handle_gl != 0 {
+			unsafe {
+				gl::DeleteTextures(1, &self.handle_gl);
+			}
+			self.handle_gl = 0;
+		}
+	}
+	
 	pub fn new(width: u32, height: u32, levels: u32, image_format: ImageFormat) -> Texture {
 		Texture {
 			width,
@@ -64,65 +84,153 @@ impl Texture {
 		}
 	}
 	
-	#[deprecated]
-	pub fn load_ktx_from_path(path: &Path, image_format: ImageFormat) -> Result<Texture, Box<dyn error::Error>> {
-		// Open file
-		let ktx_decoder = ktx::Decoder::new(OpenOptions::new().read(true).open(path)?)?;
-		
-		// Get header
-//		let header = ktx_decoder.header();
-		let (img_width, img_height) = (ktx_decoder.pixel_width(), ktx_decoder.pixel_height());
-		
-		// Read texture data
-		let base_level_data = ktx_decoder.read_textures().next().unwrap();
+	/// Creates and allocates a texture from decoded image data and uploads it.
+	/// Needs a current gl context.
+	pub fn from_decoded(decoded: &DecodedTexture) -> Texture {
+		let (img_width, img_height) = (decoded.width, decoded.height);
 		
 		// Allocate texture
-		let mut texture = Texture::new(img_width as u32, img_height as u32, 8, image_format);
+		let mut texture = Texture::new(img_width, img_height, 8, decoded.image_format);
 		texture.allocate();
 		
-		// Upload compressed image data
-		unsafe {
-			gl::CompressedTextureSubImage2D(texture.handle_gl, 0, 0, 0, img_width as gl::sizei, img_height as gl::sizei, image_format.as_gl_enum(), base_level_data.len() as gl::sizei, base_level_data.as_ptr() as *const gl::void);
+		match &decoded.data {
+			DecodedTextureData::Compressed(data) => {
+				// Upload compressed image data
+				unsafe {
+					gl::CompressedTextureSubImage2D(texture.handle_gl, 0, 0, 0, img_width as gl::sizei, img_height as gl::sizei, decoded.image_format.as_gl_enum(), data.len() as gl::sizei, data.as_ptr() as *const gl::void);
+				}
+			}
+			DecodedTextureData::Uncompressed {channels, data} => {
+				let pixel_format_gl = match channels {
+					1 => gl::RED,
+					2 => gl::RG,
+					3 => gl::RGB,
+					_ => gl::RGBA,
+				};
+				
+				// Upload image data, rows of 1 and 3 channel images aren't 4 byte aligned
+				unsafe {
+					gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+					gl::TextureSubImage2D(texture.handle_gl, 0, 0, 0, img_width as gl::sizei, img_height as gl::sizei, pixel_format_gl, gl::UNSIGNED_BYTE, data.as_ptr() as *const gl::void);
+					gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+				}
+				
+				// Generate mipmaps
+				unsafe {
+					gl::TextureParameteri(texture.texture_gl(), gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as gl::int);
+					gl::TextureParameteri(texture.texture_gl(), gl::TEXTURE_MAG_FILTER, gl::LINEAR as gl::int);
+					
+					gl::GenerateTextureMipmap(texture.texture_gl());
+				}
+			}
 		}
 		
-//		// Generate mipmaps
-//		unsafe {
-//			gl::TextureParameteri(texture.texture_gl(), gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as gl::int);
-//			gl::TextureParameteri(texture.texture_gl(), gl::TEXTURE_MAG_FILTER, gl::LINEAR as gl::int);
-//			
-//			gl::GenerateTextureMipmap(texture.texture_gl());
-//		}
-		
-		Ok(texture)
+		texture
 	}
 	
-	#[deprecated]
-	pub fn load_png_from_path(path: &Path, image_format: ImageFormat) -> Result<Texture, Box<dyn error::Error>> {
-		// Load texture
-		let result = lodepng::decode32_file(path)?;
+	/// Decodes the base level of a ktx file. Doesn't touch gl so this can be called from any thread.
+	/// If no image format is given, the internal format from the ktx header is used.
+	pub fn decode_ktx_from_path(path: &Path, image_format: Option<ImageFormat>) -> Result<DecodedTexture, Box<dyn error::Error>> {
+		Texture::decode_ktx(&fs::read(path)?, image_format)
+	}
+	
+	/// Like `decode_ktx_from_path`, for the contents of a ktx file that were already read.
+	pub fn decode_ktx(file_data: &[u8], image_format: Option<ImageFormat>) -> Result<DecodedTexture, Box<dyn error::Error>> {
+		// Read header
+		let ktx_decoder = ktx::Decoder::new(file_data)?;
 		
-		let (img_width, img_height) = (result.width, result.height);
+		// Get header
+		let (img_width, img_height) = (ktx_decoder.pixel_width(), ktx_decoder.pixel_height());
+		let image_format = image_format.unwrap_or_else(|| ImageFormat::get(ktx_decoder.gl_internal_format()));
 		
-		// Allocate texture
-		//let mut texture = Texture::new(img_width as u32, img_height as u32, 8, ImageFormat::get(gl::SRGB8_ALPHA8));
-		let mut texture = Texture::new(img_width as u32, img_height as u32, 8, image_format);
-		texture.allocate();
+		// Read texture data
+		let base_level_data = ktx_decoder.read_textures().next().ok_or("Ktx file contains no texture data")?;
 		
-		// Upload image data
-		unsafe {
-			let data_buffer_ptr = result.buffer.as_ptr();
-			gl::TextureSubImage2D(texture.handle_gl, 0, 0, 0, img_width as gl::sizei, img_height as gl::sizei, gl::RGBA, gl::UNSIGNED_BYTE, data_buffer_ptr as *const gl::void);
-		}
+		Ok(DecodedTexture {
+			width: img_width as u32,
+			height: img_height as u32,
+			image_format,
+			data: DecodedTextureData::Compressed(base_level_data),
+		})
+	}
+	
+	/// Decodes a png file keeping its channels, 8 bits per channel. The image format is chosen with
+	/// `png_image_format` unless one is given. Doesn't touch gl so this can be called from any thread.
+	pub fn decode_png_from_path(path: &Path, image_format: Option<ImageFormat>, srgb: bool) -> Result<DecodedTexture, Box<dyn error::Error>> {
+		Texture::decode_png(&fs::read(path)?, image_format, srgb)
+	}
+	
+	/// Like `decode_png_from_path`, for the contents of a png file that were already read.
+	pub fn decode_png(file_data: &[u8], image_format: Option<ImageFormat>, srgb: bool) -> Result<DecodedTexture, Box<dyn error::Error>> {
+		// Read the header to find out which channels the image has
+		let mut png_decoder = lodepng::Decoder::new();
+		png_decoder.inspect(file_data)?;
 		
-		// Generate mipmaps
-		unsafe {
-			gl::TextureParameteri(texture.texture_gl(), gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as gl::int);
-			gl::TextureParameteri(texture.texture_gl(), gl::TEXTURE_MAG_FILTER, gl::LINEAR as gl::int);
-			
-			gl::GenerateTextureMipmap(texture.texture_gl());
-		}
+		let png_color = &png_decoder.info_png().color;
+		let (color_type, channels) = match png_color.colortype() {
+			ColorType::GREY | ColorType::GREY_ALPHA if png_color.can_have_alpha() => (ColorType::GREY_ALPHA, 2),
+			ColorType::GREY => (ColorType::GREY, 1),
+			ColorType::RGB | ColorType::PALETTE if !png_color.can_have_alpha() => (ColorType::RGB, 3),
+			_ => (ColorType::RGBA, 4),
+		};
 		
-		Ok(texture)
+		// Decode and flatten pixels
+		let mut data = Vec::new();
+		let (width, height) = match lodepng::decode_memory(file_data, color_type, 8)? {
+			Image::Grey(bitmap) => {
+				data.extend(bitmap.buffer.iter().map(|p| p.0));
+				(bitmap.width, bitmap.height)
+			}
+			Image::GreyAlpha(bitmap) => {
+				for p in &bitmap.buffer {
+					data.extend_from_slice(&[p.0, p.1]);
+				}
+				(bitmap.width, bitmap.height)
+			}
+			Image::RGB(bitmap) => {
+				for p in &bitmap.buffer {
+					data.extend_from_slice(&[p.r, p.g, p.b]);
+				}
+				(bitmap.width, bitmap.height)
+			}
+			Image::RGBA(bitmap) => {
+				for p in &bitmap.buffer {
+					data.extend_from_slice(&[p.r, p.g, p.b, p.a]);
+				}
+				(bitmap.width, bitmap.height)
+			}
+			_ => return Err(Box::from("Png decoded to an unexpected pixel type")),
+		};
+		
+		Ok(DecodedTexture {
+			width: width as u32,
+			height: height as u32,
+			image_format: image_format.unwrap_or_else(|| Texture::png_image_format(channels, srgb)),
+			data: DecodedTextureData::Uncompressed {channels, data},
+		})
+	}
+	
+	/// The image format for an uncompressed 8 bit image with the given number of channels.
+	/// There are no srgb formats with less than 3 channels, those are always linear.
+	pub fn png_image_format(channels: u32, srgb: bool) -> ImageFormat {
+		ImageFormat::get(match (channels, srgb) {
+			(1, _) => gl::R8,
+			(2, _) => gl::RG8,
+			(3, false) => gl::RGB8,
+			(3, true) => gl::SRGB8,
+			(_, false) => gl::RGBA8,
+			(_, true) => gl::SRGB8_ALPHA8,
+		})
+	}
+	
+	#[deprecated]
+	pub fn load_ktx_from_path(path: &Path, image_format: ImageFormat) -> Result<Texture, Box<dyn error::Error>> {
+		Ok(Texture::from_decoded(&Texture::decode_ktx_from_path(path, Some(image_format))?))
+	}
+	
+	#[deprecated]
+	pub fn load_png_from_path(path: &Path, image_format: ImageFormat) -> Result<Texture, Box<dyn error::Error>> {
+		Ok(Texture::from_decoded(&Texture::decode_png_from_path(path, Some(image_format), false)?))
 	}
 	
 	/*
@@ -156,3 +264,80 @@ impl Texture {
 	}
 	*/
 }
+
+impl Drop for Texture {
+	fn drop(&mut self) {
+		self.dispose();
+	}
+}
+
+/// Image data decoded on the cpu, waiting to be uploaded into a `Texture`.
+pub struct DecodedTexture {
+	pub width: u32,
+	pub height: u32,
+	pub image_format: ImageFormat,
+	pub data: DecodedTextureData,
+}
+
+pub enum DecodedTextureData {
+	/// Block compressed base level, uploaded as is
+	Compressed(Vec<u8>),
+	/// Uncompressed 8 bit pixels with 1 to 4 channels, mipmaps get generated after upload
+	Uncompressed {
+		channels: u32,
+		data: Vec<u8>,
+	},
+}
+
+/// Loads `.ktx` and `.png` textures for the texture `AssetCache`.
+///
+/// Ktx textures use the internal format stored in their header, png textures
+/// keep their channels and get the matching 8 bit format, srgb if `png_srgb` is set.
+pub struct TextureLoader {
+	pub png_srgb: bool,
+}
+
+impl TextureLoader {
+	/// Reads and decodes a texture file, also hashing its contents for the dependency graph.
+	fn decode(fs_path: &Path, png_srgb: bool) -> Result<(DecodedTexture, ContentHash), AssetLoadError> {
+		let is_ktx = match fs_path.extension().and_then(|e| e.to_str()) {
+			Some("ktx") => true,
+			Some("png") => false,
+			_ => return Err(AssetLoadError::from(format!("Unsupported texture file type: {}", fs_path.display()))),
+		};
+		
+		let decoded = fs::read(fs_path).map_err(Box::<dyn error::Error>::from).and_then(|file_data| {
+			let decoded = if is_ktx {Texture::decode_ktx(&file_data, None)?} else {Texture::decode_png(&file_data, None, png_srgb)?};
+			Ok((decoded, hash_content(&file_data)))
+		});
+		decoded.map_err(|e| AssetLoadError::from(format!("Failed to load texture {}: {}", fs_path.display(), e)))
+	}
+	
+	pub fn new() -> Self {
+		Self {
+			png_srgb: false,
+		}
+	}
+}
+
+impl AssetLoader<Texture> for TextureLoader {
+	fn load(&self, path: &AssetPath) -> Result<Texture, AssetLoadError> {
+		let fs_path = unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(path);
+		let (decoded, content_hash) = Self::decode(&fs_path, self.png_srgb)?;
+		unsafe {&mut ASSET_MANAGER_INSTANCE}.record_asset(path, Some(content_hash), &[]);
+		
+		Ok(Texture::from_decoded(&decoded))
+	}
+	
+	fn load_async(&self, path: &AssetPath, service: &mut AssetLoadService, handle: &LoadHandle<Texture>) {
+		let fs_path = unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(path);
+		let path = path.to_absolute();
+		let png_srgb = self.png_srgb;
+		
+		// Hash on the worker from the bytes it reads anyway, record once uploaded
+		service.load_into(handle, move || Self::decode(&fs_path, png_srgb), move |(decoded, content_hash)| {
+			unsafe {&mut ASSET_MANAGER_INSTANCE}.record_asset(&path.as_path(), Some(content_hash), &[]);
+			Ok(Texture::from_decoded(&decoded))
+		});
+	}
+}
@@ -0,0 +1,445 @@
+use std::error::Error;
+use naga::front::glsl::{Frontend, Options};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use crate::render::shader::ShaderStage;
+use crate::structured_shader_language::{SslDiagnostic, SslSeverity, TranspiledShaderSource};
+
+/// Extensions the validator understands (or that don't change what is valid glsl),
+/// shaders requiring any other extension are skipped.
+const SUPPORTED_EXTENSIONS: &[&str] = &[
+	"GL_ARB_derivative_control",
+	"GL_ARB_shading_language_420pack",
+	"GL_ARB_explicit_uniform_location",
+	"GL_ARB_separate_shader_objects",
+];
+
+/// First location given to interface variables without explicit location,
+/// high enough to not collide with the explicit ones.
+const IMPLICIT_LOCATION_BASE: u32 = 16;
+
+const INTERPOLATION_QUALIFIERS: &[&str] = &["flat", "smooth", "noperspective", "centroid", "sample"];
+
+/// Outcome of validating a shader that didn't have any errors.
+#[derive(Clone, Debug)]
+pub enum GlslValidation {
+	Valid,
+	/// The shader uses something the validator doesn't support,
+	/// the warning points at what and where
+	Skipped(SslDiagnostic),
+}
+
+/// Validates transpiled glsl offline with naga's glsl front end, without needing a gl context.
+///
+/// naga only understands vulkan flavored glsl so the source is adapted first: the `#version`
+/// is raised to 450, combined samplers are split into texture and sampler, loose uniforms are
+/// wrapped into blocks and the gl builtins are renamed. Every line stays on its line so
+/// errors can be mapped back to the ssl files through the source map.
+pub struct GlslValidator {
+	frontend: Frontend,
+}
+
+impl GlslValidator {
+	pub fn validate(&mut self, source: &TranspiledShaderSource, stage: ShaderStage) -> Result<GlslValidation, Vec<SslDiagnostic>> {
+		let naga_stage = match stage {
+			ShaderStage::Vertex => naga::ShaderStage::Vertex,
+			ShaderStage::Fragment => naga::ShaderStage::Fragment,
+			ShaderStage::Compute => naga::ShaderStage::Compute,
+			_ => {
+				// Point at the start of the shader's own file
+				let file_name = source.source_map.file_name(0).unwrap_or("<transpiled>");
+				let message = format!("Skipped validation: {:?} shaders are not supported by the validator", stage);
+				return Ok(GlslValidation::Skipped(SslDiagnostic::warning(file_name, 1, 1, message)));
+			}
+		};
+		
+		// Adapt the source for naga
+		let adapted_source = match adapt_gl_source(&source.source_code) {
+			Ok(adapted_source) => adapted_source,
+			Err((output_line, reason)) => {
+				let diagnostic = diagnostic_at(source, output_line, 1, format!("Skipped validation: {}", reason));
+				return Ok(GlslValidation::Skipped(SslDiagnostic {severity: SslSeverity::Warning, ..diagnostic}));
+			}
+		};
+		
+		// Parse the shader, this already catches syntax and most type errors
+		let module = self.frontend.parse(&Options::from(naga_stage), &adapted_source).map_err(|errors| {
+			errors.errors.iter()
+				.map(|error| {
+					let location = error.meta.location(&adapted_source);
+					diagnostic_at(source, location.line_number, location.line_position, error.kind.to_string())
+				})
+				.collect::<Vec<_>>()
+		})?;
+		
+		// Validate the parsed module
+		let mut validator = Validator::new(ValidationFlags::all(), Capabilities::all());
+		validator.validate(&module).map_err(|error| {
+			let (line, column) = error.location(&adapted_source).map_or((1, 1), |l| (l.line_number, l.line_position));
+			
+			// The interesting part of validation errors is usually at the end of the chain
+			let mut message = error.as_inner().to_string();
+			let mut cause = error.as_inner().source();
+			while let Some(inner) = cause {
+				message.push_str(": ");
+				message.push_str(&inner.to_string());
+				cause = inner.source();
+			}
+			
+			vec![diagnostic_at(source, line, column, message)]
+		})?;
+		
+		Ok(GlslValidation::Valid)
+	}
+	
+	pub fn new() -> Self {
+		Self {
+			frontend: Frontend::default(),
+		}
+	}
+}
+
+/// Makes a diagnostic for a line of the transpiled output, pointing at the ssl file it came from if possible.
+fn diagnostic_at(source: &TranspiledShaderSource, output_line: u32, column: u32, message: String) -> SslDiagnostic {
+	match source.source_map.map_output_line(output_line) {
+		Some((file_name, line)) => SslDiagnostic::new(file_name, line, column, message),
+		None => SslDiagnostic::new("<transpiled>", output_line, column, message),
+	}
+}
+
+/// Rewrites opengl glsl into vulkan glsl naga can parse, keeping every line on its line.
+/// Returns the 1-based line and the reason if the source uses something that can't be adapted.
+pub fn adapt_gl_source(source_code: &str) -> Result<String, (u32, String)> {
+	let mut adapter = SourceAdapter::new();
+	let mut adapted = String::with_capacity(source_code.len() + 256);
+	
+	for (index, line) in source_code.lines().enumerate() {
+		adapter.adapt_line(line, &mut adapted).map_err(|reason| (index as u32 + 1, reason))?;
+		adapted.push('\n');
+	}
+	
+	Ok(adapted)
+}
+
+/// A combined sampler uniform or function parameter that got split into texture and sampler.
+struct SplitSampler {
+	name: String,
+	type_name: String,
+}
+
+struct SourceAdapter {
+	split_samplers: Vec<SplitSampler>,
+	/// Name of the function (or constructor) for every open parenthesis, empty if there is none
+	call_stack: Vec<String>,
+	/// Set after a combined sampler parameter type, the name following it has to be split
+	pending_parameter_type: Option<String>,
+	next_uniform_binding: u32,
+	next_input_location: u32,
+	next_output_location: u32,
+	brace_depth: i32,
+	in_block_comment: bool,
+}
+
+impl SourceAdapter {
+	fn adapt_line(&mut self, line: &str, adapted: &mut String) -> Result<(), String> {
+		// Comments are dropped, they would only get in the way
+		let line = strip_comments(line, &mut self.in_block_comment);
+		let code = line.trim();
+		
+		if code.starts_with("#version") {
+			adapted.push_str("#version 450 core");
+			return Ok(());
+		}
+		if code.starts_with("#extension") {
+			let mut parts = code["#extension".len()..].split(':');
+			let name = parts.next().unwrap_or("").trim();
+			let behavior = parts.next().unwrap_or("").trim();
+			
+			if behavior == "require" && !SUPPORTED_EXTENSIONS.contains(&name) {
+				return Err(format!("Requires {} which the validator doesn't support", name));
+			}
+			return Ok(());
+		}
+		if code.starts_with('#') {
+			adapted.push_str(&line);
+			return Ok(());
+		}
+		
+		// Global uniforms
+		if self.brace_depth == 0 && self.call_stack.is_empty() {
+			if let Some(declaration) = UniformDeclaration::parse(code) {
+				if let Some(texture_type) = texture_type_of(&declaration.type_name) {
+					// Split combined samplers into texture and sampler
+					let binding = declaration.binding.unwrap_or(0);
+					adapted.push_str(&format!("layout(set = 0, binding = {}) uniform {} {}_texture; ", binding, texture_type, declaration.name));
+					adapted.push_str(&format!("layout(set = 1, binding = {}) uniform {} {}_sampler;", binding, sampler_type_of(&declaration.type_name), declaration.name));
+					
+					self.split_samplers.push(SplitSampler {
+						name: declaration.name,
+						type_name: declaration.type_name,
+					});
+				} else if declaration.is_opaque() {
+					adapted.push_str(&line);
+				} else {
+					// Loose uniforms go into a block each
+					adapted.push_str(&format!("layout(set = 2, binding = {}) uniform _Uniform_{} {{ {} {}{}; }};", self.next_uniform_binding, declaration.name, declaration.type_name, declaration.name, declaration.array));
+					self.next_uniform_binding += 1;
+				}
+				return Ok(());
+			}
+			
+			// The linker assigns locations by name in gl, naga needs them explicitly
+			if let Some((direction, num_locations)) = interface_direction(code) {
+				let location = match direction {
+					"in" => &mut self.next_input_location,
+					_ => &mut self.next_output_location,
+				};
+				adapted.push_str(&format!("layout(location = {}) ", *location));
+				*location += num_locations;
+			}
+		}
+		
+		self.adapt_code(&line, adapted)
+	}
+	
+	fn adapt_code(&mut self, line: &str, adapted: &mut String) -> Result<(), String> {
+		let mut chars = line.char_indices().peekable();
+		let mut last_identifier = String::new();
+		
+		while let Some((start, c)) = chars.next() {
+			if !(c.is_ascii_alphabetic() || c == '_') {
+				match c {
+					'(' => self.call_stack.push(last_identifier.clone()),
+					')' => {self.call_stack.pop();},
+					'{' => self.brace_depth += 1,
+					'}' => self.brace_depth -= 1,
+					_ => {},
+				}
+				if !c.is_whitespace() {
+					last_identifier.clear();
+				}
+				adapted.push(c);
+				
+				// Skip the rest of numbers so suffixes aren't taken for identifiers
+				if c.is_ascii_digit() {
+					while let Some((_, c)) = chars.peek() {
+						if !is_identifier_char(*c) {break;}
+						adapted.push(*c);
+						chars.next();
+					}
+				}
+				continue;
+			}
+			
+			let mut end = start + c.len_utf8();
+			while let Some((i, c)) = chars.peek() {
+				if !is_identifier_char(*c) {break;}
+				end = i + c.len_utf8();
+				chars.next();
+			}
+			let identifier = &line[start..end];
+			last_identifier = String::from(identifier);
+			
+			// Name of a combined sampler parameter
+			if let Some(type_name) = self.pending_parameter_type.take() {
+				adapted.push_str(&format!("{}_texture, {} {}_sampler", identifier, sampler_type_of(&type_name), identifier));
+				self.split_samplers.push(SplitSampler {
+					name: String::from(identifier),
+					type_name,
+				});
+				continue;
+			}
+			
+			if is_combined_sampler_type(identifier) && !line[end..].trim_start().starts_with('(') {
+				// Only function parameters can be split
+				if self.brace_depth != 0 || self.call_stack.is_empty() {
+					return Err(format!("Uses {} outside of uniforms and parameters which the validator doesn't support", identifier));
+				}
+				adapted.push_str(&texture_type_of(identifier).unwrap());
+				self.pending_parameter_type = Some(String::from(identifier));
+				continue;
+			}
+			
+			match identifier {
+				"gl_VertexID" => adapted.push_str("int(gl_VertexIndex)"),
+				"gl_InstanceID" => adapted.push_str("int(gl_InstanceIndex)"),
+				_ => match self.split_samplers.iter().find(|s| s.name == identifier) {
+					// Builtins take the combined sampler, own functions the split parameters
+					Some(sampler) => {
+						let callee = self.call_stack.last().map_or("", |c| c.as_str());
+						if callee.starts_with("texture") || callee.starts_with("texel") {
+							adapted.push_str(&format!("{}({}_texture, {}_sampler)", sampler.type_name, identifier, identifier));
+						} else {
+							adapted.push_str(&format!("{}_texture, {}_sampler", identifier, identifier));
+						}
+					}
+					None => adapted.push_str(identifier),
+				}
+			}
+		}
+		
+		Ok(())
+	}
+	
+	fn new() -> Self {
+		Self {
+			split_samplers: Vec::new(),
+			call_stack: Vec::new(),
+			pending_parameter_type: None,
+			next_uniform_binding: 0,
+			next_input_location: IMPLICIT_LOCATION_BASE,
+			next_output_location: IMPLICIT_LOCATION_BASE,
+			brace_depth: 0,
+			in_block_comment: false,
+		}
+	}
+}
+
+/// Returns the code of a line without comments. `in_block_comment` carries
+/// an unterminated block comment over to the next line.
+pub fn strip_comments(line: &str, in_block_comment: &mut bool) -> String {
+	let mut code = String::with_capacity(line.len());
+	let mut rest = line;
+	
+	loop {
+		if *in_block_comment {
+			match rest.find("*/") {
+				Some(end) => {
+					rest = &rest[end + 2..];
+					*in_block_comment = false;
+				}
+				None => return code,
+			}
+		}
+		
+		let line_comment = rest.find("//");
+		let block_comment = rest.find("/*");
+		match (line_comment, block_comment) {
+			(Some(l), Some(b)) if b < l => {
+				code.push_str(&rest[..b]);
+				rest = &rest[b + 2..];
+				*in_block_comment = true;
+			}
+			(None, Some(b)) => {
+				code.push_str(&rest[..b]);
+				rest = &rest[b + 2..];
+				*in_block_comment = true;
+			}
+			(Some(l), _) => {
+				code.push_str(&rest[..l]);
+				return code;
+			}
+			(None, None) => {
+				code.push_str(rest);
+				return code;
+			}
+		}
+	}
+}
+
+/// Returns `in` or `out` and the number of locations taken for a global
+/// interface variable without layout qualifier.
+pub fn interface_direction(code: &str) -> Option<(&'static str, u32)> {
+	if code.starts_with("layout") || code.contains('{') || !code.ends_with(';') {
+		return None;
+	}
+	
+	let mut tokens = code.split_whitespace().skip_while(|t| INTERPOLATION_QUALIFIERS.contains(t));
+	let direction = match tokens.next()? {
+		"in" => "in",
+		"out" => "out",
+		_ => return None,
+	};
+	
+	// Arrays take a location per element
+	let num_locations = match (code.find('['), code.find(']')) {
+		(Some(open), Some(close)) => code[open + 1..close].trim().parse::<u32>().unwrap_or(1),
+		_ => 1,
+	};
+	Some((direction, num_locations))
+}
+
+fn is_identifier_char(c: char) -> bool {
+	c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn is_combined_sampler_type(identifier: &str) -> bool {
+	["sampler", "isampler", "usampler"].iter().any(|p| identifier.starts_with(p) && identifier.len() > p.len())
+}
+
+/// The texture type for the texture half of a combined sampler.
+fn texture_type_of(type_name: &str) -> Option<String> {
+	if !is_combined_sampler_type(type_name) {
+		return None;
+	}
+	Some(type_name.replacen("sampler", "texture", 1).trim_end_matches("Shadow").to_string())
+}
+
+fn sampler_type_of(type_name: &str) -> &'static str {
+	if type_name.ends_with("Shadow") {"samplerShadow"} else {"sampler"}
+}
+
+/// A single line `[layout(...)] uniform <type> <name>[array] [= <initializer>];`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UniformDeclaration {
+	pub binding: Option<u32>,
+	pub type_name: String,
+	pub name: String,
+	/// Array suffix including the brackets, empty if not an array
+	pub array: String,
+}
+
+impl UniformDeclaration {
+	/// Parses a loose uniform, `None` for anything else (including uniform blocks).
+	pub fn parse(code: &str) -> Option<UniformDeclaration> {
+		let mut rest = code;
+		let mut binding = None;
+		
+		// Layout qualifier
+		if rest.starts_with("layout") {
+			let open = rest.find('(')?;
+			let close = rest.find(')')?;
+			
+			for qualifier in rest[open + 1..close].split(',') {
+				let mut parts = qualifier.split('=');
+				if parts.next()?.trim() == "binding" {
+					binding = parts.next()?.trim().parse::<u32>().ok();
+				}
+			}
+			rest = rest[close + 1..].trim_start();
+		}
+		
+		// Blocks are fine as they are
+		if !rest.starts_with("uniform ") || rest.contains('{') || !rest.ends_with(';') {
+			return None;
+		}
+		rest = rest["uniform ".len()..rest.len() - 1].trim();
+		
+		// Drop the initializer, naga doesn't support them
+		if let Some(equals) = rest.find('=') {
+			rest = rest[..equals].trim();
+		}
+		
+		let mut parts = rest.split_whitespace();
+		let type_name = String::from(parts.next()?);
+		let name_and_array = parts.next()?;
+		if parts.next().is_some() {
+			return None;
+		}
+		
+		let (name, array) = match name_and_array.find('[') {
+			Some(bracket) => name_and_array.split_at(bracket),
+			None => (name_and_array, ""),
+		};
+		
+		Some(UniformDeclaration {
+			binding,
+			type_name,
+			name: String::from(name),
+			array: String::from(array),
+		})
+	}
+	
+	pub fn is_opaque(&self) -> bool {
+		["image", "iimage", "uimage", "atomic_uint"].iter().any(|p| self.type_name.starts_with(p))
+	}
+}
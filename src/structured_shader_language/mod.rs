@@ -1,3 +1,9 @@
 mod ssl_transpiler; pub use ssl_transpiler::*;
+mod ssl_include_resolver; pub use ssl_include_resolver::*;
 mod ssl_source_parser; pub use ssl_source_parser::*;
+mod ssl_tokenizer; pub use ssl_tokenizer::*;
+mod ssl_diagnostic; pub use ssl_diagnostic::*;
+mod ssl_source_map; pub use ssl_source_map::*;
 mod parsed_source; pub use parsed_source::*;
+#[cfg(feature = "glsl-validation")] mod glsl_validator;
+#[cfg(feature = "glsl-validation")] pub use glsl_validator::*;
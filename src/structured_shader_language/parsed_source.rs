@@ -1,34 +1,177 @@
+use crate::render::shader::ShaderStage;
+use crate::structured_shader_language::{SslDiagnostic, SourceLocation};
+
 
 pub struct ParsedSource {
+	/// Name of the parsed file, used for diagnostics
+	pub file_name: String,
+	
 	pub shader_type: Option<SourceShaderType>,
+	/// Where the `@shadertype` directive is, if there is one
+	pub shader_type_location: Option<SourceLocation>,
 	
 	pub namespace: Option<String>,
 	pub glsl_version: Option<String>,
 	
-	pub import_declarations: Vec<String>,
+	pub import_declarations: Vec<ImportDeclaration>,
 	
 	/// The full usable "stripped" glsl source code without ssl directives
 	pub source_tree: Vec<SourceToken>,
 	
 	pub exported_functions: Vec<ExportedFunction>,
+	/// Exported structs, constants and blocks, in source order
+	pub exported_declarations: Vec<ExportedDeclaration>,
 }
 
 pub enum SourceToken {
 	TextSource {
 		body: String,
+		/// Line in the original file the body starts at
+		line: u32,
 	},
 	HiddenSource {
 		body: String,
+		/// Line in the original file the body starts at
+		line: u32,
+	},
+	/// The body of an `@exportfunc` block
+	ExportedFunctionSource {
+		body: String,
+		/// Line in the original file the body starts at
+		line: u32,
+		/// Index into `exported_functions`
+		function_index: usize,
 	},
 }
 
+pub struct ImportDeclaration {
+	pub namespace: String,
+	pub location: SourceLocation,
+}
+
 pub struct ExportedFunction {
+	pub name: String,
 	pub signature: String,
+	/// Line in the original file the signature starts at
+	pub line: u32,
 }
 
+/// A declaration copied as a whole into every shader importing the include.
+/// Unlike functions these are not part of the include body itself.
+pub struct ExportedDeclaration {
+	pub kind: ExportKind,
+	/// Name of the struct or block, `None` for constants
+	pub name: Option<String>,
+	pub body: String,
+	/// Line in the original file the body starts at
+	pub line: u32,
+}
+
+/// Kinds of exported declarations, in the order they are emitted in
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum ExportKind {
+	/// Constants and `#define`s
+	Const,
+	Struct,
+	/// Uniform and shader storage blocks
+	Block,
+}
+
+impl ExportKind {
+	pub fn all() -> &'static [ExportKind] {
+		use ExportKind::*;
+		&[Const, Struct, Block]
+	}
+	
+	/// The directive starting an export of this kind
+	pub fn directive_name(&self) -> &'static str {
+		use ExportKind::*;
+		match self {
+			Const => "@exportconst",
+			Struct => "@exportstruct",
+			Block => "@exportblock",
+		}
+	}
+	
+	pub fn from_directive_name(name: &str) -> Option<ExportKind> {
+		Self::all().iter().copied().find(|k| k.directive_name() == name)
+	}
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum SourceShaderType {
 	Include,
 	Vertex,
-	Fragment,
+	TessControl,
 	TessEval,
+	Geometry,
+	Fragment,
+	Compute,
+}
+
+impl SourceShaderType {
+	pub fn all() -> &'static [SourceShaderType] {
+		use SourceShaderType::*;
+		&[Include, Vertex, TessControl, TessEval, Geometry, Fragment, Compute]
+	}
+	
+	/// The name used in the `@shadertype` directive
+	pub fn directive_name(&self) -> &'static str {
+		use SourceShaderType::*;
+		match self {
+			Include => "include",
+			Vertex => "vertex",
+			TessControl => "tesscontrol",
+			TessEval => "tesseval",
+			Geometry => "geometry",
+			Fragment => "fragment",
+			Compute => "compute",
+		}
+	}
+	
+	pub fn from_directive_name(name: &str) -> Option<SourceShaderType> {
+		Self::all().iter().copied().find(|t| t.directive_name() == name)
+	}
+	
+	/// Returns the pipeline stage, or `None` for includes.
+	pub fn as_engine_stage(&self) -> Option<ShaderStage> {
+		use SourceShaderType as S;
+		use ShaderStage as E;
+		
+		match self {
+			S::Include => None,
+			S::Vertex => Some(E::Vertex),
+			S::TessControl => Some(E::TessellationControl),
+			S::TessEval => Some(E::TessellationEval),
+			S::Geometry => Some(E::Geometry),
+			S::Fragment => Some(E::Fragment),
+			S::Compute => Some(E::Compute),
+		}
+	}
+}
+
+impl ParsedSource {
+	/// Checks that the declared `@shadertype` fits the use of the source,
+	/// either as the given pipeline stage or as include (`None`).
+	/// Sources without a `@shadertype` directive fit anywhere.
+	pub fn validate_shader_type(&self, expected_stage: Option<ShaderStage>) -> Result<(), SslDiagnostic> {
+		let shader_type = match self.shader_type {
+			Some(t) => t,
+			None => return Ok(()),
+		};
+		
+		let declared_stage = shader_type.as_engine_stage();
+		if declared_stage == expected_stage {
+			return Ok(());
+		}
+		
+		let message = match (declared_stage, expected_stage) {
+			(None, Some(stage)) => format!("File is an include (`@shadertype include`) and can't be used as {:?} shader", stage),
+			(Some(_), None) => format!("File is a {} shader (`@shadertype {}`) and can't be used as include", shader_type.directive_name(), shader_type.directive_name()),
+			(_, expected) => format!("File is a {} shader (`@shadertype {}`) but is used as {:?} shader", shader_type.directive_name(), shader_type.directive_name(), expected.unwrap()),
+		};
+		
+		let location = self.shader_type_location.unwrap_or(SourceLocation {line: 1, column: 1});
+		Err(SslDiagnostic::new(&self.file_name, location.line, location.column, message))
+	}
 }
@@ -0,0 +1,84 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SslSeverity {
+	Error,
+	/// Doesn't fail anything, e.g. a shader the validator had to skip
+	Warning,
+}
+
+/// A problem found in ssl source code.
+#[derive(Clone, Debug)]
+pub struct SslDiagnostic {
+	pub file: String,
+	/// 1-based line
+	pub line: u32,
+	/// 1-based column
+	pub column: u32,
+	pub severity: SslSeverity,
+	pub message: String,
+}
+
+impl SslDiagnostic {
+	pub fn new(file: &str, line: u32, column: u32, message: String) -> Self {
+		Self {
+			file: String::from(file),
+			line,
+			column,
+			severity: SslSeverity::Error,
+			message,
+		}
+	}
+	
+	pub fn warning(file: &str, line: u32, column: u32, message: String) -> Self {
+		Self {
+			severity: SslSeverity::Warning,
+			..Self::new(file, line, column, message)
+		}
+	}
+}
+
+impl fmt::Display for SslDiagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		let severity = match self.severity {
+			SslSeverity::Error => "error",
+			SslSeverity::Warning => "warning",
+		};
+		write!(f, "{}:{}:{}: {}: {}", self.file, self.line, self.column, severity, self.message)
+	}
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SourceLocation {
+	/// 1-based line
+	pub line: u32,
+	/// 1-based column
+	pub column: u32,
+}
+
+/// Error wrapping all diagnostics of a failed ssl parse or transpile.
+#[derive(Debug)]
+pub struct SslError {
+	pub diagnostics: Vec<SslDiagnostic>,
+}
+
+impl Error for SslError {}
+
+impl fmt::Display for SslError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		write!(f, "{} ssl error(s)", self.diagnostics.len())?;
+		for diagnostic in &self.diagnostics {
+			write!(f, "\n  {}", diagnostic)?;
+		}
+		Ok(())
+	}
+}
+
+impl From<Vec<SslDiagnostic>> for SslError {
+	fn from(diagnostics: Vec<SslDiagnostic>) -> Self {
+		Self {
+			diagnostics,
+		}
+	}
+}
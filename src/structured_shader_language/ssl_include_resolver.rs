@@ -0,0 +1,61 @@
+use std::fs;
+use crate::asset::{AssetPathBuf, ASSET_MANAGER_INSTANCE};
+use crate::structured_shader_language::{ParsedSource, SSLSourceParser, SslDiagnostic};
+
+/// Finds includes by namespace for the transpiler.
+pub trait IncludeResolver {
+	/// Returns the include with the given namespace, `Ok(None)` if there is no such include
+	/// or the diagnostics of the include file if it was found but is broken.
+	fn resolve(&mut self, namespace: &str) -> Result<Option<ParsedSource>, Vec<SslDiagnostic>>;
+}
+
+/// Resolves includes through the asset system.
+///
+/// The namespace `a.b.c` maps to the file `a/b/c.incl.ssl` which is looked
+/// up in each search directory in order, the first existing file wins.
+pub struct AssetIncludeResolver {
+	search_dirs: Vec<AssetPathBuf>,
+}
+
+impl AssetIncludeResolver {
+	pub fn include_file_name(namespace: &str) -> String {
+		format!("{}.incl.ssl", namespace.replace('.', "/"))
+	}
+	
+	pub fn new(search_dirs: Vec<AssetPathBuf>) -> Self {
+		Self {
+			search_dirs,
+		}
+	}
+}
+
+impl IncludeResolver for AssetIncludeResolver {
+	fn resolve(&mut self, namespace: &str) -> Result<Option<ParsedSource>, Vec<SslDiagnostic>> {
+		let include_file_name = Self::include_file_name(namespace);
+		
+		for search_dir in &self.search_dirs {
+			let asset_path = search_dir.as_path().join(&AssetPathBuf::from(&include_file_name).as_path()).unwrap();
+			
+			// Try the next directory if the file doesn't exist
+			let file_path = unsafe {&ASSET_MANAGER_INSTANCE}.resolve_asset_fs_path(&asset_path.as_path());
+			let source_code = match fs::read_to_string(file_path) {
+				Ok(source_code) => source_code,
+				Err(_) => continue,
+			};
+			
+			// Parse the include
+			let file_name = asset_path.as_path().inner_path_slice;
+			let parsed_source = SSLSourceParser::new().parse_source(file_name, &source_code)?;
+			parsed_source.validate_shader_type(None).map_err(|d| vec![d])?;
+			
+			// Make sure the file really declares the namespace it was found by
+			if parsed_source.namespace.as_deref() != Some(namespace) {
+				let message = format!("Include was resolved for namespace `{}` but declares `{}`", namespace, parsed_source.namespace.as_deref().unwrap_or("<none>"));
+				return Err(vec![SslDiagnostic::new(file_name, 1, 1, message)]);
+			}
+			
+			return Ok(Some(parsed_source));
+		}
+		Ok(None)
+	}
+}
@@ -0,0 +1,141 @@
+/// Maps lines of transpiled glsl back to the ssl files they came from.
+///
+/// The transpiler emits a `#line <line> <source string>` directive in front of every
+/// chunk of copied code, the source string number being the index into `files`.
+/// Driver messages like `1(23) : error ...` therefore already carry the original
+/// line and only the file has to be looked up. For tools that see the raw output
+/// (without honoring `#line`) the segments map output lines directly.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+	/// File names by source string number
+	files: Vec<String>,
+	/// Sorted by `output_line`
+	segments: Vec<SourceMapSegment>,
+}
+
+/// A run of output lines copied verbatim from one file.
+#[derive(Copy, Clone, Debug)]
+pub struct SourceMapSegment {
+	/// First output line of the segment (1-based)
+	pub output_line: u32,
+	pub file_index: u32,
+	/// Line in the original file `output_line` corresponds to (1-based)
+	pub source_line: u32,
+}
+
+impl SourceMap {
+	/// Returns the source string number of a file, adding it to the file table if it isn't in there yet.
+	pub fn file_index(&mut self, file_name: &str) -> u32 {
+		if let Some(index) = self.files.iter().position(|f| f == file_name) {
+			return index as u32;
+		}
+		self.files.push(String::from(file_name));
+		(self.files.len() - 1) as u32
+	}
+	
+	pub fn file_name(&self, file_index: u32) -> Option<&str> {
+		self.files.get(file_index as usize).map(|f| f.as_str())
+	}
+	
+	pub fn files(&self) -> &[String] {
+		&self.files
+	}
+	
+	pub fn segments(&self) -> &[SourceMapSegment] {
+		&self.segments
+	}
+	
+	/// Starts a new segment. Segments have to be added in output order.
+	pub fn add_segment(&mut self, segment: SourceMapSegment) {
+		debug_assert!(self.segments.last().map_or(true, |s| s.output_line <= segment.output_line), "Source map segments out of order");
+		self.segments.push(segment);
+	}
+	
+	/// Moves all segments down by `offset` lines, for when lines were inserted in front of them.
+	pub fn offset_output_lines(&mut self, offset: u32) {
+		for segment in &mut self.segments {
+			segment.output_line += offset;
+		}
+	}
+	
+	/// Maps a line of the raw transpiled output to file name and original line.
+	/// Lines in front of the first segment (the generated header) have no origin.
+	pub fn map_output_line(&self, output_line: u32) -> Option<(&str, u32)> {
+		let segment_index = match self.segments.binary_search_by_key(&output_line, |s| s.output_line) {
+			Ok(index) => index,
+			Err(0) => return None,
+			Err(index) => index - 1,
+		};
+		let segment = &self.segments[segment_index];
+		
+		let file_name = self.file_name(segment.file_index)?;
+		Some((file_name, segment.source_line + (output_line - segment.output_line)))
+	}
+	
+	/// Rewrites the locations in a driver info log from `<source string>(<line>)`
+	/// or `<source string>:<line>` into `<file>:<line>`.
+	///
+	/// Covers the formats of the common drivers (`0(12) : error`, `0:12(5): error`,
+	/// `ERROR: 0:12: ...`), lines without a known location are left as they are.
+	pub fn rewrite_info_log(&self, info_log: &str) -> String {
+		let mut rewritten = String::with_capacity(info_log.len());
+		
+		for log_line in info_log.lines() {
+			match find_log_location(log_line) {
+				Some((start, end, file_index, line)) if self.file_name(file_index).is_some() => {
+					rewritten.push_str(&log_line[..start]);
+					rewritten.push_str(&format!("{}:{}", self.file_name(file_index).unwrap(), line));
+					rewritten.push_str(&log_line[end..]);
+				}
+				_ => rewritten.push_str(log_line),
+			}
+			rewritten.push('\n');
+		}
+		
+		rewritten
+	}
+	
+	pub fn new() -> Self {
+		Self {
+			files: Vec::new(),
+			segments: Vec::new(),
+		}
+	}
+}
+
+/// Finds the first `<number>(<number>)` or `<number>:<number>` in a log line
+/// and returns its byte range together with source string number and line.
+fn find_log_location(log_line: &str) -> Option<(usize, usize, u32, u32)> {
+	let bytes = log_line.as_bytes();
+	
+	fn digit_run(bytes: &[u8], start: usize) -> usize {
+		bytes[start..].iter().take_while(|b| b.is_ascii_digit()).count()
+	}
+	
+	let mut i = 0;
+	while i < bytes.len() {
+		// Only start at the beginning of a number
+		if !bytes[i].is_ascii_digit() || (i > 0 && bytes[i - 1].is_ascii_alphanumeric()) {
+			i += 1;
+			continue;
+		}
+		
+		let source_len = digit_run(bytes, i);
+		let separator = i + source_len;
+		
+		if separator + 1 < bytes.len() && (bytes[separator] == b'(' || bytes[separator] == b':') {
+			let line_len = digit_run(bytes, separator + 1);
+			let line_end = separator + 1 + line_len;
+			
+			let closed = bytes[separator] == b':' || (line_end < bytes.len() && bytes[line_end] == b')');
+			if line_len > 0 && closed {
+				let source_string = log_line[i..separator].parse::<u32>().ok()?;
+				let line = log_line[separator + 1..line_end].parse::<u32>().ok()?;
+				let end = if bytes[separator] == b'(' {line_end + 1} else {line_end};
+				return Some((i, end, source_string, line));
+			}
+		}
+		i += source_len;
+	}
+	None
+}
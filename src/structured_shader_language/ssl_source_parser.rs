@@ -1,140 +1,337 @@
-use crate::structured_shader_language::{ParsedSource, SourceToken, ExportedFunction};
-use std::ops::Index;
+use crate::structured_shader_language::{ParsedSource, SourceToken, SourceShaderType, SourceLocation, ImportDeclaration, ExportedFunction, ExportedDeclaration, ExportKind, SslDiagnostic, SslTokenizer, Token, TokenKind};
 
 pub struct SSLSourceParser {}
 
+/// Block opened by a directive and closed by `@end`
+enum OpenBlock {
+	None,
+	ExportFunc {
+		line: u32,
+		column: u32,
+		/// Signature collected so far, `None` once the opening brace was found
+		signature: Option<String>,
+		/// Line of the first token of the signature
+		signature_line: Option<u32>,
+	},
+	Hide {
+		line: u32,
+		column: u32,
+	},
+	ExportDecl {
+		line: u32,
+		column: u32,
+		kind: ExportKind,
+	},
+}
+
+/// Finds the name of a function from its signature (the last identifier before the parameter list).
+fn find_function_name(signature: &str) -> Option<String> {
+	let mut last_ident = None;
+	for token in SslTokenizer::new(signature).filter(|t| !t.is_trivia()) {
+		if token.is_punct('(') {
+			return last_ident.map(String::from);
+		}
+		if token.kind == TokenKind::Ident {
+			last_ident = Some(token.text);
+		}
+	}
+	None
+}
+
+/// Finds the name of an exported struct (the identifier after `struct`)
+/// or block (the last identifier before the opening brace).
+fn find_declaration_name(kind: ExportKind, body: &str) -> Option<String> {
+	let mut tokens = SslTokenizer::new(body).filter(|t| !t.is_trivia());
+	
+	match kind {
+		ExportKind::Const => None,
+		ExportKind::Struct => {
+			tokens.find(|t| t.kind == TokenKind::Ident && t.text == "struct")?;
+			tokens.next().filter(|t| t.kind == TokenKind::Ident).map(|t| String::from(t.text))
+		}
+		ExportKind::Block => {
+			let mut last_ident = None;
+			for token in tokens {
+				if token.is_punct('{') {
+					return last_ident.map(String::from);
+				}
+				if token.kind == TokenKind::Ident {
+					last_ident = Some(token.text);
+				}
+			}
+			None
+		}
+	}
+}
+
 impl SSLSourceParser {
-	pub fn parse_source(&mut self, source_code: String) -> ParsedSource {
-		// Allocate full body string
-		//let mut full_body_buffer = String::with_capacity(source_code.len());
+	/// Parses ssl source code. `file_name` is only used for diagnostics.
+	///
+	/// All problems in the source are collected and returned together
+	/// instead of stopping at the first one.
+	pub fn parse_source(&mut self, file_name: &str, source_code: &str) -> Result<ParsedSource, Vec<SslDiagnostic>> {
+		let mut diagnostics = Vec::<SslDiagnostic>::new();
+		let mut error = |token: &Token, message: String| {
+			diagnostics.push(SslDiagnostic::new(file_name, token.line, token.column, message));
+		};
+		
 		let mut token_tree = Vec::<SourceToken>::new();
 		let mut current_body_buffer = String::new();
+		// Original line the current body starts at
+		let mut current_body_line = 1u32;
 		
+		let mut shader_type = Option::<SourceShaderType>::None;
+		let mut shader_type_location = Option::<SourceLocation>::None;
 		let mut shader_namespace = Option::<String>::None;
 		let mut glsl_version = Option::<String>::None;
 		
-		let mut import_declarations = Vec::<String>::new();
+		let mut import_declarations = Vec::<ImportDeclaration>::new();
 		
 		let mut export_func_list = Vec::<ExportedFunction>::new();
+		let mut export_decl_list = Vec::<ExportedDeclaration>::new();
 		
-		let mut inside_export_func = false;
-		let mut expect_export_func_signature = false;
-//		let mut export_func_signature = Option::<String>::None;
-		
-		let mut inside_hide_block = false;
+		let mut open_block = OpenBlock::None;
 		
-		// Split lines
-		let lines = source_code.lines();
-		
-		// Iterate over source lines
-		for line in lines {
-			// Check for ssl directive
-			if line.trim_start().starts_with("@") {
-				// Process ssl directive
-				let mut split_line = line.split_ascii_whitespace();
+		let mut tokens = SslTokenizer::new(source_code).peekable();
+		while let Some(token) = tokens.next() {
+			if token.kind != TokenKind::Directive {
+				if token.is_punct('@') {
+					error(&token, String::from("Ssl directives must be the first thing on a line"));
+				}
 				
-				let directive = split_line.next();
+				// Collect the signature of the current exported function
+				if let OpenBlock::ExportFunc {signature: signature @ Some(_), signature_line, ..} = &mut open_block {
+					if token.is_punct('{') {
+						let finished_signature = signature.take().unwrap();
+						let finished_signature = finished_signature.trim();
+						
+						if finished_signature.is_empty() {
+							error(&token, String::from("Expected function signature before '{'"));
+						}
+						else if let Some(name) = find_function_name(finished_signature) {
+							export_func_list.push(ExportedFunction {
+								name,
+								signature: String::from(finished_signature),
+								line: signature_line.unwrap_or(token.line),
+							});
+						}
+						else {
+							error(&token, String::from("Could not find the function name in the signature"));
+						}
+					}
+					else if token.is_punct(';') {
+						error(&token, String::from("Exported function has no body (found ';' before '{')"));
+						*signature = None;
+					}
+					else if token.is_trivia() {
+						// Collapse all whitespace and comments into single spaces
+						let signature = signature.as_mut().unwrap();
+						if !signature.is_empty() && !signature.ends_with(' ') {
+							signature.push(' ');
+						}
+					}
+					else {
+						signature.as_mut().unwrap().push_str(token.text);
+						signature_line.get_or_insert(token.line);
+					}
+				}
+				
+				// Check for unterminated block comments
+				if token.kind == TokenKind::BlockComment && !token.text.ends_with("*/") {
+					error(&token, String::from("Unterminated block comment"));
+				}
 				
-				#[allow(unused)]
-				let mut valid_directive = true;
-				if let Some(directive) = directive {
-					if directive.eq("@shadertype") {
-						// TODO: Implement
-					}
-					else if directive.eq("@glslversion") {
-						let version_string = format!("{} {}", split_line.next().unwrap(), split_line.next().unwrap_or(""));
-						glsl_version = Some(version_string);
-					} 
-					else if directive.eq("@namespace") {
-						shader_namespace = Some(String::from(split_line.next().unwrap()));
-					}
-					else if directive.eq("@import") {
-						if let Some(import) = split_line.next() {
-							import_declarations.push(String::from(import));
+				// Simply append token to buffer
+				current_body_buffer.push_str(token.text);
+				continue;
+			}
+			
+			// Collect the directive arguments (up to the end of the line, ignoring comments)
+			let mut args = Vec::<String>::new();
+			let mut current_arg = String::new();
+			let mut directive_newline = Option::<&str>::None;
+			while let Some(arg_token) = tokens.peek() {
+				match arg_token.kind {
+					TokenKind::Newline => {
+						directive_newline = Some(arg_token.text);
+						tokens.next();
+						break;
+					}
+					TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment => {
+						if !current_arg.is_empty() {
+							args.push(std::mem::replace(&mut current_arg, String::new()));
 						}
 					}
-					else if directive.eq("@exportfunc") {
-						// End current normal text block
-						token_tree.push(SourceToken::TextSource {body: current_body_buffer});
-						current_body_buffer = String::new();
-						
-						inside_export_func = true;
-						expect_export_func_signature = true;
+					_ => current_arg.push_str(arg_token.text),
+				}
+				tokens.next();
+			}
+			if !current_arg.is_empty() {
+				args.push(current_arg);
+			}
+			
+			// Remove the indentation in front of the directive from the body
+			let trimmed_len = current_body_buffer.trim_end_matches(|c| c == ' ' || c == '\t').len();
+			current_body_buffer.truncate(trimmed_len);
+			
+			// Keep the directive line as empty line so line numbers inside the bodies stay the same as in the file
+			if let Some(newline) = directive_newline {
+				current_body_buffer.push_str(newline);
+			}
+			let next_body_line = token.line + 1;
+			
+			// Process ssl directive
+			let directive = token.text;
+			match directive {
+				"@shadertype" => {
+					if args.len() != 1 {
+						error(&token, String::from("@shadertype expects exactly one shader type"));
 					}
-					else if directive.eq("@hide") {
-						// End current normal text block
-						token_tree.push(SourceToken::TextSource {body: current_body_buffer});
-						current_body_buffer = String::new();
-						
-						inside_hide_block = true;
+					else if shader_type.is_some() {
+						error(&token, String::from("Duplicate @shadertype directive"));
+					}
+					else if let Some(parsed_type) = SourceShaderType::from_directive_name(&args[0]) {
+						shader_type = Some(parsed_type);
+						shader_type_location = Some(SourceLocation {line: token.line, column: token.column});
+					}
+					else {
+						let valid_names = SourceShaderType::all().iter().map(|t| t.directive_name()).collect::<Vec<_>>();
+						error(&token, format!("Unknown shader type `{}` (expected one of: {})", args[0], valid_names.join(", ")));
+					}
+				}
+				"@glslversion" => {
+					if args.is_empty() || args.len() > 2 {
+						error(&token, String::from("@glslversion expects a version and an optional profile (e.g. `@glslversion 430 core`)"));
+					}
+					else if glsl_version.is_some() {
+						error(&token, String::from("Duplicate @glslversion directive"));
+					}
+					else {
+						glsl_version = Some(args.join(" "));
+					}
+				}
+				"@namespace" => {
+					if args.len() != 1 {
+						error(&token, String::from("@namespace expects exactly one namespace"));
+					}
+					else if shader_namespace.is_some() {
+						error(&token, String::from("Duplicate @namespace directive"));
+					}
+					else {
+						shader_namespace = args.pop();
+					}
+				}
+				"@import" => {
+					if args.len() != 1 {
+						error(&token, String::from("@import expects exactly one namespace"));
+					}
+					else {
+						import_declarations.push(ImportDeclaration {
+							namespace: args.pop().unwrap(),
+							location: SourceLocation {line: token.line, column: token.column},
+						});
+					}
+				}
+				"@exportfunc" | "@hide" | "@exportconst" | "@exportstruct" | "@exportblock" => {
+					match open_block {
+						OpenBlock::None => {
+							// End current normal text block
+							token_tree.push(SourceToken::TextSource {body: current_body_buffer, line: current_body_line});
+							current_body_buffer = String::new();
+							current_body_line = next_body_line;
+							
+							open_block = if directive == "@exportfunc" {
+								OpenBlock::ExportFunc {line: token.line, column: token.column, signature: Some(String::new()), signature_line: None}
+							}
+							else if directive == "@hide" {
+								OpenBlock::Hide {line: token.line, column: token.column}
+							}
+							else {
+								OpenBlock::ExportDecl {line: token.line, column: token.column, kind: ExportKind::from_directive_name(directive).unwrap()}
+							};
+						}
+						_ => error(&token, format!("{} inside of another block (missing @end?)", directive)),
 					}
-					else if directive.eq("@end") {
-						if inside_export_func {
-							inside_export_func = false;
+				}
+				"@end" => {
+					match open_block {
+						OpenBlock::ExportFunc {signature, ..} => {
+							if signature.is_some() {
+								error(&token, String::from("@exportfunc block ended before the function body started"));
+							}
 							
 							// Push onto tree
-							token_tree.push(SourceToken::TextSource {body: current_body_buffer});
+							let function_index = export_func_list.len().saturating_sub(1);
+							token_tree.push(SourceToken::ExportedFunctionSource {body: current_body_buffer, line: current_body_line, function_index});
 							current_body_buffer = String::new();
+							current_body_line = next_body_line;
 						}
-						else if inside_hide_block {
-							inside_hide_block = false;
-							
+						OpenBlock::Hide {..} => {
 							// Push onto tree
-							token_tree.push(SourceToken::HiddenSource {body: current_body_buffer});
+							token_tree.push(SourceToken::HiddenSource {body: current_body_buffer, line: current_body_line});
 							current_body_buffer = String::new();
+							current_body_line = next_body_line;
 						}
+						OpenBlock::ExportDecl {line, kind, ..} => {
+							let body = String::from(current_body_buffer.trim_end());
+							let name = find_declaration_name(kind, &body);
+							
+							if body.trim().is_empty() {
+								error(&token, format!("Empty {} block (starting at line {})", kind.directive_name(), line));
+							}
+							else if kind != ExportKind::Const && name.is_none() {
+								error(&token, format!("Could not find the declared name in {} block (starting at line {})", kind.directive_name(), line));
+							}
+							
+							// Declarations are emitted by the transpiler, not as part of the body
+							export_decl_list.push(ExportedDeclaration {kind, name, body, line: current_body_line});
+							current_body_buffer = String::new();
+							current_body_line = next_body_line;
+						}
+						OpenBlock::None => error(&token, String::from("@end without a matching block directive")),
 					}
-					else {
-						// Unknown directive
-						valid_directive = false;
-					}
+					open_block = OpenBlock::None;
 				}
-				else {
-					// Blank directive is invalid
-					valid_directive = false;
-				};
-				
-				// DEBUG: Prevent unused var
-				drop(valid_directive);
-			}
-			else {
-				// Get export func signature
-				if inside_export_func && expect_export_func_signature {
-					expect_export_func_signature = false;
-					
-					// Get signature (TODO: This is super bad, but works for now)
-					let signature = String::from(line.index(..line.find("{").unwrap()).trim());
-					export_func_list.push(ExportedFunction {signature})
-				}
-				
-				// Simply append line to buffer
-				current_body_buffer.push_str(line);
-				current_body_buffer.push('\n');
+				_ => error(&token, format!("Unknown directive {}", directive)),
 			}
 		}
 		
 		// End last block
-		if !current_body_buffer.is_empty() {
-			if inside_hide_block {
-				// Push onto tree
-				token_tree.push(SourceToken::HiddenSource {body: current_body_buffer});
+		match open_block {
+			OpenBlock::None => {
+				if !current_body_buffer.is_empty() {
+					// Push onto tree
+					token_tree.push(SourceToken::TextSource {body: current_body_buffer, line: current_body_line});
+				}
+			}
+			OpenBlock::ExportFunc {line, column, ..} => {
+				diagnostics.push(SslDiagnostic::new(file_name, line, column, String::from("Unterminated @exportfunc block (missing @end)")));
+			}
+			OpenBlock::Hide {line, column} => {
+				diagnostics.push(SslDiagnostic::new(file_name, line, column, String::from("Unterminated @hide block (missing @end)")));
 			}
-			else {
-				// Push onto tree
-				token_tree.push(SourceToken::TextSource {body: current_body_buffer});
+			OpenBlock::ExportDecl {line, column, kind} => {
+				diagnostics.push(SslDiagnostic::new(file_name, line, column, format!("Unterminated {} block (missing @end)", kind.directive_name())));
 			}
 		}
 		
+		if !diagnostics.is_empty() {
+			return Err(diagnostics);
+		}
+		
 		// Make parsed source
 		let parsed_source = ParsedSource {
-			shader_type: None,
+			file_name: String::from(file_name),
+			shader_type,
+			shader_type_location,
 			namespace: shader_namespace,
 			glsl_version,
 			import_declarations,
 			exported_functions: export_func_list,
+			exported_declarations: export_decl_list,
 			source_tree: token_tree,
 		};
-		parsed_source
+		Ok(parsed_source)
 	}
 	
 	pub fn new() -> Self {
@@ -0,0 +1,175 @@
+use std::str::CharIndices;
+use std::iter::Peekable;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum TokenKind {
+	/// An ssl directive like `@import`, only recognized as first token on a line
+	Directive,
+	Ident,
+	Number,
+	/// Any other single character (operators, braces, `#`, ...)
+	Punct,
+	Whitespace,
+	Newline,
+	LineComment,
+	/// A block comment, may be unterminated at the end of the source
+	BlockComment,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Token<'s> {
+	pub kind: TokenKind,
+	pub text: &'s str,
+	/// 1-based line
+	pub line: u32,
+	/// 1-based column in chars
+	pub column: u32,
+}
+
+impl<'s> Token<'s> {
+	pub fn is_punct(&self, c: char) -> bool {
+		self.kind == TokenKind::Punct && self.text.starts_with(c)
+	}
+	
+	pub fn is_trivia(&self) -> bool {
+		match self.kind {
+			TokenKind::Whitespace | TokenKind::Newline | TokenKind::LineComment | TokenKind::BlockComment => true,
+			_ => false,
+		}
+	}
+}
+
+/// Lossless tokenizer for ssl source code.
+///
+/// Every char of the source ends up in exactly one token so
+/// concatenating all token texts gives back the original source.
+pub struct SslTokenizer<'s> {
+	source: &'s str,
+	chars: Peekable<CharIndices<'s>>,
+	line: u32,
+	column: u32,
+	at_line_start: bool,
+}
+
+impl<'s> SslTokenizer<'s> {
+	fn bump(&mut self) -> Option<char> {
+		let (_, c) = self.chars.next()?;
+		if c == '\n' {
+			self.line += 1;
+			self.column = 1;
+		}
+		else {
+			self.column += 1;
+		}
+		Some(c)
+	}
+	
+	fn peek_char(&mut self) -> Option<char> {
+		self.chars.peek().map(|(_, c)| *c)
+	}
+	
+	fn peek_second_char(&self) -> Option<char> {
+		let mut ahead = self.chars.clone();
+		ahead.next();
+		ahead.next().map(|(_, c)| c)
+	}
+	
+	fn current_pos(&mut self) -> usize {
+		self.chars.peek().map_or(self.source.len(), |(i, _)| *i)
+	}
+	
+	fn bump_while<F: Fn(char) -> bool>(&mut self, predicate: F) {
+		while let Some(c) = self.peek_char() {
+			if !predicate(c) {
+				break;
+			}
+			self.bump();
+		}
+	}
+	
+	fn is_ident_char(c: char) -> bool {
+		c.is_ascii_alphanumeric() || c == '_'
+	}
+	
+	pub fn new(source: &'s str) -> Self {
+		Self {
+			source,
+			chars: source.char_indices().peekable(),
+			line: 1,
+			column: 1,
+			at_line_start: true,
+		}
+	}
+}
+
+impl<'s> Iterator for SslTokenizer<'s> {
+	type Item = Token<'s>;
+	
+	fn next(&mut self) -> Option<Token<'s>> {
+		let start = self.current_pos();
+		let (line, column) = (self.line, self.column);
+		let first = self.bump()?;
+		
+		let kind = match first {
+			'\n' => TokenKind::Newline,
+			'\r' if self.peek_char() == Some('\n') => {
+				self.bump();
+				TokenKind::Newline
+			}
+			c if c.is_whitespace() => {
+				self.bump_while(|c| c.is_whitespace() && c != '\n' && c != '\r');
+				TokenKind::Whitespace
+			}
+			'/' if self.peek_char() == Some('/') => {
+				self.bump_while(|c| c != '\n' && c != '\r');
+				TokenKind::LineComment
+			}
+			'/' if self.peek_char() == Some('*') => {
+				self.bump();
+				
+				// Read until the closing `*/` or the end of the source
+				while let Some(c) = self.bump() {
+					if c == '*' && self.peek_char() == Some('/') {
+						self.bump();
+						break;
+					}
+				}
+				TokenKind::BlockComment
+			}
+			'@' if self.at_line_start && self.peek_char().map_or(false, Self::is_ident_char) => {
+				self.bump_while(Self::is_ident_char);
+				TokenKind::Directive
+			}
+			c if c.is_ascii_digit() || (c == '.' && self.peek_char().map_or(false, |c| c.is_ascii_digit())) => {
+				// Good enough for glsl literals like `1.0e-5f` or `0xffu`
+				loop {
+					match self.peek_char() {
+						Some(c) if Self::is_ident_char(c) || c == '.' => {self.bump();},
+						Some('+') | Some('-') if self.source[start..self.current_pos()].ends_with(|c| c == 'e' || c == 'E') && self.peek_second_char().map_or(false, |c| c.is_ascii_digit()) => {self.bump();},
+						_ => break,
+					}
+				}
+				TokenKind::Number
+			}
+			c if Self::is_ident_char(c) => {
+				self.bump_while(Self::is_ident_char);
+				TokenKind::Ident
+			}
+			_ => TokenKind::Punct,
+		};
+		
+		// Directives are only valid if nothing but whitespace came before them on the line
+		match kind {
+			TokenKind::Newline => self.at_line_start = true,
+			TokenKind::Whitespace => {},
+			_ => self.at_line_start = false,
+		}
+		
+		Some(Token {
+			kind,
+			text: &self.source[start..self.current_pos()],
+			line,
+			column,
+		})
+	}
+}
@@ -1,19 +1,223 @@
-use crate::structured_shader_language::{ParsedSource, SourceToken};
+use crate::structured_shader_language::{ParsedSource, SourceToken, SslDiagnostic, IncludeResolver, SourceMap, SourceMapSegment, ExportKind, SslTokenizer, Token, TokenKind};
+use std::collections::{HashMap, HashSet};
 use std::fmt::{self, Write};
+use std::rc::Rc;
 
-pub struct SSLTranspiler<'a> {
-	import_scope: Vec<&'a ParsedSource>,
+pub struct SSLTranspiler {
+	import_scope: Vec<Rc<ParsedSource>>,
+	include_resolver: Option<Box<dyn IncludeResolver>>,
+	strip_unused_functions: bool,
 }
 
-impl<'a> SSLTranspiler<'a> {
+/// Dfs state of an include during import resolution
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum VisitState {
+	Visiting,
+	Done,
+}
+
+impl SSLTranspiler {
 	/// Adds the given include into the import scope.
-	pub fn add_include(&mut self, include: &'a ParsedSource) {
+	pub fn add_include(&mut self, include: Rc<ParsedSource>) {
 		self.import_scope.push(include);
 	}
 	
+	/// Sets the resolver used to find includes that aren't in the import scope yet.
+	/// Resolved includes are added to the import scope so they are only loaded once.
+	pub fn set_include_resolver(&mut self, resolver: Box<dyn IncludeResolver>) {
+		self.include_resolver = Some(resolver);
+	}
+	
+	/// Sets if exported functions of includes that the shader never calls are left out (on by default).
+	/// Turning this off is only useful for debugging the transpiler.
+	pub fn set_strip_unused_functions(&mut self, strip: bool) {
+		self.strip_unused_functions = strip;
+	}
+	
+	/// All includes known to the transpiler, including the ones found by the resolver.
+	pub fn import_scope(&self) -> &[Rc<ParsedSource>] {
+		&self.import_scope
+	}
+	
+	/// Finds the include with the given namespace in the import scope or, failing that, through the resolver.
+	fn find_include(&mut self, namespace: &str) -> Result<Option<Rc<ParsedSource>>, Vec<SslDiagnostic>> {
+		if let Some(include) = self.import_scope.iter().find(|i| i.namespace.as_deref() == Some(namespace)) {
+			return Ok(Some(Rc::clone(include)));
+		}
+		
+		if let Some(resolver) = &mut self.include_resolver {
+			if let Some(include) = resolver.resolve(namespace)? {
+				let include = Rc::new(include);
+				self.import_scope.push(Rc::clone(&include));
+				return Ok(Some(include));
+			}
+		}
+		Ok(None)
+	}
+	
+	/// Resolves all imports of `source`, transitively, and returns the
+	/// includes in dependency order (every include comes after its own imports).
+	pub fn resolve_imports(&mut self, source: &ParsedSource) -> Result<Vec<Rc<ParsedSource>>, Vec<SslDiagnostic>> {
+		let mut ordered = Vec::<Rc<ParsedSource>>::new();
+		let mut visit_states = HashMap::<String, VisitState>::new();
+		let mut diagnostics = Vec::<SslDiagnostic>::new();
+		
+		// The chain of namespaces currently being visited, for cycle errors
+		let mut import_chain = Vec::<String>::new();
+		if let Some(namespace) = &source.namespace {
+			import_chain.push(namespace.clone());
+			visit_states.insert(namespace.clone(), VisitState::Visiting);
+		}
+		
+		fn visit(transpiler: &mut SSLTranspiler, importer: &ParsedSource, ordered: &mut Vec<Rc<ParsedSource>>, visit_states: &mut HashMap<String, VisitState>, import_chain: &mut Vec<String>, diagnostics: &mut Vec<SslDiagnostic>) {
+			for import in &importer.import_declarations {
+				match visit_states.get(&import.namespace) {
+					Some(VisitState::Done) => continue,
+					Some(VisitState::Visiting) => {
+						let cycle_start = import_chain.iter().position(|n| n == &import.namespace).unwrap_or(0);
+						let cycle = import_chain[cycle_start..].join(" -> ");
+						diagnostics.push(SslDiagnostic::new(&importer.file_name, import.location.line, import.location.column, format!("Import cycle: {} -> {}", cycle, import.namespace)));
+						continue;
+					}
+					None => {},
+				}
+				
+				let include = match transpiler.find_include(&import.namespace) {
+					Ok(Some(include)) => include,
+					Ok(None) => {
+						diagnostics.push(SslDiagnostic::new(&importer.file_name, import.location.line, import.location.column, format!("Unresolved import `{}`", import.namespace)));
+						visit_states.insert(import.namespace.clone(), VisitState::Done);
+						continue;
+					}
+					Err(include_diagnostics) => {
+						diagnostics.extend(include_diagnostics);
+						visit_states.insert(import.namespace.clone(), VisitState::Done);
+						continue;
+					}
+				};
+				
+				// Visit the imports of the include first
+				visit_states.insert(import.namespace.clone(), VisitState::Visiting);
+				import_chain.push(import.namespace.clone());
+				
+				visit(transpiler, &include, ordered, visit_states, import_chain, diagnostics);
+				
+				import_chain.pop();
+				visit_states.insert(import.namespace.clone(), VisitState::Done);
+				ordered.push(include);
+			}
+		}
+		
+		visit(self, source, &mut ordered, &mut visit_states, &mut import_chain, &mut diagnostics);
+		
+		if diagnostics.is_empty() {
+			Ok(ordered)
+		}
+		else {
+			Err(diagnostics)
+		}
+	}
+	
+	/// Finds the names of all exported include functions that can be called from the shader.
+	///
+	/// The calls are followed from `main` through the function definitions, across includes.
+	/// Code that is emitted no matter what (the shader's own source and include code outside
+	/// of exported functions) has to compile as well, so the functions defined there and the
+	/// calls in global code like macros are roots too. Overloads share a name and are kept
+	/// or stripped together.
+	pub fn find_reachable_functions(source: &ParsedSource, includes: &[Rc<ParsedSource>]) -> HashSet<String> {
+		// Collect all code with a flag if it's emitted unconditionally
+		let mut code = Vec::<(&str, bool)>::new();
+		for token in &source.source_tree {
+			match token {
+				SourceToken::TextSource {body, ..} | SourceToken::HiddenSource {body, ..} | SourceToken::ExportedFunctionSource {body, ..} => code.push((body, true)),
+			}
+		}
+		for include in includes {
+			for token in &include.source_tree {
+				match token {
+					SourceToken::TextSource {body, ..} => code.push((body, true)),
+					SourceToken::ExportedFunctionSource {body, ..} => code.push((body, false)),
+					SourceToken::HiddenSource {..} => {},
+				}
+			}
+			// Macros in exported declarations might call functions too
+			for declaration in &include.exported_declarations {
+				code.push((&declaration.body, true));
+			}
+		}
+		
+		// Build the call graph
+		let mut calls = HashMap::<&str, Vec<&str>>::new();
+		let mut roots = vec!["main"];
+		for (code, always_emitted) in code {
+			let scan = scan_calls(code);
+			for (name, callees) in scan.definitions {
+				if always_emitted {
+					roots.push(name);
+				}
+				calls.entry(name).or_insert_with(Vec::new).extend(callees);
+			}
+			if always_emitted {
+				roots.extend(scan.global_calls);
+			}
+		}
+		
+		// Follow the calls
+		let mut reachable = HashSet::<&str>::new();
+		let mut worklist = roots;
+		while let Some(name) = worklist.pop() {
+			if let Some(callees) = calls.get(name) {
+				if reachable.insert(name) {
+					worklist.extend(callees);
+				}
+			}
+		}
+		
+		// Only the exported functions can be stripped
+		includes.iter()
+			.flat_map(|i| i.exported_functions.iter())
+			.filter(|f| reachable.contains(f.name.as_str()))
+			.map(|f| f.name.clone())
+			.collect()
+	}
+	
 	#[allow(unused_must_use)] // DEBUG: Allow unused fmt results for now
-	pub fn transpile(&mut self, source: &ParsedSource) -> TranspiledShaderSource {
+	pub fn transpile(&mut self, source: &ParsedSource) -> Result<TranspiledShaderSource, Vec<SslDiagnostic>> {
+		// Resolve all (transitive) imports
+		let includes = self.resolve_imports(source)?;
+		
+		// Find the functions that are actually used (`None` keeps all)
+		let reachable_functions = if self.strip_unused_functions {
+			Some(Self::find_reachable_functions(source, &includes))
+		}
+		else {
+			None
+		};
+		let is_function_used = |name: &str| reachable_functions.as_ref().map_or(true, |r| r.contains(name));
+		
 		let mut buffer = String::new();
+		let mut source_map = SourceMap::new();
+		
+		// Assign the source string numbers, the shader itself is always 0
+		source_map.file_index(&source.file_name);
+		for include in &includes {
+			source_map.file_index(&include.file_name);
+		}
+		
+		/// Writes a `#line` directive so the driver reports the following
+		/// code at its original location, and records it in the source map
+		fn emit_line_directive(buffer: &mut String, source_map: &mut SourceMap, file_name: &str, line: u32) {
+			if !buffer.is_empty() && !buffer.ends_with('\n') {
+				buffer.push('\n');
+			}
+			
+			let file_index = source_map.file_index(file_name);
+			buffer.push_str(&format!("#line {} {}\n", line, file_index));
+			
+			let output_line = buffer.matches('\n').count() as u32 + 1;
+			source_map.add_segment(SourceMapSegment {output_line, file_index, source_line: line});
+		}
 		
 		// Write glsl version directive
 		if let Some(version) = &source.glsl_version {
@@ -21,33 +225,99 @@ impl<'a> SSLTranspiler<'a> {
 		}
 		
 		// Write divider
-		buffer.push_str("\n// [[ import forward declarations ]] //\n\n");
+		buffer.push_str("\n// [[ exported declarations ]] //\n\n");
 		
-		// Write import functions' forward declarations
-		for import_decl in &source.import_declarations {
-			// Find include
-			for include in &self.import_scope {
-				if let Some(include_namespace) = &include.namespace {
-					if include_namespace.eq(import_decl) {
-						// Emit forward declartions for all exported functions
-						for exported_func in &include.exported_functions {
-							buffer.push_str(&exported_func.signature);
-							buffer.push_str(";\n");
+		// Write the exported declarations of all includes and the shader itself,
+		// grouped by kind since later kinds may use earlier ones (e.g. a block using a struct)
+		{
+			let mut diagnostics = Vec::<SslDiagnostic>::new();
+			
+			// Declarations already written, by kind and name (or normalized body for constants)
+			let mut emitted_named = HashMap::<(ExportKind, &str), (String, &str, u32)>::new();
+			let mut emitted_unnamed = HashSet::<String>::new();
+			// Members of blocks without an instance name are global, by member name
+			let mut global_block_members = HashMap::<String, (&str, &str, u32)>::new();
+			
+			let declaring_sources = includes.iter().map(|i| &**i).chain(std::iter::once(source));
+			let declarations = declaring_sources.flat_map(|s| s.exported_declarations.iter().map(move |d| (s, d))).collect::<Vec<_>>();
+			
+			for kind in ExportKind::all() {
+				for (declaring_source, declaration) in declarations.iter().filter(|(_, d)| d.kind == *kind) {
+					let normalized_body = normalize_declaration(&declaration.body);
+					
+					// Skip declarations that were already emitted, error on conflicting ones
+					match &declaration.name {
+						Some(name) => {
+							if let Some((first_body, first_file, first_line)) = emitted_named.get(&(*kind, name.as_str())) {
+								if first_body != &normalized_body {
+									let message = format!("Conflicting definition of `{}` (first defined at {}:{})", name, first_file, first_line);
+									diagnostics.push(SslDiagnostic::new(&declaring_source.file_name, declaration.line, 1, message));
+								}
+								continue;
+							}
+							emitted_named.insert((*kind, name.as_str()), (normalized_body, &declaring_source.file_name, declaration.line));
+						}
+						None => {
+							if !emitted_unnamed.insert(normalized_body) {
+								continue;
+							}
 						}
 					}
+					
+					// Two blocks without an instance name can't declare the same member
+					if let (ExportKind::Block, Some(block_name)) = (kind, &declaration.name) {
+						for member in global_block_member_names(&declaration.body).unwrap_or_default() {
+							if let Some((first_block, first_file, first_line)) = global_block_members.get(&member) {
+								let message = format!("Member `{}` of block `{}` is already declared by block `{}` (at {}:{})", member, block_name, first_block, first_file, first_line);
+								diagnostics.push(SslDiagnostic::new(&declaring_source.file_name, declaration.line, 1, message));
+								continue;
+							}
+							global_block_members.insert(member, (block_name.as_str(), &declaring_source.file_name, declaration.line));
+						}
+					}
+					
+					emit_line_directive(&mut buffer, &mut source_map, &declaring_source.file_name, declaration.line);
+					buffer.push_str(&declaration.body);
+					buffer.push_str("\n");
 				}
 			}
+			
+			if !diagnostics.is_empty() {
+				return Err(diagnostics);
+			}
 		}
 		
-		fn emit_source_code(buffer: &mut String, source: &ParsedSource, emit_hidden: bool) -> Result<(), fmt::Error> {
+		// Write divider
+		buffer.push_str("\n// [[ import forward declarations ]] //\n\n");
+		
+		// Write import functions' forward declarations
+		for include in &includes {
+			// Emit forward declartions for all exported functions
+			for exported_func in include.exported_functions.iter().filter(|f| is_function_used(&f.name)) {
+				emit_line_directive(&mut buffer, &mut source_map, &include.file_name, exported_func.line);
+				buffer.push_str(&exported_func.signature);
+				buffer.push_str(";\n");
+			}
+		}
+		
+		fn emit_source_code(buffer: &mut String, source_map: &mut SourceMap, source: &ParsedSource, emit_hidden: bool, is_function_used: &dyn Fn(&str) -> bool) -> Result<(), fmt::Error> {
 			for token in &source.source_tree {
 				match token {
-					SourceToken::TextSource {body} => {
+					SourceToken::TextSource {body, line} => {
+						emit_line_directive(buffer, source_map, &source.file_name, *line);
 						buffer.push_str(&body);
 					}
-					SourceToken::HiddenSource {body} => {
+					SourceToken::HiddenSource {body, line} => {
 						// Only emit hidden block if the flag is given
 						if emit_hidden {
+							emit_line_directive(buffer, source_map, &source.file_name, *line);
+							buffer.push_str(&body);
+						}
+					}
+					SourceToken::ExportedFunctionSource {body, line, function_index} => {
+						// Leave out functions nobody calls
+						if is_function_used(&source.exported_functions[*function_index].name) {
+							emit_line_directive(buffer, source_map, &source.file_name, *line);
 							buffer.push_str(&body);
 						}
 					}
@@ -60,50 +330,217 @@ impl<'a> SSLTranspiler<'a> {
 		write!(buffer, "\n// [[ own source ]] //\n\n");
 		
 		// Emit actual source code
-		emit_source_code(&mut buffer, source, true);
+		emit_source_code(&mut buffer, &mut source_map, source, true, &|_| true);
 		
-		// Emit import source code
-		// Write import functions' forward declarations
-		for import_decl in &source.import_declarations {
-			// Find include
-			for include in &self.import_scope {
-				if let Some(include_namespace) = &include.namespace {
-					if include_namespace.eq(import_decl) {
-						// Write divider
-						write!(buffer, "\n// [[ import source for \"{}\" ]] //\n\n", include_namespace);
-						
-						// Emit the source code (without the hidden blocks)
-						emit_source_code(&mut buffer, include, false);
-					}
-				}
-			}
+		// Emit import source code, every include exactly once
+		for include in &includes {
+			// Write divider
+			write!(buffer, "\n// [[ import source for \"{}\" ]] //\n\n", include.namespace.as_deref().unwrap_or("<unknown>"));
+			
+			// Emit the source code (without the hidden blocks)
+			emit_source_code(&mut buffer, &mut source_map, include, false, &is_function_used);
 		}
 		
 		// Writer trailer
+		if !buffer.ends_with('\n') {
+			buffer.push('\n');
+		}
 		buffer.push_str("\n// [[ end of transpiled source ]] //\n");
-		
+
 //		// DEBUG: Print transpiled source to console
 //		println!("--------{}\n{}\n--------", source.namespace.as_ref().map_or("<unknown>", |s| s.as_str()), buffer);
-		
+
 		// Make TranspiledSource and return
-		TranspiledShaderSource::new(buffer)
+		Ok(TranspiledShaderSource::new(buffer, source_map))
 	}
 	
 	pub fn new() -> Self {
 		Self {
 			import_scope: Vec::new(),
+			include_resolver: None,
+			strip_unused_functions: true,
+		}
+	}
+}
+
+/// Normalizes a declaration for comparison, ignoring comments and differences in whitespace.
+fn normalize_declaration(body: &str) -> String {
+	SslTokenizer::new(body)
+		.filter(|t| !t.is_trivia())
+		.map(|t| t.text)
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+/// Functions defined in a piece of glsl code and the functions called in it
+struct CallScan<'s> {
+	/// Name and called functions of every function defined at the top level
+	definitions: Vec<(&'s str, Vec<&'s str>)>,
+	/// Functions called outside of any definition (preprocessor lines, array sizes, ...)
+	global_calls: Vec<&'s str>,
+}
+
+/// Finds the function definitions in `code` and the calls in and outside of them.
+/// A call is any identifier followed by `(`, so constructors like `vec3(` are
+/// calls too, they just never match a function definition.
+fn scan_calls(code: &str) -> CallScan<'_> {
+	let mut scan = CallScan {definitions: Vec::new(), global_calls: Vec::new()};
+	
+	fn calls<'t, 's>(tokens: &'t [Token<'s>]) -> impl Iterator<Item = &'s str> + 't {
+		tokens.windows(2).filter(|w| w[0].kind == TokenKind::Ident && w[1].is_punct('(')).map(|w| w[0].text)
+	}
+	
+	/// Index of the bracket closing the one at `open`, or the end of the tokens
+	fn matching_close(tokens: &[Token], open: usize, open_char: char, close_char: char) -> usize {
+		let mut depth = 0;
+		for (i, token) in tokens.iter().enumerate().skip(open) {
+			if token.is_punct(open_char) {
+				depth += 1;
+			}
+			else if token.is_punct(close_char) {
+				depth -= 1;
+				if depth == 0 {
+					return i;
+				}
+			}
+		}
+		tokens.len()
+	}
+	
+	// Split off the preprocessor lines, their calls are global
+	let mut tokens = Vec::<Token>::new();
+	{
+		fn end_line<'s>(line_tokens: &mut Vec<Token<'s>>, tokens: &mut Vec<Token<'s>>, global_calls: &mut Vec<&'s str>) {
+			if line_tokens.first().map_or(false, |t| t.is_punct('#')) {
+				global_calls.extend(calls(line_tokens));
+				line_tokens.clear();
+			}
+			else {
+				tokens.append(line_tokens);
+			}
 		}
+		
+		let mut line_tokens = Vec::<Token>::new();
+		for token in SslTokenizer::new(code) {
+			if token.kind == TokenKind::Newline {
+				// Preprocessor lines can be continued with a backslash
+				if !line_tokens.last().map_or(false, |t| t.is_punct('\\')) {
+					end_line(&mut line_tokens, &mut tokens, &mut scan.global_calls);
+				}
+			}
+			else if !token.is_trivia() {
+				line_tokens.push(token);
+			}
+		}
+		end_line(&mut line_tokens, &mut tokens, &mut scan.global_calls);
 	}
+	
+	// Find the definitions at the top level
+	let mut depth = 0;
+	let mut i = 0;
+	while i < tokens.len() {
+		let token = &tokens[i];
+		if token.is_punct('{') {
+			depth += 1;
+		}
+		else if token.is_punct('}') {
+			depth -= 1;
+		}
+		else if token.kind == TokenKind::Ident && tokens.get(i + 1).map_or(false, |t| t.is_punct('(')) {
+			let params_end = matching_close(&tokens, i + 1, '(', ')');
+			let next = tokens.get(params_end + 1);
+			
+			if depth == 0 && next.map_or(false, |t| t.is_punct('{')) {
+				let body_end = matching_close(&tokens, params_end + 1, '{', '}');
+				scan.definitions.push((token.text, calls(&tokens[params_end + 1..body_end]).collect()));
+				i = body_end + 1;
+				continue;
+			}
+			
+			// Forward declarations don't call anything
+			let is_declaration = depth == 0 && i > 0 && tokens[i - 1].kind == TokenKind::Ident && next.map_or(false, |t| t.is_punct(';'));
+			if !is_declaration {
+				scan.global_calls.push(token.text);
+			}
+		}
+		i += 1;
+	}
+	
+	scan
+}
+
+/// Finds the member names of a block declared without an instance name, whose members share
+/// the global scope. Returns `None` for blocks with an instance name.
+fn global_block_member_names(body: &str) -> Option<Vec<String>> {
+	let tokens = SslTokenizer::new(body).filter(|t| !t.is_trivia()).collect::<Vec<_>>();
+	let open_brace = tokens.iter().position(|t| t.is_punct('{'))?;
+	
+	let mut members = Vec::new();
+	let mut last_ident = None;
+	// Array sizes and layout qualifiers of the members
+	let mut nesting = 0;
+	
+	for (i, token) in tokens.iter().enumerate().skip(open_brace + 1) {
+		if token.is_punct('}') {
+			let has_instance_name = tokens[i + 1..].iter().any(|t| t.kind == TokenKind::Ident);
+			return if has_instance_name {None} else {Some(members)};
+		}
+		else if token.is_punct('[') || token.is_punct('(') {
+			nesting += 1;
+		}
+		else if token.is_punct(']') || token.is_punct(')') {
+			nesting -= 1;
+		}
+		else if nesting == 0 && (token.is_punct(';') || token.is_punct(',')) {
+			members.extend(last_ident.take().map(String::from));
+		}
+		else if nesting == 0 && token.kind == TokenKind::Ident {
+			last_ident = Some(token.text);
+		}
+	}
+	None
 }
 
 pub struct TranspiledShaderSource {
 	pub source_code: String,
+	pub source_map: SourceMap,
 }
 
 impl TranspiledShaderSource {
-	pub fn new(source_code: String) -> Self {
+	/// Returns a copy of the source with the given `#define`s inserted right after the `#version` directive.
+	pub fn with_defines(&self, defines: &[(&str, &str)]) -> TranspiledShaderSource {
+		if defines.is_empty() {
+			return TranspiledShaderSource::new(self.source_code.clone(), self.source_map.clone());
+		}
+		
+		let mut define_lines = String::new();
+		for (name, value) in defines {
+			define_lines.push_str(&format!("#define {} {}\n", name, value));
+		}
+		
+		// Insert after the version directive (which has to stay the first line) or at the very start
+		let insert_pos = if self.source_code.starts_with("#version") {
+			self.source_code.find('\n').map_or(self.source_code.len(), |i| i + 1)
+		}
+		else {
+			0
+		};
+		
+		let mut source_code = String::with_capacity(self.source_code.len() + define_lines.len());
+		source_code.push_str(&self.source_code[..insert_pos]);
+		source_code.push_str(&define_lines);
+		source_code.push_str(&self.source_code[insert_pos..]);
+		
+		let mut source_map = self.source_map.clone();
+		source_map.offset_output_lines(defines.len() as u32);
+		
+		TranspiledShaderSource::new(source_code, source_map)
+	}
+	
+	pub fn new(source_code: String, source_map: SourceMap) -> Self {
 		Self {
-			source_code
+			source_code,
+			source_map,
 		}
 	}
 }
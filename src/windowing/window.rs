@@ -35,7 +35,7 @@ impl Window {
 			
 			// Set glfw window hints
 			glfw.default_window_hints();
-			glfw.window_hint(WindowHint::Resizable(false));
+			glfw.window_hint(WindowHint::Resizable(true));
 			
 			// Create glfw handle
 			let (window, channel) = glfw.create_window(self.width, self.height, &self.title, WindowMode::Windowed).expect("Failed to init glfw window");
@@ -47,6 +47,10 @@ impl Window {
 			window_glfw.set_key_polling(true);
 			window_glfw.set_scroll_polling(true);
 			
+			// Enable resize callbacks
+			window_glfw.set_size_polling(true);
+			window_glfw.set_framebuffer_size_polling(true);
+			
 			// Set initial window color to avoid artifacts (only on windows for now)
 			if cfg!(windows) {
 				set_initial_background_color_win32(&window_glfw, 0x000000);
@@ -84,6 +88,17 @@ impl Window {
 		(self.width, self.height)
 	}
 	
+	/// Size of the default framebuffer in pixels, may differ from the window size on high dpi screens.
+	pub fn get_framebuffer_size(&self) -> (u32, u32) {
+		match &self.window_glfw {
+			Some(window) => {
+				let (width, height) = window.get_framebuffer_size();
+				(width as u32, height as u32)
+			}
+			None => (self.width, self.height),
+		}
+	}
+	
 	pub fn set_title(&mut self, title: String) {
 		self.title = title;
 		self.title_dirty = true;
@@ -162,6 +177,15 @@ impl Window {
 			context_borrow.glfw_mut().poll_events();
 		}
 		
+		// Take over the size if the user resized the window
+		if let Some(window) = &self.window_glfw {
+			if !self.size_dirty {
+				let (width, height) = window.get_size();
+				self.width = width as u32;
+				self.height = height as u32;
+			}
+		}
+		
 		// Flush messages out of queue
 		glfw::flush_messages(self.message_channel.need())
 	}
@@ -0,0 +1,147 @@
+//! Lifetime of the handles and entries of the `AssetCache`, with a loader that doesn't touch the disk.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use render_demo::asset::{AssetCache, AssetLoader, AssetLoadError, AssetPath, LoadStatus, WeakHandle};
+use render_demo::render::{Texture, ImageFormat};
+use gl_bindings::gl;
+
+/// Loads the number of the load as the asset. Paths containing `/missing` fail.
+struct CountingLoader {
+	num_loads: Rc<Cell<usize>>,
+}
+
+impl AssetLoader<usize> for CountingLoader {
+	fn load(&self, path: &AssetPath) -> Result<usize, AssetLoadError> {
+		self.num_loads.set(self.num_loads.get() + 1);
+		
+		if format!("{:?}", path).contains("/missing") {
+			return Err(AssetLoadError::from(format!("No such asset {:?}", path)));
+		}
+		Ok(self.num_loads.get())
+	}
+}
+
+fn counting_cache() -> (AssetCache<usize>, Rc<Cell<usize>>) {
+	let num_loads = Rc::new(Cell::new(0));
+	(AssetCache::new(Box::new(CountingLoader {num_loads: Rc::clone(&num_loads)})), num_loads)
+}
+
+#[test]
+fn loads_every_path_once() {
+	let (mut cache, num_loads) = counting_cache();
+	
+	let a = cache.load(&AssetPath::from_str("/textures/a.png"));
+	let b = cache.load(&AssetPath::from_str("textures/a.png"));
+	
+	assert!(a.ptr_eq(&b), "relative and absolute paths share the entry");
+	assert_eq!(num_loads.get(), 1);
+	assert_eq!(cache.len(), 1);
+	assert!(cache.get(&AssetPath::from_str("/textures/a.png")).unwrap().ptr_eq(&a));
+	assert!(cache.get(&AssetPath::from_str("/textures/b.png")).is_none());
+}
+
+#[test]
+fn keeps_failed_loads_cached() {
+	let (mut cache, num_loads) = counting_cache();
+	
+	let handle = cache.load(&AssetPath::from_str("/missing.png"));
+	assert_eq!(handle.status(), LoadStatus::Failed);
+	assert!(handle.get().is_none());
+	
+	// Loading again doesn't retry, only a reload does
+	cache.load(&AssetPath::from_str("/missing.png"));
+	assert_eq!(num_loads.get(), 1);
+	
+	assert!(cache.reload(&AssetPath::from_str("/missing.png")));
+	assert_eq!(num_loads.get(), 2);
+	assert_eq!(handle.status(), LoadStatus::Failed);
+}
+
+#[test]
+fn reloads_are_seen_through_every_handle() {
+	let (mut cache, _) = counting_cache();
+	
+	let a = cache.load(&AssetPath::from_str("/a.png"));
+	let b = a.clone();
+	assert_eq!(*a.get().unwrap(), 1);
+	
+	cache.reload_all();
+	assert_eq!(*a.get().unwrap(), 2);
+	assert_eq!(*b.get().unwrap(), 2);
+	assert!(!cache.reload(&AssetPath::from_str("/b.png")), "uncached paths aren't reloaded");
+}
+
+#[test]
+fn unloads_only_assets_without_handles() {
+	let (mut cache, num_loads) = counting_cache();
+	
+	let kept = cache.load(&AssetPath::from_str("/kept.png"));
+	let kept_clone = kept.clone();
+	let dropped = cache.load(&AssetPath::from_str("/dropped.png"));
+	drop(cache.get(&AssetPath::from_str("/dropped.png")));
+	
+	// Both are still referenced
+	assert_eq!(cache.unload_unused(), 0);
+	
+	drop(dropped);
+	assert_eq!(cache.unload_unused(), 1);
+	assert_eq!(cache.len(), 1);
+	assert!(cache.get(&AssetPath::from_str("/dropped.png")).is_none());
+	
+	// A single remaining clone keeps the asset alive
+	drop(kept);
+	assert_eq!(cache.unload_unused(), 0);
+	assert_eq!(*kept_clone.get().unwrap(), 1);
+	
+	// Loading an unloaded path loads it again
+	drop(kept_clone);
+	assert_eq!(cache.unload_unused(), 1);
+	assert!(cache.is_empty());
+	
+	cache.load(&AssetPath::from_str("/dropped.png"));
+	assert_eq!(num_loads.get(), 3);
+}
+
+#[test]
+fn weak_handles_dont_keep_assets_loaded() {
+	let (mut cache, _) = counting_cache();
+	
+	let handle = cache.load(&AssetPath::from_str("/a.png"));
+	let weak = handle.downgrade();
+	assert!(weak.upgrade().unwrap().ptr_eq(&handle));
+	assert!(WeakHandle::<usize>::new().upgrade().is_none());
+	
+	// Only the weak handle is left
+	drop(handle);
+	assert_eq!(cache.unload_unused(), 1);
+	assert!(cache.is_empty());
+	assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn handles_outlive_the_cache() {
+	let (mut cache, _) = counting_cache();
+	
+	let handle = cache.load(&AssetPath::from_str("/a.png"));
+	drop(cache);
+	
+	assert!(handle.is_ready());
+	assert_eq!(*handle.get().unwrap(), 1);
+}
+
+#[test]
+fn picks_png_image_format_from_channels() {
+	let format = |channels, srgb| Texture::png_image_format(channels, srgb);
+	
+	assert_eq!(format(1, false), ImageFormat::get(gl::R8));
+	assert_eq!(format(2, false), ImageFormat::get(gl::RG8));
+	assert_eq!(format(3, false), ImageFormat::get(gl::RGB8));
+	assert_eq!(format(4, false), ImageFormat::get(gl::RGBA8));
+	
+	// Grey images have no srgb format
+	assert_eq!(format(1, true), ImageFormat::get(gl::R8));
+	assert_eq!(format(2, true), ImageFormat::get(gl::RG8));
+	assert_eq!(format(3, true), ImageFormat::get(gl::SRGB8));
+	assert_eq!(format(4, true), ImageFormat::get(gl::SRGB8_ALPHA8));
+}
@@ -0,0 +1,192 @@
+//! Invalidation through the asset dependency graph, an include pulled in by a program used by a material.
+
+use std::cell::Cell;
+use std::fs;
+use std::rc::Rc;
+use render_demo::asset::{ASSET_MANAGER_INSTANCE, AssetCache, AssetDependency, AssetDependencyGraph, AssetLoader, AssetLoadError, AssetNode, AssetPath, AssetPathBuf, ContentHash};
+
+const INCLUDE: &str = "/shaders/lighting.incl.ssl";
+const PROGRAM: &str = "/shaders/scene.program";
+const MATERIAL: &str = "/surfaces/head.surface";
+
+/// The include and the material's own file have hash 1 when recorded, the program 2
+fn include_program_material_graph() -> AssetDependencyGraph {
+	let mut graph = AssetDependencyGraph::new();
+	
+	let node = |content_hash: ContentHash, dependency: Option<&str>| AssetNode {
+		content_hash: Some(content_hash),
+		dependencies: dependency.into_iter().map(|path| AssetDependency {
+			path: AssetPathBuf::from(path),
+			content_hash: Some(if path == PROGRAM {2} else {1}),
+		}).collect(),
+	};
+	graph.insert(&AssetPath::from_str(INCLUDE), node(1, None));
+	graph.insert(&AssetPath::from_str(PROGRAM), node(2, Some(INCLUDE)));
+	graph.insert(&AssetPath::from_str(MATERIAL), node(1, Some(PROGRAM)));
+	graph
+}
+
+/// Hashes of the files as recorded, except for the changed file whose hash is 99
+fn hashes_with_changed(changed: &'static str) -> impl Fn(&AssetPath) -> Option<ContentHash> {
+	move |path| {
+		let path = path.to_absolute();
+		if path == AssetPathBuf::from(changed) {
+			Some(99)
+		}
+		else if path == AssetPathBuf::from(PROGRAM) {
+			Some(2)
+		}
+		else {
+			Some(1)
+		}
+	}
+}
+
+#[test]
+fn finds_transitive_dependents() {
+	let graph = include_program_material_graph();
+	
+	assert_eq!(graph.dependents_of(&AssetPath::from_str(INCLUDE)), vec![AssetPathBuf::from(PROGRAM), AssetPathBuf::from(MATERIAL)]);
+	assert_eq!(graph.dependents_of(&AssetPath::from_str(PROGRAM)), vec![AssetPathBuf::from(MATERIAL)]);
+	assert!(graph.dependents_of(&AssetPath::from_str(MATERIAL)).is_empty());
+	
+	// Relative paths find the same nodes
+	assert_eq!(graph.dependents_of(&AssetPath::from_str("shaders/lighting.incl.ssl")).len(), 2);
+}
+
+#[test]
+fn changed_include_invalidates_program_and_material() {
+	let graph = include_program_material_graph();
+	let current_hash = hashes_with_changed(INCLUDE);
+	
+	assert_eq!(graph.changed_files(&current_hash), vec![AssetPathBuf::from(INCLUDE)]);
+	assert!(graph.is_stale(&AssetPath::from_str(PROGRAM), &current_hash));
+	assert!(graph.is_stale(&AssetPath::from_str(MATERIAL), &current_hash));
+}
+
+#[test]
+fn changed_material_leaves_program_and_include_valid() {
+	let graph = include_program_material_graph();
+	let current_hash = hashes_with_changed(MATERIAL);
+	
+	assert_eq!(graph.changed_files(&current_hash), vec![AssetPathBuf::from(MATERIAL)]);
+	assert!(graph.is_stale(&AssetPath::from_str(MATERIAL), &current_hash));
+	assert!(!graph.is_stale(&AssetPath::from_str(PROGRAM), &current_hash));
+	assert!(!graph.is_stale(&AssetPath::from_str(INCLUDE), &current_hash));
+}
+
+#[test]
+fn unchanged_files_are_not_stale() {
+	let graph = include_program_material_graph();
+	let current_hash = hashes_with_changed("/unrelated.png");
+	
+	assert!(graph.changed_files(&current_hash).is_empty());
+	assert!(!graph.is_stale(&AssetPath::from_str(MATERIAL), &current_hash));
+	assert!(graph.is_stale(&AssetPath::from_str("/never_recorded.program"), &current_hash));
+}
+
+#[test]
+fn deleted_dependency_is_a_change() {
+	let graph = include_program_material_graph();
+	let current_hash = |path: &AssetPath| if path.to_absolute() == AssetPathBuf::from(INCLUDE) {None} else {hashes_with_changed(INCLUDE)(path)};
+	
+	assert_eq!(graph.changed_files(&current_hash), vec![AssetPathBuf::from(INCLUDE)]);
+	assert!(graph.is_stale(&AssetPath::from_str(MATERIAL), &current_hash));
+}
+
+#[test]
+fn dependencies_with_own_nodes_are_checked_through_them() {
+	const TEXTURE: &str = "/textures/head_albedo.png";
+	
+	// The material was recorded before its texture finished loading, so it has no hash for it
+	let mut graph = include_program_material_graph();
+	graph.insert(&AssetPath::from_str(MATERIAL), AssetNode {
+		content_hash: Some(1),
+		dependencies: vec![AssetDependency {path: AssetPathBuf::from(TEXTURE), content_hash: None}],
+	});
+	assert_eq!(graph.changed_files(hashes_with_changed("/unrelated.png")), vec![AssetPathBuf::from(TEXTURE)]);
+	assert!(graph.is_stale(&AssetPath::from_str(MATERIAL), hashes_with_changed("/unrelated.png")));
+	
+	// Once the texture recorded its own node, that node is checked instead
+	graph.insert(&AssetPath::from_str(TEXTURE), AssetNode {content_hash: Some(1), dependencies: Vec::new()});
+	assert!(graph.changed_files(hashes_with_changed("/unrelated.png")).is_empty());
+	assert!(!graph.is_stale(&AssetPath::from_str(MATERIAL), hashes_with_changed("/unrelated.png")));
+	
+	assert_eq!(graph.changed_files(hashes_with_changed(TEXTURE)), vec![AssetPathBuf::from(TEXTURE)]);
+	assert!(graph.is_stale(&AssetPath::from_str(MATERIAL), hashes_with_changed(TEXTURE)));
+}
+
+/// Counts its loads and records the include as a dependency of every program
+struct ProgramLoader {
+	num_loads: Rc<Cell<usize>>,
+}
+
+impl AssetLoader<usize> for ProgramLoader {
+	fn load(&self, path: &AssetPath) -> Result<usize, AssetLoadError> {
+		self.num_loads.set(self.num_loads.get() + 1);
+		unsafe {&mut ASSET_MANAGER_INSTANCE}.record_dependencies(path, &[AssetPathBuf::from(INCLUDE)]);
+		Ok(self.num_loads.get())
+	}
+}
+
+/// Fails the first load, like a program with a broken include that gets fixed afterwards
+struct FailingOnceLoader {
+	num_loads: Rc<Cell<usize>>,
+}
+
+impl AssetLoader<usize> for FailingOnceLoader {
+	fn load(&self, _path: &AssetPath) -> Result<usize, AssetLoadError> {
+		self.num_loads.set(self.num_loads.get() + 1);
+		if self.num_loads.get() == 1 {
+			return Err(AssetLoadError::from("broken include"));
+		}
+		Ok(self.num_loads.get())
+	}
+}
+
+#[test]
+fn reloads_assets_affected_by_changed_files() {
+	// Set up an asset root with the three files
+	let asset_root = std::env::temp_dir().join(format!("render_demo_asset_dependencies_{}", std::process::id()));
+	let _ = fs::remove_dir_all(&asset_root);
+	fs::create_dir_all(asset_root.join("shaders")).unwrap();
+	fs::create_dir_all(asset_root.join("surfaces")).unwrap();
+	fs::write(asset_root.join("shaders/lighting.incl.ssl"), "// v1").unwrap();
+	fs::write(asset_root.join("shaders/scene.program"), "// program").unwrap();
+	fs::write(asset_root.join("surfaces/head.surface"), "// surface").unwrap();
+	
+	let asset_manager = unsafe {&mut ASSET_MANAGER_INSTANCE};
+	asset_manager.init(asset_root.clone());
+	
+	let num_loads = Rc::new(Cell::new(0));
+	let mut program_cache = AssetCache::new(Box::new(ProgramLoader {num_loads: Rc::clone(&num_loads)}));
+	let program = program_cache.load(&AssetPath::from_str(PROGRAM));
+	asset_manager.record_dependencies(&AssetPath::from_str(MATERIAL), &[AssetPathBuf::from(PROGRAM)]);
+	
+	// Nothing changed yet
+	assert!(asset_manager.changed_files().is_empty());
+	assert!(!asset_manager.is_asset_stale(&AssetPath::from_str(MATERIAL)));
+	assert_eq!(program_cache.reload_affected_by(&asset_manager.changed_files()), 0);
+	
+	// Change the include
+	fs::write(asset_root.join("shaders/lighting.incl.ssl"), "// v2").unwrap();
+	assert_eq!(asset_manager.changed_files(), vec![AssetPathBuf::from(INCLUDE)]);
+	assert!(asset_manager.is_asset_stale(&AssetPath::from_str(PROGRAM)));
+	assert!(asset_manager.is_asset_stale(&AssetPath::from_str(MATERIAL)));
+	
+	// Only the cached program is reloaded, which records the new include hash
+	assert_eq!(program_cache.reload_affected_by(&asset_manager.changed_files()), 1);
+	assert_eq!(*program.get().unwrap(), 2);
+	assert!(!asset_manager.is_asset_stale(&AssetPath::from_str(PROGRAM)));
+	
+	// A failed asset never recorded its dependencies, so it's retried on every reload
+	let num_failing_loads = Rc::new(Cell::new(0));
+	let mut failing_cache = AssetCache::new(Box::new(FailingOnceLoader {num_loads: Rc::clone(&num_failing_loads)}));
+	let failing = failing_cache.load(&AssetPath::from_str(PROGRAM));
+	assert!(failing.get().is_none());
+	assert_eq!(failing_cache.reload_affected_by(&[]), 1);
+	assert_eq!(*failing.get().unwrap(), 2);
+	assert_eq!(failing_cache.reload_affected_by(&[]), 0);
+	
+	let _ = fs::remove_dir_all(&asset_root);
+}
@@ -0,0 +1,33 @@
+//! Layout of the bloom mip chain and the weights of its octaves.
+
+use render_demo::render::bloom::{bloom_octave_sizes, bloom_octave_weights, BLOOM_MIN_OCTAVE_SIZE};
+
+#[test]
+fn octaves_halve_the_resolution() {
+	let sizes = bloom_octave_sizes((1600, 900), 6);
+	assert_eq!(sizes, vec![(800, 450), (400, 225), (200, 112), (100, 56), (50, 28), (25, 14)]);
+}
+
+#[test]
+fn small_scenes_get_fewer_octaves() {
+	let sizes = bloom_octave_sizes((64, 16), 8);
+	assert_eq!(sizes, vec![(32, 8), (16, 4), (8, 2)]);
+	assert!(sizes.iter().all(|&(w, h)| w >= BLOOM_MIN_OCTAVE_SIZE && h >= BLOOM_MIN_OCTAVE_SIZE));
+	
+	assert!(bloom_octave_sizes((3, 3), 8).is_empty());
+	assert!(bloom_octave_sizes((1600, 900), 0).is_empty());
+}
+
+#[test]
+fn weights_are_normalized_and_fall_off() {
+	for octaves in 1..=8 {
+		let weights = bloom_octave_weights(octaves, 0.85);
+		assert_eq!(weights.len(), octaves as usize);
+		
+		let sum: f32 = weights.iter().sum();
+		assert!((sum - 1.0).abs() < 1e-5, "sum {} for {} octaves", sum, octaves);
+		assert!(weights.windows(2).all(|pair| pair[1] < pair[0]));
+	}
+	
+	assert_eq!(bloom_octave_weights(4, 1.0), vec![0.25; 4]);
+}
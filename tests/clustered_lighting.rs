@@ -0,0 +1,197 @@
+//! The cluster grid and the cpu reference of the cluster light assignment.
+
+use cgmath::{Deg, Matrix4, Rad, SquareMatrix, InnerSpace, Vector2, Vector3, Vector4, perspective};
+use render_demo::render::clustered::{ClusterGrid, LightList, PointLight, SpotLight, AreaLight, assign_lights, cluster_view_matrix, CLUSTER_GRID_DIMENSIONS, CLUSTER_MAX_LIGHTS};
+
+fn projection() -> Matrix4<f32> {
+	perspective(Deg(90.0), 16.0 / 9.0, 0.1, 100.0)
+}
+
+fn grid() -> ClusterGrid {
+	ClusterGrid::from_projection(CLUSTER_GRID_DIMENSIONS, &projection(), (1.0 / 256.0, 4096.0))
+}
+
+/// Cluster containing a view space point, found the way the fragment shader does.
+fn cluster_of(grid: &ClusterGrid, view_position: Vector3<f32>) -> u32 {
+	let clip = projection() * view_position.extend(1.0);
+	let screen_tex_coord = Vector2::new(clip.x / clip.w, clip.y / clip.w) * 0.5 + Vector2::new(0.5, 0.5);
+	grid.cluster_index(grid.cluster_at(screen_tex_coord, clip.w))
+}
+
+fn point_light(position: Vector3<f32>, radius: f32) -> PointLight {
+	PointLight::new(position, Vector3::new(1.0, 1.0, 1.0), radius)
+}
+
+#[test]
+fn cluster_indices_round_trip() {
+	let grid = grid();
+	assert_eq!(grid.num_clusters(), 16 * 9 * 24);
+	
+	for index in 0..grid.num_clusters() {
+		assert_eq!(grid.cluster_index(grid.cluster_coord(index)), index);
+	}
+	assert_eq!(grid.cluster_coord(16 * 9 + 16 + 1), (1, 1, 1));
+}
+
+#[test]
+fn depth_slices_cover_the_depth_planes() {
+	let grid = grid();
+	
+	// Depths outside of the planes are clamped to the first and last slice
+	assert_eq!(grid.depth_slice(0.0), 0);
+	assert_eq!(grid.depth_slice(1.0 / 256.0), 0);
+	assert_eq!(grid.depth_slice(100000.0), 23);
+	
+	// Every depth lies in the range of its slice
+	for &depth in &[0.01, 0.5, 1.0, 10.0, 250.0, 4000.0] {
+		let (near, far) = grid.slice_depth_range(grid.depth_slice(depth));
+		assert!(near <= depth && depth <= far, "{} not in {}..{}", depth, near, far);
+	}
+	
+	// The slices are contiguous
+	for slice in 0..23 {
+		let (_, far) = grid.slice_depth_range(slice);
+		let (next_near, _) = grid.slice_depth_range(slice + 1);
+		assert!((far - next_near).abs() <= far * 1e-5);
+	}
+}
+
+#[test]
+fn cluster_space_depth_is_the_view_distance() {
+	let cluster_view = cluster_view_matrix(&projection(), &Matrix4::identity());
+	
+	let position = cluster_view * Vector4::new(1.0, 2.0, -10.0, 1.0);
+	assert!((position - Vector4::new(1.0, 2.0, 10.0, 1.0)).magnitude2() < 1e-8);
+}
+
+#[test]
+fn clusters_contain_the_points_that_fall_into_them() {
+	let grid = grid();
+	let cluster_view = cluster_view_matrix(&projection(), &Matrix4::identity());
+	
+	for &view_position in &[Vector3::new(0.3, -0.2, -1.0), Vector3::new(-4.0, 2.0, -7.5), Vector3::new(30.0, -15.0, -60.0), Vector3::new(0.001, 0.001, -0.02)] {
+		let (bounds_min, bounds_max) = grid.cluster_bounds(grid.cluster_coord(cluster_of(&grid, view_position)));
+		let position = (cluster_view * view_position.extend(1.0)).truncate();
+		
+		let epsilon = position.z * 1e-4;
+		assert!(bounds_min.x - epsilon <= position.x && position.x <= bounds_max.x + epsilon, "{:?} not in {:?}..{:?}", position, bounds_min, bounds_max);
+		assert!(bounds_min.y - epsilon <= position.y && position.y <= bounds_max.y + epsilon, "{:?} not in {:?}..{:?}", position, bounds_min, bounds_max);
+		assert!(bounds_min.z - epsilon <= position.z && position.z <= bounds_max.z + epsilon, "{:?} not in {:?}..{:?}", position, bounds_min, bounds_max);
+	}
+}
+
+#[test]
+fn assigns_lights_to_the_clusters_they_touch() {
+	let grid = grid();
+	let view = Matrix4::from_translation(Vector3::new(0.0, 0.0, -5.0));
+	let cluster_view = cluster_view_matrix(&projection(), &view);
+	
+	// The light is at the view space position (0, 0, -10)
+	let mut lights = LightList::new();
+	lights.point_lights.push(point_light(Vector3::new(100.0, 0.0, 0.0), 1.0));
+	lights.point_lights.push(point_light(Vector3::new(0.0, 0.0, -5.0), 0.5));
+	
+	let assignment = assign_lights(&grid, &cluster_view, &lights);
+	assert_eq!(assignment.point_lights(cluster_of(&grid, Vector3::new(0.0, 0.0, -10.0))), &[1]);
+	assert_eq!(assignment.point_lights(cluster_of(&grid, Vector3::new(0.1, 0.1, -10.2))), &[1]);
+	
+	// Clusters away from the light don't get it
+	assert!(assignment.point_lights(cluster_of(&grid, Vector3::new(0.0, 0.0, -20.0))).is_empty());
+	assert!(assignment.point_lights(cluster_of(&grid, Vector3::new(-8.0, 4.0, -10.0))).is_empty());
+	assert!(assignment.point_lights(grid.cluster_index((0, 0, 0))).is_empty());
+	
+	// The light behind the camera isn't in any cluster, the other one only in a few
+	let num_clusters = assignment.cluster_lights.iter().filter(|&&[_, num_points, _, _]| num_points > 0).count();
+	assert!(num_clusters > 0 && num_clusters < 32, "{} clusters", num_clusters);
+	assert_eq!(assignment.light_indices.iter().filter(|&&i| i == 0).count(), 0);
+}
+
+#[test]
+fn compacts_the_lists_in_cluster_order() {
+	let grid = grid();
+	let cluster_view = cluster_view_matrix(&projection(), &Matrix4::identity());
+	
+	let mut lights = LightList::new();
+	lights.point_lights.push(point_light(Vector3::new(0.0, 0.0, -10.0), 2.0));
+	lights.point_lights.push(point_light(Vector3::new(2.0, 1.0, -12.0), 3.0));
+	lights.spot_lights.push(SpotLight::new(Vector3::new(0.0, 0.0, -11.0), Vector3::new(0.0, 0.0, -1.0), Vector3::new(1.0, 1.0, 1.0), 2.0, Rad(0.3), Rad(0.5)));
+	lights.area_lights.push(AreaLight::new(Vector3::new(0.0, 0.0, -9.0), Vector3::new(0.0, 0.0, 1.0), Vector3::new(1.0, 0.0, 0.0), Vector2::new(1.0, 1.0), Vector3::new(1.0, 1.0, 1.0), 3.0, Rad(1.0)));
+	
+	let assignment = assign_lights(&grid, &cluster_view, &lights);
+	assert_eq!(assignment.cluster_lights.len(), grid.num_clusters() as usize);
+	
+	// The offsets are the prefix sum of the list lengths
+	let mut offset = 0;
+	for &[cluster_offset, num_points, num_spots, num_areas] in &assignment.cluster_lights {
+		assert_eq!(cluster_offset, offset);
+		offset += num_points + num_spots + num_areas;
+	}
+	assert_eq!(offset as usize, assignment.light_indices.len());
+	
+	// The point lights come first, then the spot and area lights
+	let cluster = cluster_of(&grid, Vector3::new(0.0, 0.0, -10.5));
+	let [cluster_offset, _, _, _] = assignment.cluster_lights[cluster as usize];
+	assert_eq!(assignment.point_lights(cluster), &[0, 1]);
+	assert_eq!(assignment.spot_lights(cluster), &[0]);
+	assert_eq!(assignment.area_lights(cluster), &[0]);
+	assert_eq!(&assignment.light_indices[(cluster_offset as usize)..(cluster_offset as usize + 4)], &[0, 1, 0, 0]);
+}
+
+#[test]
+fn culls_clusters_behind_area_lights() {
+	let grid = grid();
+	let cluster_view = cluster_view_matrix(&projection(), &Matrix4::identity());
+	
+	// Facing the camera
+	let mut lights = LightList::new();
+	lights.area_lights.push(AreaLight::new(Vector3::new(0.0, 0.0, -10.0), Vector3::new(0.0, 0.0, 1.0), Vector3::new(1.0, 0.0, 0.0), Vector2::new(1.0, 1.0), Vector3::new(1.0, 1.0, 1.0), 4.0, Rad(1.0)));
+	
+	let in_front = cluster_of(&grid, Vector3::new(0.0, 0.0, -5.0));
+	let behind = cluster_of(&grid, Vector3::new(0.0, 0.0, -20.0));
+	
+	// Both clusters are within the radius of the light
+	let assignment = assign_lights(&grid, &cluster_view, &lights);
+	assert_eq!(assignment.area_lights(in_front), &[0]);
+	assert!(assignment.area_lights(behind).is_empty());
+	
+	// Facing away from the camera
+	lights.area_lights[0].normal = Vector3::new(0.0, 0.0, -1.0);
+	
+	let assignment = assign_lights(&grid, &cluster_view, &lights);
+	assert!(assignment.area_lights(in_front).is_empty());
+	assert_eq!(assignment.area_lights(behind), &[0]);
+}
+
+#[test]
+fn caps_the_lights_of_a_cluster() {
+	let grid = grid();
+	let cluster_view = cluster_view_matrix(&projection(), &Matrix4::identity());
+	
+	let mut lights = LightList::new();
+	for _ in 0..(CLUSTER_MAX_LIGHTS + 6) {
+		lights.point_lights.push(point_light(Vector3::new(0.0, 0.0, -10.0), 1.0));
+	}
+	lights.spot_lights.push(SpotLight::new(Vector3::new(0.0, 0.0, -10.0), Vector3::new(0.0, 0.0, -1.0), Vector3::new(1.0, 1.0, 1.0), 1.0, Rad(0.3), Rad(0.5)));
+	
+	// The lights past the cap are dropped, in the order they are assigned in
+	let assignment = assign_lights(&grid, &cluster_view, &lights);
+	let cluster = cluster_of(&grid, Vector3::new(0.0, 0.0, -10.0));
+	assert_eq!(assignment.point_lights(cluster), (0..CLUSTER_MAX_LIGHTS).collect::<Vec<_>>().as_slice());
+	assert!(assignment.spot_lights(cluster).is_empty());
+}
+
+#[test]
+fn packs_the_light_data() {
+	let spot_light = SpotLight::new(Vector3::new(1.0, 2.0, 3.0), Vector3::new(0.0, -1.0, 0.0), Vector3::new(4.0, 5.0, 6.0), 7.0, Rad(0.0), Deg(60.0).into());
+	let data = spot_light.light_data();
+	assert_eq!(data.position_and_radius, Vector4::new(1.0, 2.0, 3.0, 7.0));
+	assert_eq!(data.intensities_and_cos_inner_angle, Vector4::new(4.0, 5.0, 6.0, 1.0));
+	assert!((data.direction_and_cos_outer_angle.w - 0.5).abs() < 1e-6);
+	
+	let area_light = AreaLight::new(Vector3::new(1.0, 2.0, 3.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector2::new(2.0, 0.5), Vector3::new(4.0, 5.0, 6.0), 8.0, Deg(90.0).into());
+	let data = area_light.light_data();
+	assert_eq!(data.center_and_plane_width, Vector4::new(1.0, 2.0, 3.0, 2.0));
+	assert_eq!(data.intensities_and_plane_height, Vector4::new(4.0, 5.0, 6.0, 0.5));
+	assert_eq!(data.tangent_and_radius, Vector4::new(1.0, 0.0, 0.0, 8.0));
+	assert!(data.normal_and_cos_outer_angle.w.abs() < 1e-6);
+}
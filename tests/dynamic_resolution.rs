@@ -0,0 +1,72 @@
+//! The dynamic resolution controller, driven with synthetic gpu frametimes.
+
+use render_demo::render::{DynamicResolutionController, DYNAMIC_RESOLUTION_COOLDOWN_FRAMES, DYNAMIC_RESOLUTION_SCALE_STEP};
+
+/// Feeds frames whose gpu time scales with the pixel count like a fillrate bound gpu would.
+fn run_frames(controller: &mut DynamicResolutionController, frames: u32, full_res_frametime_ms: f32) -> f32 {
+	for _ in 0..frames {
+		let scale = controller.current_scale();
+		controller.update(full_res_frametime_ms * scale * scale);
+	}
+	controller.current_scale()
+}
+
+#[test]
+fn keeps_the_scale_within_the_target() {
+	let mut controller = DynamicResolutionController::new(0.5, 1.0, 16.0);
+	assert_eq!(controller.current_scale(), 1.0);
+	
+	// Frames within the target don't change anything
+	assert_eq!(run_frames(&mut controller, 200, 15.0), 1.0);
+	
+	// A too slow gpu lowers the scale until the frametime is near the target
+	let scale = run_frames(&mut controller, 500, 32.0);
+	assert!(scale < 1.0);
+	let frametime = 32.0 * scale * scale;
+	assert!(frametime > 16.0 * 0.85 && frametime < 16.0 * 1.15, "frametime {} at scale {}", frametime, scale);
+}
+
+#[test]
+fn drops_quickly_and_recovers_one_step_at_a_time() {
+	let mut controller = DynamicResolutionController::new(0.5, 1.0, 16.0);
+	
+	// The first change happens after the cooldown and goes straight to the ideal scale
+	assert_eq!(run_frames(&mut controller, DYNAMIC_RESOLUTION_COOLDOWN_FRAMES - 1, 32.0), 1.0);
+	let dropped = run_frames(&mut controller, 1, 32.0);
+	assert!((dropped - 0.7).abs() < 0.001, "dropped to {}", dropped);
+	
+	// Once the load is gone the scale climbs back by single steps per cooldown
+	let mut previous = dropped;
+	for _ in 0..3 {
+		let scale = run_frames(&mut controller, DYNAMIC_RESOLUTION_COOLDOWN_FRAMES, 4.0);
+		assert!((scale - previous - DYNAMIC_RESOLUTION_SCALE_STEP).abs() < 0.001, "{} -> {}", previous, scale);
+		previous = scale;
+	}
+	assert_eq!(run_frames(&mut controller, 1000, 4.0), 1.0);
+}
+
+#[test]
+fn respects_the_limits() {
+	let mut controller = DynamicResolutionController::new(0.5, 1.0, 16.0);
+	assert_eq!(run_frames(&mut controller, 500, 1000.0), 0.5);
+	
+	// Lowering the maximum clamps the current scale
+	controller.set_limits(0.25, 0.4, 16.0);
+	assert_eq!(controller.current_scale(), 0.4);
+	assert_eq!(run_frames(&mut controller, 500, 1.0), 0.4);
+	
+	// A maximum below the minimum wins
+	let controller = DynamicResolutionController::new(0.5, 0.3, 16.0);
+	assert_eq!(controller.min_scale(), 0.3);
+	assert_eq!(controller.current_scale(), 0.3);
+}
+
+#[test]
+fn ignores_invalid_frametimes() {
+	let mut controller = DynamicResolutionController::new(0.5, 1.0, 16.0);
+	for &frametime in &[0.0, -1.0, std::f32::NAN, std::f32::INFINITY] {
+		controller.update(frametime);
+	}
+	assert_eq!(controller.smoothed_frametime_ms(), None);
+	assert_eq!(controller.current_scale(), 1.0);
+}
@@ -0,0 +1,228 @@
+//! Validates the glsl of every permutation of every `.program` asset with naga,
+//! so broken shaders fail here instead of when the demo compiles them.
+#![cfg(feature = "glsl-validation")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use render_demo::asset::ASSET_MANAGER_INSTANCE;
+use render_demo::render::shader::ShaderStage;
+use render_demo::render::shader::managed::TranspiledProgram;
+use render_demo::structured_shader_language::{GlslValidator, GlslValidation, ParsedSource, SSLSourceParser, SSLTranspiler, SslDiagnostic, SslSeverity, TranspiledShaderSource, UniformDeclaration, adapt_gl_source, strip_comments, interface_direction};
+
+#[test]
+fn transpiled_programs_are_valid_glsl() {
+	let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	
+	let asset_manager = unsafe {&mut ASSET_MANAGER_INSTANCE};
+	asset_manager.init(manifest_dir.join("assets"));
+	
+	let mut validator = GlslValidator::new();
+	let mut failures = Vec::<String>::new();
+	
+	for program_path in &asset_manager.find_assets(".program").unwrap() {
+		let program = match TranspiledProgram::load(&program_path.as_path(), true) {
+			Ok(program) => program,
+			Err(err) => {
+				failures.push(format!("{:?}: {}", program_path, err));
+				continue;
+			}
+		};
+		
+		for (stage, _) in &program.shaders {
+			for key in program.permutation_layout.all_keys() {
+				let source = program.variant_source(*stage, key).unwrap();
+				
+				match validator.validate(&source, *stage) {
+					Ok(GlslValidation::Valid) => {},
+					Ok(GlslValidation::Skipped(warning)) => {
+						// Skipping is fine, but the warning has to point into the ssl sources like an error would
+						assert_eq!(warning.severity, SslSeverity::Warning);
+						assert!(warning.file.ends_with(".ssl"), "Skip warning doesn't point to an ssl file: {}", warning);
+						eprintln!("{}", warning);
+						break;
+					}
+					Err(diagnostics) => {
+						for diagnostic in &diagnostics {
+							failures.push(format!("{} {:?}: {}", program.stage_file_name(*stage), program.permutation_layout.defines(key), diagnostic));
+						}
+					}
+				}
+			}
+		}
+	}
+	
+	assert!(failures.is_empty(), "{} glsl error(s):\n{}", failures.len(), failures.join("\n"));
+}
+
+/// Parses a fixture from `tests/fixtures/ssl`, named by its path relative to the fixture root
+fn parse_fixture(file_name: &str) -> ParsedSource {
+	let fixture_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ssl").join(&file_name[1..]);
+	let source_code = fs::read_to_string(fixture_path).unwrap();
+	SSLSourceParser::new().parse_source(file_name, &source_code).unwrap()
+}
+
+/// Transpiles a fixture shader from `tests/fixtures/ssl/broken`, which are all broken on purpose
+fn transpile_broken_fixture(file_name: &str) -> TranspiledShaderSource {
+	let mut transpiler = SSLTranspiler::new();
+	transpiler.add_include(Rc::new(parse_fixture("/broken/lib.incl.ssl")));
+	transpiler.transpile(&parse_fixture(file_name)).unwrap()
+}
+
+fn validation_error(source: &TranspiledShaderSource) -> SslDiagnostic {
+	match GlslValidator::new().validate(source, ShaderStage::Fragment) {
+		Ok(validation) => panic!("Expected the validation to fail, got {:?}", validation),
+		Err(diagnostics) => {
+			assert_eq!(diagnostics.len(), 1, "Expected exactly one diagnostic, got {:?}", diagnostics);
+			diagnostics.into_iter().next().unwrap()
+		}
+	}
+}
+
+fn skip_warning(source: &TranspiledShaderSource, stage: ShaderStage) -> SslDiagnostic {
+	match GlslValidator::new().validate(source, stage) {
+		Ok(GlslValidation::Skipped(warning)) => warning,
+		other => panic!("Expected the validation to be skipped, got {:?}", other),
+	}
+}
+
+#[test]
+fn reports_errors_at_the_ssl_line() {
+	let diagnostic = validation_error(&transpile_broken_fixture("/broken/overload_error.frag.ssl"));
+	
+	assert_eq!((diagnostic.file.as_str(), diagnostic.line), ("/broken/overload_error.frag.ssl", 11), "{}", diagnostic);
+	assert_eq!(diagnostic.severity, SslSeverity::Error);
+	assert!(diagnostic.message.contains("brighten"), "{}", diagnostic);
+	assert!(diagnostic.to_string().starts_with("/broken/overload_error.frag.ssl:11:"), "{}", diagnostic);
+}
+
+#[test]
+fn reports_errors_in_includes_at_the_include_line() {
+	let diagnostic = validation_error(&transpile_broken_fixture("/broken/include_error.frag.ssl"));
+	
+	assert_eq!((diagnostic.file.as_str(), diagnostic.line), ("/broken/lib.incl.ssl", 13), "{}", diagnostic);
+	assert!(diagnostic.message.contains("darkness"), "{}", diagnostic);
+}
+
+#[test]
+fn skipped_stages_are_reported_as_warnings() {
+	let source = transpile_broken_fixture("/broken/overload_error.frag.ssl");
+	let warning = skip_warning(&source, ShaderStage::TessellationEval);
+	
+	assert_eq!(warning.severity, SslSeverity::Warning);
+	assert_eq!((warning.file.as_str(), warning.line), ("/broken/overload_error.frag.ssl", 1));
+	assert!(warning.to_string().contains(": warning: Skipped validation:"), "{}", warning);
+}
+
+#[test]
+fn unsupported_extensions_skip_at_the_ssl_line() {
+	let source_code = "@shadertype fragment\n@glslversion 430 core\n#extension GL_NV_gpu_shader5 : require\n\nvoid main() {}\n";
+	let parsed_source = SSLSourceParser::new().parse_source("/extension.frag.ssl", source_code).unwrap();
+	let source = SSLTranspiler::new().transpile(&parsed_source).unwrap();
+	let warning = skip_warning(&source, ShaderStage::Fragment);
+	
+	assert_eq!((warning.file.as_str(), warning.line), ("/extension.frag.ssl", 3), "{}", warning);
+	assert!(warning.message.contains("GL_NV_gpu_shader5"), "{}", warning);
+}
+
+#[test]
+fn strips_line_and_block_comments() {
+	let mut in_block_comment = false;
+	
+	assert_eq!(strip_comments("vec3 a; // comment", &mut in_block_comment), "vec3 a; ");
+	assert_eq!(strip_comments("vec3 /* inline */ a;", &mut in_block_comment), "vec3  a;");
+	assert!(!in_block_comment);
+	
+	// Block comments continue on the next lines
+	assert_eq!(strip_comments("float b; /* starts", &mut in_block_comment), "float b; ");
+	assert!(in_block_comment);
+	assert_eq!(strip_comments("still // commented", &mut in_block_comment), "");
+	assert_eq!(strip_comments("ends */ float c;", &mut in_block_comment), " float c;");
+	assert!(!in_block_comment);
+}
+
+#[test]
+fn parses_loose_uniforms() {
+	assert_eq!(UniformDeclaration::parse("uniform mat4 uViewProjection;"), Some(UniformDeclaration {
+		binding: None,
+		type_name: String::from("mat4"),
+		name: String::from("uViewProjection"),
+		array: String::new(),
+	}));
+	
+	let sampler = UniformDeclaration::parse("layout(binding = 3) uniform sampler2D uAlbedo;").unwrap();
+	assert_eq!((sampler.binding, sampler.type_name.as_str(), sampler.name.as_str()), (Some(3), "sampler2D", "uAlbedo"));
+	
+	let array = UniformDeclaration::parse("uniform vec4 uKernel[16] = vec4[16](vec4(0.0));").unwrap();
+	assert_eq!((array.name.as_str(), array.array.as_str()), ("uKernel", "[16]"));
+	
+	assert!(UniformDeclaration::parse("layout(binding = 1) uniform image2D uOutput;").unwrap().is_opaque());
+	
+	// Blocks, multi line declarations and other globals are left alone
+	assert_eq!(UniformDeclaration::parse("uniform Camera {"), None);
+	assert_eq!(UniformDeclaration::parse("uniform float uExposure"), None);
+	assert_eq!(UniformDeclaration::parse("const float PI = 3.14159;"), None);
+}
+
+#[test]
+fn finds_interface_direction_and_locations() {
+	assert_eq!(interface_direction("in vec3 vNormal;"), Some(("in", 1)));
+	assert_eq!(interface_direction("flat out uint vMaterial;"), Some(("out", 1)));
+	assert_eq!(interface_direction("out vec4 vCoefficients[3];"), Some(("out", 3)));
+	
+	// Explicit locations, blocks and anything else are left alone
+	assert_eq!(interface_direction("layout(location = 0) in vec3 aPosition;"), None);
+	assert_eq!(interface_direction("out VertexData {"), None);
+	assert_eq!(interface_direction("uniform float uExposure;"), None);
+}
+
+#[test]
+fn assigns_locations_to_interface_variables() {
+	let adapted = adapt_gl_source("#version 430 core\nin vec3 vNormal;\nin vec2 vUvs[2];\nin vec4 vColor;\nout vec4 outColor;\n").unwrap();
+	let lines = adapted.lines().collect::<Vec<_>>();
+	
+	// Inputs and outputs are counted separately, arrays take a location per element
+	assert_eq!(lines[0], "#version 450 core");
+	assert_eq!(lines[1], "layout(location = 16) in vec3 vNormal;");
+	assert_eq!(lines[2], "layout(location = 17) in vec2 vUvs[2];");
+	assert_eq!(lines[3], "layout(location = 19) in vec4 vColor;");
+	assert_eq!(lines[4], "layout(location = 16) out vec4 outColor;");
+}
+
+#[test]
+fn splits_combined_samplers() {
+	let source_code = "#version 430 core\nlayout(binding = 2) uniform sampler2DShadow uShadowMap;\nfloat shadow(sampler2DShadow map, vec3 uvw) {\n\treturn texture(map, uvw);\n}\nfloat sampleShadow(vec3 uvw) {\n\treturn shadow(uShadowMap, uvw);\n}\n";
+	let adapted = adapt_gl_source(source_code).unwrap();
+	let lines = adapted.lines().collect::<Vec<_>>();
+	
+	// Every line stays on its line
+	assert_eq!(lines.len(), source_code.lines().count());
+	
+	assert_eq!(lines[1], "layout(set = 0, binding = 2) uniform texture2D uShadowMap_texture; layout(set = 1, binding = 2) uniform samplerShadow uShadowMap_sampler;");
+	assert_eq!(lines[2], "float shadow(texture2D map_texture, samplerShadow map_sampler, vec3 uvw) {");
+	// Builtins get the combined sampler back, own functions the split parameters
+	assert_eq!(lines[3], "\treturn texture(sampler2DShadow(map_texture, map_sampler), uvw);");
+	assert_eq!(lines[6], "\treturn shadow(uShadowMap_texture, uShadowMap_sampler, uvw);");
+}
+
+#[test]
+fn wraps_loose_uniforms_into_blocks() {
+	let adapted = adapt_gl_source("uniform float uExposure;\nuniform vec2 uTexelSizes[4];\n").unwrap();
+	let lines = adapted.lines().collect::<Vec<_>>();
+	
+	assert_eq!(lines[0], "layout(set = 2, binding = 0) uniform _Uniform_uExposure { float uExposure; };");
+	assert_eq!(lines[1], "layout(set = 2, binding = 1) uniform _Uniform_uTexelSizes { vec2 uTexelSizes[4]; };");
+}
+
+#[test]
+fn combined_samplers_in_locals_are_unsupported() {
+	let source_code = "#version 430 core\nvoid main() {\n\tsampler2D local;\n}\n";
+	
+	match adapt_gl_source(source_code) {
+		Err((line, reason)) => {
+			assert_eq!(line, 3);
+			assert!(reason.contains("sampler2D"), "{}", reason);
+		}
+		Ok(adapted) => panic!("Expected the adapter to give up, got:\n{}", adapted),
+	}
+}
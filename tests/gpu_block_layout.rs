@@ -0,0 +1,157 @@
+//! Checks the std140/std430 layouts computed for `gpu_block!` structs against the offsets from the glsl spec.
+
+use cgmath::{Matrix3, Matrix4, Vector2, Vector3, SquareMatrix};
+use render_demo::gpu_block;
+use render_demo::render::shader::{GpuBlock, BlockLayoutStd, BlockInfo, BlockMemberInfo, UniformType, array_to_bytes};
+
+gpu_block! {
+	pub struct CameraBlock {
+		pub view: Matrix4<f32>,
+		pub position: Vector3<f32>,
+		pub fovy: f32,
+		pub depth_planes: Vector2<f32>,
+		pub frame: u32,
+	}
+}
+
+gpu_block! {
+	#[derive(Copy, Clone)]
+	pub struct PointLight {
+		pub position: Vector3<f32>,
+		pub radius: f32,
+		pub color: Vector3<f32>,
+	}
+}
+
+gpu_block! {
+	pub struct LightBlock {
+		pub num_lights: u32,
+		pub lights: [PointLight; 4],
+		pub weights: [f32; 3],
+		pub normal_matrix: Matrix3<f32>,
+		pub enabled: bool,
+	}
+}
+
+fn offsets<T: GpuBlock>(std: BlockLayoutStd) -> Vec<(&'static str, usize)> {
+	T::layout(std).members.iter().map(|m| (m.name, m.offset)).collect()
+}
+
+#[test]
+fn std140_and_std430_agree_without_arrays_and_structs() {
+	for std in &[BlockLayoutStd::Std140, BlockLayoutStd::Std430] {
+		assert_eq!(offsets::<CameraBlock>(*std), vec![("view", 0), ("position", 64), ("fovy", 76), ("depth_planes", 80), ("frame", 88)]);
+	}
+	assert_eq!(CameraBlock::layout(BlockLayoutStd::Std140).size, 96);
+	assert_eq!(CameraBlock::layout(BlockLayoutStd::Std430).size, 96);
+}
+
+#[test]
+fn std140_rounds_structs_and_arrays_to_vec4() {
+	let layout = PointLight::layout(BlockLayoutStd::Std140);
+	assert_eq!(layout.align, 16);
+	assert_eq!(layout.size, 32);
+	
+	assert_eq!(offsets::<LightBlock>(BlockLayoutStd::Std140), vec![("num_lights", 0), ("lights", 16), ("weights", 144), ("normal_matrix", 192), ("enabled", 240)]);
+	assert_eq!(LightBlock::layout(BlockLayoutStd::Std140).size, 256);
+}
+
+#[test]
+fn std430_packs_scalar_arrays() {
+	assert_eq!(offsets::<LightBlock>(BlockLayoutStd::Std430), vec![("num_lights", 0), ("lights", 16), ("weights", 144), ("normal_matrix", 160), ("enabled", 208)]);
+	assert_eq!(LightBlock::layout(BlockLayoutStd::Std430).size, 224);
+}
+
+#[test]
+fn writes_members_at_their_offsets() {
+	let camera = CameraBlock {
+		view: Matrix4::identity(),
+		position: Vector3::new(1.0, 2.0, 3.0),
+		fovy: 0.5,
+		depth_planes: Vector2::new(0.1, 100.0),
+		frame: 7,
+	};
+	let bytes = camera.to_bytes(BlockLayoutStd::Std140);
+	let float_at = |offset: usize| f32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+	
+	assert_eq!(bytes.len(), 96);
+	assert_eq!((float_at(0), float_at(4), float_at(20), float_at(60)), (1.0, 0.0, 1.0, 1.0));
+	assert_eq!((float_at(64), float_at(68), float_at(72), float_at(76)), (1.0, 2.0, 3.0, 0.5));
+	assert_eq!((float_at(80), float_at(84)), (0.1, 100.0));
+	assert_eq!(&bytes[88..92], &7u32.to_le_bytes());
+	
+	// Array elements of scalars are padded to vec4 in std140
+	let light = PointLight {position: Vector3::new(0.0, 0.0, 0.0), radius: 1.0, color: Vector3::new(1.0, 1.0, 1.0)};
+	let lights = LightBlock {
+		num_lights: 1,
+		lights: [light; 4],
+		weights: [1.0, 2.0, 3.0],
+		normal_matrix: Matrix3::identity(),
+		enabled: true,
+	};
+	let bytes = lights.to_bytes(BlockLayoutStd::Std140);
+	let float_at = |offset: usize| f32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+	assert_eq!((float_at(144), float_at(160), float_at(176)), (1.0, 2.0, 3.0));
+	assert_eq!((float_at(192), float_at(212), float_at(232)), (1.0, 1.0, 1.0));
+	assert_eq!(&bytes[240..244], &1u32.to_le_bytes());
+}
+
+#[test]
+fn lays_out_runtime_arrays() {
+	let lights = [
+		PointLight {position: Vector3::new(1.0, 2.0, 3.0), radius: 4.0, color: Vector3::new(5.0, 6.0, 7.0)},
+		PointLight {position: Vector3::new(8.0, 9.0, 10.0), radius: 11.0, color: Vector3::new(12.0, 13.0, 14.0)},
+	];
+	let bytes = array_to_bytes(&lights, BlockLayoutStd::Std430);
+	let float_at = |offset: usize| f32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+	
+	// Structs are rounded to their vec3 alignment
+	assert_eq!(bytes.len(), 64);
+	assert_eq!((float_at(0), float_at(12), float_at(16), float_at(24)), (1.0, 4.0, 5.0, 7.0));
+	assert_eq!((float_at(32), float_at(44), float_at(48), float_at(56)), (8.0, 11.0, 12.0, 14.0));
+	
+	// Scalars are only padded in std140
+	assert_eq!(array_to_bytes(&[1u32, 2, 3], BlockLayoutStd::Std430).len(), 12);
+	assert_eq!(array_to_bytes(&[1u32, 2, 3], BlockLayoutStd::Std140).len(), 48);
+}
+
+#[test]
+fn checks_layout_against_reflected_block() {
+	let member = |name: &str, offset: i32| BlockMemberInfo {
+		name: String::from(name),
+		member_type: UniformType::Float,
+		offset,
+		array_size: 1,
+		array_stride: 0,
+	};
+	let mut block = BlockInfo {
+		name: String::from("Lights"),
+		binding: 0,
+		data_size: 256,
+		members: vec![
+			member("num_lights", 0),
+			member("lights[0].position", 16),
+			member("lights[0].radius", 28),
+			member("lights[1].position", 48),
+			member("weights", 144),
+			member("normal_matrix", 192),
+			member("enabled", 240),
+		],
+	};
+	
+	let layout = LightBlock::layout(BlockLayoutStd::Std140);
+	assert_eq!(layout.check(&block), Ok(()));
+	
+	// Instance names are ignored
+	for member in &mut block.members {
+		member.name = format!("lights_block.{}", member.name);
+	}
+	assert_eq!(layout.check(&block), Ok(()));
+	
+	// The std430 layout has the matrix somewhere else
+	assert!(LightBlock::layout(BlockLayoutStd::Std430).check(&block).is_err());
+	
+	// Shader expects more data
+	block.data_size = 272;
+	assert!(layout.check(&block).is_err());
+}
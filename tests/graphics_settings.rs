@@ -0,0 +1,138 @@
+//! Presets, validation, change detection and persistence of the graphics settings.
+
+use std::env;
+use std::fs;
+use render_demo::render::{AntiAliasingMode, GraphicsConfiguration, GraphicsSetting, GraphicsSettingsError, GraphicsSettingsTable, QualityPreset, UpscaleFilter, scale_resolution};
+
+#[test]
+fn presets_are_valid() {
+	for &preset in &[QualityPreset::Low, QualityPreset::Medium, QualityPreset::High, QualityPreset::Ultra] {
+		let configuration = GraphicsSettingsTable::from_preset(preset).resolve().unwrap();
+		assert_eq!(configuration, GraphicsConfiguration::from_preset(preset));
+	}
+}
+
+#[test]
+fn rejects_invalid_settings() {
+	let mut settings = GraphicsSettingsTable::new();
+	settings.render_scale = Some(0.0);
+	match settings.resolve() {
+		Err(GraphicsSettingsError::RenderScaleOutOfRange {..}) => {},
+		_ => panic!("Expected RenderScaleOutOfRange"),
+	}
+	
+	settings.render_scale = Some(std::f32::NAN);
+	assert!(settings.resolve().is_err());
+	
+	let mut settings = GraphicsSettingsTable::new();
+	settings.sss_kernel_size = Some(13);
+	match settings.resolve() {
+		Err(GraphicsSettingsError::UnsupportedSssKernelSize {kernel_size}) => assert_eq!(kernel_size, 13),
+		_ => panic!("Expected UnsupportedSssKernelSize"),
+	}
+	
+	let mut settings = GraphicsSettingsTable::new();
+	settings.bloom_octaves = Some(0);
+	match settings.resolve() {
+		Err(GraphicsSettingsError::BloomOctavesOutOfRange {..}) => {},
+		_ => panic!("Expected BloomOctavesOutOfRange"),
+	}
+	
+	let mut settings = GraphicsSettingsTable::new();
+	settings.bloom_intensity = Some(1.5);
+	match settings.resolve() {
+		Err(GraphicsSettingsError::BloomIntensityOutOfRange {..}) => {},
+		_ => panic!("Expected BloomIntensityOutOfRange"),
+	}
+	
+	settings.bloom_intensity = None;
+	settings.bloom_threshold = Some(-1.0);
+	match settings.resolve() {
+		Err(GraphicsSettingsError::InvalidBloomThreshold {..}) => {},
+		_ => panic!("Expected InvalidBloomThreshold"),
+	}
+	
+	let mut settings = GraphicsSettingsTable::new();
+	settings.target_frametime_ms = Some(0.0);
+	match settings.resolve() {
+		Err(GraphicsSettingsError::InvalidTargetFrametime {..}) => {},
+		_ => panic!("Expected InvalidTargetFrametime"),
+	}
+}
+
+#[test]
+fn overrides_apply_on_top_of_the_preset() {
+	let mut settings = GraphicsSettingsTable::from_preset(QualityPreset::Low);
+	let mut configuration = settings.resolve().unwrap();
+	configuration.sss_kernel_size = 21;
+	configuration.anti_aliasing = AntiAliasingMode::Ehaa;
+	
+	// Only the settings that differ from the preset are stored
+	settings.set_configuration(&configuration);
+	assert_eq!(settings.sss_kernel_size, Some(21));
+	assert_eq!(settings.anti_aliasing, Some(AntiAliasingMode::Ehaa));
+	assert_eq!(settings.render_scale, None);
+	assert_eq!(settings.resolve().unwrap(), configuration);
+	
+	// Switching presets drops the overrides
+	settings.set_preset(QualityPreset::High);
+	assert_eq!(settings, GraphicsSettingsTable::from_preset(QualityPreset::High));
+}
+
+#[test]
+fn detects_changed_settings() {
+	let low = GraphicsConfiguration::from_preset(QualityPreset::Low);
+	assert!(low.changed_settings(&low.clone()).is_empty());
+	
+	let mut changed = low.clone();
+	changed.bloom_octaves = 5;
+	changed.vsync = !low.vsync;
+	assert_eq!(low.changed_settings(&changed), vec![GraphicsSetting::BloomOctaves, GraphicsSetting::Vsync]);
+	
+	let high = GraphicsConfiguration::from_preset(QualityPreset::High);
+	assert_eq!(low.changed_settings(&high).len(), 6);
+	
+	let mut dynamic = low.clone();
+	dynamic.target_frametime_ms = 8.0;
+	assert_eq!(low.changed_settings(&dynamic), vec![GraphicsSetting::DynamicResolution]);
+}
+
+#[test]
+fn scales_the_resolution() {
+	let mut configuration = GraphicsConfiguration::new();
+	configuration.render_scale = 0.5;
+	assert_eq!(configuration.scaled_resolution((1600, 900)), (800, 450));
+	
+	configuration.render_scale = 0.25;
+	assert_eq!(configuration.scaled_resolution((2, 2)), (1, 1));
+	
+	assert_eq!(scale_resolution((1600, 900), 0.75), (1200, 675));
+}
+
+#[test]
+fn round_trips_through_ron() {
+	let mut settings = GraphicsSettingsTable::from_preset(QualityPreset::Medium);
+	settings.render_scale = Some(0.6);
+	settings.tessellation = Some(true);
+	settings.dynamic_resolution = Some(true);
+	settings.upscale_filter = Some(UpscaleFilter::Bilinear);
+	
+	let path = env::temp_dir().join(format!("render_demo_graphics_settings_{}.ron", std::process::id()));
+	settings.save(&path).unwrap();
+	let loaded = GraphicsSettingsTable::load(&path);
+	
+	// Missing settings fall back to the preset, invalid ones fail the load
+	fs::write(&path, "GraphicsSettings(preset: Low, bloom_octaves: Some(3))").unwrap();
+	let partial = GraphicsSettingsTable::load(&path);
+	fs::write(&path, "GraphicsSettings(preset: Low, sss_kernel_size: Some(4))").unwrap();
+	let invalid = GraphicsSettingsTable::load(&path);
+	let _ = fs::remove_file(&path);
+	
+	assert_eq!(loaded.unwrap(), settings);
+	
+	let partial = partial.unwrap();
+	assert_eq!(partial.bloom_octaves, Some(3));
+	assert_eq!(partial.resolve().unwrap().sss_kernel_size, GraphicsConfiguration::from_preset(QualityPreset::Low).sss_kernel_size);
+	
+	assert!(invalid.is_err());
+}
@@ -0,0 +1,155 @@
+//! The timing statistics and the scope tree of the profiler, timed on the cpu only.
+
+use render_demo::render::performance::{PerformanceProfiler, TimingHistory, PERFORMANCE_FRAME_SCOPE};
+
+fn history_of(samples: &[f32]) -> TimingHistory {
+	let mut history = TimingHistory::new(samples.len());
+	for &sample in samples {
+		history.push(sample);
+	}
+	history
+}
+
+#[test]
+fn keeps_a_rolling_window() {
+	let mut history = TimingHistory::new(4);
+	assert!(history.is_empty());
+	assert_eq!(history.average(), None);
+	assert_eq!(history.statistics(), None);
+	
+	for sample in 1..=6 {
+		history.push(sample as f32);
+	}
+	assert_eq!(history.samples().collect::<Vec<_>>(), vec![3.0, 4.0, 5.0, 6.0]);
+	assert_eq!(history.total_samples(), 6);
+	assert_eq!(history.latest(), Some(6.0));
+	assert_eq!(history.average(), Some(4.5));
+	assert_eq!((history.min(), history.max()), (Some(3.0), Some(6.0)));
+	
+	// Samples that aren't finite are ignored
+	history.push(std::f32::NAN);
+	history.push(std::f32::INFINITY);
+	assert_eq!(history.latest(), Some(6.0));
+	assert_eq!(history.total_samples(), 6);
+}
+
+#[test]
+fn takes_nearest_rank_percentiles() {
+	let history = history_of(&[5.0, 1.0, 4.0, 2.0, 3.0, 10.0, 9.0, 8.0, 7.0, 6.0]);
+	assert_eq!(history.percentile(0.0), Some(1.0));
+	assert_eq!(history.percentile(10.0), Some(1.0));
+	assert_eq!(history.percentile(11.0), Some(2.0));
+	assert_eq!(history.percentile(50.0), Some(5.0));
+	assert_eq!(history.percentile(95.0), Some(10.0));
+	assert_eq!(history.percentile(100.0), Some(10.0));
+	
+	let statistics = history.statistics().unwrap();
+	assert_eq!(statistics.num_samples, 10);
+	assert_eq!(statistics.latest_ms, 6.0);
+	assert_eq!(statistics.average_ms, 5.5);
+	assert_eq!((statistics.min_ms, statistics.max_ms), (1.0, 10.0));
+	assert_eq!((statistics.median_ms, statistics.p95_ms, statistics.p99_ms), (5.0, 10.0, 10.0));
+	
+	// A single spike only shows in the highest percentiles
+	let mut samples = vec![2.0; 99];
+	samples.push(50.0);
+	let statistics = history_of(&samples).statistics().unwrap();
+	assert_eq!((statistics.median_ms, statistics.p95_ms, statistics.p99_ms, statistics.max_ms), (2.0, 2.0, 2.0, 50.0));
+}
+
+#[test]
+fn nests_scopes_in_the_frame() {
+	let mut profiler = PerformanceProfiler::new(16);
+	assert!(!profiler.is_gpu_timing_enabled());
+	
+	for _ in 0..3 {
+		profiler.begin_frame();
+		
+		profiler.begin_scope("scene");
+		profiler.scope("shadows", |_| {});
+		profiler.end_scope();
+		
+		profiler.scope("post", |profiler| {
+			profiler.scope("bloom", |_| {});
+		});
+		
+		profiler.end_frame();
+	}
+	assert_eq!(profiler.num_frames(), 3);
+	
+	// Every scope gets one timer, found by its path
+	let paths = profiler.timers().iter().map(|t| t.path()).collect::<Vec<_>>();
+	assert_eq!(paths, vec!["frame", "frame/scene", "frame/scene/shadows", "frame/post", "frame/post/bloom"]);
+	
+	let bloom = profiler.timer("frame/post/bloom").unwrap();
+	assert_eq!((bloom.name(), bloom.depth()), ("bloom", 2));
+	assert_eq!(bloom.parent(), Some(3));
+	assert!(profiler.timer("frame/bloom").is_none());
+	
+	// Every frame adds one cpu sample per scope, the gpu isn't timed
+	for timer in profiler.timers() {
+		assert_eq!(timer.cpu.len(), 3, "{}", timer.path());
+		assert!(timer.gpu.is_empty());
+	}
+	assert_eq!(profiler.cpu_statistics(PERFORMANCE_FRAME_SCOPE).unwrap().num_samples, 3);
+	assert_eq!(profiler.gpu_statistics(PERFORMANCE_FRAME_SCOPE), None);
+	assert_eq!(profiler.resolved_gpu_frametime_ms(), None);
+	
+	// Enclosing scopes take at least as long as their children
+	let frame = profiler.cpu_statistics("frame").unwrap();
+	let scene = profiler.cpu_statistics("frame/scene").unwrap();
+	let shadows = profiler.cpu_statistics("frame/scene/shadows").unwrap();
+	assert!(frame.latest_ms >= scene.latest_ms && scene.latest_ms >= shadows.latest_ms);
+	
+	let children = profiler.children(0).map(|(_, t)| t.name()).collect::<Vec<_>>();
+	assert_eq!(children, vec!["scene", "post"]);
+}
+
+#[test]
+fn closes_scopes_left_open() {
+	let mut profiler = PerformanceProfiler::new(16);
+	
+	profiler.begin_frame();
+	profiler.begin_scope("scene");
+	profiler.end_frame();
+	
+	// Not ending the frame at all closes it with the next one
+	profiler.begin_frame();
+	profiler.begin_scope("scene");
+	profiler.begin_frame();
+	profiler.end_frame();
+	
+	// Scopes are still nested in the frame afterwards
+	profiler.begin_frame();
+	profiler.scope("post", |_| {});
+	profiler.end_frame();
+	
+	assert_eq!(profiler.timer("frame").unwrap().cpu.len(), 4);
+	assert_eq!(profiler.timer("frame/scene").unwrap().cpu.len(), 2);
+	assert_eq!(profiler.timer("frame/post").unwrap().cpu.len(), 1);
+	assert_eq!(profiler.timers().len(), 3);
+}
+
+#[test]
+fn reports_the_scope_tree() {
+	let mut profiler = PerformanceProfiler::new(16);
+	profiler.begin_frame();
+	profiler.scope("scene", |profiler| profiler.scope("shadows", |_| {}));
+	profiler.scope("post", |_| {});
+	profiler.end_frame();
+	
+	let report = profiler.report();
+	assert_eq!(report.lines().count(), 5);
+	assert!(report.lines().nth(1).unwrap().starts_with("frame "));
+	assert!(report.lines().nth(2).unwrap().starts_with("  scene "));
+	assert!(report.lines().nth(3).unwrap().starts_with("    shadows "));
+	assert!(report.lines().nth(4).unwrap().starts_with("  post "));
+	
+	// The gpu column is empty without gpu timing
+	assert!(report.lines().skip(1).all(|l| l.trim_end().ends_with('-')));
+	
+	// Resetting drops the samples but keeps the timers
+	profiler.reset();
+	assert_eq!(profiler.timers().len(), 4);
+	assert!(profiler.timers().iter().all(|t| t.cpu.is_empty()));
+}
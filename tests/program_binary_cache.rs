@@ -0,0 +1,195 @@
+//! Keys, validation and eviction of the on-disk program binary cache.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use render_demo::render::shader::ShaderStage;
+use render_demo::render::shader::managed::{ProgramBinaryCache, ProgramBinaryKey, MAX_ENTRY_AGE_SECS};
+
+const DRIVER: &[&str] = &["Vendor", "Renderer 1.0", "4.6.0 Driver 100.1"];
+const VERTEX_SOURCE: &[u8] = b"#version 430 core\nvoid main() {}\n";
+const FRAGMENT_SOURCE: &[u8] = b"#version 430 core\nout vec4 c;\nvoid main() {c = vec4(1.0);}\n";
+const FORMAT: u32 = 0x8741;
+
+/// An empty cache directory for a test
+fn cache_dir(test_name: &str) -> PathBuf {
+	let cache_dir = std::env::temp_dir().join(format!("render_demo_program_binary_cache_{}_{}", test_name, std::process::id()));
+	let _ = fs::remove_dir_all(&cache_dir);
+	cache_dir
+}
+
+fn open_cache(cache_dir: &PathBuf, driver_info: &[&str]) -> ProgramBinaryCache {
+	let mut cache = ProgramBinaryCache::new();
+	cache.open(cache_dir.clone(), driver_info).unwrap();
+	cache
+}
+
+fn program_key(cache: &ProgramBinaryCache, fragment_source: &[u8]) -> ProgramBinaryKey {
+	cache.key(&[(ShaderStage::Vertex, VERTEX_SOURCE), (ShaderStage::Fragment, fragment_source)])
+}
+
+fn binary_path(cache_dir: &PathBuf, key: ProgramBinaryKey) -> PathBuf {
+	cache_dir.join(format!("{:016x}.bin", key))
+}
+
+fn now() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// Rewrites the last used time of an entry in the saved index
+fn set_last_used(cache_dir: &PathBuf, key: ProgramBinaryKey, last_used: u64) {
+	let index_path = cache_dir.join("index.ron");
+	let index = fs::read_to_string(&index_path).unwrap();
+	
+	let entry_start = index.find(&format!("{:016x}.bin", key)).unwrap();
+	let value_start = entry_start + index[entry_start..].find("last_used:").unwrap() + "last_used:".len();
+	let value_end = value_start + index[value_start..].find(|c: char| c != ' ' && !c.is_ascii_digit()).unwrap();
+	
+	fs::write(&index_path, format!("{} {}{}", &index[..value_start], last_used, &index[value_end..])).unwrap();
+}
+
+#[test]
+fn key_covers_driver_stages_and_sources() {
+	let cache = open_cache(&cache_dir("key"), DRIVER);
+	let key = program_key(&cache, FRAGMENT_SOURCE);
+	
+	assert_eq!(program_key(&cache, FRAGMENT_SOURCE), key);
+	assert_ne!(program_key(&cache, b"#version 430 core\n#define USE_FOG 1\nvoid main() {}\n"), key);
+	assert_ne!(cache.key(&[(ShaderStage::Vertex, VERTEX_SOURCE), (ShaderStage::Compute, FRAGMENT_SOURCE)]), key);
+	assert_ne!(cache.key(&[(ShaderStage::Fragment, FRAGMENT_SOURCE), (ShaderStage::Vertex, VERTEX_SOURCE)]), key);
+	
+	// Moving text from one stage to the next changes the key too
+	assert_ne!(
+		cache.key(&[(ShaderStage::Vertex, b"ab"), (ShaderStage::Fragment, b"c")]),
+		cache.key(&[(ShaderStage::Vertex, b"a"), (ShaderStage::Fragment, b"bc")]),
+	);
+	
+	let other_driver = open_cache(&cache_dir("key_other_driver"), &["Vendor", "Renderer 1.0", "4.6.0 Driver 101.0"]);
+	assert_ne!(program_key(&other_driver, FRAGMENT_SOURCE), key);
+}
+
+#[test]
+fn loads_stored_binaries_after_reopening() {
+	let cache_dir = cache_dir("reopen");
+	let binary = vec![1u8, 2, 3, 4, 5];
+	
+	let key = {
+		let mut cache = open_cache(&cache_dir, DRIVER);
+		let key = program_key(&cache, FRAGMENT_SOURCE);
+		assert_eq!(cache.load(key), None);
+		
+		cache.store(key, FORMAT, &binary);
+		assert_eq!(cache.load(key), Some((FORMAT, binary.clone())));
+		key
+	};
+	
+	let mut cache = open_cache(&cache_dir, DRIVER);
+	assert_eq!(cache.load(key), Some((FORMAT, binary)));
+}
+
+#[test]
+fn binaries_of_another_driver_are_not_loaded() {
+	let cache_dir = cache_dir("stale_driver");
+	
+	let old_key = {
+		let mut cache = open_cache(&cache_dir, DRIVER);
+		let key = program_key(&cache, FRAGMENT_SOURCE);
+		cache.store(key, FORMAT, &[1, 2, 3]);
+		key
+	};
+	
+	// After a driver update the same program gets a new key, the old binary is never looked up
+	let mut cache = open_cache(&cache_dir, &["Vendor", "Renderer 1.0", "4.6.0 Driver 101.0"]);
+	let new_key = program_key(&cache, FRAGMENT_SOURCE);
+	assert_ne!(new_key, old_key);
+	assert_eq!(cache.load(new_key), None);
+}
+
+#[test]
+fn rejects_truncated_and_corrupt_files() {
+	let cache_dir = cache_dir("corrupt");
+	let mut cache = open_cache(&cache_dir, DRIVER);
+	let binary = vec![7u8; 64];
+	
+	let key = program_key(&cache, FRAGMENT_SOURCE);
+	let path = binary_path(&cache_dir, key);
+	let corruptions: Vec<Box<dyn Fn(Vec<u8>) -> Vec<u8>>> = vec![
+		// Truncated in the middle of the header
+		Box::new(|data| data[..6].to_vec()),
+		// Truncated binary
+		Box::new(|data| data[..data.len() - 8].to_vec()),
+		// Wrong magic
+		Box::new(|mut data| {data[0] = b'X'; data}),
+		// Key of another program
+		Box::new(|mut data| {data[8] ^= 0xff; data}),
+	];
+	
+	for corrupt in corruptions {
+		cache.store(key, FORMAT, &binary);
+		let data = fs::read(&path).unwrap();
+		fs::write(&path, corrupt(data)).unwrap();
+		
+		// The broken entry is dropped
+		assert_eq!(cache.load(key), None);
+		assert!(!path.exists());
+	}
+	
+	// A missing file is no problem either
+	cache.store(key, FORMAT, &binary);
+	fs::remove_file(&path).unwrap();
+	assert_eq!(cache.load(key), None);
+}
+
+#[test]
+fn evicts_entries_not_used_for_too_long() {
+	let cache_dir = cache_dir("evict_age");
+	
+	let (old_key, recent_key) = {
+		let mut cache = open_cache(&cache_dir, DRIVER);
+		let old_key = program_key(&cache, FRAGMENT_SOURCE);
+		let recent_key = program_key(&cache, b"void main() {}\n");
+		cache.store(old_key, FORMAT, &[1, 2, 3]);
+		cache.store(recent_key, FORMAT, &[4, 5, 6]);
+		(old_key, recent_key)
+	};
+	set_last_used(&cache_dir, old_key, now() - MAX_ENTRY_AGE_SECS - 60);
+	set_last_used(&cache_dir, recent_key, now() - MAX_ENTRY_AGE_SECS + 60 * 60);
+	
+	let mut cache = open_cache(&cache_dir, DRIVER);
+	assert!(!binary_path(&cache_dir, old_key).exists());
+	assert_eq!(cache.load(old_key), None);
+	assert_eq!(cache.load(recent_key), Some((FORMAT, vec![4, 5, 6])));
+}
+
+#[test]
+fn evicts_least_recently_used_entries_above_the_size_limit() {
+	let cache_dir = cache_dir("evict_size");
+	let binary = vec![0u8; 1000];
+	
+	let keys = {
+		let mut cache = open_cache(&cache_dir, DRIVER);
+		let keys = (0..3).map(|i| program_key(&cache, format!("void main() {{}} // {}\n", i).as_bytes())).collect::<Vec<_>>();
+		for key in &keys {
+			cache.store(*key, FORMAT, &binary);
+		}
+		keys
+	};
+	
+	// The second entry was used the longest time ago, then the first
+	set_last_used(&cache_dir, keys[0], now() - 20);
+	set_last_used(&cache_dir, keys[1], now() - 30);
+	set_last_used(&cache_dir, keys[2], now() - 10);
+	
+	// Room for two entries (including their headers)
+	let mut cache = ProgramBinaryCache::new();
+	cache.set_limits(2 * 1016, MAX_ENTRY_AGE_SECS);
+	cache.open(cache_dir.clone(), DRIVER).unwrap();
+	
+	assert_eq!(cache.load(keys[1]), None);
+	assert!(cache.load(keys[0]).is_some());
+	assert!(cache.load(keys[2]).is_some());
+	
+	// Only the two entries are left
+	let num_files = fs::read_dir(&cache_dir).unwrap().filter(|e| e.as_ref().unwrap().path().extension().map_or(false, |e| e == "bin")).count();
+	assert_eq!(num_files, 2);
+}
@@ -0,0 +1,30 @@
+//! Programs whose sources fail to transpile are kept by the `ProgramLoader` and drawn with the error program.
+
+use std::path::PathBuf;
+use render_demo::asset::{ASSET_MANAGER_INSTANCE, AssetCache, AssetPath, AssetPathBuf, LoadStatus};
+use render_demo::render::shader::managed::{ProgramLoader, ProgramBuildStatus};
+
+const PROGRAM: &str = "/broken/broken_import.program";
+
+#[test]
+fn broken_sources_use_the_error_program() {
+	let asset_manager = unsafe {&mut ASSET_MANAGER_INSTANCE};
+	asset_manager.init(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ssl"));
+	
+	let mut program_cache = AssetCache::new(Box::new(ProgramLoader::new()));
+	let handle = program_cache.load(&AssetPath::from_str(PROGRAM));
+	assert_eq!(handle.status(), LoadStatus::Ready);
+	
+	let program = handle.get().unwrap();
+	assert!(!program.has_sources());
+	assert!(program.is_using_error_program(program.permutation_layout().default_key()));
+	
+	// The transpile error is the build result
+	let build_result = program.last_build_result().unwrap();
+	assert_eq!(build_result.status, ProgramBuildStatus::SourceError);
+	assert!(build_result.source_error.as_deref().unwrap().contains("Unresolved import `imports.cycle_a_typo`"), "{}", build_result);
+	
+	// The listed source is recorded as dependency so fixing it reloads the program
+	let dependencies = asset_manager.dependency_graph().dependencies_of(&AssetPath::from_str(PROGRAM));
+	assert_eq!(dependencies, vec![AssetPathBuf::from("/imports/unknown_namespace.frag.ssl")]);
+}
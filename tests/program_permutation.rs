@@ -0,0 +1,144 @@
+//! Packing of program permutations into `PermutationKey`s.
+
+use render_demo::render::shader::managed::{PermutationKey, PermutationLayout};
+use render_demo::render::shader::managed::ProgramAssetSchema::PermutationDef;
+
+fn flag(name: &str) -> PermutationDef {
+	PermutationDef {
+		name: String::from(name),
+		values: PermutationDef::default_values(),
+		default: None,
+	}
+}
+
+fn values(name: &str, values: &[&str], default: Option<&str>) -> PermutationDef {
+	PermutationDef {
+		name: String::from(name),
+		values: values.iter().map(|v| String::from(*v)).collect(),
+		default: default.map(String::from),
+	}
+}
+
+/// A flag, a three valued permutation defaulting to its last value and another flag
+fn test_layout() -> PermutationLayout {
+	PermutationLayout::from_defs(&[
+		flag("USE_SHADOWS"),
+		values("QUALITY", &["LOW", "MEDIUM", "HIGH"], Some("HIGH")),
+		flag("USE_FOG"),
+	]).unwrap()
+}
+
+#[test]
+fn empty_layout_has_a_single_zero_key() {
+	let layout = PermutationLayout::from_defs(&[]).unwrap();
+	
+	assert!(layout.is_empty());
+	assert_eq!(layout.default_key(), PermutationKey(0));
+	assert_eq!(layout.all_keys(), vec![PermutationKey(0)]);
+	assert!(layout.defines(layout.default_key()).is_empty());
+}
+
+#[test]
+fn permutations_are_packed_in_declaration_order() {
+	let layout = test_layout();
+	
+	// USE_SHADOWS takes bit 0, QUALITY bits 1-2 and USE_FOG bit 3
+	assert_eq!(layout.key(&[("QUALITY", "LOW")]).unwrap(), PermutationKey(0b0000));
+	assert_eq!(layout.key(&[("USE_SHADOWS", "1"), ("QUALITY", "LOW")]).unwrap(), PermutationKey(0b0001));
+	assert_eq!(layout.key(&[("QUALITY", "MEDIUM")]).unwrap(), PermutationKey(0b0010));
+	assert_eq!(layout.key(&[("QUALITY", "HIGH")]).unwrap(), PermutationKey(0b0100));
+	assert_eq!(layout.key(&[("USE_FOG", "1"), ("QUALITY", "LOW")]).unwrap(), PermutationKey(0b1000));
+}
+
+#[test]
+fn default_key_uses_declared_defaults() {
+	let layout = test_layout();
+	let key = layout.default_key();
+	
+	assert_eq!(key, PermutationKey(0b0100));
+	assert_eq!(layout.value_of(key, "USE_SHADOWS"), Some("0"));
+	assert_eq!(layout.value_of(key, "QUALITY"), Some("HIGH"));
+	assert_eq!(layout.value_of(key, "USE_FOG"), Some("0"));
+	assert_eq!(layout.key(&[]).unwrap(), key);
+}
+
+#[test]
+fn with_flag_and_with_value_only_touch_their_own_bits() {
+	let layout = test_layout();
+	let key = layout.default_key();
+	
+	let shadows = layout.with_flag(key, "USE_SHADOWS", true).unwrap();
+	assert_eq!(shadows, PermutationKey(0b0101));
+	assert_eq!(layout.with_flag(shadows, "USE_SHADOWS", false).unwrap(), key);
+	
+	let medium = layout.with_value(shadows, "QUALITY", "MEDIUM").unwrap();
+	assert_eq!(medium, PermutationKey(0b0011));
+	assert_eq!(layout.value_of(medium, "USE_SHADOWS"), Some("1"));
+	assert_eq!(layout.value_of(medium, "USE_FOG"), Some("0"));
+	
+	// Setting a value twice doesn't leave bits of the old value behind
+	let low = layout.with_value(medium, "QUALITY", "LOW").unwrap();
+	assert_eq!(low, PermutationKey(0b0001));
+}
+
+#[test]
+fn unknown_names_and_values_are_rejected() {
+	let layout = test_layout();
+	let key = layout.default_key();
+	
+	assert_eq!(layout.with_value(key, "QUALITY", "ULTRA"), None);
+	assert_eq!(layout.with_value(key, "USE_BLOOM", "1"), None);
+	assert_eq!(layout.with_flag(key, "QUALITY", true), None);
+	assert_eq!(layout.value_of(key, "USE_BLOOM"), None);
+	assert!(layout.key(&[("QUALITY", "ULTRA")]).is_err());
+}
+
+#[test]
+fn out_of_range_value_indices_have_no_value() {
+	let layout = test_layout();
+	
+	// QUALITY has three values, so index 3 (0b11 in bits 1-2) is unused
+	let key = PermutationKey(0b0110);
+	assert_eq!(layout.value_of(key, "QUALITY"), None);
+	assert_eq!(layout.defines(key), vec![("USE_SHADOWS", "0"), ("QUALITY", ""), ("USE_FOG", "0")]);
+	assert!(!layout.all_keys().contains(&key));
+}
+
+#[test]
+fn keys_round_trip_through_values_and_defines() {
+	let layout = test_layout();
+	let keys = layout.all_keys();
+	
+	assert_eq!(keys.len(), 2 * 3 * 2);
+	assert_eq!(keys[0], layout.default_key());
+	
+	for key in keys {
+		let defines = layout.defines(key);
+		assert_eq!(defines.iter().map(|(name, _)| *name).collect::<Vec<_>>(), vec!["USE_SHADOWS", "QUALITY", "USE_FOG"]);
+		assert_eq!(layout.key(&defines).unwrap(), key);
+	}
+}
+
+#[test]
+fn single_value_permutations_take_no_bits() {
+	let layout = PermutationLayout::from_defs(&[
+		values("BACKEND", &["GL"], None),
+		flag("USE_FOG"),
+	]).unwrap();
+	
+	assert_eq!(layout.with_flag(layout.default_key(), "USE_FOG", true).unwrap(), PermutationKey(0b1));
+	assert_eq!(layout.value_of(PermutationKey(0b1), "BACKEND"), Some("GL"));
+	assert_eq!(layout.all_keys().len(), 2);
+}
+
+#[test]
+fn invalid_definitions_are_rejected() {
+	assert!(PermutationLayout::from_defs(&[values("EMPTY", &[], None)]).is_err());
+	assert!(PermutationLayout::from_defs(&[flag("USE_FOG"), flag("USE_FOG")]).is_err());
+	assert!(PermutationLayout::from_defs(&[values("QUALITY", &["LOW", "HIGH"], Some("ULTRA"))]).is_err());
+	
+	// 65 flags need 65 bits
+	let too_many = (0..65).map(|i| flag(&format!("FLAG_{}", i))).collect::<Vec<_>>();
+	assert!(PermutationLayout::from_defs(&too_many).is_err());
+	assert!(PermutationLayout::from_defs(&too_many[..64]).is_ok());
+}
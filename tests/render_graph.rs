@@ -0,0 +1,182 @@
+//! Compiles render graphs without a gl context, the graph compilation doesn't touch gl.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use gl_bindings::gl;
+use render_demo::render::{ImageFormat, Texture};
+use render_demo::render::render_graph::{RenderGraph, RenderGraphError, TextureDesc, PassId, ResourceAccess, ResourceLifetime};
+
+fn hdr_desc() -> TextureDesc {
+	TextureDesc::new(1280, 720, ImageFormat::get(gl::R11F_G11F_B10F))
+}
+
+fn depth_desc() -> TextureDesc {
+	TextureDesc::new(1280, 720, ImageFormat::get(gl::DEPTH_COMPONENT32F))
+}
+
+#[test]
+fn culls_passes_nothing_reads() {
+	let mut graph = RenderGraph::new();
+	let scene = graph.create_texture("scene", hdr_desc());
+	let unused = graph.create_texture("unused", hdr_desc());
+	let backbuffer = graph.import_backbuffer("backbuffer", (1280, 720));
+	
+	let scene_pass = graph.add_pass("scene").color_attachment(0, scene).id();
+	let unused_pass = graph.add_pass("unused").read(scene).color_attachment(0, unused).id();
+	let debug_pass = graph.add_pass("debug").side_effects().id();
+	let composite_pass = graph.add_pass("composite").read(scene).color_attachment(0, backbuffer).id();
+	
+	let compiled = graph.compile().unwrap();
+	assert_eq!(compiled.pass_order(), &[scene_pass, debug_pass, composite_pass]);
+	assert_eq!(compiled.culled_passes(), &[unused_pass]);
+	assert_eq!(compiled.lifetime(unused), None);
+	assert_eq!(compiled.physical_slot(unused), None);
+}
+
+#[test]
+fn orders_writers_before_readers() {
+	let mut graph = RenderGraph::new();
+	let a = graph.create_texture("a", hdr_desc());
+	let b = graph.create_texture("b", hdr_desc());
+	let backbuffer = graph.import_backbuffer("backbuffer", (1280, 720));
+	
+	let write_a = graph.add_pass("write_a").color_attachment(0, a).id();
+	let write_b = graph.add_pass("write_b").color_attachment(0, b).id();
+	let combine = graph.add_pass("combine").read(b).read(a).color_attachment(0, backbuffer).id();
+	
+	let compiled = graph.compile().unwrap();
+	assert_eq!(compiled.pass_order(), &[write_a, write_b, combine]);
+	assert_eq!(compiled.lifetime(a), Some(ResourceLifetime {first_use: 0, last_use: 2}));
+	assert_eq!(compiled.lifetime(b), Some(ResourceLifetime {first_use: 1, last_use: 2}));
+}
+
+#[test]
+fn aliases_transients_with_disjoint_lifetimes() {
+	let mut graph = RenderGraph::new();
+	let scene = graph.create_texture("scene", hdr_desc());
+	let depth = graph.create_texture("depth", depth_desc());
+	let intermediate = graph.create_texture("intermediate", hdr_desc());
+	let resolved = graph.create_texture("resolved", hdr_desc());
+	let backbuffer = graph.import_backbuffer("backbuffer", (1280, 720));
+	
+	graph.add_pass("scene").color_attachment(0, scene).depth_attachment(depth);
+	graph.add_pass("blur_x").read(scene).read(depth).color_attachment(0, intermediate);
+	graph.add_pass("blur_y").read(intermediate).read(depth).color_attachment(0, resolved);
+	graph.add_pass("composite").read(resolved).color_attachment(0, backbuffer);
+	
+	let compiled = graph.compile().unwrap();
+	
+	// The scene is dead once blur_x is done, so the resolved texture can reuse it
+	assert_eq!(compiled.physical_slot(resolved), compiled.physical_slot(scene));
+	assert_ne!(compiled.physical_slot(intermediate), compiled.physical_slot(scene));
+	
+	// Different formats never alias
+	assert_ne!(compiled.physical_slot(depth), compiled.physical_slot(scene));
+	assert_eq!(compiled.physical_slots().len(), 3);
+}
+
+#[test]
+fn derives_barriers_from_hazards() {
+	let mut graph = RenderGraph::new();
+	let image = graph.create_texture("image", hdr_desc());
+	let scene = graph.create_texture("scene", hdr_desc());
+	let backbuffer = graph.import_backbuffer("backbuffer", (1280, 720));
+	
+	graph.add_pass("compute").write_storage(image);
+	graph.add_pass("scene").read(image).color_attachment(0, scene);
+	graph.add_pass("composite").read(scene).read(image).color_attachment(0, backbuffer);
+	
+	let compiled = graph.compile().unwrap();
+	assert!(compiled.barriers(0).is_empty());
+	
+	// Image stores need an explicit barrier before sampling
+	let barriers = compiled.barriers(1);
+	assert_eq!(barriers.len(), 1);
+	assert_eq!(barriers[0].resource, image);
+	assert_eq!(barriers[0].from, ResourceAccess::StorageWrite);
+	assert_eq!(barriers[0].memory_barrier_bits(), gl::TEXTURE_FETCH_BARRIER_BIT);
+	
+	// Rendering and then sampling is ordered by gl, sampling twice isn't a hazard at all
+	let barriers = compiled.barriers(2);
+	assert_eq!(barriers.len(), 1);
+	assert_eq!(barriers[0].resource, scene);
+	assert_eq!(barriers[0].memory_barrier_bits(), 0);
+}
+
+#[test]
+fn keeps_passes_writing_imported_textures() {
+	let history = Rc::new(RefCell::new(Texture::new(1280, 720, 1, ImageFormat::get(gl::R11F_G11F_B10F))));
+	
+	let mut graph = RenderGraph::new();
+	let scene = graph.create_texture("scene", hdr_desc());
+	let history = graph.import_texture("history", history);
+	
+	// Reading an imported texture nobody wrote yet is fine, it has last frame's contents
+	let scene_pass = graph.add_pass("scene").read(history).color_attachment(0, scene).id();
+	let store_pass = graph.add_pass("store_history").read(scene).color_attachment(0, history).id();
+	
+	let compiled = graph.compile().unwrap();
+	assert_eq!(compiled.pass_order(), &[scene_pass, store_pass]);
+	assert_eq!(compiled.physical_slot(history), None);
+}
+
+#[test]
+fn culled_readers_dont_block_later_writers() {
+	let mut graph = RenderGraph::new();
+	let scene = graph.create_texture("scene", hdr_desc());
+	let unused = graph.create_texture("unused", hdr_desc());
+	let backbuffer = graph.import_backbuffer("backbuffer", (1280, 720));
+	
+	graph.add_pass("scene").color_attachment(0, scene);
+	let unused_pass = graph.add_pass("unused").read(scene).color_attachment(0, unused).id();
+	graph.add_pass("overwrite").color_attachment(0, scene);
+	graph.add_pass("composite").read(scene).color_attachment(0, backbuffer);
+	
+	let compiled = graph.compile().unwrap();
+	assert_eq!(compiled.pass_order().len(), 3);
+	assert!(compiled.is_culled(unused_pass));
+	assert!(!compiled.is_culled(PassId(0)));
+}
+
+#[test]
+fn rejects_invalid_graphs() {
+	{// Reading a transient before it's written
+		let mut graph = RenderGraph::new();
+		let scene = graph.create_texture("scene", hdr_desc());
+		let backbuffer = graph.import_backbuffer("backbuffer", (1280, 720));
+		graph.add_pass("composite").read(scene).color_attachment(0, backbuffer);
+		graph.add_pass("scene").color_attachment(0, scene);
+		
+		match graph.compile() {
+			Err(RenderGraphError::ReadBeforeWrite {pass, resource}) => {
+				assert_eq!(pass, "composite");
+				assert_eq!(resource, "scene");
+			}
+			_ => panic!("Expected ReadBeforeWrite"),
+		}
+	}
+	
+	{// Attachments of different sizes
+		let mut graph = RenderGraph::new();
+		let scene = graph.create_texture("scene", hdr_desc());
+		let small = graph.create_texture("small", TextureDesc::new(640, 360, ImageFormat::get(gl::RGB8)));
+		graph.add_pass("scene").color_attachment(0, scene).color_attachment(1, small).side_effects();
+		
+		match graph.compile() {
+			Err(RenderGraphError::InvalidAttachments {..}) => {},
+			_ => panic!("Expected InvalidAttachments"),
+		}
+	}
+	
+	{// The backbuffer with another attachment
+		let mut graph = RenderGraph::new();
+		let depth = graph.create_texture("depth", depth_desc());
+		let backbuffer = graph.import_backbuffer("backbuffer", (1280, 720));
+		graph.add_pass("composite").color_attachment(0, backbuffer).depth_attachment(depth);
+		
+		match graph.compile() {
+			Err(RenderGraphError::InvalidAttachments {..}) => {},
+			_ => panic!("Expected InvalidAttachments"),
+		}
+	}
+}
@@ -0,0 +1,156 @@
+//! Ordering and toggling of render subsystems, the dummy subsystems don't touch gl.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use render_demo::render::{GraphicsConfiguration, GraphicsSetting, RenderSubsystem, RenderSubsystemRegistry, ReconfigureEvent, FrameResources};
+use render_demo::render::render_graph::RenderGraph;
+
+struct DummySubsystem {
+	name: &'static str,
+	run_after: Vec<&'static str>,
+	run_before: Vec<&'static str>,
+	affected_by: Vec<GraphicsSetting>,
+}
+
+impl RenderSubsystem for DummySubsystem {
+	fn name(&self) -> &'static str {
+		self.name
+	}
+	
+	fn initialize(&mut self) {}
+	fn deinitialize(&mut self) {}
+	fn reconfigure(&mut self, _event: ReconfigureEvent<'_>) {}
+	
+	fn affected_by(&self, setting: GraphicsSetting) -> bool {
+		self.affected_by.contains(&setting)
+	}
+	
+	fn run_after(&self) -> &[&'static str] {
+		&self.run_after
+	}
+	
+	fn run_before(&self) -> &[&'static str] {
+		&self.run_before
+	}
+	
+	fn add_passes<'a>(&'a self, _graph: &mut RenderGraph<'a>, _frame: &mut FrameResources) {}
+}
+
+fn dummy(name: &'static str, run_after: &[&'static str], run_before: &[&'static str]) -> Box<dyn RenderSubsystem> {
+	Box::new(DummySubsystem {
+		name,
+		run_after: Vec::from(run_after),
+		run_before: Vec::from(run_before),
+		affected_by: Vec::new(),
+	})
+}
+
+/// Logs its initialize and deinitialize calls
+struct LifecycleSubsystem {
+	name: &'static str,
+	log: Rc<RefCell<Vec<String>>>,
+}
+
+impl RenderSubsystem for LifecycleSubsystem {
+	fn name(&self) -> &'static str {
+		self.name
+	}
+	
+	fn initialize(&mut self) {
+		self.log.borrow_mut().push(format!("initialize {}", self.name));
+	}
+	
+	fn deinitialize(&mut self) {
+		self.log.borrow_mut().push(format!("deinitialize {}", self.name));
+	}
+	
+	fn reconfigure(&mut self, _event: ReconfigureEvent<'_>) {}
+	
+	fn add_passes<'a>(&'a self, _graph: &mut RenderGraph<'a>, _frame: &mut FrameResources) {}
+}
+
+#[test]
+fn orders_by_constraints() {
+	let mut registry = RenderSubsystemRegistry::new();
+	registry.register(dummy("tonemap", &["bloom"], &[]), true).unwrap();
+	registry.register(dummy("bloom", &[], &[]), true).unwrap();
+	registry.register(dummy("sss", &[], &["bloom"]), true).unwrap();
+	
+	// Constraints on subsystems that aren't registered are ignored
+	registry.register(dummy("fog", &["volumetrics"], &[]), true).unwrap();
+	
+	assert_eq!(registry.names(), vec!["sss", "bloom", "tonemap", "fog"]);
+}
+
+#[test]
+fn rejects_duplicates_and_cycles() {
+	let mut registry = RenderSubsystemRegistry::new();
+	registry.register(dummy("a", &["b"], &[]), true).unwrap();
+	assert!(registry.register(dummy("a", &[], &[]), true).is_err());
+	
+	assert!(registry.register(dummy("b", &["a"], &[]), true).is_err());
+	assert_eq!(registry.names(), vec!["a"]);
+}
+
+#[test]
+fn toggles_at_runtime() {
+	let mut registry = RenderSubsystemRegistry::new();
+	registry.register(dummy("sss", &[], &[]), true).unwrap();
+	registry.register(dummy("bloom", &[], &[]), false).unwrap();
+	
+	assert_eq!(registry.is_enabled("bloom"), Some(false));
+	assert!(registry.set_enabled("bloom", true));
+	assert_eq!(registry.is_enabled("bloom"), Some(true));
+	
+	assert!(!registry.set_enabled("missing", true));
+	assert_eq!(registry.is_enabled("missing"), None);
+	
+	assert!(registry.unregister("sss").is_some());
+	assert_eq!(registry.names(), vec!["bloom"]);
+}
+
+#[test]
+fn reconfigures_only_affected_subsystems() {
+	let mut registry = RenderSubsystemRegistry::new();
+	registry.register(Box::new(DummySubsystem {name: "sss", run_after: vec![], run_before: vec![], affected_by: vec![GraphicsSetting::SssKernelSize]}), true).unwrap();
+	registry.register(Box::new(DummySubsystem {name: "bloom", run_after: vec![], run_before: vec![], affected_by: vec![GraphicsSetting::BloomOctaves]}), false).unwrap();
+	registry.register(dummy("fog", &[], &[]), true).unwrap();
+	
+	let configuration = GraphicsConfiguration::new();
+	let event = |changed_settings| ReconfigureEvent {
+		configuration: &configuration,
+		resolution: (1280, 720),
+		only_resize: false,
+		changed_settings,
+	};
+	
+	assert_eq!(registry.reconfigure_affected(event(&[GraphicsSetting::SssKernelSize])), vec!["sss"]);
+	assert_eq!(registry.reconfigure_affected(event(&[GraphicsSetting::BloomOctaves, GraphicsSetting::SssKernelSize])), vec!["sss", "bloom"]);
+	assert!(registry.reconfigure_affected(event(&[GraphicsSetting::Vsync])).is_empty());
+}
+
+#[test]
+fn only_initialized_subsystems_are_deinitialized() {
+	let log = Rc::new(RefCell::new(Vec::new()));
+	let lifecycle = |name| Box::new(LifecycleSubsystem {name, log: Rc::clone(&log)});
+	
+	let mut registry = RenderSubsystemRegistry::new();
+	registry.register(lifecycle("sss"), true).unwrap();
+	
+	// Never initialized, so nothing to deinitialize
+	assert_eq!(registry.is_initialized("sss"), Some(false));
+	assert!(registry.unregister("sss").is_some());
+	assert!(log.borrow().is_empty());
+	
+	// Subsystems registered later are initialized on the next call, the others only once
+	registry.register(lifecycle("sss"), true).unwrap();
+	registry.initialize_all();
+	registry.register(lifecycle("bloom"), true).unwrap();
+	registry.initialize_all();
+	assert_eq!(registry.is_initialized("bloom"), Some(true));
+	
+	assert!(registry.unregister("bloom").is_some());
+	registry.deinitialize_all();
+	registry.deinitialize_all();
+	assert_eq!(*log.borrow(), vec!["initialize sss", "initialize bloom", "deinitialize bloom", "deinitialize sss"]);
+}
@@ -0,0 +1,94 @@
+#version 430 core
+#define BLOOM_KARIS_AVERAGE 0
+
+// [[ exported declarations ]] //
+
+
+// [[ import forward declarations ]] //
+
+
+// [[ own source ]] //
+
+#line 1 0
+
+
+
+
+// Only the first downsample of the hdr scene uses the karis average and the threshold
+#ifndef BLOOM_KARIS_AVERAGE
+#	define BLOOM_KARIS_AVERAGE 0
+#endif
+
+layout(binding = 0) uniform sampler2D texSource;
+
+/** Brightness below which the scene doesn't bloom, 0 lets the whole scene bloom */
+uniform float uThreshold = 0.0;
+
+in vec2 vScreenTexCoord;
+
+out vec3 outFragBloom;
+
+float luminance(vec3 color) {
+	return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+/** Weighs the samples by their inverse luma, keeps single very bright texels from flickering */
+vec3 karisAverage(vec3 a, vec3 b, vec3 c, vec3 d) {
+	float weightA = 1.0 / (1.0 + luminance(a));
+	float weightB = 1.0 / (1.0 + luminance(b));
+	float weightC = 1.0 / (1.0 + luminance(c));
+	float weightD = 1.0 / (1.0 + luminance(d));
+	return (a * weightA + b * weightB + c * weightC + d * weightD) / (weightA + weightB + weightC + weightD);
+}
+
+/** Threshold with a soft knee of half the threshold so there's no hard cutoff */
+vec3 softThreshold(vec3 color, float threshold) {
+	float knee = threshold * 0.5;
+	float brightness = max(color.r, max(color.g, color.b));
+	
+	float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+	soft = (soft * soft) / (4.0 * knee + 0.00001);
+	
+	float contribution = max(soft, brightness - threshold) / max(brightness, 0.00001);
+	return color * contribution;
+}
+
+void main() {
+	vec2 texelSize = 1.0 / vec2(textureSize(texSource, 0));
+	vec2 texCoord = vScreenTexCoord.st;
+	
+	// 13 bilinear taps covering a 4x4 texel box as five overlapping 2x2 groups
+	vec3 a = texture(texSource, texCoord + texelSize * vec2(-2.0, 2.0)).rgb;
+	vec3 b = texture(texSource, texCoord + texelSize * vec2(0.0, 2.0)).rgb;
+	vec3 c = texture(texSource, texCoord + texelSize * vec2(2.0, 2.0)).rgb;
+	vec3 d = texture(texSource, texCoord + texelSize * vec2(-2.0, 0.0)).rgb;
+	vec3 e = texture(texSource, texCoord).rgb;
+	vec3 f = texture(texSource, texCoord + texelSize * vec2(2.0, 0.0)).rgb;
+	vec3 g = texture(texSource, texCoord + texelSize * vec2(-2.0, -2.0)).rgb;
+	vec3 h = texture(texSource, texCoord + texelSize * vec2(0.0, -2.0)).rgb;
+	vec3 i = texture(texSource, texCoord + texelSize * vec2(2.0, -2.0)).rgb;
+	vec3 j = texture(texSource, texCoord + texelSize * vec2(-1.0, 1.0)).rgb;
+	vec3 k = texture(texSource, texCoord + texelSize * vec2(1.0, 1.0)).rgb;
+	vec3 l = texture(texSource, texCoord + texelSize * vec2(-1.0, -1.0)).rgb;
+	vec3 m = texture(texSource, texCoord + texelSize * vec2(1.0, -1.0)).rgb;
+	
+#if BLOOM_KARIS_AVERAGE == 1
+	// Karis average per group, the center group weighs half
+	vec3 downsampled = karisAverage(j, k, l, m) * 0.5
+		+ karisAverage(a, b, d, e) * 0.125
+		+ karisAverage(b, c, e, f) * 0.125
+		+ karisAverage(d, e, g, h) * 0.125
+		+ karisAverage(e, f, h, i) * 0.125;
+	
+	outFragBloom = softThreshold(downsampled, uThreshold);
+#else
+	vec3 downsampled = e * 0.125
+		+ (a + c + g + i) * 0.03125
+		+ (b + d + f + h) * 0.0625
+		+ (j + k + l + m) * 0.125;
+	
+	outFragBloom = downsampled;
+#endif
+}
+
+// [[ end of transpiled source ]] //
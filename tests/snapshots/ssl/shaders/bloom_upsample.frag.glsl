@@ -0,0 +1,48 @@
+#version 430 core
+
+// [[ exported declarations ]] //
+
+
+// [[ import forward declarations ]] //
+
+
+// [[ own source ]] //
+
+#line 1 0
+
+
+
+
+/** The downsampled octave of the target's size */
+layout(binding = 0) uniform sampler2D texCurrent;
+/** The next smaller octave, already upsampled */
+layout(binding = 1) uniform sampler2D texLower;
+
+uniform float uCurrentWeight = 1.0;
+uniform float uLowerWeight = 1.0;
+
+in vec2 vScreenTexCoord;
+
+out vec3 outFragBloom;
+
+void main() {
+	vec2 texelSize = 1.0 / vec2(textureSize(texLower, 0));
+	vec2 texCoord = vScreenTexCoord.st;
+	
+	// 3x3 tent filter on the lower octave
+	vec3 lower = texture(texLower, texCoord).rgb * 4.0;
+	lower += texture(texLower, texCoord + texelSize * vec2(-1.0, 0.0)).rgb * 2.0;
+	lower += texture(texLower, texCoord + texelSize * vec2(1.0, 0.0)).rgb * 2.0;
+	lower += texture(texLower, texCoord + texelSize * vec2(0.0, -1.0)).rgb * 2.0;
+	lower += texture(texLower, texCoord + texelSize * vec2(0.0, 1.0)).rgb * 2.0;
+	lower += texture(texLower, texCoord + texelSize * vec2(-1.0, -1.0)).rgb;
+	lower += texture(texLower, texCoord + texelSize * vec2(1.0, -1.0)).rgb;
+	lower += texture(texLower, texCoord + texelSize * vec2(-1.0, 1.0)).rgb;
+	lower += texture(texLower, texCoord + texelSize * vec2(1.0, 1.0)).rgb;
+	lower *= 1.0 / 16.0;
+	
+	vec3 current = texture(texCurrent, texCoord).rgb;
+	outFragBloom = current * uCurrentWeight + lower * uLowerWeight;
+}
+
+// [[ end of transpiled source ]] //
@@ -0,0 +1,48 @@
+#version 430 core
+
+// [[ exported declarations ]] //
+
+
+// [[ import forward declarations ]] //
+
+#line 5 1
+vec4 calcOversizedScreenTriangleCoord(int vertexID, out vec2 screenTexCoord);
+
+// [[ own source ]] //
+
+#line 1 0
+
+
+
+
+
+
+out vec2 vScreenTexCoord;
+
+
+#line 10 0
+void main() {
+	gl_Position = calcOversizedScreenTriangleCoord(gl_VertexID, vScreenTexCoord);
+}
+
+
+// [[ import source for "oversized_screen_triangle" ]] //
+
+#line 1 1
+
+
+
+
+#line 5 1
+vec4 calcOversizedScreenTriangleCoord(int vertexID, out vec2 screenTexCoord) {
+//	vec2 xy = -1.0 + vec2(float((vertexID & 1) << 2), float((vertexID & 2) << 1));
+//	screenTexCoord = xy * 0.5 + 0.5;
+//	return vec4(xy, 0.0, 1.0);
+	
+	vec2 xy = -1.0 + vec2((ivec2(vertexID) & ivec2(1, 2)) << ivec2(2, 1));
+	screenTexCoord = xy * 0.5 + 0.5;
+	return vec4(xy, 0.0, 1.0);
+}
+
+
+// [[ end of transpiled source ]] //
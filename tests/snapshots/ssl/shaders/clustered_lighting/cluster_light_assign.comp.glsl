@@ -0,0 +1,272 @@
+#version 430 core
+
+// [[ exported declarations ]] //
+
+#line 5 1
+#define CURRENT_LIGHT_TYPE_POINT_LIGHT 0
+#define CURRENT_LIGHT_TYPE_SPOT_LIGHT 1
+#define CURRENT_LIGHT_TYPE_AREA_LIGHT 2
+
+// Kept clear of the texture units of the forward shaders
+#define SSBO_POINT_LIGHT_DATA_BINDING 8
+#define SSBO_SPOT_LIGHT_DATA_BINDING 9
+#define SSBO_AREA_LIGHT_DATA_BINDING 10
+#line 5 2
+#define UBO_CLUSTER_GRID_BINDING 14
+#define SSBO_CLUSTER_LIGHT_GRID_BINDING 11
+#define SSBO_CLUSTER_LIGHT_INDICES_BINDING 12
+#define SSBO_CLUSTER_ASSIGNMENT_SCRATCH_BINDING 13
+
+#define CLUSTER_MAX_LIGHTS 64u
+#line 16 1
+struct PointLightData {
+	vec4 positionAndRadius;
+	vec4 intensitiesAndNothing;
+};
+#line 23 1
+struct SpotLightData {
+	vec4 positionAndRadius;
+	vec4 intensitiesAndCosInnerAngle;
+	vec4 directionAndCosOuterAngle;
+};
+#line 31 1
+struct AreaLightData {
+	vec4 centerAndPlaneWidth;
+	vec4 intensitiesAndPlaneHeight;
+	vec4 normalAndCosOuterAngle;
+	vec4 tangentAndRadius;
+};
+#line 40 1
+layout(std430, binding = SSBO_POINT_LIGHT_DATA_BINDING)
+readonly restrict buffer ssboPointLightData {
+	PointLightData pointLights[];
+};
+#line 47 1
+layout(std430, binding = SSBO_SPOT_LIGHT_DATA_BINDING)
+readonly restrict buffer ssboSpotLightData {
+	SpotLightData spotLights[];
+};
+#line 54 1
+layout(std430, binding = SSBO_AREA_LIGHT_DATA_BINDING)
+readonly restrict buffer ssboAreaLightData {
+	AreaLightData areaLights[];
+};
+#line 14 2
+layout(std140, binding = UBO_CLUSTER_GRID_BINDING)
+uniform ubClusterGrid {
+	/** World space to cluster space, that is view space with the clip space w as depth */
+	mat4 clusterView;
+	/** Number of clusters along each axis and the total number in w */
+	uvec4 clusterDimensions;
+	/** Number of point, spot and area lights */
+	uvec4 clusterLightCounts;
+	/** Half extents of the view frustum at depth 1 */
+	vec2 clusterTileScale;
+	/** Depth range covered by the exponentially distributed depth slices */
+	vec2 clusterDepthPlanes;
+};
+
+// [[ import forward declarations ]] //
+
+#line 36 2
+uvec3 clusterCoord(uint index);
+#line 65 2
+void clusterBounds(uvec3 cluster, out vec3 boundsMin, out vec3 boundsMax);
+
+// [[ own source ]] //
+
+#line 1 0
+
+
+
+
+
+
+
+// One cluster per invocation, every cluster tests all the lights.
+// The lights are written to a fixed size slot per cluster first, the
+// final lists are compacted once the prefix sum gave each cluster its offset.
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+layout(std430, binding = SSBO_CLUSTER_LIGHT_GRID_BINDING)
+restrict buffer ssboClusterLightGrid {
+	uvec4 clusterLightGrid[];
+};
+
+layout(std430, binding = SSBO_CLUSTER_ASSIGNMENT_SCRATCH_BINDING)
+restrict buffer ssboClusterAssignmentScratch {
+	uint clusterAssignmentScratch[];
+};
+
+bool sphereIntersectsBounds(vec3 center, float radius, vec3 boundsMin, vec3 boundsMax) {
+	vec3 closestPoint = clamp(center, boundsMin, boundsMax);
+	vec3 delta = closestPoint - center;
+	return dot(delta, delta) <= radius*radius;
+}
+
+bool boundsInFrontOfPlane(vec3 planePoint, vec3 planeNormal, vec3 boundsMin, vec3 boundsMax) {
+	vec3 boundsCenter = (boundsMin + boundsMax) * 0.5;
+	vec3 boundsExtents = (boundsMax - boundsMin) * 0.5;
+	return dot(boundsCenter - planePoint, planeNormal) + dot(boundsExtents, abs(planeNormal)) >= 0.0;
+}
+
+void main() {
+	uint cluster = gl_GlobalInvocationID.x;
+	if(cluster >= clusterDimensions.w) {
+		return;
+	}
+	
+	vec3 boundsMin;
+	vec3 boundsMax;
+	clusterBounds(clusterCoord(cluster), boundsMin, boundsMax);
+	
+	uint scratchOffset = cluster * CLUSTER_MAX_LIGHTS;
+	uint numAssigned = 0u;
+	
+	// Assign point lights
+	for(uint i = 0u; i < clusterLightCounts.x && numAssigned < CLUSTER_MAX_LIGHTS; i++) {
+		vec4 positionAndRadius = pointLights[i].positionAndRadius;
+		vec3 center = (clusterView * vec4(positionAndRadius.xyz, 1.0)).xyz;
+		
+		if(sphereIntersectsBounds(center, positionAndRadius.w, boundsMin, boundsMax)) {
+			clusterAssignmentScratch[scratchOffset + numAssigned] = i;
+			numAssigned++;
+		}
+	}
+	uint numPointLights = numAssigned;
+	
+	// Assign spot lights, culled by the sphere around their cone
+	for(uint i = 0u; i < clusterLightCounts.y && numAssigned < CLUSTER_MAX_LIGHTS; i++) {
+		vec4 positionAndRadius = spotLights[i].positionAndRadius;
+		vec3 center = (clusterView * vec4(positionAndRadius.xyz, 1.0)).xyz;
+		
+		if(sphereIntersectsBounds(center, positionAndRadius.w, boundsMin, boundsMax)) {
+			clusterAssignmentScratch[scratchOffset + numAssigned] = i;
+			numAssigned++;
+		}
+	}
+	uint numSpotLights = numAssigned - numPointLights;
+	
+	// Assign area lights, they only light what is in front of them
+	for(uint i = 0u; i < clusterLightCounts.z && numAssigned < CLUSTER_MAX_LIGHTS; i++) {
+		AreaLightData light = areaLights[i];
+		vec3 center = (clusterView * vec4(light.centerAndPlaneWidth.xyz, 1.0)).xyz;
+		vec3 normal = (clusterView * vec4(light.normalAndCosOuterAngle.xyz, 0.0)).xyz;
+		
+		if(sphereIntersectsBounds(center, light.tangentAndRadius.w, boundsMin, boundsMax) && boundsInFrontOfPlane(center, normal, boundsMin, boundsMax)) {
+			clusterAssignmentScratch[scratchOffset + numAssigned] = i;
+			numAssigned++;
+		}
+	}
+	uint numAreaLights = numAssigned - numPointLights - numSpotLights;
+	
+	// The offset is filled in by the prefix sum
+	clusterLightGrid[cluster] = uvec4(0u, numPointLights, numSpotLights, numAreaLights);
+}
+
+// [[ import source for "clustered_lighting.light_data" ]] //
+
+#line 1 1
+
+
+
+
+#line 14 1
+
+
+#line 21 1
+
+
+#line 29 1
+
+
+#line 38 1
+
+
+#line 45 1
+
+
+#line 52 1
+
+
+#line 59 1
+
+/**
+ * Inverse square falloff, windowed so it reaches zero at the radius of the light.
+ */
+
+#line 71 1
+
+/**
+ * Returns the intensities of the light reaching the point and the direction to the light as L.
+ */
+
+#line 84 1
+
+
+#line 98 1
+
+/**
+ * Area lights are rectangles lighting the side their normal points to. They are shaded
+ * from a single representative point: where the reflection vector hits the rectangle,
+ * clamped onto it. V points from the surface to the eye.
+ */
+
+
+// [[ import source for "clustered_lighting.cluster_grid" ]] //
+
+#line 1 2
+
+
+
+
+#line 12 2
+
+
+#line 28 2
+
+
+#line 34 2
+
+
+#line 36 2
+uvec3 clusterCoord(uint index) {
+	return uvec3(index % clusterDimensions.x, (index / clusterDimensions.x) % clusterDimensions.y, index / (clusterDimensions.x * clusterDimensions.y));
+}
+
+#line 40 2
+
+/**
+ * Depths outside of the depth planes fall into the first or last slice.
+ */
+
+#line 50 2
+
+/**
+ * Index of the cluster containing the fragment at screenTexCoord (0..1 over the render target) and the depth.
+ */
+
+#line 60 2
+
+/**
+ * Axis aligned bounds of the cluster in cluster space.
+ */
+
+#line 65 2
+void clusterBounds(uvec3 cluster, out vec3 boundsMin, out vec3 boundsMax) {
+	// Depth range of the slice
+	float depthRatio = clusterDepthPlanes.y / clusterDepthPlanes.x;
+	float nearDepth = clusterDepthPlanes.x * pow(depthRatio, float(cluster.z) / float(clusterDimensions.z));
+	float farDepth = clusterDepthPlanes.x * pow(depthRatio, float(cluster.z + 1u) / float(clusterDimensions.z));
+	
+	// The tile in ndc, scaled to the frustum at depth 1
+	vec2 tileMin = (vec2(cluster.xy) / vec2(clusterDimensions.xy) * 2.0 - 1.0) * clusterTileScale;
+	vec2 tileMax = (vec2(cluster.xy + 1u) / vec2(clusterDimensions.xy) * 2.0 - 1.0) * clusterTileScale;
+	
+	// The frustum widens with the depth, so the tile at both depths spans the bounds
+	boundsMin = vec3(min(tileMin * nearDepth, tileMin * farDepth), nearDepth);
+	boundsMax = vec3(max(tileMax * nearDepth, tileMax * farDepth), farDepth);
+}
+
+
+// [[ end of transpiled source ]] //
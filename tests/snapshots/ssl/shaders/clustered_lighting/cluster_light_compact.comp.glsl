@@ -0,0 +1,108 @@
+#version 430 core
+
+// [[ exported declarations ]] //
+
+#line 5 1
+#define UBO_CLUSTER_GRID_BINDING 14
+#define SSBO_CLUSTER_LIGHT_GRID_BINDING 11
+#define SSBO_CLUSTER_LIGHT_INDICES_BINDING 12
+#define SSBO_CLUSTER_ASSIGNMENT_SCRATCH_BINDING 13
+
+#define CLUSTER_MAX_LIGHTS 64u
+#line 14 1
+layout(std140, binding = UBO_CLUSTER_GRID_BINDING)
+uniform ubClusterGrid {
+	/** World space to cluster space, that is view space with the clip space w as depth */
+	mat4 clusterView;
+	/** Number of clusters along each axis and the total number in w */
+	uvec4 clusterDimensions;
+	/** Number of point, spot and area lights */
+	uvec4 clusterLightCounts;
+	/** Half extents of the view frustum at depth 1 */
+	vec2 clusterTileScale;
+	/** Depth range covered by the exponentially distributed depth slices */
+	vec2 clusterDepthPlanes;
+};
+
+// [[ import forward declarations ]] //
+
+
+// [[ own source ]] //
+
+#line 1 0
+
+
+
+
+
+
+// Copies the lights of every cluster from its slot in the
+// assignment scratch into the compacted light index list.
+layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
+
+layout(std430, binding = SSBO_CLUSTER_LIGHT_GRID_BINDING)
+readonly restrict buffer ssboClusterLightGrid {
+	uvec4 clusterLightGrid[];
+};
+
+layout(std430, binding = SSBO_CLUSTER_ASSIGNMENT_SCRATCH_BINDING)
+readonly restrict buffer ssboClusterAssignmentScratch {
+	uint clusterAssignmentScratch[];
+};
+
+layout(std430, binding = SSBO_CLUSTER_LIGHT_INDICES_BINDING)
+restrict buffer ssboClusterLightIndices {
+	uint clusterLightIndices[];
+};
+
+void main() {
+	uint cluster = gl_GlobalInvocationID.x;
+	if(cluster >= clusterDimensions.w) {
+		return;
+	}
+	
+	uvec4 clusterLights = clusterLightGrid[cluster];
+	uint numLights = clusterLights.y + clusterLights.z + clusterLights.w;
+	uint scratchOffset = cluster * CLUSTER_MAX_LIGHTS;
+	
+	for(uint i = 0u; i < numLights; i++) {
+		clusterLightIndices[clusterLights.x + i] = clusterAssignmentScratch[scratchOffset + i];
+	}
+}
+
+// [[ import source for "clustered_lighting.cluster_grid" ]] //
+
+#line 1 1
+
+
+
+
+#line 12 1
+
+
+#line 28 1
+
+
+#line 34 1
+
+
+#line 40 1
+
+/**
+ * Depths outside of the depth planes fall into the first or last slice.
+ */
+
+#line 50 1
+
+/**
+ * Index of the cluster containing the fragment at screenTexCoord (0..1 over the render target) and the depth.
+ */
+
+#line 60 1
+
+/**
+ * Axis aligned bounds of the cluster in cluster space.
+ */
+
+
+// [[ end of transpiled source ]] //
@@ -0,0 +1,122 @@
+#version 430 core
+
+// [[ exported declarations ]] //
+
+#line 5 1
+#define UBO_CLUSTER_GRID_BINDING 14
+#define SSBO_CLUSTER_LIGHT_GRID_BINDING 11
+#define SSBO_CLUSTER_LIGHT_INDICES_BINDING 12
+#define SSBO_CLUSTER_ASSIGNMENT_SCRATCH_BINDING 13
+
+#define CLUSTER_MAX_LIGHTS 64u
+#line 14 1
+layout(std140, binding = UBO_CLUSTER_GRID_BINDING)
+uniform ubClusterGrid {
+	/** World space to cluster space, that is view space with the clip space w as depth */
+	mat4 clusterView;
+	/** Number of clusters along each axis and the total number in w */
+	uvec4 clusterDimensions;
+	/** Number of point, spot and area lights */
+	uvec4 clusterLightCounts;
+	/** Half extents of the view frustum at depth 1 */
+	vec2 clusterTileScale;
+	/** Depth range covered by the exponentially distributed depth slices */
+	vec2 clusterDepthPlanes;
+};
+
+// [[ import forward declarations ]] //
+
+
+// [[ own source ]] //
+
+#line 1 0
+
+
+
+
+
+
+// Parallel prefix sum over the number of lights per cluster, giving every
+// cluster the offset of its list in the compacted light index list.
+// A single work group of 1024 invocations (the minimum every gpu supports)
+// does all clusters, each invocation sums up a contiguous range of them.
+#define CLUSTER_SIZE_ACCUM_GROUP_SIZE 1024u
+
+layout(local_size_x = 1024, local_size_y = 1, local_size_z = 1) in;
+
+layout(std430, binding = SSBO_CLUSTER_LIGHT_GRID_BINDING)
+restrict buffer ssboClusterLightGrid {
+	uvec4 clusterLightGrid[];
+};
+
+shared uint sharedAccumClusterSizes[CLUSTER_SIZE_ACCUM_GROUP_SIZE];
+
+void main() {
+	uint invocation = gl_LocalInvocationID.x;
+	uint numClusters = clusterDimensions.w;
+	uint clustersPerInvocation = (numClusters + CLUSTER_SIZE_ACCUM_GROUP_SIZE - 1u) / CLUSTER_SIZE_ACCUM_GROUP_SIZE;
+	uint firstCluster = invocation * clustersPerInvocation;
+	uint endCluster = min(firstCluster + clustersPerInvocation, numClusters);
+	
+	// Sum up the sizes of our clusters
+	uint ownSize = 0u;
+	for(uint cluster = firstCluster; cluster < endCluster; cluster++) {
+		uvec4 clusterLights = clusterLightGrid[cluster];
+		ownSize += clusterLights.y + clusterLights.z + clusterLights.w;
+	}
+	sharedAccumClusterSizes[invocation] = ownSize;
+	barrier();
+	
+	// Inclusive scan over the sums of all invocations (Hillis-Steele)
+	for(uint stride = 1u; stride < CLUSTER_SIZE_ACCUM_GROUP_SIZE; stride *= 2u) {
+		uint addend = invocation >= stride ? sharedAccumClusterSizes[invocation - stride] : 0u;
+		barrier();
+		sharedAccumClusterSizes[invocation] += addend;
+		barrier();
+	}
+	
+	// Write the offsets of our clusters, starting where the previous invocations ended
+	uint offset = sharedAccumClusterSizes[invocation] - ownSize;
+	for(uint cluster = firstCluster; cluster < endCluster; cluster++) {
+		uvec4 clusterLights = clusterLightGrid[cluster];
+		clusterLightGrid[cluster].x = offset;
+		offset += clusterLights.y + clusterLights.z + clusterLights.w;
+	}
+}
+
+// [[ import source for "clustered_lighting.cluster_grid" ]] //
+
+#line 1 1
+
+
+
+
+#line 12 1
+
+
+#line 28 1
+
+
+#line 34 1
+
+
+#line 40 1
+
+/**
+ * Depths outside of the depth planes fall into the first or last slice.
+ */
+
+#line 50 1
+
+/**
+ * Index of the cluster containing the fragment at screenTexCoord (0..1 over the render target) and the depth.
+ */
+
+#line 60 1
+
+/**
+ * Axis aligned bounds of the cluster in cluster space.
+ */
+
+
+// [[ end of transpiled source ]] //
@@ -0,0 +1,739 @@
+#version 430 core
+
+// [[ exported declarations ]] //
+
+#line 5 1
+#define CURRENT_LIGHT_TYPE_POINT_LIGHT 0
+#define CURRENT_LIGHT_TYPE_SPOT_LIGHT 1
+#define CURRENT_LIGHT_TYPE_AREA_LIGHT 2
+
+// Kept clear of the texture units of the forward shaders
+#define SSBO_POINT_LIGHT_DATA_BINDING 8
+#define SSBO_SPOT_LIGHT_DATA_BINDING 9
+#define SSBO_AREA_LIGHT_DATA_BINDING 10
+#line 5 2
+#define UBO_CLUSTER_GRID_BINDING 14
+#define SSBO_CLUSTER_LIGHT_GRID_BINDING 11
+#define SSBO_CLUSTER_LIGHT_INDICES_BINDING 12
+#define SSBO_CLUSTER_ASSIGNMENT_SCRATCH_BINDING 13
+
+#define CLUSTER_MAX_LIGHTS 64u
+#line 16 1
+struct PointLightData {
+	vec4 positionAndRadius;
+	vec4 intensitiesAndNothing;
+};
+#line 23 1
+struct SpotLightData {
+	vec4 positionAndRadius;
+	vec4 intensitiesAndCosInnerAngle;
+	vec4 directionAndCosOuterAngle;
+};
+#line 31 1
+struct AreaLightData {
+	vec4 centerAndPlaneWidth;
+	vec4 intensitiesAndPlaneHeight;
+	vec4 normalAndCosOuterAngle;
+	vec4 tangentAndRadius;
+};
+#line 40 1
+layout(std430, binding = SSBO_POINT_LIGHT_DATA_BINDING)
+readonly restrict buffer ssboPointLightData {
+	PointLightData pointLights[];
+};
+#line 47 1
+layout(std430, binding = SSBO_SPOT_LIGHT_DATA_BINDING)
+readonly restrict buffer ssboSpotLightData {
+	SpotLightData spotLights[];
+};
+#line 54 1
+layout(std430, binding = SSBO_AREA_LIGHT_DATA_BINDING)
+readonly restrict buffer ssboAreaLightData {
+	AreaLightData areaLights[];
+};
+#line 14 2
+layout(std140, binding = UBO_CLUSTER_GRID_BINDING)
+uniform ubClusterGrid {
+	/** World space to cluster space, that is view space with the clip space w as depth */
+	mat4 clusterView;
+	/** Number of clusters along each axis and the total number in w */
+	uvec4 clusterDimensions;
+	/** Number of point, spot and area lights */
+	uvec4 clusterLightCounts;
+	/** Half extents of the view frustum at depth 1 */
+	vec2 clusterTileScale;
+	/** Depth range covered by the exponentially distributed depth slices */
+	vec2 clusterDepthPlanes;
+};
+#line 7 3
+layout(std430, binding = SSBO_CLUSTER_LIGHT_GRID_BINDING)
+readonly restrict buffer ssboClusterLightGrid {
+	/** Offset into the index list and the number of point, spot and area lights of each cluster */
+	uvec4 clusterLightGrid[];
+};
+#line 15 3
+layout(std430, binding = SSBO_CLUSTER_LIGHT_INDICES_BINDING)
+readonly restrict buffer ssboClusterLightIndices {
+	/** The point lights of each cluster, followed by its spot and area lights */
+	uint clusterLightIndices[];
+};
+
+// [[ import forward declarations ]] //
+
+#line 64 1
+float lightDistanceAttenuation(float distanceToLight, float radius);
+#line 76 1
+vec3 pointLightIncidence(PointLightData light, vec3 position, out vec3 L);
+#line 86 1
+vec3 spotLightIncidence(SpotLightData light, vec3 position, out vec3 L);
+#line 105 1
+vec3 areaLightIncidence(AreaLightData light, vec3 position, vec3 N, vec3 V, out vec3 L);
+#line 30 2
+uint clusterIndex(uvec3 cluster);
+#line 45 2
+uint clusterDepthSlice(float depth);
+#line 55 2
+uint clusterIndexAt(vec2 screenTexCoord, float depth);
+
+// [[ own source ]] //
+
+#line 1 0
+
+
+
+
+
+
+
+
+#extension GL_ARB_derivative_control : require
+
+// TODO: Try using a bit twiddling rsqrt approximation for optimization everywhere and see how it goes
+
+layout(binding = 1) uniform sampler2D texMaterialAlbedo;
+layout(binding = 2) uniform sampler2D texMaterialNormal;
+layout(binding = 4) uniform sampler2D texMaterialTransmission;
+
+/** Size of the render targets, to find the fragment's cluster */
+uniform vec2 uScreenSize;
+
+in vec2 tModelTexCoord;
+in vec3 tNormal;
+in vec3 tTangent;
+in vec3 tBitangent;
+in vec3 tVertexWorldspace;
+in vec3 tEyeDirWorldspace;
+noperspective in vec3 tBaryCoord;
+flat in vec2 tBaryNormals[3];
+flat in vec2 tBaryScreenCoords[3];
+in vec3 tVertexColor;
+
+flat in float tFuckOffPrimitiveIDFuckingShitDoesnWorkIndex;
+
+layout(location = 0) out vec3 outFrag; // HDR GL_R11F_G11F_B10F
+layout(location = 1) out vec3 outEdgeHeuristic; // GL_RGB8  // GL_RG16_SNORM
+layout(location = 2) out vec3 outEdgeHeuristic2; // GL_RGB8  // GL_RG16_SNORM
+
+vec3 separablesssTransmittance(vec2 modelTexCoord, float translucency, float sssWidth, vec3 worldPosition, vec3 worldNormal, vec3 lightVector, sampler2D transmissionTex) {
+	float scale = 8.25 * (1.0 - translucency) / sssWidth;
+	
+	// Shrink world position inwards to avoid artifacts
+	vec4 shrinkedPos = vec4(worldPosition - 0.005 * worldNormal, 1.0);
+	
+	// Sample transmission map
+	float sampledTransmission = texture(transmissionTex, modelTexCoord.st).x;
+//	float sampledTransmission = 1.0;
+	float d = scale * (1.0 - sampledTransmission); // TODO: Denormalize transmission map value via ramp
+	
+	// Calculate transmittance profile
+	float dd = -d * d;
+	vec3 profile = vec3(0.233, 0.455, 0.649) * exp(dd / 0.0064) +
+		vec3(0.100, 0.336, 0.344) * exp(dd / 0.0484) +
+		vec3(0.118, 0.198, 0.000) * exp(dd / 0.1870) +
+		vec3(0.113, 0.007, 0.007) * exp(dd / 0.5670) +
+		vec3(0.358, 0.004, 0.000) * exp(dd / 1.9900) +
+		vec3(0.078, 0.000, 0.000) * exp(dd / 7.4100);
+	
+	// Approximate transmitted light
+	return profile * clamp(0.3 + dot(lightVector, -worldNormal), 0.0, 1.0);
+}
+
+/**
+ * HdotVL can be either HdotV or HdotL
+ */
+vec3 fresnelSchlick(float HdotVorL, vec3 F0) {
+	float invHdotL = 1.0 - HdotVorL;
+	float Hdot2 = invHdotL*invHdotL;
+	float Hdot4 = Hdot2*Hdot2;
+	float Hdot5 = Hdot4*invHdotL;
+	return F0 + (1.0 - F0) * Hdot5;
+}
+
+float distributionGGXTrowbridge(float NdotH, float alpha) {
+	float alpha2 = alpha*alpha;
+	
+	float term = NdotH*NdotH * (alpha2 - 1.0) + 1.0;
+	return alpha2 / (3.141592 * term*term);
+}
+
+float geometrySmithSchlickGGX(float NdotL, float NdotV, float alpha) {
+	float k = 0.5 * alpha;
+	vec2 NdotLandV = vec2(NdotL, NdotV);
+	vec2 termsG1 = NdotLandV / (NdotLandV * (1.0 - k) + k); // Both components are a G1(v) term
+	return termsG1.x * termsG1.y;
+}
+
+vec3 cookTorranceSpecular(float NdotV, float NdotL, float NdotH, float HdotVorL, vec3 albedo, float roughness, float inverseMetalness, out vec3 Ks) {
+	float alpha = roughness*roughness;
+	
+	// Schlick F0
+	const float ior = 1.47; // 1.47 is pretty representative for all kinds of materials
+	float f = abs((1.0 - ior) / (1.0 + ior));
+	vec3 F0 = mix(albedo, vec3(f*f), inverseMetalness); // Lerp to albedo color for conductors
+	
+	// Fresnel term: Schlick Fresnel
+	vec3 F = fresnelSchlick(HdotVorL, F0);
+	
+	// Distribution term: GGX Trowbridge-Reitz
+	float D = distributionGGXTrowbridge(NdotH, alpha);
+	
+	// Geometry shadowing term: GGX approximation of Schlick approximation of Beckman as Smith term
+	float G = geometrySmithSchlickGGX(NdotL, NdotV, alpha);
+	
+	// Finally, solve our brdf and write out Ks
+	vec3 specular = (D * F * G) / (4.0 * NdotV * NdotH/*NdotL*/ + 0.001); // 4.0 apparently is the correct factor eventhough the original formula used pi
+	Ks = F;
+	return specular;
+}
+
+// Rerange x. At edge0 return 0, at edge1 return 1, linearly interpolate between them and then clamp
+float rangecoeff(float edge0, float edge1, float x) {
+	return clamp((x - edge0) / (edge1 - edge0), 0.0, 1.0);
+}
+
+/**
+ * Light reflected towards the eye from a single light. L points to the light and
+ * the intensities reaching the fragment already have the attenuation applied.
+ */
+vec3 shadeLight(vec3 N, vec3 V, vec3 L, vec3 lightIntensities, vec3 albedo, float roughness, float inverseMetalness, vec2 texCoord) {
+	vec3 H = normalize(V + L);
+	
+	//vec3 clampedDots = max(vec3(dot(N, L), dot(N, H), dot(H, L)), vec3(0.0));
+	float NdotV = max(0.0, dot(N, V));
+	float NdotL = max(0.0, dot(N, L));
+	float NdotH = max(0.0, dot(N, H));
+	float HdotL = max(0.0, dot(H, L));
+	
+	// Specular: Cook-Torrance and a side of extra stuff, please
+	vec3 Ks;
+	vec3 specular = cookTorranceSpecular(NdotV, NdotL, NdotH, HdotL, albedo, roughness, inverseMetalness, Ks) * lightIntensities;
+	
+	// Diffuse: Lambert
+	vec3 Kd = (1.0 - Ks) * inverseMetalness;
+	vec3 rawDiffuse = albedo * lightIntensities;
+	vec3 lambert = NdotL * rawDiffuse;
+	
+	// Subsurface
+	float sssTranslucency = 0.83;
+	float sssWidth = 0.012; // 0.012
+	vec3 subsurface = rawDiffuse * separablesssTransmittance(texCoord.st, sssTranslucency, 16.0*sssWidth, tVertexWorldspace.xyz, N, L, texMaterialTransmission);
+	
+	return (Kd * lambert) + subsurface + specular; // I'm not entirely sure, but the fresnel factor is already applied to specular in the specular term
+}
+
+void main() {
+	vec2 texCoord = tModelTexCoord;
+	
+	vec3 N;
+	{// Normal mapping
+		N = normalize(tNormal);
+		
+		// Get tangent and bitangent
+		vec3 T = normalize(tTangent);
+		vec3 B = normalize(tBitangent);
+		
+		// Construct tbn matrix
+		mat3 tbnMatrix = mat3(T, B, N);
+		
+		// Sample and transform normal
+		vec3 sampledNormal = texture(texMaterialNormal, texCoord.st).xyz * vec3(2.0) - 1.0;
+		N = normalize(tbnMatrix * sampledNormal);
+	}
+	
+	// Shade fragment
+	vec3 albedo = texture(texMaterialAlbedo, texCoord).rgb;
+	float roughness = 0.41;
+	float inverseMetalness = 1.0 - 0.0;
+	
+	vec3 V = normalize(tEyeDirWorldspace - tVertexWorldspace);
+	
+	vec3 radiance = vec3(0.0);
+	{// Shade the lights in the fragment's cluster
+		uvec4 clusterLights = clusterLightGrid[clusterIndexAt(gl_FragCoord.xy / uScreenSize, 1.0 / gl_FragCoord.w)];
+		uint lightListIndex = clusterLights.x;
+		
+		for(uint i = 0u; i < clusterLights.y; i++) {
+			vec3 L;
+			vec3 lightIntensities = pointLightIncidence(pointLights[clusterLightIndices[lightListIndex]], tVertexWorldspace, L);
+			radiance += shadeLight(N, V, L, lightIntensities, albedo, roughness, inverseMetalness, texCoord);
+			lightListIndex++;
+		}
+		
+		for(uint i = 0u; i < clusterLights.z; i++) {
+			vec3 L;
+			vec3 lightIntensities = spotLightIncidence(spotLights[clusterLightIndices[lightListIndex]], tVertexWorldspace, L);
+			radiance += shadeLight(N, V, L, lightIntensities, albedo, roughness, inverseMetalness, texCoord);
+			lightListIndex++;
+		}
+		
+		for(uint i = 0u; i < clusterLights.w; i++) {
+			vec3 L;
+			vec3 lightIntensities = areaLightIncidence(areaLights[clusterLightIndices[lightListIndex]], tVertexWorldspace, N, V, L);
+			radiance += shadeLight(N, V, L, lightIntensities, albedo, roughness, inverseMetalness, texCoord);
+			lightListIndex++;
+		}
+	}
+	
+	// Ambient light
+//	radiance += albedo * inverseMetalness * vec3(0.01);
+	radiance += albedo * inverseMetalness * vec3(0.01);
+	
+	vec3 shadedFrag = radiance;
+	//vec3 shadedFrag = vec3(1.0);
+	
+	{
+		vec3 baryCoord = tBaryCoord.xyz;
+		
+		// Extrapolate distance to vertex based on bary to get the actual distance to the edge 
+	//	float edgeDist0 = distance(gl_FragCoord.xy, tBaryScreenCoords[0]) / (1.0 - baryCoord[0]) - distance(gl_FragCoord.xy, tBaryScreenCoords[0]);
+	//	float edgeDist1 = distance(gl_FragCoord.xy, tBaryScreenCoords[1]) / (1.0 - baryCoord[1]) - distance(gl_FragCoord.xy, tBaryScreenCoords[1]);
+	//	float edgeDist2 = distance(gl_FragCoord.xy, tBaryScreenCoords[2]) / (1.0 - baryCoord[2]) - distance(gl_FragCoord.xy, tBaryScreenCoords[2]);
+	
+		// Might want to optimize this with https://en.wikipedia.org/wiki/Distance_from_a_point_to_a_line
+		// and a custom bit twiddling rsqrt approximation e.g. https://github.com/BruceKnowsHow/Ebin-Shaders/blob/master/shaders/lib/Utility/fastMath.glsl
+//		vec2 edgeTangent0 = vec2(-tBaryNormals[0].y, tBaryNormals[0].x);
+//		vec2 edgeTangent1 = vec2(-tBaryNormals[1].y, tBaryNormals[1].x);
+//		vec2 edgeTangent2 = vec2(-tBaryNormals[2].y, tBaryNormals[2].x);
+//		float edgeDist0 = distance(gl_FragCoord.xy, tBaryScreenCoords[1] + edgeTangent0 * dot(gl_FragCoord.xy - tBaryScreenCoords[1], edgeTangent0));
+//		float edgeDist1 = distance(gl_FragCoord.xy, tBaryScreenCoords[2] + edgeTangent1 * dot(gl_FragCoord.xy - tBaryScreenCoords[2], edgeTangent1));
+//		float edgeDist2 = distance(gl_FragCoord.xy, tBaryScreenCoords[0] + edgeTangent2 * dot(gl_FragCoord.xy - tBaryScreenCoords[0], edgeTangent2));
+		
+		float edgeDist0 = abs(dot(vec4(tBaryScreenCoords[2].yx - tBaryScreenCoords[1].yx, tBaryScreenCoords[2].xy), vec4(gl_FragCoord.x, -gl_FragCoord.y, tBaryScreenCoords[1].y, -tBaryScreenCoords[1].x))) / distance(tBaryScreenCoords[1].xy, tBaryScreenCoords[2].xy);
+		float edgeDist1 = abs(dot(vec4(tBaryScreenCoords[0].yx - tBaryScreenCoords[2].yx, tBaryScreenCoords[0].xy), vec4(gl_FragCoord.x, -gl_FragCoord.y, tBaryScreenCoords[2].y, -tBaryScreenCoords[2].x))) / distance(tBaryScreenCoords[2].xy, tBaryScreenCoords[0].xy);
+		float edgeDist2 = abs(dot(vec4(tBaryScreenCoords[1].yx - tBaryScreenCoords[0].yx, tBaryScreenCoords[1].xy), vec4(gl_FragCoord.x, -gl_FragCoord.y, tBaryScreenCoords[0].y, -tBaryScreenCoords[0].x))) / distance(tBaryScreenCoords[0].xy, tBaryScreenCoords[1].xy);
+		
+		const float PI = 3.141592;
+		const float TAU = 2.0 * PI;
+		
+		vec3 normalsX = vec3(tBaryNormals[0].x, tBaryNormals[1].x, tBaryNormals[2].x);
+		vec3 normalsY = vec3(tBaryNormals[0].y, tBaryNormals[1].y, tBaryNormals[2].y);
+		vec3 dists = vec3(edgeDist0, edgeDist1, edgeDist2);
+		
+		vec3 edgeAngleIndices = (atan(-normalsY, normalsX) / TAU + 0.5);
+		vec3 edgeDistIndices = clamp(dists / 0.55, vec3(0.0), vec3(1.0));
+		
+		outEdgeHeuristic = edgeAngleIndices;
+		outEdgeHeuristic2 = edgeDistIndices;
+		
+		
+		vec3 baryEdgeWidths = vec3(edgeDist0, edgeDist1, edgeDist2);
+		
+		//vec2 baryNormal0 = vec2(baryDerivativeX[0], baryDerivativeY[0]);
+		vec2 baryNormal0 = tBaryNormals[0];
+		//vec2 n0 = step(vec2(0.5), abs(baryNormal0)) * sign(baryNormal0);
+		vec2 n0 = baryNormal0;
+		//vec4 coeffs0 = max(vec4(0.0), vec4(n0.x, n0.y, -n0.x, -n0.y)) * rangecoeff(baryEdgeWidths[0] * 0.5, 0.0, baryCoord[0]);
+		vec4 coeffs0 = max(vec4(0.0), vec4(n0.x, n0.y, -n0.x, -n0.y)) * rangecoeff(0.5, 0.0, baryEdgeWidths[0]);
+		
+		//vec2 baryNormal1 = vec2(baryDerivativeX[1], baryDerivativeY[1]);
+		vec2 baryNormal1 = tBaryNormals[1];
+		//vec2 n1 = step(vec2(0.5), abs(baryNormal1)) * sign(baryNormal1);
+		vec2 n1 = baryNormal1;
+		//vec4 coeffs1 = max(vec4(0.0), vec4(n1.x, n1.y, -n1.x, -n1.y)) * rangecoeff(baryEdgeWidths[1] * 0.5, 0.0, baryCoord[1]);
+		vec4 coeffs1 = max(vec4(0.0), vec4(n1.x, n1.y, -n1.x, -n1.y)) * rangecoeff(0.5, 0.0, baryEdgeWidths[1]);
+		
+		//vec2 baryNormal2 = vec2(baryDerivativeX[2], baryDerivativeY[2]);
+		vec2 baryNormal2 = tBaryNormals[2];
+		//vec2 n2 = step(vec2(0.5), abs(baryNormal2)) * sign(baryNormal2);
+		vec2 n2 = baryNormal2;
+		//vec4 coeffs2 = max(vec4(0.0), vec4(n2.x, n2.y, -n2.x, -n2.y)) * rangecoeff(baryEdgeWidths[2] * 0.5, 0.0, baryCoord[2]);
+		vec4 coeffs2 = max(vec4(0.0), vec4(n2.x, n2.y, -n2.x, -n2.y)) * rangecoeff(0.5, 0.0, baryEdgeWidths[2]);
+		
+		vec4 actualCoeffs = max(coeffs0, max(coeffs1, coeffs2));
+		//outEdgeHeuristic = actualCoeffs;
+		
+		// DEBUG:
+		//shadedFrag = vec3(actualCoeffs.x, 0.0, 0.0);
+		//shadedFrag = vec3(1.0);
+	}
+	
+	/*
+	// Calculate edge heuristic
+	float minBary = min(tBaryCoord.x, min(tBaryCoord.y, tBaryCoord.z));
+	
+	vec3 baryDerivativeX = dFdxFine(tBaryCoord);
+	vec3 baryDerivativeY = dFdyFine(tBaryCoord);
+	vec3 baryWidthDerivative = fwidthFine(tBaryCoord);
+	
+	vec2 baryNormal;
+	float baryWidth;
+	if(tBaryCoord.x < tBaryCoord.y && tBaryCoord.x < tBaryCoord.z) {
+		baryNormal = vec2(baryDerivativeX[0], baryDerivativeY[0]);
+		baryWidth = baryWidthDerivative[0];
+	}
+	else if(tBaryCoord.y < tBaryCoord.x && tBaryCoord.y < tBaryCoord.z) {
+		baryNormal = vec2(baryDerivativeX[1], baryDerivativeY[1]);
+		baryWidth = baryWidthDerivative[1];
+	}
+	else {
+		baryNormal = vec2(baryDerivativeX[2], baryDerivativeY[2]);
+		baryWidth = baryWidthDerivative[2];
+	}
+	
+	// DEBUG:
+	float minEdgeDist = min(tBaryCoord.x, min(tBaryCoord.y, tBaryCoord.z));
+	
+	vec3 baryFactor = smoothstep(baryWidthDerivative, vec3(0.0), tBaryCoord.xyz);
+	vec3 barySelfFactor = smoothstep(baryWidthDerivative.xyz * 0.5, vec3(0.0), tBaryCoord.xyz);
+	vec3 nx = barySelfFactor * (baryDerivativeX.xyz / baryWidthDerivative.xyz);
+	vec3 ny = barySelfFactor * (baryDerivativeY.xyz / baryWidthDerivative.xyz);
+	
+	vec2 trendDirection = vec2(nx.x + nx.y + nx.z, ny.x + ny.y + ny.z);
+	float trendDirectionLength = length(trendDirection);
+	float trendConfidence = trendDirectionLength / (barySelfFactor.x + barySelfFactor.y + barySelfFactor.z);
+	 
+//	vec4 edgeCoeffs = mix(vec4(0.25), min(vec4(0.0), vec4(trendDirection, -trendDirection) / trendDirectionLength), trendConfidence);
+//	vec4 edgeCoeffs = min(vec4(0.0), vec4(trendDirection, -trendDirection) / trendDirectionLength);
+	
+	//outEdgeHeuristic = edgeCoeffs;
+//	outEdgeHeuristic = vec4(0.5);
+//	outEdgeHeuristic.x = 0.25;
+	
+	float edgeCoverageFactor = smoothstep(0.0, baryWidth*0.5*2, minBary);
+//	edgeCoverageFactor = mix(0.5, 1.0, edgeCoverageFactor);
+//	baryColor *= edgeCoverageFactor;
+//	baryColor = vec2(edgeCoverageFactor);
+	
+	// Write edge heuristic frag
+//	outEdgeHeuristic = normalize(baryNormal) * (1.0 - edgeCoverageFactor); // inverted edge distance normalized to 0..1 (actual range 0.5 to 1.5)
+	*/
+	
+	/*
+	{// New algorithm 2
+		vec3 baryCoord = tBaryCoord.xyz;
+		//vec3 baryDerivativeX = dFdxFine(tBaryCoord.xyz);
+		//vec3 baryDerivativeY = dFdyFine(tBaryCoord.xyz);
+		vec3 baryDerivativeWidth = fwidthFine(tBaryCoord.xyz);
+		
+		//float edgeDist0 = distance(gl_FragCoord.xy, tBaryScreenCoords[0]) / (1.0 - baryCoord[0]);
+		//float edgeDist1 = distance(gl_FragCoord.xy, tBaryScreenCoords[1]) / (1.0 - baryCoord[1]);
+		//float edgeDist2 = distance(gl_FragCoord.xy, tBaryScreenCoords[2]) / (1.0 - baryCoord[2]);
+		//vec3 baryEdgeWidths = vec3(edgeDist0, edgeDist1, edgeDist2);
+		
+		vec3 baryEdgeWidths = baryDerivativeWidth;
+		
+		//vec2 baryNormal0 = vec2(baryDerivativeX[0], baryDerivativeY[0]);
+		vec2 baryNormal0 = tBaryNormals[0];
+		vec2 n0 = step(vec2(0.5), abs(baryNormal0)) * sign(baryNormal0);
+		vec4 coeffs0 = max(vec4(0.0), vec4(n0.x, n0.y, -n0.x, -n0.y)) * smoothstep(baryEdgeWidths[0] * 0.5, 0.0, baryCoord[0]);
+		
+		//vec2 baryNormal1 = vec2(baryDerivativeX[1], baryDerivativeY[1]);
+		vec2 baryNormal1 = tBaryNormals[1];
+		vec2 n1 = step(vec2(0.5), abs(baryNormal1)) * sign(baryNormal1);
+		vec4 coeffs1 = max(vec4(0.0), vec4(n1.x, n1.y, -n1.x, -n1.y)) * smoothstep(baryEdgeWidths[1] * 0.5, 0.0, baryCoord[1]);
+		
+		//vec2 baryNormal2 = vec2(baryDerivativeX[2], baryDerivativeY[2]);
+		vec2 baryNormal2 = tBaryNormals[2];
+		vec2 n2 = step(vec2(0.5), abs(baryNormal2)) * sign(baryNormal2);
+		vec4 coeffs2 = max(vec4(0.0), vec4(n2.x, n2.y, -n2.x, -n2.y)) * smoothstep(baryEdgeWidths[2] * 0.5, 0.0, baryCoord[2]);
+		
+		vec4 actualCoeffs = max(coeffs0, max(coeffs1, coeffs2));
+		outEdgeHeuristic = actualCoeffs;
+		
+		//outFrag = vec3(1.0 - baryEdgeWidths[0]);
+		//outFrag = vec3(smoothstep(0.5, 0.0, baryEdgeWidths[0]));
+		//outFrag = vec3(smoothstep(0.5, 0.0, edgeDist0));
+		//outFrag = vec3(smoothstep(baryDerivativeWidth[0] * 0.5, 0.0, baryCoord[0]));
+	}
+	*/
+	
+	/*
+	{// Test 3
+		vec3 baryCoord = tBaryCoord.xyz;
+		vec3 baryDerivativeX = dFdxFine(tBaryCoord.xyz);
+		vec3 baryDerivativeY = dFdyFine(tBaryCoord.xyz);
+		vec3 baryDerivativeWidth = fwidthFine(tBaryCoord.xyz);
+		
+		vec2 baryNormal;
+		if(baryCoord.x < baryCoord.y && baryCoord.x < baryCoord.z) {
+			//baryNormal = vec2(baryDerivativeX[0], baryDerivativeY[0]);
+			baryNormal = tBaryNormals[0];
+		}
+		else if(baryCoord.y < baryCoord.x && baryCoord.y < baryCoord.z) {
+			//baryNormal = vec2(baryDerivativeX[1], baryDerivativeY[1]);
+			baryNormal = tBaryNormals[1];
+		}
+		else {
+			//baryNormal = vec2(baryDerivativeX[2], baryDerivativeY[2]);
+			baryNormal = tBaryNormals[2];
+		}
+		
+		vec3 coeffs = smoothstep(baryDerivativeWidth.xyz * 0.5, vec3(0.0), baryCoord.xyz);
+		outEdgeHeuristic.x = max(coeffs[0], max(coeffs[1], coeffs[2]));
+		outEdgeHeuristic.yz = normalize(baryNormal) * 0.5 + 0.5;
+		
+		//outFrag = vec3(normalize(baryNormal.xy) * 0.5 + 0.5, tFuckOffPrimitiveIDFuckingShitDoesnWorkIndex);
+	}
+	*/
+	
+	/*
+	{// New algorithm
+		vec3 baryCoord = tBaryCoord;
+//		vec3 baryDerivativeX = dFdxFine(baryCoord);
+//		vec3 baryDerivativeY = dFdyFine(baryCoord);
+		vec3 baryDerivativeWidth = fwidthFine(baryCoord);
+		
+//		vec2 n0 = -vec2(baryDerivativeX[0], baryDerivativeY[0]) / baryDerivativeWidth[0];
+		vec2 n0 = tBaryNormals[0];
+		vec4 coeffs0 = max(vec4(0.0), vec4(n0.x, n0.y, -n0.x, -n0.y)) * smoothstep(baryDerivativeWidth[0] * 0.5, 0, baryCoord[0]);
+		
+//		vec2 n1 = -vec2(baryDerivativeX[1], baryDerivativeY[1]) / baryDerivativeWidth[1];
+		vec2 n1 = tBaryNormals[1];
+		vec4 coeffs1 = max(vec4(0.0), vec4(n1.x, n1.y, -n1.x, -n1.y)) * smoothstep(baryDerivativeWidth[1] * 0.5, 0, baryCoord[1]);
+		
+//		vec2 n2 = -vec2(baryDerivativeX[2], baryDerivativeY[2]) / baryDerivativeWidth[2];
+		vec2 n2 = tBaryNormals[2];
+		vec4 coeffs2 = max(vec4(0.0), vec4(n2.x, n2.y, -n2.x, -n2.y)) * smoothstep(baryDerivativeWidth[2] * 0.5, 0, baryCoord[2]);
+		
+		vec4 actualCoeffs = max(coeffs0, max(coeffs1, coeffs2));
+		
+		// Write coeffs out
+		outEdgeHeuristic = actualCoeffs;
+	}
+	*/
+	
+//	baryColor = vec2(1.0 - edgeCoverageFactor, 1.0);
+	/*
+	baryColor = vec2(0.25);
+	if(minBary < baryWidth*0.5) {
+		baryColor = vec2(1.0);
+	}
+	*/
+	
+	/*
+	{// Blinn phong
+		vec3 L = normalize(vec3(0.2, 0.5, 0.4));
+		
+		const float specularHardness = 100.0;
+		const vec3 lightColor = vec3(1.0, 1.0, 1.0);
+		const vec3 ambient = vec3(0.025);
+		
+		// Specular
+		vec3 V = tPositionWorldSpace;
+		vec3 H = normalize(L + V);
+		float NdotH = dot(N, H);
+		float specularFactor = pow(max(0.0, NdotH), specularHardness);
+		vec3 specular = specularFactor * lightColor;
+		
+		// Diffuse
+		float NdotL = dot(N, L);
+		float diffuseFactor =  max(0.0, max(0.0, NdotL) - specularFactor);
+		vec3 diffuse = (ambient + vec3(diffuseFactor)) * texture(texMaterialAlbedo, tModelTexCoord.st).rgb;
+		
+		vec3 shadedFrag = diffuse + specular;
+		outFrag = shadedFrag;
+	}
+	*/
+	
+	// DEBUG:
+//	outFrag = N * 0.5 + 0.5;
+	
+	// Write hdr scene frag
+	outFrag = shadedFrag;
+	
+//	outFrag = tVertexColor;
+//	outFrag = vec3(1.0);
+//	outFrag.z = tFuckOffPrimitiveIDFuckingShitDoesnWorkIndex;
+	
+//	outFrag = vec3(edgeCoeffs.xyz);
+//	outFrag = vec3(max(barySelfFactor.x, max(barySelfFactor.y, barySelfFactor.z)), 1.0 - trendConfidence, 0.0);
+//	outFrag = vec3(trendDirection * 0.5 + 0.5, 0.0);
+//	outFrag = vec3(trendConfidence);
+}
+
+// [[ import source for "clustered_lighting.light_data" ]] //
+
+#line 1 1
+
+
+
+
+#line 14 1
+
+
+#line 21 1
+
+
+#line 29 1
+
+
+#line 38 1
+
+
+#line 45 1
+
+
+#line 52 1
+
+
+#line 59 1
+
+/**
+ * Inverse square falloff, windowed so it reaches zero at the radius of the light.
+ */
+
+#line 64 1
+float lightDistanceAttenuation(float distanceToLight, float radius) {
+	float ratio = distanceToLight / radius;
+	float ratio2 = ratio*ratio;
+	float window = clamp(1.0 - ratio2*ratio2, 0.0, 1.0);
+	return (window*window) / max(distanceToLight*distanceToLight, 0.0001);
+}
+
+#line 71 1
+
+/**
+ * Returns the intensities of the light reaching the point and the direction to the light as L.
+ */
+
+#line 76 1
+vec3 pointLightIncidence(PointLightData light, vec3 position, out vec3 L) {
+	vec3 toLight = light.positionAndRadius.xyz - position;
+	float distanceToLight = length(toLight);
+	L = toLight / max(distanceToLight, 0.0001);
+	
+	return light.intensitiesAndNothing.rgb * lightDistanceAttenuation(distanceToLight, light.positionAndRadius.w);
+}
+
+#line 84 1
+
+
+#line 86 1
+vec3 spotLightIncidence(SpotLightData light, vec3 position, out vec3 L) {
+	vec3 toLight = light.positionAndRadius.xyz - position;
+	float distanceToLight = length(toLight);
+	L = toLight / max(distanceToLight, 0.0001);
+	
+	// Fade out between the inner and the outer cone
+	float cosAngle = dot(-L, light.directionAndCosOuterAngle.xyz);
+	float coneAttenuation = smoothstep(light.directionAndCosOuterAngle.w, light.intensitiesAndCosInnerAngle.w, cosAngle);
+	
+	return light.intensitiesAndCosInnerAngle.rgb * coneAttenuation * lightDistanceAttenuation(distanceToLight, light.positionAndRadius.w);
+}
+
+#line 98 1
+
+/**
+ * Area lights are rectangles lighting the side their normal points to. They are shaded
+ * from a single representative point: where the reflection vector hits the rectangle,
+ * clamped onto it. V points from the surface to the eye.
+ */
+
+#line 105 1
+vec3 areaLightIncidence(AreaLightData light, vec3 position, vec3 N, vec3 V, out vec3 L) {
+	vec3 center = light.centerAndPlaneWidth.xyz;
+	vec3 normal = light.normalAndCosOuterAngle.xyz;
+	vec3 tangent = light.tangentAndRadius.xyz;
+	vec3 bitangent = cross(normal, tangent);
+	vec2 halfExtents = vec2(light.centerAndPlaneWidth.w, light.intensitiesAndPlaneHeight.w) * 0.5;
+	
+	// Intersect the reflection vector with the plane of the light, or else use the closest point on the plane
+	vec3 R = reflect(-V, N);
+	float RdotNormal = dot(R, normal);
+	float planeDistance = dot(center - position, normal);
+	float t = planeDistance / RdotNormal;
+	vec3 planePoint = (RdotNormal < -0.0001 && t > 0.0) ? position + R * t : position + normal * planeDistance;
+	
+	// Clamp the point onto the rectangle
+	vec3 centerToPoint = planePoint - center;
+	vec2 pxy = clamp(vec2(dot(centerToPoint, tangent), dot(centerToPoint, bitangent)), -halfExtents, halfExtents);
+	vec3 representativePoint = center + tangent * pxy.x + bitangent * pxy.y;
+	
+	vec3 toLight = representativePoint - position;
+	float distanceToLight = length(toLight);
+	L = toLight / max(distanceToLight, 0.0001);
+	
+	// Falls off towards the outer angle, an outer angle of 90 degrees gives a lambertian emitter
+	float cosOuterAngle = light.normalAndCosOuterAngle.w;
+	float emission = clamp((dot(-L, normal) - cosOuterAngle) / max(1.0 - cosOuterAngle, 0.0001), 0.0, 1.0);
+	
+	return light.intensitiesAndPlaneHeight.rgb * emission * lightDistanceAttenuation(distanceToLight, light.tangentAndRadius.w);
+}
+
+
+// [[ import source for "clustered_lighting.cluster_grid" ]] //
+
+#line 1 2
+
+
+
+
+#line 12 2
+
+
+#line 28 2
+
+
+#line 30 2
+uint clusterIndex(uvec3 cluster) {
+	return cluster.x + (cluster.y + cluster.z * clusterDimensions.y) * clusterDimensions.x;
+}
+
+#line 34 2
+
+
+#line 40 2
+
+/**
+ * Depths outside of the depth planes fall into the first or last slice.
+ */
+
+#line 45 2
+uint clusterDepthSlice(float depth) {
+	float slice = log(max(depth, clusterDepthPlanes.x) / clusterDepthPlanes.x) / log(clusterDepthPlanes.y / clusterDepthPlanes.x) * float(clusterDimensions.z);
+	return min(uint(slice), clusterDimensions.z - 1u);
+}
+
+#line 50 2
+
+/**
+ * Index of the cluster containing the fragment at screenTexCoord (0..1 over the render target) and the depth.
+ */
+
+#line 55 2
+uint clusterIndexAt(vec2 screenTexCoord, float depth) {
+	uvec2 tile = min(uvec2(max(screenTexCoord, vec2(0.0)) * vec2(clusterDimensions.xy)), clusterDimensions.xy - 1u);
+	return clusterIndex(uvec3(tile, clusterDepthSlice(depth)));
+}
+
+#line 60 2
+
+/**
+ * Axis aligned bounds of the cluster in cluster space.
+ */
+
+
+// [[ import source for "clustered_lighting.cluster_lists" ]] //
+
+#line 1 3
+
+
+
+
+
+
+#line 13 3
+
+
+
+// [[ end of transpiled source ]] //
@@ -0,0 +1,29 @@
+#version 430 core
+
+// [[ exported declarations ]] //
+
+
+// [[ import forward declarations ]] //
+
+
+// [[ own source ]] //
+
+#line 1 0
+
+
+
+
+
+#line 6 0
+float approximateLightTransport(sampler2D transmissionTex, vec2 texCoord, float scale) {
+	float transmission = texture(transmissionTex, texCoord.st).r;
+	return transmission * scale;
+}
+
+#line 11 0
+
+void main() {
+	gl_Position = vec4(0.0, 0.0, 0.0, 1.0);
+}
+
+// [[ end of transpiled source ]] //
@@ -1,10 +1,121 @@
 #version 430 core
+#define SUBSURFACE 0
+
+// [[ exported declarations ]] //
+
+#line 5 1
+#define CURRENT_LIGHT_TYPE_POINT_LIGHT 0
+#define CURRENT_LIGHT_TYPE_SPOT_LIGHT 1
+#define CURRENT_LIGHT_TYPE_AREA_LIGHT 2
+
+// Kept clear of the texture units of the forward shaders
+#define SSBO_POINT_LIGHT_DATA_BINDING 8
+#define SSBO_SPOT_LIGHT_DATA_BINDING 9
+#define SSBO_AREA_LIGHT_DATA_BINDING 10
+#line 5 2
+#define UBO_CLUSTER_GRID_BINDING 14
+#define SSBO_CLUSTER_LIGHT_GRID_BINDING 11
+#define SSBO_CLUSTER_LIGHT_INDICES_BINDING 12
+#define SSBO_CLUSTER_ASSIGNMENT_SCRATCH_BINDING 13
+
+#define CLUSTER_MAX_LIGHTS 64u
+#line 16 1
+struct PointLightData {
+	vec4 positionAndRadius;
+	vec4 intensitiesAndNothing;
+};
+#line 23 1
+struct SpotLightData {
+	vec4 positionAndRadius;
+	vec4 intensitiesAndCosInnerAngle;
+	vec4 directionAndCosOuterAngle;
+};
+#line 31 1
+struct AreaLightData {
+	vec4 centerAndPlaneWidth;
+	vec4 intensitiesAndPlaneHeight;
+	vec4 normalAndCosOuterAngle;
+	vec4 tangentAndRadius;
+};
+#line 40 1
+layout(std430, binding = SSBO_POINT_LIGHT_DATA_BINDING)
+readonly restrict buffer ssboPointLightData {
+	PointLightData pointLights[];
+};
+#line 47 1
+layout(std430, binding = SSBO_SPOT_LIGHT_DATA_BINDING)
+readonly restrict buffer ssboSpotLightData {
+	SpotLightData spotLights[];
+};
+#line 54 1
+layout(std430, binding = SSBO_AREA_LIGHT_DATA_BINDING)
+readonly restrict buffer ssboAreaLightData {
+	AreaLightData areaLights[];
+};
+#line 14 2
+layout(std140, binding = UBO_CLUSTER_GRID_BINDING)
+uniform ubClusterGrid {
+	/** World space to cluster space, that is view space with the clip space w as depth */
+	mat4 clusterView;
+	/** Number of clusters along each axis and the total number in w */
+	uvec4 clusterDimensions;
+	/** Number of point, spot and area lights */
+	uvec4 clusterLightCounts;
+	/** Half extents of the view frustum at depth 1 */
+	vec2 clusterTileScale;
+	/** Depth range covered by the exponentially distributed depth slices */
+	vec2 clusterDepthPlanes;
+};
+#line 7 3
+layout(std430, binding = SSBO_CLUSTER_LIGHT_GRID_BINDING)
+readonly restrict buffer ssboClusterLightGrid {
+	/** Offset into the index list and the number of point, spot and area lights of each cluster */
+	uvec4 clusterLightGrid[];
+};
+#line 15 3
+layout(std430, binding = SSBO_CLUSTER_LIGHT_INDICES_BINDING)
+readonly restrict buffer ssboClusterLightIndices {
+	/** The point lights of each cluster, followed by its spot and area lights */
+	uint clusterLightIndices[];
+};
+
+// [[ import forward declarations ]] //
+
+#line 64 1
+float lightDistanceAttenuation(float distanceToLight, float radius);
+#line 76 1
+vec3 pointLightIncidence(PointLightData light, vec3 position, out vec3 L);
+#line 86 1
+vec3 spotLightIncidence(SpotLightData light, vec3 position, out vec3 L);
+#line 105 1
+vec3 areaLightIncidence(AreaLightData light, vec3 position, vec3 N, vec3 V, out vec3 L);
+#line 30 2
+uint clusterIndex(uvec3 cluster);
+#line 45 2
+uint clusterDepthSlice(float depth);
+#line 55 2
+uint clusterIndexAt(vec2 screenTexCoord, float depth);
+
+// [[ own source ]] //
+
+#line 1 0
+
+
+
+
+
+
+
+
 #extension GL_ARB_derivative_control : require
 
 layout(binding = 1) uniform sampler2D texMaterialAlbedo;
 layout(binding = 2) uniform sampler2D texMaterialNormal;
 layout(binding = 4) uniform sampler2D texMaterialTransmission;
 
+/** Size of the render targets, to find the fragment's cluster */
+uniform vec2 uScreenSize;
+
 in vec2 tModelTexCoord;
 in vec3 tNormal;
 in vec3 tTangent;
@@ -93,6 +204,40 @@ vec3 cookTorranceSpecular(float NdotV, float NdotL, float NdotH, float HdotVorL,
 	return specular;
 }
 
+/**
+ * Light reflected towards the eye from a single light. L points to the light and
+ * the intensities reaching the fragment already have the attenuation applied.
+ */
+vec3 shadeLight(vec3 N, vec3 V, vec3 L, vec3 lightIntensities, vec3 albedo, float roughness, float inverseMetalness, vec2 texCoord) {
+	vec3 H = normalize(V + L);
+	
+	//vec3 clampedDots = max(vec3(dot(N, L), dot(N, H), dot(H, L)), vec3(0.0));
+	float NdotV = max(0.0, dot(N, V));
+	float NdotL = max(0.0, dot(N, L));
+	float NdotH = max(0.0, dot(N, H));
+	float HdotL = max(0.0, dot(H, L));
+	
+	// Specular: Cook-Torrance and a side of extra stuff, please
+	vec3 Ks;
+	vec3 specular = cookTorranceSpecular(NdotV, NdotL, NdotH, HdotL, albedo, roughness, inverseMetalness, Ks) * lightIntensities;
+	
+	// Diffuse: Lambert
+	vec3 Kd = (1.0 - Ks) * inverseMetalness;
+	vec3 rawDiffuse = albedo * lightIntensities;
+	vec3 lambert = NdotL * rawDiffuse;
+	
+	// Subsurface
+#if SUBSURFACE
+	float sssTranslucency = 0.83;
+	float sssWidth = 0.012; // 0.012
+	vec3 subsurface = rawDiffuse * separablesssTransmittance(texCoord.st, sssTranslucency, 16.0*sssWidth, tVertexWorldspace.xyz, N, L, texMaterialTransmission);
+#else
+	vec3 subsurface = vec3(0.0);
+#endif
+	
+	return (Kd * lambert) + subsurface + specular; // I'm not entirely sure, but the fresnel factor is already applied to specular in the specular term
+}
+
 void main() {
 	vec2 texCoord = tModelTexCoord;
 	
@@ -118,39 +263,32 @@ void main() {
 	float inverseMetalness = 1.0 - 0.0;
 	
 	vec3 V = normalize(tEyeDirWorldspace - tVertexWorldspace);
-	float NdotV = max(0.0, dot(N, V));
 	
 	vec3 radiance = vec3(0.0);
-	/* per light */ {
-		float lightAttenuation = 1.0;
-		
-		//vec3 L = normalize(vec3(0.2, 0.5, 0.4)); // DEBUG:
-		vec3 L = normalize(vec3(-3.0, 3.0, 4.0) - tVertexWorldspace);
-		vec3 H = normalize(V + L);
-		
-		//vec3 clampedDots = max(vec3(dot(N, L), dot(N, H), dot(H, L)), vec3(0.0));
-		float NdotL = max(0.0, dot(N, L));
-		float NdotH = max(0.0, dot(N, H));
-		float HdotL = max(0.0, dot(H, L));
+	{// Shade the lights in the fragment's cluster
+		uvec4 clusterLights = clusterLightGrid[clusterIndexAt(gl_FragCoord.xy / uScreenSize, 1.0 / gl_FragCoord.w)];
+		uint lightListIndex = clusterLights.x;
 		
-		//vec3 lightIntensities = vec3(1.0, 1.0, 1.0);
-		vec3 lightIntensities = vec3(1.0);
-		
-		// Specular: Cook-Torrance and a side of extra stuff, please
-		vec3 Ks;
-		vec3 specular = cookTorranceSpecular(NdotV, NdotL, NdotH, HdotL, albedo, roughness, inverseMetalness, Ks);
-		
-		// Diffuse: Lambert
-		vec3 Kd = (1.0 - Ks) * inverseMetalness;
-		vec3 rawDiffuse = albedo * lightIntensities * lightAttenuation;
-		vec3 lambert = NdotL * rawDiffuse;
+		for(uint i = 0u; i < clusterLights.y; i++) {
+			vec3 L;
+			vec3 lightIntensities = pointLightIncidence(pointLights[clusterLightIndices[lightListIndex]], tVertexWorldspace, L);
+			radiance += shadeLight(N, V, L, lightIntensities, albedo, roughness, inverseMetalness, texCoord);
+			lightListIndex++;
+		}
 		
-		// Subsurface
-		float sssTranslucency = 0.83;
-		float sssWidth = 0.012; // 0.012
-		vec3 subsurface = rawDiffuse * separablesssTransmittance(texCoord.st, sssTranslucency, 16.0*sssWidth, tVertexWorldspace.xyz, N, L, texMaterialTransmission);
+		for(uint i = 0u; i < clusterLights.z; i++) {
+			vec3 L;
+			vec3 lightIntensities = spotLightIncidence(spotLights[clusterLightIndices[lightListIndex]], tVertexWorldspace, L);
+			radiance += shadeLight(N, V, L, lightIntensities, albedo, roughness, inverseMetalness, texCoord);
+			lightListIndex++;
+		}
 		
-		radiance += (Kd * lambert) + subsurface + specular; // I'm not entirely sure, but the fresnel factor is already applied to specular in the specular term
+		for(uint i = 0u; i < clusterLights.w; i++) {
+			vec3 L;
+			vec3 lightIntensities = areaLightIncidence(areaLights[clusterLightIndices[lightListIndex]], tVertexWorldspace, N, V, L);
+			radiance += shadeLight(N, V, L, lightIntensities, albedo, roughness, inverseMetalness, texCoord);
+			lightListIndex++;
+		}
 	}
 	
 	// Ambient light
@@ -351,3 +489,180 @@ void main() {
 //	outFrag = vec3(trendDirection * 0.5 + 0.5, 0.0);
 //	outFrag = vec3(trendConfidence);
 }
+
+// [[ import source for "clustered_lighting.light_data" ]] //
+
+#line 1 1
+
+
+
+
+#line 14 1
+
+
+#line 21 1
+
+
+#line 29 1
+
+
+#line 38 1
+
+
+#line 45 1
+
+
+#line 52 1
+
+
+#line 59 1
+
+/**
+ * Inverse square falloff, windowed so it reaches zero at the radius of the light.
+ */
+
+#line 64 1
+float lightDistanceAttenuation(float distanceToLight, float radius) {
+	float ratio = distanceToLight / radius;
+	float ratio2 = ratio*ratio;
+	float window = clamp(1.0 - ratio2*ratio2, 0.0, 1.0);
+	return (window*window) / max(distanceToLight*distanceToLight, 0.0001);
+}
+
+#line 71 1
+
+/**
+ * Returns the intensities of the light reaching the point and the direction to the light as L.
+ */
+
+#line 76 1
+vec3 pointLightIncidence(PointLightData light, vec3 position, out vec3 L) {
+	vec3 toLight = light.positionAndRadius.xyz - position;
+	float distanceToLight = length(toLight);
+	L = toLight / max(distanceToLight, 0.0001);
+	
+	return light.intensitiesAndNothing.rgb * lightDistanceAttenuation(distanceToLight, light.positionAndRadius.w);
+}
+
+#line 84 1
+
+
+#line 86 1
+vec3 spotLightIncidence(SpotLightData light, vec3 position, out vec3 L) {
+	vec3 toLight = light.positionAndRadius.xyz - position;
+	float distanceToLight = length(toLight);
+	L = toLight / max(distanceToLight, 0.0001);
+	
+	// Fade out between the inner and the outer cone
+	float cosAngle = dot(-L, light.directionAndCosOuterAngle.xyz);
+	float coneAttenuation = smoothstep(light.directionAndCosOuterAngle.w, light.intensitiesAndCosInnerAngle.w, cosAngle);
+	
+	return light.intensitiesAndCosInnerAngle.rgb * coneAttenuation * lightDistanceAttenuation(distanceToLight, light.positionAndRadius.w);
+}
+
+#line 98 1
+
+/**
+ * Area lights are rectangles lighting the side their normal points to. They are shaded
+ * from a single representative point: where the reflection vector hits the rectangle,
+ * clamped onto it. V points from the surface to the eye.
+ */
+
+#line 105 1
+vec3 areaLightIncidence(AreaLightData light, vec3 position, vec3 N, vec3 V, out vec3 L) {
+	vec3 center = light.centerAndPlaneWidth.xyz;
+	vec3 normal = light.normalAndCosOuterAngle.xyz;
+	vec3 tangent = light.tangentAndRadius.xyz;
+	vec3 bitangent = cross(normal, tangent);
+	vec2 halfExtents = vec2(light.centerAndPlaneWidth.w, light.intensitiesAndPlaneHeight.w) * 0.5;
+	
+	// Intersect the reflection vector with the plane of the light, or else use the closest point on the plane
+	vec3 R = reflect(-V, N);
+	float RdotNormal = dot(R, normal);
+	float planeDistance = dot(center - position, normal);
+	float t = planeDistance / RdotNormal;
+	vec3 planePoint = (RdotNormal < -0.0001 && t > 0.0) ? position + R * t : position + normal * planeDistance;
+	
+	// Clamp the point onto the rectangle
+	vec3 centerToPoint = planePoint - center;
+	vec2 pxy = clamp(vec2(dot(centerToPoint, tangent), dot(centerToPoint, bitangent)), -halfExtents, halfExtents);
+	vec3 representativePoint = center + tangent * pxy.x + bitangent * pxy.y;
+	
+	vec3 toLight = representativePoint - position;
+	float distanceToLight = length(toLight);
+	L = toLight / max(distanceToLight, 0.0001);
+	
+	// Falls off towards the outer angle, an outer angle of 90 degrees gives a lambertian emitter
+	float cosOuterAngle = light.normalAndCosOuterAngle.w;
+	float emission = clamp((dot(-L, normal) - cosOuterAngle) / max(1.0 - cosOuterAngle, 0.0001), 0.0, 1.0);
+	
+	return light.intensitiesAndPlaneHeight.rgb * emission * lightDistanceAttenuation(distanceToLight, light.tangentAndRadius.w);
+}
+
+
+// [[ import source for "clustered_lighting.cluster_grid" ]] //
+
+#line 1 2
+
+
+
+
+#line 12 2
+
+
+#line 28 2
+
+
+#line 30 2
+uint clusterIndex(uvec3 cluster) {
+	return cluster.x + (cluster.y + cluster.z * clusterDimensions.y) * clusterDimensions.x;
+}
+
+#line 34 2
+
+
+#line 40 2
+
+/**
+ * Depths outside of the depth planes fall into the first or last slice.
+ */
+
+#line 45 2
+uint clusterDepthSlice(float depth) {
+	float slice = log(max(depth, clusterDepthPlanes.x) / clusterDepthPlanes.x) / log(clusterDepthPlanes.y / clusterDepthPlanes.x) * float(clusterDimensions.z);
+	return min(uint(slice), clusterDimensions.z - 1u);
+}
+
+#line 50 2
+
+/**
+ * Index of the cluster containing the fragment at screenTexCoord (0..1 over the render target) and the depth.
+ */
+
+#line 55 2
+uint clusterIndexAt(vec2 screenTexCoord, float depth) {
+	uvec2 tile = min(uvec2(max(screenTexCoord, vec2(0.0)) * vec2(clusterDimensions.xy)), clusterDimensions.xy - 1u);
+	return clusterIndex(uvec3(tile, clusterDepthSlice(depth)));
+}
+
+#line 60 2
+
+/**
+ * Axis aligned bounds of the cluster in cluster space.
+ */
+
+
+// [[ import source for "clustered_lighting.cluster_lists" ]] //
+
+#line 1 3
+
+
+
+
+
+
+#line 13 3
+
+
+
+// [[ end of transpiled source ]] //
@@ -0,0 +1,76 @@
+//! Exported constants, structs and blocks of ssl includes, their order and deduplication.
+
+use std::rc::Rc;
+use render_demo::structured_shader_language::{SSLSourceParser, SSLTranspiler, SslDiagnostic};
+
+const LIGHTS_INCLUDE: &str = "@shadertype include\n@namespace lights\n\n@exportconst\n#define SSBO_POINT_LIGHT_DATA_BINDING 8\n@end\n\n@exportstruct\nstruct PointLightData {\n\tvec4 positionAndRadius;\n};\n@end\n\n@exportblock\nlayout(std430, binding = SSBO_POINT_LIGHT_DATA_BINDING)\nreadonly restrict buffer ssboPointLightData {\n\tPointLightData pointLights[];\n};\n@end\n\n@exportfunc\nvec3 lightPosition(uint light) {\n\treturn pointLights[light].positionAndRadius.xyz;\n}\n@end\n";
+
+/// Transpiles a fragment shader importing `imports` with the given includes in scope
+fn transpile(includes: &[(&str, &str)], imports: &[&str]) -> Result<String, Vec<SslDiagnostic>> {
+	let mut transpiler = SSLTranspiler::new();
+	for (file_name, source_code) in includes {
+		transpiler.add_include(Rc::new(SSLSourceParser::new().parse_source(file_name, source_code).unwrap()));
+	}
+	
+	let imports = imports.iter().map(|i| format!("@import {}\n", i)).collect::<String>();
+	let shader_source = format!("@shadertype fragment\n@glslversion 430 core\n{}\nvoid main() {{\n\tlightPosition(0u);\n}}\n", imports);
+	let shader = SSLSourceParser::new().parse_source("/test.frag.ssl", &shader_source).unwrap();
+	
+	transpiler.transpile(&shader).map(|t| t.source_code)
+}
+
+fn position_of(source_code: &str, text: &str) -> usize {
+	source_code.find(text).unwrap_or_else(|| panic!("`{}` is missing in:\n{}", text, source_code))
+}
+
+#[test]
+fn orders_declarations_before_forward_declarations() {
+	let source_code = transpile(&[("/lights.incl.ssl", LIGHTS_INCLUDE)], &["lights"]).unwrap();
+	
+	let define = position_of(&source_code, "#define SSBO_POINT_LIGHT_DATA_BINDING");
+	let struct_decl = position_of(&source_code, "struct PointLightData");
+	let block = position_of(&source_code, "buffer ssboPointLightData");
+	let forward_declaration = position_of(&source_code, "vec3 lightPosition(uint light);");
+	
+	assert!(define < struct_decl && struct_decl < block && block < forward_declaration);
+}
+
+#[test]
+fn emits_shared_declarations_once() {
+	// Both includes import the lights, the shader imports both
+	let a = "@shadertype include\n@namespace a\n@import lights\n";
+	let b = "@shadertype include\n@namespace b\n@import lights\n";
+	let source_code = transpile(&[("/lights.incl.ssl", LIGHTS_INCLUDE), ("/a.incl.ssl", a), ("/b.incl.ssl", b)], &["a", "b"]).unwrap();
+	
+	assert_eq!(source_code.matches("struct PointLightData").count(), 1);
+	assert_eq!(source_code.matches("buffer ssboPointLightData").count(), 1);
+	assert_eq!(source_code.matches("#define SSBO_POINT_LIGHT_DATA_BINDING").count(), 1);
+}
+
+#[test]
+fn reports_conflicting_struct_definitions() {
+	let other = "@shadertype include\n@namespace other\n\n@exportstruct\nstruct PointLightData {\n\tvec3 position;\n};\n@end\n";
+	let diagnostics = transpile(&[("/lights.incl.ssl", LIGHTS_INCLUDE), ("/other.incl.ssl", other)], &["lights", "other"]).err().unwrap();
+	
+	assert_eq!(diagnostics.len(), 1);
+	assert_eq!((diagnostics[0].file.as_str(), diagnostics[0].line), ("/other.incl.ssl", 5));
+	assert!(diagnostics[0].message.starts_with("Conflicting definition of `PointLightData`"), "{}", diagnostics[0]);
+}
+
+#[test]
+fn reports_blocks_declaring_the_same_member() {
+	// Blocks without an instance name put their members into the global scope
+	let spot_lights = "@shadertype include\n@namespace spot_lights\n\n@exportblock\nlayout(std430, binding = 9)\nreadonly restrict buffer ssboSpotLightData {\n\tvec4 spotLightData[];\n\tvec4 pointLights[];\n};\n@end\n";
+	let diagnostics = transpile(&[("/lights.incl.ssl", LIGHTS_INCLUDE), ("/spot_lights.incl.ssl", spot_lights)], &["lights", "spot_lights"]).err().unwrap();
+	
+	assert_eq!(diagnostics.len(), 1);
+	assert_eq!((diagnostics[0].file.as_str(), diagnostics[0].line), ("/spot_lights.incl.ssl", 5));
+	assert_eq!(diagnostics[0].message, "Member `pointLights` of block `ssboSpotLightData` is already declared by block `ssboPointLightData` (at /lights.incl.ssl:15)");
+}
+
+#[test]
+fn allows_same_member_in_blocks_with_instance_names() {
+	let spot_lights = "@shadertype include\n@namespace spot_lights\n\n@exportblock\nlayout(std430, binding = 9)\nreadonly restrict buffer ssboSpotLightData {\n\tvec4 pointLights[];\n} spotLightBlock;\n@end\n";
+	
+	assert!(transpile(&[("/lights.incl.ssl", LIGHTS_INCLUDE), ("/spot_lights.incl.ssl", spot_lights)], &["lights", "spot_lights"]).is_ok());
+}
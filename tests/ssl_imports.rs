@@ -0,0 +1,58 @@
+//! Import resolution of the fixtures in `tests/fixtures/ssl/imports`, which are all broken on purpose.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Once;
+use render_demo::asset::{ASSET_MANAGER_INSTANCE, AssetPathBuf};
+use render_demo::structured_shader_language::{SSLSourceParser, SSLTranspiler, AssetIncludeResolver, SslDiagnostic};
+
+static INIT_ASSET_MANAGER: Once = Once::new();
+
+/// Transpiles a fixture shader, resolving its imports next to it, and returns the diagnostics
+fn transpile_fixture(fixture: &str) -> Vec<SslDiagnostic> {
+	let fixture_root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ssl");
+	INIT_ASSET_MANAGER.call_once(|| unsafe {&mut ASSET_MANAGER_INSTANCE}.init(fixture_root.clone()));
+	
+	let file_name = format!("/imports/{}", fixture);
+	let source_code = fs::read_to_string(fixture_root.join("imports").join(fixture)).unwrap();
+	let parsed_source = SSLSourceParser::new().parse_source(&file_name, &source_code).unwrap();
+	
+	let mut transpiler = SSLTranspiler::new();
+	transpiler.set_include_resolver(Box::new(AssetIncludeResolver::new(vec![AssetPathBuf::from("/")])));
+	
+	match transpiler.transpile(&parsed_source) {
+		Ok(_) => panic!("Expected {} to fail", fixture),
+		Err(diagnostics) => diagnostics,
+	}
+}
+
+fn assert_single_diagnostic(diagnostics: &[SslDiagnostic], file: &str, line: u32, column: u32, message: &str) {
+	assert_eq!(diagnostics.len(), 1, "Expected exactly one diagnostic, got {:?}", diagnostics);
+	
+	let diagnostic = &diagnostics[0];
+	assert!(diagnostic.file.ends_with(file), "Expected the diagnostic in {}, got {}", file, diagnostic);
+	assert_eq!((diagnostic.line, diagnostic.column), (line, column), "Wrong location of {}", diagnostic);
+	assert_eq!(diagnostic.message, message);
+}
+
+#[test]
+fn reports_two_file_import_cycle() {
+	let diagnostics = transpile_fixture("cycle.frag.ssl");
+	
+	// Reported at the import that closes the cycle
+	assert_single_diagnostic(&diagnostics, "imports/cycle_b.incl.ssl", 5, 1, "Import cycle: imports.cycle_a -> imports.cycle_b -> imports.cycle_a");
+}
+
+#[test]
+fn reports_self_import() {
+	let diagnostics = transpile_fixture("self_import.frag.ssl");
+	
+	assert_single_diagnostic(&diagnostics, "imports/self_import.incl.ssl", 3, 1, "Import cycle: imports.self_import -> imports.self_import");
+}
+
+#[test]
+fn reports_unknown_namespace() {
+	let diagnostics = transpile_fixture("unknown_namespace.frag.ssl");
+	
+	assert_single_diagnostic(&diagnostics, "imports/unknown_namespace.frag.ssl", 3, 1, "Unresolved import `imports.cycle_a_typo`");
+}
@@ -0,0 +1,52 @@
+//! Transpiles every `.program` asset and compares the glsl with the snapshots in `tests/snapshots/ssl`.
+//!
+//! Run with `UPDATE_SNAPSHOTS=1` to rewrite the snapshots after an intended change to the transpiler or the shaders.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use render_demo::asset::ASSET_MANAGER_INSTANCE;
+use render_demo::render::shader::managed::TranspiledProgram;
+
+#[test]
+fn transpiled_programs_match_snapshots() {
+	let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	let snapshot_dir = manifest_dir.join("tests/snapshots/ssl");
+	let update_snapshots = env::var_os("UPDATE_SNAPSHOTS").is_some();
+	
+	let asset_manager = unsafe {&mut ASSET_MANAGER_INSTANCE};
+	asset_manager.init(manifest_dir.join("assets"));
+	
+	let programs = asset_manager.find_assets(".program").unwrap();
+	assert!(!programs.is_empty(), "No programs found in the asset folder");
+	
+	let mut failures = Vec::<String>::new();
+	for program_path in &programs {
+		let program = match TranspiledProgram::load(&program_path.as_path(), true) {
+			Ok(program) => program,
+			Err(err) => {
+				failures.push(format!("{:?}: {}", program_path, err));
+				continue;
+			}
+		};
+		
+		for (stage, _) in &program.shaders {
+			let source_code = program.variant_source(*stage, program.permutation_layout.default_key()).unwrap().source_code;
+			let snapshot_path = snapshot_dir.join(program.stage_file_name(*stage));
+			
+			if update_snapshots {
+				fs::create_dir_all(snapshot_path.parent().unwrap()).unwrap();
+				fs::write(&snapshot_path, &source_code).unwrap();
+				continue;
+			}
+			
+			match fs::read_to_string(&snapshot_path) {
+				Ok(snapshot) if snapshot == source_code => {},
+				Ok(_) => failures.push(format!("{}: transpiled output differs from the snapshot", snapshot_path.display())),
+				Err(err) => failures.push(format!("{}: failed to read snapshot: {}", snapshot_path.display(), err)),
+			}
+		}
+	}
+	
+	assert!(failures.is_empty(), "{} snapshot(s) failed (rerun with UPDATE_SNAPSHOTS=1 if the change is intended):\n{}", failures.len(), failures.join("\n"));
+}
@@ -0,0 +1,87 @@
+//! Maps driver info logs of a transpiled shader back to the ssl files through its source map.
+
+use std::rc::Rc;
+use render_demo::structured_shader_language::{SSLSourceParser, SSLTranspiler, TranspiledShaderSource};
+
+const SHADER_FILE: &str = "/shaders/test.frag.ssl";
+const INCLUDE_FILE: &str = "/shaders/util.incl.ssl";
+
+/// A fragment shader calling `square` from an include, `outColor` is assigned on line 8 and `square` starts on line 5
+fn transpile_test_shader() -> TranspiledShaderSource {
+	let include = SSLSourceParser::new().parse_source(INCLUDE_FILE, "@shadertype include\n@namespace util\n\n@exportfunc\nfloat square(float x) {\n\treturn x * x;\n}\n@end\n").unwrap();
+	let shader = SSLSourceParser::new().parse_source(SHADER_FILE, "@shadertype fragment\n@glslversion 430 core\n@import util\n\nout vec4 outColor;\n\nvoid main() {\n\toutColor = vec4(square(2.0));\n}\n").unwrap();
+	
+	let mut transpiler = SSLTranspiler::new();
+	transpiler.add_include(Rc::new(include));
+	transpiler.transpile(&shader).unwrap()
+}
+
+/// Output line of the first line of the transpiled source containing `text`
+fn output_line_of(transpiled: &TranspiledShaderSource, text: &str) -> u32 {
+	transpiled.source_code.lines().position(|l| l.contains(text)).unwrap() as u32 + 1
+}
+
+#[test]
+fn numbers_files_by_source_string() {
+	let transpiled = transpile_test_shader();
+	let source_map = &transpiled.source_map;
+	
+	assert_eq!(source_map.files(), &[String::from(SHADER_FILE), String::from(INCLUDE_FILE)]);
+	assert!(transpiled.source_code.contains("#line 1 0\n"));
+	assert!(transpiled.source_code.contains("#line 5 1\nfloat square(float x) {"));
+}
+
+#[test]
+fn maps_raw_output_lines() {
+	let transpiled = transpile_test_shader();
+	let source_map = &transpiled.source_map;
+	
+	assert_eq!(source_map.map_output_line(output_line_of(&transpiled, "outColor = vec4")), Some((SHADER_FILE, 8)));
+	assert_eq!(source_map.map_output_line(output_line_of(&transpiled, "return x * x;")), Some((INCLUDE_FILE, 6)));
+	assert_eq!(source_map.map_output_line(output_line_of(&transpiled, "float square(float x);")), Some((INCLUDE_FILE, 5)));
+	
+	// The generated header has no origin
+	assert_eq!(source_map.map_output_line(1), None);
+}
+
+#[test]
+fn rewrites_nvidia_info_log() {
+	let source_map = transpile_test_shader().source_map;
+	let info_log = "0(8) : error C1008: undefined variable \"outColr\"\n1(6) : warning C7533: global variable gl_FragColor is deprecated after version 120\n";
+	
+	assert_eq!(source_map.rewrite_info_log(info_log), format!(
+		"{}:8 : error C1008: undefined variable \"outColr\"\n{}:6 : warning C7533: global variable gl_FragColor is deprecated after version 120\n",
+		SHADER_FILE, INCLUDE_FILE,
+	));
+}
+
+#[test]
+fn rewrites_mesa_info_log() {
+	let source_map = transpile_test_shader().source_map;
+	let info_log = "0:8(2): error: `outColr' undeclared\n1:6(9): error: operands to arithmetic operators must be numeric\n";
+	
+	assert_eq!(source_map.rewrite_info_log(info_log), format!(
+		"{}:8(2): error: `outColr' undeclared\n{}:6(9): error: operands to arithmetic operators must be numeric\n",
+		SHADER_FILE, INCLUDE_FILE,
+	));
+}
+
+#[test]
+fn rewrites_amd_and_intel_info_log() {
+	let source_map = transpile_test_shader().source_map;
+	let info_log = "ERROR: 0:8: 'outColr' : undeclared identifier\nERROR: 1 compilation errors.  No code generated.\n";
+	
+	assert_eq!(source_map.rewrite_info_log(info_log), format!(
+		"ERROR: {}:8: 'outColr' : undeclared identifier\nERROR: 1 compilation errors.  No code generated.\n",
+		SHADER_FILE,
+	));
+}
+
+#[test]
+fn keeps_lines_without_known_location() {
+	let source_map = transpile_test_shader().source_map;
+	
+	// Unknown source strings and numbers inside identifiers stay as they are
+	let info_log = "7(3) : error C0000: syntax error\nvec4(12) is fine\nLink failed.\n";
+	assert_eq!(source_map.rewrite_info_log(info_log), info_log);
+}
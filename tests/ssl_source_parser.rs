@@ -0,0 +1,112 @@
+//! Diagnostics and exported functions of the `SSLSourceParser`, one case per test.
+
+use render_demo::structured_shader_language::{SSLSourceParser, ParsedSource, SslDiagnostic};
+
+const FILE_NAME: &str = "test.incl.ssl";
+
+fn parse(source: &str) -> Result<ParsedSource, Vec<SslDiagnostic>> {
+	SSLSourceParser::new().parse_source(FILE_NAME, source)
+}
+
+/// Parses source that has exactly one problem and returns its diagnostic
+fn parse_single_error(source: &str) -> SslDiagnostic {
+	let mut diagnostics = match parse(source) {
+		Ok(_) => panic!("Expected a diagnostic"),
+		Err(diagnostics) => diagnostics,
+	};
+	
+	assert_eq!(diagnostics.len(), 1, "Expected exactly one diagnostic, got {:?}", diagnostics);
+	let diagnostic = diagnostics.pop().unwrap();
+	assert_eq!(diagnostic.file, FILE_NAME);
+	diagnostic
+}
+
+/// Checks the message starts with `kind` and the diagnostic points at `line`:`column`
+fn assert_diagnostic(diagnostic: &SslDiagnostic, kind: &str, line: u32, column: u32) {
+	assert!(diagnostic.message.starts_with(kind), "Expected `{}`, got `{}`", kind, diagnostic.message);
+	assert_eq!((diagnostic.line, diagnostic.column), (line, column), "Wrong location of `{}`", diagnostic.message);
+}
+
+#[test]
+fn exports_multi_line_signature() {
+	let parsed = parse("@namespace test\n\n@exportfunc\nvec3 shade(\n\tvec3 albedo,\n\tfloat roughness) {\n\treturn albedo;\n}\n@end\n").unwrap();
+	
+	assert_eq!(parsed.exported_functions.len(), 1);
+	let function = &parsed.exported_functions[0];
+	assert_eq!(function.name, "shade");
+	assert_eq!(function.signature, "vec3 shade( vec3 albedo, float roughness)");
+	assert_eq!(function.line, 4);
+}
+
+#[test]
+fn exports_signature_with_brace_on_next_line() {
+	let parsed = parse("@exportfunc\nfloat luminance(vec3 color)\n{\n\treturn dot(color, vec3(0.2126, 0.7152, 0.0722));\n}\n@end\n").unwrap();
+	
+	let function = &parsed.exported_functions[0];
+	assert_eq!(function.name, "luminance");
+	assert_eq!(function.signature, "float luminance(vec3 color)");
+	assert_eq!(function.line, 2);
+}
+
+#[test]
+fn reports_multi_line_signature_without_body() {
+	let diagnostic = parse_single_error("@exportfunc\nvec3 shade(\n\tvec3 albedo\n);\n@end\n");
+	
+	// Points at the ';', the block is still closed by the @end
+	assert_diagnostic(&diagnostic, "Exported function has no body", 4, 2);
+}
+
+#[test]
+fn reports_unterminated_hide() {
+	let diagnostic = parse_single_error("void visible() {}\n\n\t@hide\nvoid hidden() {}\n");
+	
+	assert_diagnostic(&diagnostic, "Unterminated @hide block", 3, 2);
+}
+
+#[test]
+fn reports_unterminated_exportfunc() {
+	let diagnostic = parse_single_error("@exportfunc\nvoid f() {\n}\n");
+	
+	assert_diagnostic(&diagnostic, "Unterminated @exportfunc block", 1, 1);
+}
+
+#[test]
+fn joins_only_present_glslversion_arguments() {
+	let parsed = parse("@glslversion 430\n").unwrap();
+	assert_eq!(parsed.glsl_version.as_deref(), Some("430"));
+	
+	let parsed = parse("@glslversion 430 core\n").unwrap();
+	assert_eq!(parsed.glsl_version.as_deref(), Some("430 core"));
+}
+
+#[test]
+fn reports_missing_glslversion_argument() {
+	let diagnostic = parse_single_error("@shadertype fragment\n@glslversion\nvoid main() {}\n");
+	
+	assert_diagnostic(&diagnostic, "@glslversion expects a version", 2, 1);
+}
+
+#[test]
+fn reports_unknown_directive() {
+	let diagnostic = parse_single_error("@namespace test\n  @exportfunction\nvoid f() {}\n");
+	
+	assert_diagnostic(&diagnostic, "Unknown directive @exportfunction", 2, 3);
+}
+
+#[test]
+fn collects_all_diagnostics() {
+	let diagnostics = parse("@unknown\n@glslversion\n@hide\n").err().unwrap();
+	
+	assert_eq!(diagnostics.len(), 3);
+	assert_diagnostic(&diagnostics[0], "Unknown directive @unknown", 1, 1);
+	assert_diagnostic(&diagnostics[1], "@glslversion expects a version", 2, 1);
+	assert_diagnostic(&diagnostics[2], "Unterminated @hide block", 3, 1);
+}
+
+#[test]
+fn exports_signature_line_after_comments() {
+	let parsed = parse("@exportfunc\n\n// Shades a pixel\nvec3 shade(vec3 albedo) {\n\treturn albedo;\n}\n@end\n").unwrap();
+	
+	// The line of the signature, not the one after the directive
+	assert_eq!(parsed.exported_functions[0].line, 4);
+}
@@ -0,0 +1,71 @@
+//! Token kinds and positions of the `SslTokenizer`.
+
+use render_demo::structured_shader_language::{SslTokenizer, Token, TokenKind};
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+	SslTokenizer::new(source).collect()
+}
+
+/// Kinds and texts of the tokens that aren't trivia
+fn significant(source: &str) -> Vec<(TokenKind, &str)> {
+	tokenize(source).into_iter().filter(|t| !t.is_trivia()).map(|t| (t.kind, t.text)).collect()
+}
+
+#[test]
+fn is_lossless() {
+	let source = "@exportfunc\r\nvec3 f(float x) { /* c */ return vec3(1.0e-5f); } // end\n\t@end";
+	let joined = tokenize(source).iter().map(|t| t.text).collect::<String>();
+	
+	assert_eq!(joined, source);
+}
+
+#[test]
+fn tracks_lines_and_columns() {
+	let tokens = tokenize("a\n\tbb c\r\nd");
+	let positions = tokens.iter().filter(|t| t.kind == TokenKind::Ident).map(|t| (t.text, t.line, t.column)).collect::<Vec<_>>();
+	
+	assert_eq!(positions, vec![("a", 1, 1), ("bb", 2, 2), ("c", 2, 5), ("d", 3, 1)]);
+}
+
+#[test]
+fn recognizes_directives_only_at_line_start() {
+	assert_eq!(significant("\t@hide\n"), vec![(TokenKind::Directive, "@hide")]);
+	assert_eq!(significant("x @hide"), vec![(TokenKind::Ident, "x"), (TokenKind::Punct, "@"), (TokenKind::Ident, "hide")]);
+	
+	// Comments in front of a directive count as something on the line
+	assert_eq!(significant("/* c */ @end")[0], (TokenKind::Punct, "@"));
+}
+
+#[test]
+fn reads_glsl_number_literals() {
+	let numbers = significant("1.0e-5f 0xffu .5 2E+3 a-1");
+	
+	assert_eq!(numbers, vec![
+		(TokenKind::Number, "1.0e-5f"),
+		(TokenKind::Number, "0xffu"),
+		(TokenKind::Number, ".5"),
+		(TokenKind::Number, "2E+3"),
+		(TokenKind::Ident, "a"),
+		(TokenKind::Punct, "-"),
+		(TokenKind::Number, "1"),
+	]);
+}
+
+#[test]
+fn reads_unterminated_block_comment_to_the_end() {
+	let tokens = tokenize("a /* open\nstill open");
+	let comment = tokens.last().unwrap();
+	
+	assert_eq!(comment.kind, TokenKind::BlockComment);
+	assert_eq!(comment.text, "/* open\nstill open");
+	assert_eq!((comment.line, comment.column), (1, 3));
+}
+
+#[test]
+fn keeps_crlf_as_one_newline() {
+	let tokens = tokenize("a\r\nb");
+	
+	assert_eq!(tokens[1].kind, TokenKind::Newline);
+	assert_eq!(tokens[1].text, "\r\n");
+	assert_eq!((tokens[2].line, tokens[2].column), (2, 1));
+}
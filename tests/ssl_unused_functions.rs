@@ -0,0 +1,95 @@
+//! Stripping of exported include functions the shader never calls.
+
+use std::collections::HashSet;
+use std::rc::Rc;
+use render_demo::structured_shader_language::{SSLSourceParser, SSLTranspiler};
+
+const MATH_INCLUDE: &str = "@shadertype include\n@namespace math\n\n@exportfunc\nfloat square(float x) {\n\treturn x * x;\n}\n@end\n\n@exportfunc\nfloat cube(float x) {\n\treturn square(x) * x;\n}\n@end\n\n@exportfunc\nfloat saturate(float x) {\n\treturn clamp(x, 0.0, 1.0);\n}\n@end\n\n@exportfunc\nvec3 saturate(vec3 x) {\n\treturn clamp(x, vec3(0.0), vec3(1.0));\n}\n@end\n";
+
+const LIGHTING_INCLUDE: &str = "@shadertype include\n@namespace lighting\n@import math\n\n@exportfunc\nfloat attenuation(float distance) {\n\treturn saturate(1.0 / cube(distance));\n}\n@end\n\n@exportfunc\nfloat unusedFalloff(float distance) {\n\treturn square(distance);\n}\n@end\n";
+
+/// Transpiles a fragment shader with the given body in `main` and everything in front of it,
+/// returns the reachable functions and the transpiled source with unused functions stripped
+fn transpile(globals: &str, main_body: &str) -> (HashSet<String>, String) {
+	let mut transpiler = SSLTranspiler::new();
+	transpiler.add_include(Rc::new(SSLSourceParser::new().parse_source("/math.incl.ssl", MATH_INCLUDE).unwrap()));
+	transpiler.add_include(Rc::new(SSLSourceParser::new().parse_source("/lighting.incl.ssl", LIGHTING_INCLUDE).unwrap()));
+	
+	let shader_source = format!("@shadertype fragment\n@glslversion 430 core\n@import lighting\n{}\nvoid main() {{\n\t{}\n}}\n", globals, main_body);
+	let shader = SSLSourceParser::new().parse_source("/test.frag.ssl", &shader_source).unwrap();
+	
+	let includes = transpiler.resolve_imports(&shader).unwrap();
+	let reachable = SSLTranspiler::find_reachable_functions(&shader, &includes);
+	let source_code = transpiler.transpile(&shader).unwrap().source_code;
+	(reachable, source_code)
+}
+
+fn names(names: &[&str]) -> HashSet<String> {
+	names.iter().map(|n| String::from(*n)).collect()
+}
+
+#[test]
+fn follows_calls_transitively_across_includes() {
+	// main -> attenuation -> cube -> square, attenuation -> saturate
+	let (reachable, source_code) = transpile("", "float a = attenuation(2.0);");
+	
+	assert_eq!(reachable, names(&["attenuation", "cube", "square", "saturate"]));
+	assert!(source_code.contains("float square(float x) {"));
+	assert!(!source_code.contains("unusedFalloff"));
+}
+
+#[test]
+fn unused_function_calling_a_used_one_is_stripped() {
+	// `unusedFalloff` calls `square`, which is used, but nothing calls `unusedFalloff`
+	let (reachable, source_code) = transpile("", "float s = square(2.0);");
+	
+	assert_eq!(reachable, names(&["square"]));
+	assert!(!source_code.contains("unusedFalloff"));
+	assert!(!source_code.contains("float cube(float x)"));
+}
+
+#[test]
+fn overloads_are_kept_together() {
+	let (reachable, source_code) = transpile("", "float s = saturate(2.0);");
+	
+	assert_eq!(reachable, names(&["saturate"]));
+	assert!(source_code.contains("float saturate(float x);"));
+	assert!(source_code.contains("vec3 saturate(vec3 x);"));
+}
+
+#[test]
+fn names_that_are_not_called_are_no_roots() {
+	// A variable and a forward declaration named like exported functions don't use them
+	let (reachable, _) = transpile("float cube(float x);\n", "float square = 2.0;");
+	
+	assert!(reachable.is_empty(), "{:?}", reachable);
+}
+
+#[test]
+fn functions_of_the_shader_are_roots() {
+	// Functions of the shader are always emitted, so their calls have to resolve even if main doesn't use them
+	let (reachable, _) = transpile("float helper(float x) {\n\treturn cube(x);\n}\n", "");
+	
+	assert_eq!(reachable, names(&["cube", "square"]));
+}
+
+#[test]
+fn calls_in_macros_are_roots() {
+	let (reachable, _) = transpile("#define ATTENUATE(d) \\\n\tattenuation(d)\n", "float a = ATTENUATE(2.0);");
+	
+	assert_eq!(reachable, names(&["attenuation", "cube", "square", "saturate"]));
+}
+
+#[test]
+fn keeps_everything_when_stripping_is_off() {
+	let mut transpiler = SSLTranspiler::new();
+	transpiler.set_strip_unused_functions(false);
+	transpiler.add_include(Rc::new(SSLSourceParser::new().parse_source("/math.incl.ssl", MATH_INCLUDE).unwrap()));
+	
+	let shader = SSLSourceParser::new().parse_source("/test.frag.ssl", "@shadertype fragment\n@import math\nvoid main() {}\n").unwrap();
+	let source_code = transpiler.transpile(&shader).unwrap().source_code;
+	
+	for signature in &["float square(float x);", "float cube(float x);", "float saturate(float x);", "vec3 saturate(vec3 x);"] {
+		assert!(source_code.contains(signature), "`{}` is missing in:\n{}", signature, source_code);
+	}
+}
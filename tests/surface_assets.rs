@@ -0,0 +1,55 @@
+//! Parses every `.surface` asset and checks that its program and permutation exist.
+
+use std::fs;
+use std::path::PathBuf;
+use render_demo::asset::{ASSET_MANAGER_INSTANCE, AssetPathBuf};
+use render_demo::render::material::SurfaceAssetSchema;
+use render_demo::render::shader::managed::TranspiledProgram;
+
+#[test]
+fn surface_assets_are_valid() {
+	let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	
+	let asset_manager = unsafe {&mut ASSET_MANAGER_INSTANCE};
+	asset_manager.init(manifest_dir.join("assets"));
+	
+	let mut failures = Vec::<String>::new();
+	
+	for surface_path in &asset_manager.find_assets(".surface").unwrap() {
+		let contents = fs::read_to_string(asset_manager.resolve_asset_fs_path(&surface_path.as_path())).unwrap();
+		
+		// Empty surfaces are placeholders that weren't written yet
+		if contents.trim().is_empty() {
+			continue;
+		}
+		
+		let surface_def = match ron::de::from_str::<SurfaceAssetSchema::SurfaceDef>(&contents) {
+			Ok(def) => def,
+			Err(err) => {
+				failures.push(format!("{:?}: {}", surface_path, err));
+				continue;
+			}
+		};
+		
+		// Resolve the program like the material does
+		let program_path = AssetPathBuf::from(&surface_def.program);
+		let program_path = if program_path.as_path().is_absolute() {
+			program_path
+		}
+		else {
+			surface_path.as_path().parent().unwrap().join(&program_path.as_path()).unwrap()
+		};
+		
+		match TranspiledProgram::load(&program_path.as_path(), false) {
+			Ok(program) => {
+				let values = surface_def.permutation.iter().map(|(n, v)| (n.as_str(), v.as_str())).collect::<Vec<_>>();
+				if let Err(err) = program.permutation_layout.key(&values) {
+					failures.push(format!("{:?}: {}", surface_path, err));
+				}
+			}
+			Err(err) => failures.push(format!("{:?}: program {:?}: {}", surface_path, program_path, err)),
+		}
+	}
+	
+	assert!(failures.is_empty(), "Invalid surfaces:\n{}", failures.join("\n"));
+}